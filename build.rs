@@ -0,0 +1,16 @@
+use vergen::EmitBuilder;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Outside a git checkout the commit falls back to a placeholder instead of failing the build
+    EmitBuilder::builder().build_timestamp().git_sha(true).emit()?;
+
+    // Messages of `format: protobuf`, see `src/protobuf.rs`
+    #[cfg(feature = "protobuf")]
+    {
+        println!("cargo:rerun-if-changed=proto/records.proto");
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+        // Ordered maps, so the same record always encodes to the same bytes
+        prost_build::Config::new().btree_map(["."]).compile_protos(&["proto/records.proto"], &["proto"])?;
+    }
+    Ok(())
+}
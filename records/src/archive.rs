@@ -0,0 +1,201 @@
+//! rkyv archives of the records, the payloads of `format: rkyv`.
+//!
+//! A payload is a 16-byte header, `RKYV` followed by the little-endian `SCHEMA_VERSION` and 8
+//! reserved bytes, then the archived `Record`. Derived events are archived as their typed
+//! record, every other record type as its JSON tree.
+
+use std::{collections::BTreeMap, fmt};
+
+use bytecheck::CheckBytes;
+use rkyv::{
+    ser::{ScratchSpace, Serializer},
+    Archive, Serialize,
+};
+use serde_json::Value;
+
+/// Buffer aligned for `read`, to copy payloads into
+pub use rkyv::AlignedVec;
+
+use crate::{AccountClosed, ProgramDeploy, SolTransfer, StakeEvent, TokenCreation, Vote};
+
+/// Version of the archived layout, bumped whenever `Record` or the records it holds change
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Starts every payload
+const MAGIC: &[u8; 4] = b"RKYV";
+
+/// Header length, keeping the archive aligned in a buffer aligned for it
+const HEADER_LEN: usize = 16;
+
+/// Record of a payload
+#[derive(Archive, Serialize)]
+#[archive_attr(derive(CheckBytes))]
+pub struct Record {
+    /// Key the record was published with
+    pub key: String,
+    /// Crate version and short git commit of the streamer that published the record
+    pub streamer_version: String,
+    /// Set on records of replays and backfills
+    pub historical: bool,
+    /// `<key>:<slot>:<digest>`, with `idempotency_keys`
+    pub idempotency_key: Option<String>,
+    /// Fields of a derived event without a field in its record, e.g. added by enrichment
+    pub extra: BTreeMap<String, JsonValue>,
+    pub payload: Payload,
+}
+
+#[derive(Archive, Serialize)]
+#[archive_attr(derive(CheckBytes))]
+pub enum Payload {
+    /// Record without a typed record
+    Json(JsonValue),
+    Vote(Vote),
+    StakeEvent(StakeEvent),
+    SolTransfer(SolTransfer),
+    TokenCreation(TokenCreation),
+    ProgramDeploy(ProgramDeploy),
+    AccountClosed(AccountClosed),
+}
+
+/// JSON value, recursive types omit the bounds of their recursive fields
+#[derive(Archive, Serialize)]
+#[archive(bound(serialize = "__S: ScratchSpace + Serializer"))]
+#[archive_attr(derive(CheckBytes))]
+#[archive_attr(check_bytes(
+    bound = "__C: rkyv::validation::ArchiveContext, <__C as rkyv::Fallible>::Error: std::error::Error"
+))]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(JsonNumber),
+    String(String),
+    Array(
+        #[omit_bounds]
+        #[archive_attr(omit_bounds)]
+        Vec<JsonValue>,
+    ),
+    Object(
+        #[omit_bounds]
+        #[archive_attr(omit_bounds)]
+        BTreeMap<String, JsonValue>,
+    ),
+}
+
+#[derive(Archive, Serialize)]
+#[archive_attr(derive(CheckBytes))]
+pub enum JsonNumber {
+    PosInt(u64),
+    NegInt(i64),
+    Float(f64),
+}
+
+impl From<&Value> for JsonValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::Null => JsonValue::Null,
+            Value::Bool(value) => JsonValue::Bool(*value),
+            Value::Number(number) => JsonValue::Number(match (number.as_u64(), number.as_i64()) {
+                (Some(value), _) => JsonNumber::PosInt(value),
+                (None, Some(value)) => JsonNumber::NegInt(value),
+                (None, None) => JsonNumber::Float(number.as_f64().unwrap_or_default()),
+            }),
+            Value::String(value) => JsonValue::String(value.clone()),
+            Value::Array(values) => JsonValue::Array(values.iter().map(JsonValue::from).collect()),
+            Value::Object(fields) => {
+                JsonValue::Object(fields.iter().map(|(name, value)| (name.clone(), JsonValue::from(value))).collect())
+            },
+        }
+    }
+}
+
+/// Why a payload cannot be archived or read
+#[derive(Debug)]
+pub enum Error {
+    /// The payload does not start with the header
+    Header,
+    /// The payload was archived with another layout
+    Version(u32),
+    /// The archive failed to serialize or its check failed
+    Archive(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Header => write!(f, "not an rkyv record payload"),
+            Error::Version(version) => {
+                write!(f, "record archived with schema version {}, this crate reads {}", version, SCHEMA_VERSION)
+            },
+            Error::Archive(e) => write!(f, "invalid record archive: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Archive `record` behind the header pinning its schema version
+pub fn to_bytes(record: &Record) -> Result<Vec<u8>, Error> {
+    let archive = rkyv::to_bytes::<_, 4096>(record).map_err(|e| Error::Archive(e.to_string()))?;
+
+    let mut payload = Vec::with_capacity(HEADER_LEN + archive.len());
+    payload.extend_from_slice(MAGIC);
+    payload.extend_from_slice(&SCHEMA_VERSION.to_le_bytes());
+    payload.resize(HEADER_LEN, 0);
+    payload.extend_from_slice(&archive);
+    Ok(payload)
+}
+
+/// Check the header and the archive of `payload`, returning the record to read in place. The
+/// payload must be in a buffer aligned to 16 bytes, such as an `AlignedVec`.
+pub fn read(payload: &[u8]) -> Result<&ArchivedRecord, Error> {
+    if payload.len() < HEADER_LEN || &payload[..MAGIC.len()] != MAGIC {
+        return Err(Error::Header);
+    }
+    let version = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+    if version != SCHEMA_VERSION {
+        return Err(Error::Version(version));
+    }
+    rkyv::check_archived_root::<Record>(&payload[HEADER_LEN..]).map_err(|e| Error::Archive(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_typed_records_in_place() {
+        let record = Record {
+            key: "recipient".to_string(),
+            streamer_version: "0.1.1".to_string(),
+            historical: false,
+            idempotency_key: None,
+            extra: BTreeMap::from([("label".to_string(), JsonValue::from(&Value::from("exchange")))]),
+            payload: Payload::SolTransfer(SolTransfer {
+                from: "sender".to_string(),
+                to: "recipient".to_string(),
+                lamports: 5_000_000_000,
+                signature: "signature".to_string(),
+                slot: 42,
+                cpi: true,
+            }),
+        };
+        let mut aligned = AlignedVec::new();
+        aligned.extend_from_slice(&to_bytes(&record).unwrap());
+
+        let archived = read(&aligned).unwrap();
+        assert_eq!(archived.key.as_str(), "recipient");
+        assert!(archived.extra.contains_key("label"));
+        match &archived.payload {
+            ArchivedPayload::SolTransfer(transfer) => {
+                assert_eq!(transfer.lamports, 5_000_000_000);
+                assert_eq!(transfer.slot, 42);
+                assert!(transfer.cpi);
+            },
+            _ => panic!("not a transfer"),
+        }
+
+        aligned[4] = 1;
+        assert!(matches!(read(&aligned), Err(Error::Version(1))));
+        assert!(matches!(read(b"JSON"), Err(Error::Header)));
+    }
+}
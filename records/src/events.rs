@@ -0,0 +1,218 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Vote instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub enum VoteKind {
+    Vote,
+    UpdateVoteState,
+    CompactUpdateVoteState,
+    TowerSync,
+}
+
+/// Vote of a validator, on the vote topic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub struct Vote {
+    /// Vote account
+    pub vote_account: String,
+    /// Vote authority
+    pub authority: Option<String>,
+    /// Signature of the vote transaction
+    pub signature: String,
+    pub kind: VoteKind,
+    /// Slot the vote landed in
+    pub slot: u64,
+    /// Latest slot voted on
+    pub last_voted_slot: Option<u64>,
+    /// Slots between the latest slot voted on and the landing slot
+    pub latency: Option<u64>,
+    /// Slots voted on for the first time
+    pub slots: Vec<u64>,
+    /// Root slot of the tower
+    pub root: Option<u64>,
+    /// Unix timestamp of the vote
+    pub timestamp: Option<i64>,
+}
+
+/// Stake instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub enum StakeEventKind {
+    Delegate,
+    Deactivate,
+    Withdraw,
+    Split,
+    Merge,
+}
+
+/// Stake account lifecycle event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub struct StakeEvent {
+    pub event: StakeEventKind,
+    /// Stake account
+    pub stake_account: Option<String>,
+    /// Vote account delegated to, `delegate`
+    pub vote_account: Option<String>,
+    /// Stake or withdraw authority
+    pub authority: Option<String>,
+    /// Receiving account, `withdraw`, `split` and `merge`
+    pub destination: Option<String>,
+    /// Lamports moved, `withdraw` and `split`
+    pub lamports: Option<u64>,
+    /// Signature of the transaction
+    pub signature: String,
+    /// Slot of the transaction
+    pub slot: u64,
+    /// Whether the instruction was invoked through CPI rather than at the top level
+    #[serde(default)]
+    pub cpi: bool,
+}
+
+/// System program transfer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub struct SolTransfer {
+    /// Sender
+    pub from: String,
+    /// Recipient
+    pub to: String,
+    /// Lamports transferred
+    pub lamports: u64,
+    /// Signature of the transaction
+    pub signature: String,
+    /// Slot of the transaction
+    pub slot: u64,
+    /// Whether the instruction was invoked through CPI rather than at the top level
+    #[serde(default)]
+    pub cpi: bool,
+}
+
+/// Token instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub enum TokenCreationEvent {
+    NewMint,
+    NewTokenAccount,
+}
+
+/// Mint or token account initialization
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub struct TokenCreation {
+    pub event: TokenCreationEvent,
+    /// Token or Token-2022 program
+    pub program: String,
+    /// Mint
+    pub mint: Option<String>,
+    /// Decimals of a new mint
+    pub decimals: Option<u32>,
+    /// Mint authority of a new mint
+    pub mint_authority: Option<String>,
+    /// Freeze authority of a new mint
+    pub freeze_authority: Option<String>,
+    /// New token account
+    pub account: Option<String>,
+    /// Owner of a new token account
+    pub owner: Option<String>,
+    /// Signature of the transaction
+    pub signature: String,
+    /// Slot of the transaction
+    pub slot: u64,
+    /// Whether the instruction was invoked through CPI rather than at the top level
+    #[serde(default)]
+    pub cpi: bool,
+}
+
+/// Upgradeable loader instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub enum ProgramDeployEvent {
+    Deploy,
+    Upgrade,
+    Extend,
+    SetAuthority,
+    Close,
+}
+
+/// Program lifecycle event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub struct ProgramDeploy {
+    pub event: ProgramDeployEvent,
+    /// Program
+    pub program_id: Option<String>,
+    /// Program data account
+    pub program_data: Option<String>,
+    /// Buffer the program was written from
+    pub buffer: Option<String>,
+    /// Account whose authority changed, or closed account
+    pub account: Option<String>,
+    /// Upgrade or buffer authority
+    pub authority: Option<String>,
+    /// New authority, `null` makes the program immutable
+    pub new_authority: Option<String>,
+    /// Account receiving the lamports of a closed account
+    pub recipient: Option<String>,
+    /// Maximum program size of a deploy
+    pub max_data_len: Option<u64>,
+    /// Bytes a program data account was extended by
+    pub additional_bytes: Option<u32>,
+    /// Signature of the transaction
+    pub signature: String,
+    /// Slot of the transaction
+    pub slot: u64,
+    /// Whether the instruction was invoked through CPI rather than at the top level
+    #[serde(default)]
+    pub cpi: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub enum AccountClosedEvent {
+    AccountClosed,
+}
+
+/// How an account was closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub enum CloseReason {
+    ZeroLamports,
+    AssignedToSystem,
+}
+
+/// Account closed by an account update
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+#[cfg_attr(feature = "rkyv", derive(rkyv::Archive, rkyv::Serialize), archive_attr(derive(bytecheck::CheckBytes)))]
+pub struct AccountClosed {
+    pub event: AccountClosedEvent,
+    pub reason: CloseReason,
+    /// Account address
+    pub pubkey: String,
+    /// Owner after the update
+    pub owner: String,
+    /// Owner before the update, when seen
+    pub previous_owner: Option<String>,
+    /// Balance after the update
+    pub lamports: u64,
+    /// Slot of the update
+    pub slot: u64,
+    /// Write version of the update
+    pub write_version: u64,
+    /// Transaction that closed the account, when any
+    pub signature: Option<String>,
+}
@@ -0,0 +1,19 @@
+//! Records published by `yellowstone-grpc-source`, for Rust consumers.
+//!
+//! Every record type is a typed record, with serde using the field names of the JSON records
+//! and the JSON Schema `export-schemas` writes. With the `rkyv` feature, `archive` reads the
+//! `format: rkyv` payloads in place, without deserializing them.
+
+#[cfg(feature = "rkyv")]
+pub mod archive;
+mod events;
+mod updates;
+
+pub use events::{
+    AccountClosed, AccountClosedEvent, CloseReason, ProgramDeploy, ProgramDeployEvent, SolTransfer, StakeEvent,
+    StakeEventKind, TokenCreation, TokenCreationEvent, Vote, VoteKind,
+};
+pub use updates::{
+    Account, BlockMeta, Control, Message, Slot, SlotStatus, Stamps, Transaction, TransactionBody, TransactionMeta,
+    TransactionOutcome, TransactionStatus, TransactionSummary,
+};
@@ -0,0 +1,219 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Transaction with its status meta, in the Solana RPC `jsonParsed` encoding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    /// Slot of the transaction
+    pub slot: u64,
+    /// Epoch of the slot
+    pub epoch: Option<u64>,
+    /// Unix timestamp of the block, when known
+    pub block_time: Option<i64>,
+    /// `legacy` or `0`
+    pub version: Option<Value>,
+    pub transaction: TransactionBody,
+    pub meta: Option<TransactionMeta>,
+    /// Every instruction in execution order, with `flatten_instructions`
+    pub flat_instructions: Option<Vec<Map<String, Value>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TransactionBody {
+    /// Signatures, the first one identifies the transaction
+    pub signatures: Vec<String>,
+    pub message: Message,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Message {
+    /// `pubkey`, `signer`, `writable` and `source` of each account
+    #[serde(default)]
+    pub account_keys: Vec<Map<String, Value>>,
+    /// Recent blockhash
+    #[serde(default)]
+    pub recent_blockhash: String,
+    /// Top-level instructions, parsed or `programId`, `accounts` and base58 `data`
+    #[serde(default)]
+    pub instructions: Vec<Map<String, Value>>,
+    /// Lookups of v0 transactions
+    pub address_table_lookups: Option<Vec<Map<String, Value>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionMeta {
+    /// Transaction error, `null` on success
+    #[serde(default)]
+    pub err: Option<Value>,
+    /// Fee in lamports
+    #[serde(default)]
+    pub fee: u64,
+    /// Lamports of each account before
+    #[serde(default)]
+    pub pre_balances: Vec<u64>,
+    /// Lamports of each account after
+    #[serde(default)]
+    pub post_balances: Vec<u64>,
+    /// CPIs: `index` of the top-level instruction and its `instructions`
+    #[serde(default)]
+    pub inner_instructions: Vec<Map<String, Value>>,
+    /// Program logs
+    #[serde(default)]
+    pub log_messages: Vec<String>,
+    /// Token balances before
+    #[serde(default)]
+    pub pre_token_balances: Vec<Map<String, Value>>,
+    /// Token balances after
+    #[serde(default)]
+    pub post_token_balances: Vec<Map<String, Value>>,
+    /// Compute units consumed
+    pub compute_units_consumed: Option<u64>,
+}
+
+/// Outcome of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionOutcome {
+    Success,
+    Failed,
+}
+
+/// Compact transaction of routes with `profile: summary`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionSummary {
+    /// Signature of the transaction
+    pub signature: String,
+    /// Slot of the transaction
+    pub slot: u64,
+    /// Unix timestamp of the block, when known
+    pub block_time: Option<i64>,
+    pub status: TransactionOutcome,
+    /// Fee in lamports
+    pub fee: Option<u64>,
+    /// Fee payer
+    pub fee_payer: Option<String>,
+    /// Programs invoked, sorted
+    #[serde(default)]
+    pub programs: Vec<String>,
+    /// Token `transfer` and `transferChecked` instructions
+    pub token_transfers: Option<u64>,
+}
+
+/// Status of a transaction, from `transactions_status` updates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionStatus {
+    /// Slot of the transaction
+    pub slot: u64,
+    /// Epoch of the slot
+    pub epoch: Option<u64>,
+    /// Signature of the transaction
+    pub signature: String,
+    /// Whether the transaction is a vote
+    pub is_vote: bool,
+    /// Position of the transaction in its block
+    pub index: u64,
+    /// Transaction error, `null` on success
+    #[serde(default)]
+    pub err: Option<Value>,
+}
+
+/// Account update
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Account {
+    /// Account address
+    pub pubkey: String,
+    /// Balance in lamports
+    pub lamports: u64,
+    /// Owner program
+    pub owner: String,
+    /// Rent epoch
+    pub rent_epoch: Option<u64>,
+    /// Slot of the update
+    pub slot: u64,
+    /// Account data, base64
+    pub data: Option<String>,
+    /// Transaction that caused the update, when any
+    pub txn_signature: Option<String>,
+    /// Write version of the update, on the latest account state topic
+    #[serde(rename = "writeVersion")]
+    pub write_version: Option<u64>,
+    /// Pointer to the account data offloaded by `account_blobs`, in place of `data`
+    pub blob: Option<Map<String, Value>>,
+}
+
+/// Commitment a slot reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SlotStatus {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+/// Slot status update
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Slot {
+    /// Slot
+    pub slot: u64,
+    pub status: SlotStatus,
+}
+
+/// Block metadata
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockMeta {
+    /// Slot of the block
+    pub slot: u64,
+    /// Blockhash
+    pub blockhash: String,
+    /// `rewards` and `numPartitions` of the block
+    pub rewards: Option<Map<String, Value>>,
+    /// Unix timestamp of the block
+    pub block_time: Option<i64>,
+    /// Block height
+    pub block_height: Option<u64>,
+    /// Slot of the parent block
+    pub parent_slot: u64,
+    /// Blockhash of the parent block
+    #[serde(default)]
+    pub parent_blockhash: String,
+    /// Transactions in the block
+    #[serde(default)]
+    pub executed_transaction_count: u64,
+    /// Entries in the block
+    #[serde(default)]
+    pub entries_count: u64,
+}
+
+/// Control record describing the stream, on the control topic; the fields depend on `type`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Control {
+    /// Event, e.g. `startup`, `subscription`, `reconnect`, `gap_detected` or `shutdown`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Unix time the record was emitted, in milliseconds
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+}
+
+/// Fields the streamer stamps on every record
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Stamps {
+    /// Crate version and short git commit of the streamer that published the record
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub streamer_version: Option<String>,
+    /// Set on records of replays and backfills
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub historical: Option<bool>,
+    /// `<key>:<slot>:<digest>`, identical when the record is published again, with `idempotency_keys`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+}
@@ -0,0 +1,146 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+use serde_json::{json, Value};
+use tokio::time;
+
+use crate::{metrics::Metrics, publisher::Publisher};
+
+/// Activity analyzer configuration
+#[derive(Debug, Clone)]
+pub struct ActivityConfig {
+    /// Accounts to track
+    pub accounts: Vec<String>,
+    /// Warn when a tracked account has not updated for this many seconds
+    pub stale_after_secs: u64,
+    /// Warn when a tracked account updates more often than this per second
+    pub max_updates_per_sec: u64,
+}
+
+#[derive(Debug)]
+struct AccountActivity {
+    last_update: Instant,
+    window_start: Instant,
+    window_updates: u64,
+    stale_reported: bool,
+    burst_reported: bool,
+}
+
+/// Tracks update frequency of tracked accounts and reports stale or bursting ones
+pub struct AccountActivityMonitor {
+    config: ActivityConfig,
+    accounts: Mutex<HashMap<String, AccountActivity>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl AccountActivityMonitor {
+    pub fn new(config: ActivityConfig, metrics: Option<Arc<Metrics>>) -> Self {
+        let now = Instant::now();
+        let accounts = config
+            .accounts
+            .iter()
+            .map(|pubkey| {
+                (pubkey.clone(), AccountActivity {
+                    last_update: now,
+                    window_start: now,
+                    window_updates: 0,
+                    stale_reported: false,
+                    burst_reported: false,
+                })
+            })
+            .collect();
+
+        Self {
+            config,
+            accounts: Mutex::new(accounts),
+            metrics,
+        }
+    }
+
+    /// Record an update for `pubkey`, returns a warning record when it updates abnormally fast
+    pub fn record(&self, pubkey: &str) -> Option<Value> {
+        let mut accounts = self.accounts.lock().expect("activity lock poisoned");
+        let activity = accounts.get_mut(pubkey)?;
+        let now = Instant::now();
+
+        activity.last_update = now;
+        activity.stale_reported = false;
+        if now.duration_since(activity.window_start) >= Duration::from_secs(1) {
+            activity.window_start = now;
+            activity.window_updates = 0;
+            activity.burst_reported = false;
+        }
+        activity.window_updates += 1;
+
+        if activity.window_updates > self.config.max_updates_per_sec && !activity.burst_reported {
+            activity.burst_reported = true;
+            warn!("Account {} exceeded {} updates per second", pubkey, self.config.max_updates_per_sec);
+            self.count_warning();
+            return Some(json!({
+                "type": "account_activity_warning",
+                "reason": "burst",
+                "pubkey": pubkey,
+                "updatesPerSecond": activity.window_updates,
+            }));
+        }
+        None
+    }
+
+    /// Warning records for tracked accounts that stopped updating
+    pub fn stale_accounts(&self) -> Vec<Value> {
+        let mut accounts = self.accounts.lock().expect("activity lock poisoned");
+        let stale_after = Duration::from_secs(self.config.stale_after_secs);
+
+        let mut warnings = Vec::new();
+        for (pubkey, activity) in accounts.iter_mut() {
+            let idle = activity.last_update.elapsed();
+            if idle >= stale_after && !activity.stale_reported {
+                activity.stale_reported = true;
+                warn!("Account {} has not updated for {} seconds", pubkey, idle.as_secs());
+                warnings.push(json!({
+                    "type": "account_activity_warning",
+                    "reason": "stale",
+                    "pubkey": pubkey,
+                    "secondsSinceUpdate": idle.as_secs(),
+                }));
+            }
+        }
+        for _ in &warnings {
+            self.count_warning();
+        }
+        warnings
+    }
+
+    fn count_warning(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.increment_activity_warnings();
+        }
+    }
+
+    /// Periodically publish stale account warnings in the background
+    pub fn start(self: Arc<Self>, publisher: Arc<Publisher>) {
+        let interval_secs = (self.config.stale_after_secs / 2).max(1);
+        info!(
+            "Tracking activity of {} accounts (stale after {}s, max {} updates/s)",
+            self.config.accounts.len(), self.config.stale_after_secs, self.config.max_updates_per_sec
+        );
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_secs(interval_secs));
+
+            loop {
+                interval.tick().await;
+                for warning in self.stale_accounts() {
+                    let key = warning["pubkey"].as_str().unwrap_or_default().to_owned();
+                    if let Err(e) = publisher.send(key, &warning).await {
+                        error!("Error publishing account activity warning: {:?}", e);
+                    }
+                }
+            }
+        });
+    }
+}
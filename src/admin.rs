@@ -0,0 +1,358 @@
+use std::{net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use yellowstone_grpc_proto::prelude::CommitmentLevel;
+
+use crate::{
+    dynamic::{DynamicFilterKind, DynamicFilters},
+    health::{Health, HealthStatus},
+    pause::{self, PauseMode},
+    quota, schemas,
+    sources::{GrpcEndpoint, UnaryApi, UnaryPool},
+    watch::SignatureWatch,
+};
+
+/// Admin HTTP server settings
+#[derive(Debug, Clone)]
+pub struct AdminConfig {
+    pub listen: SocketAddr,
+    /// Bearer token of the endpoints changing the stream, which are refused without one
+    pub token: Option<String>,
+}
+
+/// State shared by the admin handlers
+pub struct AdminState {
+    unary: Arc<UnaryPool>,
+    signatures: Option<Arc<SignatureWatch>>,
+    health: Option<Arc<Health>>,
+    dynamic: Option<Arc<DynamicFilters>>,
+    token: Option<String>,
+}
+
+impl AdminState {
+    pub fn new(endpoint: GrpcEndpoint) -> Self {
+        Self {
+            unary: UnaryPool::shared(&endpoint),
+            signatures: None,
+            health: None,
+            dynamic: None,
+            token: None,
+        }
+    }
+
+    /// Serve the `/healthz` and `/readyz` probes and `/status` from `health`
+    pub fn with_health(mut self, health: Arc<Health>) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Manage the signature watch through `/signatures`
+    pub fn with_signature_watch(mut self, signatures: Arc<SignatureWatch>) -> Self {
+        self.signatures = Some(signatures);
+        self
+    }
+
+    /// Manage the dynamic filters through `/filters`
+    pub fn with_dynamic_filters(mut self, dynamic: Arc<DynamicFilters>) -> Self {
+        self.dynamic = Some(dynamic);
+        self
+    }
+}
+
+/// Bind the admin server and serve it in the background
+pub async fn start(config: AdminConfig, mut state: AdminState) -> anyhow::Result<()> {
+    state.token = config.token;
+    let router = Router::new()
+        .route("/healthz", get(liveness))
+        .route("/readyz", get(readiness))
+        .route("/status", get(status))
+        .route("/pause", get(pause_status).post(pause_stream))
+        .route("/resume", post(resume_stream))
+        .route("/schemas", get(schema_names))
+        .route("/schemas/:name", get(schema))
+        .route("/unary/latest-blockhash", get(latest_blockhash))
+        .route("/unary/block-height", get(block_height))
+        .route("/unary/slot", get(slot))
+        .route("/unary/blockhash-valid", get(blockhash_valid))
+        .route("/signatures", get(watched_signatures).post(watch_signatures))
+        .route("/signatures/:signature", delete(unwatch_signature))
+        .route("/filters", get(dynamic_filters).post(register_filter))
+        .route("/filters/:id", delete(remove_filter))
+        .with_state(Arc::new(state));
+
+    let listener = tokio::net::TcpListener::bind(config.listen).await?;
+    info!("Admin server listening on {}", config.listen);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("Admin server stopped: {:?}", e);
+        }
+    });
+    Ok(())
+}
+
+/// Error response with a JSON `error` message
+struct AdminError(StatusCode, String);
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type AdminResult = Result<Json<Value>, AdminError>;
+
+/// Check the bearer token of a request to an endpoint changing the stream
+fn authorize(state: &AdminState, headers: &HeaderMap) -> Result<(), AdminError> {
+    let Some(token) = &state.token else {
+        return Err(AdminError(StatusCode::FORBIDDEN, "set `admin.token` to use this endpoint".to_string()));
+    };
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if bearer != Some(token.as_str()) {
+        return Err(AdminError(StatusCode::UNAUTHORIZED, "invalid token".to_string()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct UnaryQuery {
+    /// `processed`, `confirmed` or `finalized`, the endpoint default when omitted
+    commitment: Option<String>,
+    blockhash: Option<String>,
+}
+
+impl UnaryQuery {
+    fn commitment(&self) -> Result<Option<CommitmentLevel>, AdminError> {
+        self.commitment
+            .as_ref()
+            .map(|commitment| {
+                CommitmentLevel::from_str_name(&commitment.to_uppercase()).ok_or_else(|| {
+                    AdminError(StatusCode::BAD_REQUEST, format!("invalid commitment `{}`", commitment))
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Run a unary call on the endpoint's unary pool
+async fn call<T>(
+    state: &AdminState,
+    f: impl for<'a> FnOnce(&'a mut Box<dyn UnaryApi>) -> futures::future::BoxFuture<'a, anyhow::Result<T>>,
+) -> Result<T, AdminError> {
+    state
+        .unary
+        .call(f)
+        .await
+        .map_err(|e| AdminError(StatusCode::BAD_GATEWAY, format!("{:#}", e)))
+}
+
+async fn latest_blockhash(State(state): State<Arc<AdminState>>, Query(query): Query<UnaryQuery>) -> AdminResult {
+    let commitment = query.commitment()?;
+    let response = call(&state, |client| client.get_latest_blockhash(commitment)).await?;
+    Ok(Json(json!({
+        "slot": response.slot,
+        "blockhash": response.blockhash,
+        "lastValidBlockHeight": response.last_valid_block_height,
+    })))
+}
+
+async fn block_height(State(state): State<Arc<AdminState>>, Query(query): Query<UnaryQuery>) -> AdminResult {
+    let commitment = query.commitment()?;
+    let response = call(&state, |client| client.get_block_height(commitment)).await?;
+    Ok(Json(json!({ "blockHeight": response.block_height })))
+}
+
+async fn slot(State(state): State<Arc<AdminState>>, Query(query): Query<UnaryQuery>) -> AdminResult {
+    let commitment = query.commitment()?;
+    let response = call(&state, |client| client.get_slot(commitment)).await?;
+    Ok(Json(json!({ "slot": response.slot })))
+}
+
+async fn blockhash_valid(State(state): State<Arc<AdminState>>, Query(query): Query<UnaryQuery>) -> AdminResult {
+    let commitment = query.commitment()?;
+    let blockhash = query
+        .blockhash
+        .clone()
+        .ok_or_else(|| AdminError(StatusCode::BAD_REQUEST, "missing `blockhash` parameter".to_string()))?;
+    let response = call(&state, |client| client.is_blockhash_valid(blockhash, commitment)).await?;
+    Ok(Json(json!({
+        "slot": response.slot,
+        "valid": response.valid,
+    })))
+}
+
+/// Health report with `503` when the status is at least `unhealthy`
+fn probe(state: &AdminState, unhealthy: HealthStatus) -> Response {
+    let Some(health) = &state.health else {
+        return (StatusCode::OK, Json(json!({ "status": HealthStatus::Healthy }))).into_response();
+    };
+    let (status, report) = health.report();
+    let code = if status >= unhealthy {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+    (code, Json(report)).into_response()
+}
+
+/// Fails only when the streamer is dead, so reconnects do not restart the pod
+async fn liveness(State(state): State<Arc<AdminState>>) -> Response {
+    probe(&state, HealthStatus::Dead)
+}
+
+/// Fails while degraded, e.g. reconnecting
+async fn readiness(State(state): State<Arc<AdminState>>) -> Response {
+    probe(&state, HealthStatus::Degraded)
+}
+
+/// Connection state of the streams with their recent transitions, and the bytes received
+async fn status(State(state): State<Arc<AdminState>>) -> AdminResult {
+    let mut status = match &state.health {
+        Some(health) => health.status(),
+        None => json!({ "status": HealthStatus::Healthy, "streams": [] }),
+    };
+    status["receivedBytes"] = quota::report();
+    status["pause"] = pause::report();
+    Ok(Json(status))
+}
+
+#[derive(Debug, Deserialize)]
+struct PauseRequest {
+    #[serde(default)]
+    mode: PauseMode,
+}
+
+async fn pause_status() -> AdminResult {
+    Ok(Json(pause::report()))
+}
+
+/// Pause publishing, or the subscriptions with `{"mode": "subscription"}`
+async fn pause_stream(
+    State(state): State<Arc<AdminState>>,
+    headers: HeaderMap,
+    request: Option<Json<PauseRequest>>,
+) -> AdminResult {
+    authorize(&state, &headers)?;
+    pause::pause(request.map(|Json(request)| request.mode).unwrap_or_default());
+    Ok(Json(pause::report()))
+}
+
+async fn resume_stream(State(state): State<Arc<AdminState>>, headers: HeaderMap) -> AdminResult {
+    authorize(&state, &headers)?;
+    let resumed = pause::resume();
+    let mut report = pause::report();
+    report["resumed"] = json!(resumed);
+    Ok(Json(report))
+}
+
+/// Names of the record types with a JSON Schema
+async fn schema_names() -> AdminResult {
+    let names: Vec<&str> = schemas::schemas().into_iter().map(|(name, _)| name).collect();
+    Ok(Json(json!({ "schemas": names })))
+}
+
+/// JSON Schema of a record type, `/schemas/transaction` or `/schemas/transaction.schema.json`
+async fn schema(Path(name): Path<String>) -> AdminResult {
+    let name = name.trim_end_matches(".schema.json");
+    schemas::schema(name)
+        .map(Json)
+        .ok_or_else(|| AdminError(StatusCode::NOT_FOUND, format!("no schema for record type `{}`", name)))
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchRequest {
+    signatures: Vec<String>,
+}
+
+fn signature_watch(state: &AdminState) -> Result<&SignatureWatch, AdminError> {
+    state
+        .signatures
+        .as_deref()
+        .ok_or_else(|| AdminError(StatusCode::NOT_FOUND, "signature watch is disabled".to_string()))
+}
+
+async fn watched_signatures(State(state): State<Arc<AdminState>>) -> AdminResult {
+    Ok(Json(json!({ "signatures": signature_watch(&state)?.list() })))
+}
+
+async fn watch_signatures(
+    State(state): State<Arc<AdminState>>,
+    headers: HeaderMap,
+    Json(request): Json<WatchRequest>,
+) -> AdminResult {
+    authorize(&state, &headers)?;
+    let signature_watch = signature_watch(&state)?;
+    if let Some(invalid) = request.signatures.iter().find(|signature| Signature::from_str(signature).is_err()) {
+        return Err(AdminError(StatusCode::BAD_REQUEST, format!("invalid signature `{}`", invalid)));
+    }
+    let added = signature_watch
+        .add(request.signatures)
+        .map_err(|e| AdminError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(json!({ "added": added })))
+}
+
+async fn unwatch_signature(
+    State(state): State<Arc<AdminState>>,
+    headers: HeaderMap,
+    Path(signature): Path<String>,
+) -> AdminResult {
+    authorize(&state, &headers)?;
+    if !signature_watch(&state)?.remove(&signature) {
+        return Err(AdminError(StatusCode::NOT_FOUND, format!("signature `{}` is not watched", signature)));
+    }
+    Ok(Json(json!({ "removed": signature })))
+}
+
+#[derive(Debug, Deserialize)]
+struct FilterRequest {
+    kind: DynamicFilterKind,
+    accounts: Vec<String>,
+    ttl_secs: u64,
+}
+
+fn dynamic(state: &AdminState) -> Result<&DynamicFilters, AdminError> {
+    state
+        .dynamic
+        .as_deref()
+        .ok_or_else(|| AdminError(StatusCode::NOT_FOUND, "dynamic filters are disabled".to_string()))
+}
+
+async fn dynamic_filters(State(state): State<Arc<AdminState>>) -> AdminResult {
+    Ok(Json(json!({ "filters": dynamic(&state)?.list() })))
+}
+
+async fn register_filter(
+    State(state): State<Arc<AdminState>>,
+    headers: HeaderMap,
+    Json(request): Json<FilterRequest>,
+) -> AdminResult {
+    authorize(&state, &headers)?;
+    let dynamic = dynamic(&state)?;
+    if let Some(invalid) = request.accounts.iter().find(|account| Pubkey::from_str(account).is_err()) {
+        return Err(AdminError(StatusCode::BAD_REQUEST, format!("invalid account `{}`", invalid)));
+    }
+    let id = dynamic
+        .register(request.kind, request.accounts, Duration::from_secs(request.ttl_secs))
+        .map_err(|e| AdminError(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(json!({ "id": id, "expiresInSecs": request.ttl_secs })))
+}
+
+async fn remove_filter(State(state): State<Arc<AdminState>>, headers: HeaderMap, Path(id): Path<u64>) -> AdminResult {
+    authorize(&state, &headers)?;
+    if !dynamic(&state)?.remove(id) {
+        return Err(AdminError(StatusCode::NOT_FOUND, format!("filter {} is not registered", id)));
+    }
+    Ok(Json(json!({ "removed": id })))
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use yellowstone_grpc_records::{
+    archive::{self, JsonValue, Payload, Record},
+    AccountClosed, ProgramDeploy, SolTransfer, StakeEvent, TokenCreation, Vote,
+};
+
+/// Fields the streamer stamps on every record, carried by the envelope
+const STAMPS: [&str; 3] = ["streamerVersion", "historical", "idempotencyKey"];
+
+/// Typed record of a derived event with the fields it has no field for, `None` when `value` does
+/// not convert to `T`
+fn typed<T: DeserializeOwned + Serialize>(
+    value: &Value,
+    payload: fn(T) -> Payload,
+) -> Option<(Payload, BTreeMap<String, JsonValue>)> {
+    let record: T = serde_json::from_value(value.clone()).ok()?;
+    let known = serde_json::to_value(&record).ok()?;
+    let extra = value
+        .as_object()?
+        .iter()
+        .filter(|(name, _)| known.get(name.as_str()).is_none() && !STAMPS.contains(&name.as_str()))
+        .map(|(name, value)| (name.clone(), JsonValue::from(value)))
+        .collect();
+    Some((payload(record), extra))
+}
+
+/// Typed record of a derived event, told apart by its `event` or its fields
+fn derived_event(value: &Value) -> Option<(Payload, BTreeMap<String, JsonValue>)> {
+    let has = |names: &[&str]| names.iter().all(|name| value.get(name).is_some());
+    match value["event"].as_str() {
+        Some("account_closed") => typed(value, Payload::AccountClosed),
+        Some("delegate" | "deactivate" | "withdraw" | "split" | "merge") => typed(value, Payload::StakeEvent),
+        Some("new_mint" | "new_token_account") => typed(value, Payload::TokenCreation),
+        Some("deploy" | "upgrade" | "extend" | "set_authority" | "close") => typed(value, Payload::ProgramDeploy),
+        None if has(&["voteAccount", "kind", "slots"]) => typed(value, Payload::Vote),
+        None if has(&["from", "to", "lamports", "cpi"]) => typed(value, Payload::SolTransfer),
+        _ => None,
+    }
+}
+
+/// Archive a record behind the header pinning its schema version: derived events as their typed
+/// record, other records, and derived events that do not convert, as their JSON tree
+pub fn encode(key: &str, value: &Value) -> anyhow::Result<Vec<u8>> {
+    let (payload, extra) =
+        derived_event(value).unwrap_or_else(|| (Payload::Json(JsonValue::from(value)), BTreeMap::new()));
+    let record = Record {
+        key: key.to_owned(),
+        streamer_version: value["streamerVersion"].as_str().unwrap_or_default().to_owned(),
+        historical: value["historical"].as_bool().unwrap_or_default(),
+        idempotency_key: value["idempotencyKey"].as_str().map(str::to_owned),
+        extra,
+        payload,
+    };
+    archive::to_bytes(&record).map_err(|e| anyhow::anyhow!("failed to archive record: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use yellowstone_grpc_records::archive::{AlignedVec, ArchivedPayload};
+
+    use super::*;
+
+    fn aligned(payload: Vec<u8>) -> AlignedVec {
+        let mut aligned = AlignedVec::new();
+        aligned.extend_from_slice(&payload);
+        aligned
+    }
+
+    #[test]
+    fn archives_derived_events_as_typed_records() {
+        let transfer = json!({
+            "from": "sender",
+            "to": "recipient",
+            "lamports": 1_000_000,
+            "signature": "signature",
+            "slot": 7,
+            "cpi": false,
+            "label": "exchange",
+            "streamerVersion": "0.1.1",
+        });
+        let payload = aligned(encode("recipient", &transfer).unwrap());
+        let record = archive::read(&payload).unwrap();
+        assert_eq!(record.streamer_version.as_str(), "0.1.1");
+        assert!(matches!(&record.payload, ArchivedPayload::SolTransfer(transfer) if transfer.lamports == 1_000_000));
+        assert_eq!(record.extra.keys().map(|name| name.as_str()).collect::<Vec<_>>(), ["label"]);
+
+        // Unknown kinds and events that do not convert stay JSON trees
+        for value in [json!({ "slot": 7, "status": "CONFIRMED" }), json!({ "event": "withdraw" })] {
+            let payload = aligned(encode("key", &value).unwrap());
+            assert!(matches!(archive::read(&payload).unwrap().payload, ArchivedPayload::Json(_)));
+        }
+    }
+}
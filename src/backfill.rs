@@ -0,0 +1,184 @@
+use std::{io::Write, sync::Arc};
+
+use anyhow::Context;
+use log::{info, warn};
+use tokio::sync::mpsc;
+use yellowstone_grpc_proto::prelude::SubscribeRequest;
+
+use crate::{
+    batching::BatchController,
+    bloom, canonical,
+    config::{self, YellowstoneGrpcConfig},
+    deploys, formatters, history, mints,
+    outbox::Outbox,
+    provision,
+    publisher::Publisher,
+    routing::{self, GroupRouter},
+    sinks::SinkConnector,
+    stake, stateful, transaction_processor, transfers, votes, ProcessingMessage, ProcessorOptions, CHANNEL_SIZE,
+};
+
+/// Block provider of the configured history source, at `rpc_url` when set
+async fn block_provider(
+    config: &YellowstoneGrpcConfig,
+    rpc_url: Option<String>,
+) -> anyhow::Result<Box<dyn history::BlockProvider>> {
+    let history = config.yellowstone_grpc.history.as_ref();
+    let endpoint = rpc_url
+        .or_else(|| history.and_then(|history| history.endpoint.clone()))
+        .or_else(|| config.yellowstone_grpc.rpc_endpoint.clone());
+    history::block_provider(history, endpoint).await
+}
+
+/// Fetch the block of `slot` and print the records the formatters build from it as JSON lines,
+/// or publish it like a backfill of that one slot
+pub(crate) async fn fetch_slot(
+    config: &YellowstoneGrpcConfig,
+    slot: u64,
+    rpc_url: Option<String>,
+    publish: bool,
+) -> anyhow::Result<()> {
+    if publish {
+        return run(config, slot, slot, rpc_url).await;
+    }
+
+    let provider = block_provider(config, rpc_url).await?;
+    let Some(block) = provider.get_block(slot).await? else {
+        anyhow::bail!("no block for slot {}", slot);
+    };
+    let flatten = config.yellowstone_grpc.flatten_instructions.unwrap_or(false);
+    let numbers = config.get_number_format();
+    let mut stdout = std::io::stdout().lock();
+    for message in history::block_updates(slot, block).into_iter().map(ProcessingMessage::from_update) {
+        let record = match message {
+            Ok(ProcessingMessage::Transaction(tx)) => {
+                let mut record = formatters::format_transaction(tx)?;
+                if flatten {
+                    formatters::flatten_instructions(&mut record);
+                }
+                record
+            },
+            Ok(ProcessingMessage::BlockMetadata(block_meta)) => formatters::format_block_meta(block_meta)?,
+            _ => continue,
+        };
+        stdout.write_all(&canonical::to_vec(&record, numbers))?;
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
+
+/// Fetch blocks for `from_slot..=to_slot` from the configured history source and publish them like live updates
+pub(crate) async fn run(
+    config: &YellowstoneGrpcConfig,
+    from_slot: u64,
+    to_slot: u64,
+    rpc_url: Option<String>,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(from_slot <= to_slot, "--from-slot must not be greater than --to-slot");
+    let provider = block_provider(config, rpc_url).await?;
+    // Blocks hold every transaction of their slot, only those the live subscription streams are published
+    let (request, _) = config::get_subscribe_request(&config.yellowstone_grpc.filters.load_files().await?, None)?;
+    let groups = GroupRouter::new(config);
+
+    let sinks = Arc::new(SinkConnector::connect(config, None).await?);
+    provision::provision(&sinks, config).await?;
+    let publisher = Arc::new(Publisher::connect_main(&sinks, config, &config.get_finalized_topic()).await?);
+    let suffix = (config.get_commitment_topics().len() > 1).then_some("finalized");
+
+    let (tx_sender, tx_receiver) = mpsc::channel::<ProcessingMessage>(CHANNEL_SIZE);
+    let tx_handle = tokio::spawn(transaction_processor(tx_receiver, ProcessorOptions {
+        publisher: Arc::clone(&publisher),
+        format: config.get_record_format(),
+        metrics: None,
+        historical: true,
+        activity: None,
+        redactor: config.get_redactor(),
+        labels: config.get_labels()?.map(Arc::new),
+        producers: config.get_producer_enrichment().map(Arc::new),
+        checkpoint: None,
+        correlator: config.get_block_correlator(),
+        pricing: config.get_price_oracle().map(Arc::new),
+        routes: routing::connect(&sinks, config, suffix).await?,
+        max_in_flight: config.yellowstone_grpc.max_in_flight,
+        batching: config
+            .get_batching_config()
+            .map(|batching| BatchController::new(batching, CHANNEL_SIZE, None)),
+        bloom: bloom::connect(&sinks, config, suffix).await?,
+        account_diff: config.get_account_differ(),
+        stateful: stateful::build(&config.get_stateful_processors()),
+        integrity: config.get_integrity_chain(),
+        retention: None,
+        program_filter: config.yellowstone_grpc.filters.top_level_program_filter(),
+        priority_fee_filter: config.yellowstone_grpc.filters.priority_fee_filter(),
+        reloaded_filters: None,
+        flatten_instructions: config.yellowstone_grpc.flatten_instructions.unwrap_or(false),
+        idempotency_keys: config.yellowstone_grpc.idempotency_keys.unwrap_or(false),
+        // Backfills run to completion
+        drain_expired: tokio::sync::watch::channel(false).1,
+        votes: votes::connect(&sinks, config, suffix).await?,
+        stake_events: stake::connect(&sinks, config, suffix).await?,
+        mint_events: mints::connect(&sinks, config, suffix).await?,
+        program_deploys: deploys::connect(&sinks, config, suffix).await?,
+        transfers: transfers::connect(&sinks, config, suffix).await?,
+        outbox: config.get_outbox_config().map(Outbox::new),
+        // Windows are wall-clock time, meaningless when replaying history
+        stats: None,
+        // Blocks carry no transaction status updates
+        statuses: None,
+        // Nor account updates
+        latest_accounts: None,
+        account_closures: None,
+        blobs: None,
+        web: None,
+    }));
+
+    info!("Backfilling slots {} to {}", from_slot, to_slot);
+    let sent = send_blocks(&*provider, &request, groups.as_ref(), &tx_sender, from_slot, to_slot).await;
+
+    // The records of the blocks sent before a failure are still published
+    let _ = tx_sender.send(ProcessingMessage::Shutdown).await;
+    let _ = tx_handle.await;
+    let flushed = publisher.flush().await;
+    sent?;
+    flushed?;
+    info!("Backfill of slots {} to {} complete", from_slot, to_slot);
+
+    Ok(())
+}
+
+/// Send the updates of the blocks of `from_slot..=to_slot` matched by `request` to the processor
+async fn send_blocks(
+    provider: &dyn history::BlockProvider,
+    request: &SubscribeRequest,
+    groups: Option<&GroupRouter>,
+    tx_sender: &mpsc::Sender<ProcessingMessage>,
+    from_slot: u64,
+    to_slot: u64,
+) -> anyhow::Result<()> {
+    for slot in from_slot..=to_slot {
+        let block = provider.get_block(slot).await;
+        let Some(block) = block.with_context(|| format!("failed to fetch the block of slot {}", slot))? else {
+            warn!("No block for slot {}, skipping", slot);
+            continue;
+        };
+
+        for update in history::filter_updates(request, history::block_updates(slot, block)) {
+            let matched = groups.and_then(|groups| groups.route(&update.filters));
+            let message = match update.update_oneof.map(ProcessingMessage::from_update) {
+                Some(Ok(message)) => message,
+                _ => {
+                    warn!("Skipping an update of slot {} the processor does not handle", slot);
+                    continue;
+                },
+            };
+            let message = match matched {
+                Some(matched) => ProcessingMessage::Grouped(Box::new(message), matched),
+                None => message,
+            };
+            if tx_sender.send(message).await.is_err() {
+                anyhow::bail!("processor channel closed during backfill");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::metrics::Metrics;
+
+/// Adaptive batching settings
+#[derive(Debug, Clone)]
+pub struct BatchingConfig {
+    pub min_linger: Duration,
+    pub max_linger: Duration,
+    /// Producer batch size in bytes
+    pub batch_size: usize,
+}
+
+/// Decides when a pipeline flushes its producers, from the backlog of the processing channel.
+///
+/// With an empty channel records are flushed after `min_linger`. The linger grows linearly with
+/// the backlog up to `max_linger` for a full channel, letting the producer fill larger batches.
+pub struct BatchController {
+    config: BatchingConfig,
+    capacity: usize,
+    linger: Duration,
+    last_flush: Instant,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl BatchController {
+    pub fn new(config: BatchingConfig, capacity: usize, metrics: Option<Arc<Metrics>>) -> Self {
+        Self {
+            linger: config.min_linger,
+            config,
+            capacity: capacity.max(1),
+            last_flush: Instant::now(),
+            metrics,
+        }
+    }
+
+    fn linger_for(&self, backlog: usize) -> Duration {
+        let pressure = backlog.min(self.capacity) as f64 / self.capacity as f64;
+        let range = self.config.max_linger.saturating_sub(self.config.min_linger);
+        self.config.min_linger + range.mul_f64(pressure)
+    }
+
+    /// Whether to flush now with `backlog` messages waiting in the channel
+    pub fn should_flush(&mut self, backlog: usize) -> bool {
+        let linger = self.linger_for(backlog);
+        if linger != self.linger {
+            self.linger = linger;
+            if let Some(metrics) = &self.metrics {
+                metrics.set_batch_linger_ms(linger.as_millis() as u64);
+            }
+        }
+
+        if self.last_flush.elapsed() < linger {
+            return false;
+        }
+        self.last_flush = Instant::now();
+        if let Some(metrics) = &self.metrics {
+            metrics.increment_batch_flushes();
+        }
+        true
+    }
+}
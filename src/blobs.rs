@@ -0,0 +1,145 @@
+use log::{error, info};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{config::YellowstoneGrpcConfig, sinks::{Sink, SinkConnector}};
+
+/// Where the data of large accounts is stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobTarget {
+    /// A topic of the sink, keyed by hash
+    #[default]
+    Topic,
+    /// An S3 bucket, one object per hash
+    S3,
+}
+
+/// Account data passthrough settings
+#[derive(Debug, Clone)]
+pub struct BlobConfig {
+    /// Accounts with at least this many bytes of data are offloaded
+    pub min_size: usize,
+    pub target: BlobTarget,
+    pub topic: String,
+    pub bucket: Option<String>,
+    pub prefix: String,
+    pub region: Option<String>,
+}
+
+enum Store {
+    Topic { topic: String, sink: Box<dyn Sink> },
+    #[cfg(feature = "s3")]
+    S3 { client: aws_sdk_s3::Client, bucket: String },
+}
+
+/// Publishes the data of large accounts byte-exact, neither base64 encoded, compressed nor
+/// encrypted, to a blob topic or an object store, content-addressed by its SHA-256. The record of
+/// the main topic carries a pointer to the blob instead of the data, keeping the topic light.
+pub struct BlobStore {
+    store: Store,
+    min_size: usize,
+    prefix: String,
+}
+
+impl BlobStore {
+    /// Store the data of an account when it is large enough, returning the pointer replacing
+    /// it in the record. `None` for smaller accounts, and when storing fails so the record keeps
+    /// its data.
+    pub async fn offload(&self, data: &[u8]) -> Option<Value> {
+        if data.len() < self.min_size {
+            return None;
+        }
+        let hash = hex::encode(Sha256::digest(data));
+        let key = format!("{}{}", self.prefix, hash);
+        match self.put(&key, data).await {
+            Ok(location) => Some(json!({ "hash": hash, "size": data.len(), "location": location })),
+            Err(e) => {
+                error!("Error storing account data blob {}, publishing it inline: {:?}", key, e);
+                None
+            },
+        }
+    }
+
+    async fn put(&self, key: &str, data: &[u8]) -> anyhow::Result<Value> {
+        match &self.store {
+            Store::Topic { topic, sink } => {
+                // Acknowledged before the pointer is published, consumers never miss the blob
+                sink.send(key.to_owned(), data.to_vec()).await?.await?;
+                Ok(json!({ "topic": topic, "key": key }))
+            },
+            #[cfg(feature = "s3")]
+            Store::S3 { client, bucket } => {
+                client
+                    .put_object()
+                    .bucket(bucket)
+                    .key(key)
+                    .body(data.to_vec().into())
+                    .send()
+                    .await?;
+                Ok(json!({ "bucket": bucket, "key": key }))
+            },
+        }
+    }
+
+    /// Replace the data of an account record with the pointer to its blob
+    pub fn attach(record: &mut Value, pointer: Value) {
+        let Some(object) = record.as_object_mut() else {
+            return;
+        };
+        object.remove("data");
+        object.insert("blob".to_owned(), pointer);
+    }
+
+    /// Flush the blobs, at shutdown
+    pub async fn finish(&self) {
+        if let Store::Topic { sink, .. } = &self.store {
+            if let Err(e) = sink.flush().await {
+                error!("Error flushing account data blobs: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Connect the blob store, `suffix` is appended to the blob topic in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<BlobStore>> {
+    let Some(mut blob_config) = config.get_blob_config() else {
+        return Ok(None);
+    };
+    if let Some(suffix) = suffix {
+        blob_config.topic = format!("{}-{}", blob_config.topic, suffix);
+    }
+
+    let store = match blob_config.target {
+        BlobTarget::Topic => {
+            info!("Publishing account data of {}+ bytes to topic '{}'", blob_config.min_size, blob_config.topic);
+            Store::Topic {
+                sink: sinks.open(&blob_config.topic).await?,
+                topic: blob_config.topic,
+            }
+        },
+        #[cfg(feature = "s3")]
+        BlobTarget::S3 => {
+            let bucket = blob_config.bucket.clone().ok_or_else(|| anyhow::anyhow!("`bucket` is required by s3"))?;
+            info!("Storing account data of {}+ bytes in bucket '{}'", blob_config.min_size, bucket);
+            let sdk_config = crate::sinks::aws_sdk_config(blob_config.region.as_deref()).await;
+            Store::S3 {
+                client: aws_sdk_s3::Client::new(&sdk_config),
+                bucket,
+            }
+        },
+        #[cfg(not(feature = "s3"))]
+        BlobTarget::S3 => anyhow::bail!("`account_blobs.target: s3` requires the `s3` feature"),
+    };
+    Ok(Some(BlobStore {
+        store,
+        min_size: blob_config.min_size,
+        prefix: blob_config.prefix,
+    }))
+}
@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+
+use serde_json::json;
+
+use crate::{codec, config::YellowstoneGrpcConfig, derived::DerivedStream, sinks::SinkConnector};
+
+/// Buckets still open behind the newest one, for transactions arriving slightly out of order
+const OPEN_BUCKETS: u64 = 2;
+
+/// Signature bloom filter settings
+#[derive(Debug, Clone)]
+pub struct BloomConfig {
+    pub topic: String,
+    pub slots_per_filter: u64,
+    pub false_positive_rate: f64,
+}
+
+/// Bloom filters of the transaction signatures seen in each range of `slots_per_filter` slots.
+///
+/// A range is closed, and its filter published, once transactions of a range two ranges later
+/// arrive. Filters are sized for the signatures of their range, so each one has the configured
+/// false positive rate. Signatures are uniformly random, so the bit positions are derived
+/// directly from their bytes: `h1` and `h2` are the little-endian `u64`s at offsets 0 and 8
+/// (`h2` with its lowest bit set), and hash `i` sets bit `(h1 + i * h2) mod bits` (wrapping
+/// arithmetic), bit `b` being bit `b % 8` of byte `b / 8`.
+pub struct SignatureBloom {
+    stream: DerivedStream,
+    config: BloomConfig,
+    buckets: BTreeMap<u64, Vec<[u8; 16]>>,
+}
+
+impl SignatureBloom {
+    /// Record the signature of a transaction at `slot`
+    pub fn observe(&mut self, slot: u64, signature: &[u8]) {
+        let Some(prefix) = signature.get(..16).and_then(|prefix| <[u8; 16]>::try_from(prefix).ok()) else {
+            return;
+        };
+        self.buckets.entry(slot / self.config.slots_per_filter).or_default().push(prefix);
+    }
+
+    /// Publish the filters of the ranges that are closed
+    pub async fn publish_closed(&mut self) {
+        let Some(newest) = self.buckets.last_key_value().map(|(bucket, _)| *bucket) else {
+            return;
+        };
+        while let Some(entry) = self.buckets.first_entry() {
+            if *entry.key() + OPEN_BUCKETS > newest {
+                break;
+            }
+            let bucket = *entry.key();
+            let signatures = entry.remove();
+            self.publish(bucket, &signatures).await;
+        }
+    }
+
+    /// Publish the filters of every open range, at shutdown
+    pub async fn finish(&mut self) {
+        for (bucket, signatures) in std::mem::take(&mut self.buckets) {
+            self.publish(bucket, &signatures).await;
+        }
+        self.stream.flush().await;
+    }
+
+    async fn publish(&self, bucket: u64, signatures: &[[u8; 16]]) {
+        let (bits, hashes) = filter_size(signatures.len(), self.config.false_positive_rate);
+        let mut filter = vec![0u8; (bits / 8) as usize];
+        for signature in signatures {
+            let h1 = u64::from_le_bytes(signature[..8].try_into().expect("8 bytes"));
+            let h2 = u64::from_le_bytes(signature[8..].try_into().expect("8 bytes")) | 1;
+            for i in 0..hashes {
+                let bit = h1.wrapping_add(i.wrapping_mul(h2)) % bits;
+                filter[(bit / 8) as usize] |= 1 << (bit % 8);
+            }
+        }
+
+        let from_slot = bucket * self.config.slots_per_filter;
+        let record = json!({
+            "fromSlot": from_slot,
+            "toSlot": from_slot + self.config.slots_per_filter - 1,
+            "count": signatures.len(),
+            "bits": bits,
+            "hashes": hashes,
+            "filter": codec::encode_base64(&filter),
+        });
+        self.stream.send(from_slot.to_string(), &record).await;
+    }
+}
+
+/// Bits (a multiple of 8) and hash count of a filter holding `count` items at `false_positive_rate`
+fn filter_size(count: usize, false_positive_rate: f64) -> (u64, u64) {
+    let count = count.max(1) as f64;
+    let ln2 = std::f64::consts::LN_2;
+    let bits = (-count * false_positive_rate.ln() / (ln2 * ln2)).ceil().max(8.0) as u64;
+    let bits = bits.div_ceil(8) * 8;
+    let hashes = ((bits as f64 / count) * ln2).round().clamp(1.0, 16.0) as u64;
+    (bits, hashes)
+}
+
+/// Connect the signature bloom topic, `suffix` is appended to it in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<SignatureBloom>> {
+    let Some(bloom_config) = config.get_signature_bloom_config() else {
+        return Ok(None);
+    };
+
+    Ok(Some(SignatureBloom {
+        stream: DerivedStream::connect(sinks, config, "signature bloom filters", &bloom_config.topic, suffix).await?,
+        config: bloom_config,
+        buckets: BTreeMap::new(),
+    }))
+}
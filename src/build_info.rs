@@ -0,0 +1,12 @@
+/// Crate version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Short commit the binary was built from
+pub const GIT_SHA: &str = env!("VERGEN_GIT_SHA");
+
+/// RFC 3339 time the binary was built at
+pub const BUILD_TIMESTAMP: &str = env!("VERGEN_BUILD_TIMESTAMP");
+
+/// `<version>+<commit>`, stamped on every record as `streamerVersion` so consumers can tell which
+/// deployed build produced it
+pub const FULL_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+", env!("VERGEN_GIT_SHA"));
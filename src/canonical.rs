@@ -0,0 +1,164 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Number, Value};
+
+/// Largest integer a JavaScript number holds exactly, 2^53 - 1
+pub const MAX_SAFE_INTEGER: u64 = 9_007_199_254_740_991;
+
+/// How integers a JavaScript number cannot hold exactly are written in JSON records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LargeIntegers {
+    /// As JSON numbers, exact for 64-bit parsers
+    #[default]
+    Number,
+    /// Integers beyond ±(2^53 - 1) as decimal strings, smaller ones stay numbers
+    String,
+}
+
+/// Suffixes, compared ignoring case, of the fields `big_numbers` writes as strings
+const BIG_NUMBER_SUFFIXES: [&str; 8] = ["lamports", "amount", "balance", "balances", "fee", "supply", "slot", "slots"];
+
+/// How numbers are written in JSON records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub large_integers: LargeIntegers,
+    /// Lamport, amount and slot integers as strings whatever their value, token amounts as
+    /// `{value, decimals}`
+    pub big_numbers: bool,
+}
+
+/// Canonical JSON of a record: object keys sorted by their UTF-8 bytes at every level, no
+/// whitespace, strings escaped like serde_json and floats in their shortest round-trip form.
+/// The same record always serializes to the same bytes, whatever the order its fields were added.
+pub fn to_vec(value: &Value, format: NumberFormat) -> Vec<u8> {
+    let mut out = Vec::with_capacity(256);
+    write(value, None, format, &mut out);
+    out
+}
+
+/// Write `value`, the value of the field `field` or an item of its array
+fn write(value: &Value, field: Option<&str>, format: NumberFormat, out: &mut Vec<u8>) {
+    match value {
+        Value::Object(object) if format.big_numbers && is_token_amount(object) => {
+            let amount = json!({ "value": object["amount"], "decimals": object["decimals"] });
+            write(&amount, field, format, out);
+        },
+        Value::Object(object) => {
+            let mut fields: Vec<_> = object.iter().collect();
+            fields.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+            out.push(b'{');
+            for (index, (name, value)) in fields.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(b',');
+                }
+                write_json(name, out);
+                out.push(b':');
+                write(value, Some(name), format, out);
+            }
+            out.push(b'}');
+        },
+        Value::Array(items) => {
+            out.push(b'[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(b',');
+                }
+                write(item, field, format, out);
+            }
+            out.push(b']');
+        },
+        Value::Number(number) if quoted(number, field, format) => write_json(&number.to_string(), out),
+        scalar => write_json(scalar, out),
+    }
+}
+
+fn write_json(value: &(impl Serialize + ?Sized), out: &mut Vec<u8>) {
+    serde_json::to_writer(out, value).expect("JSON is written to memory");
+}
+
+/// Whether `number` is written as a string
+fn quoted(number: &Number, field: Option<&str>, format: NumberFormat) -> bool {
+    let integer = number.is_u64() || number.is_i64();
+    (format.large_integers == LargeIntegers::String && !is_safe(number))
+        || (format.big_numbers && integer && field.is_some_and(is_big_number_field))
+}
+
+fn is_big_number_field(name: &str) -> bool {
+    let name = name.as_bytes();
+    BIG_NUMBER_SUFFIXES.iter().any(|suffix| {
+        name.len() >= suffix.len() && name[name.len() - suffix.len()..].eq_ignore_ascii_case(suffix.as_bytes())
+    })
+}
+
+/// A token amount of the parsed transactions, `uiTokenAmount` of the token balances and
+/// `tokenAmount` of checked instructions, whose raw `amount` is already a string
+fn is_token_amount(object: &Map<String, Value>) -> bool {
+    object.get("amount").is_some_and(Value::is_string)
+        && object.get("decimals").is_some_and(Value::is_u64)
+        && object.contains_key("uiAmountString")
+}
+
+/// Whether a JavaScript number holds `number` exactly, floats are already doubles
+fn is_safe(number: &Number) -> bool {
+    match (number.as_u64(), number.as_i64()) {
+        (Some(value), _) => value <= MAX_SAFE_INTEGER,
+        (None, Some(value)) => value.unsigned_abs() <= MAX_SAFE_INTEGER,
+        (None, None) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(value: Value, format: NumberFormat) -> String {
+        String::from_utf8(to_vec(&value, format)).unwrap()
+    }
+
+    #[test]
+    fn sorts_keys_at_every_level() {
+        let value = json!({ "b": 1, "a": { "d": [true, null], "c": "x" } });
+        assert_eq!(encode(value, NumberFormat::default()), r#"{"a":{"c":"x","d":[true,null]},"b":1}"#);
+    }
+
+    #[test]
+    fn quotes_unsafe_integers_only_when_asked() {
+        let value = json!({ "small": MAX_SAFE_INTEGER, "large": MAX_SAFE_INTEGER + 1, "negative": -(1i64 << 60) });
+        assert_eq!(
+            encode(value.clone(), NumberFormat::default()),
+            r#"{"large":9007199254740992,"negative":-1152921504606846976,"small":9007199254740991}"#
+        );
+        let strings = NumberFormat {
+            large_integers: LargeIntegers::String,
+            big_numbers: false,
+        };
+        assert_eq!(
+            encode(value, strings),
+            r#"{"large":"9007199254740992","negative":"-1152921504606846976","small":9007199254740991}"#
+        );
+    }
+
+    #[test]
+    fn big_numbers_quotes_amount_fields_and_splits_token_amounts() {
+        let format = NumberFormat {
+            large_integers: LargeIntegers::String,
+            big_numbers: true,
+        };
+        let value = json!({
+            "slot": 5,
+            "preBalances": [1, 2],
+            "lamportsPerSignature": 5000,
+            "computeUnits": 7,
+            "ratio": 0.5,
+            "uiTokenAmount": { "amount": "1500", "decimals": 3, "uiAmount": 1.5, "uiAmountString": "1.5" },
+        });
+        assert_eq!(
+            encode(value, format),
+            concat!(
+                r#"{"computeUnits":7,"lamportsPerSignature":5000,"preBalances":["1","2"],"ratio":0.5,"slot":"5","#,
+                r#""uiTokenAmount":{"decimals":3,"value":"1500"}}"#
+            )
+        );
+    }
+}
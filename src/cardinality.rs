@@ -0,0 +1,81 @@
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use yellowstone_grpc_proto::prelude::{
+    subscribe_request_filter_accounts_filter::Filter as AccountsFilterOneof,
+    subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof, SubscribeRequest,
+    SubscribeRequestFilterAccountsFilter,
+};
+
+use crate::rpc::RpcClient;
+
+/// What happens when an owner filter matches more accounts than allowed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CardinalityAction {
+    /// Log a warning and subscribe anyway
+    #[default]
+    Warn,
+    /// Refuse to start
+    Refuse,
+}
+
+/// Owner cardinality guard settings
+#[derive(Debug, Clone)]
+pub struct OwnerCardinalityConfig {
+    pub max_accounts: u64,
+    pub action: CardinalityAction,
+    pub rpc: RpcClient,
+}
+
+/// Estimate how many accounts each owner filter of `request` matches before subscribing, since
+/// a broad owner such as the Token program overwhelms both the provider and the pipeline. The
+/// count comes from `getProgramAccounts` without account data and with the memcmp and data size
+/// filters of the subscription; filters without an RPC equivalent are ignored, so it is an upper
+/// bound. Owners the RPC cannot count, e.g. excluded from its secondary indexes, are logged.
+pub async fn check(config: &OwnerCardinalityConfig, request: &SubscribeRequest) -> anyhow::Result<()> {
+    for (name, filter) in &request.accounts {
+        let filters: Vec<Value> = filter.filters.iter().filter_map(rpc_filter).collect();
+        for owner in &filter.owner {
+            let count = match config.rpc.count_program_accounts(owner, &filters).await {
+                Ok(count) => count,
+                Err(e) => {
+                    warn!("Could not estimate the accounts of owner {} in filter '{}': {:#}", owner, name, e);
+                    continue;
+                },
+            };
+            if count <= config.max_accounts {
+                info!("Owner {} in filter '{}' matches {} accounts", owner, name, count);
+                continue;
+            }
+
+            let message = format!(
+                "owner {} in filter '{}' matches {} accounts, more than `owner_cardinality.max_accounts` ({})",
+                owner, name, count, config.max_accounts
+            );
+            match config.action {
+                CardinalityAction::Warn => warn!("{}, narrow it with memcmp or data size filters", message),
+                CardinalityAction::Refuse => anyhow::bail!("Refusing to subscribe: {}", message),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `getProgramAccounts` filter of a subscription filter, `None` for token account state and
+/// lamports filters
+fn rpc_filter(filter: &SubscribeRequestFilterAccountsFilter) -> Option<Value> {
+    match filter.filter.as_ref()? {
+        AccountsFilterOneof::Memcmp(memcmp) => {
+            let (bytes, encoding) = match memcmp.data.as_ref()? {
+                AccountsFilterMemcmpOneof::Bytes(data) => (bs58::encode(data).into_string(), "base58"),
+                AccountsFilterMemcmpOneof::Base58(data) => (data.clone(), "base58"),
+                AccountsFilterMemcmpOneof::Base64(data) => (data.clone(), "base64"),
+            };
+            Some(json!({ "memcmp": { "offset": memcmp.offset, "bytes": bytes, "encoding": encoding } }))
+        },
+        AccountsFilterOneof::Datasize(size) => Some(json!({ "dataSize": size })),
+        AccountsFilterOneof::TokenAccountState(_) | AccountsFilterOneof::Lamports(_) => None,
+    }
+}
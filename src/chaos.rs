@@ -0,0 +1,134 @@
+use std::{sync::OnceLock, time::Duration};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use log::warn;
+use rand::Rng;
+use yellowstone_grpc_proto::prelude::{subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdate};
+
+use crate::{
+    retention::RetentionCutoff,
+    sinks::{Delivery, Sink},
+    sources::{Source, Watermarks},
+};
+
+/// Fault injection settings, each probability is per update or per record
+#[derive(Debug, Clone)]
+pub struct ChaosConfig {
+    pub disconnect_probability: f64,
+    pub corrupt_probability: f64,
+    pub ack_delay_probability: f64,
+    pub ack_delay: Duration,
+}
+
+static CHAOS: OnceLock<ChaosConfig> = OnceLock::new();
+
+/// Enable fault injection for the sources and sinks created from now on
+pub fn install(config: ChaosConfig) {
+    warn!("Chaos mode enabled, injecting faults: {:?}", config);
+    let _ = CHAOS.set(config);
+}
+
+fn roll(probability: f64) -> bool {
+    probability > 0.0 && rand::thread_rng().gen_bool(probability.min(1.0))
+}
+
+/// Wrap a gRPC subscription so it fails and corrupts updates at random
+pub fn wrap_source(source: Box<dyn Source>) -> Box<dyn Source> {
+    match CHAOS.get() {
+        Some(config) => Box::new(ChaosSource {
+            inner: source,
+            config: config.clone(),
+        }),
+        None => source,
+    }
+}
+
+/// Wrap a sink so its acknowledgements are delayed at random
+pub fn wrap_sink(sink: Box<dyn Sink>) -> Box<dyn Sink> {
+    match CHAOS.get() {
+        Some(config) => Box::new(ChaosSink {
+            inner: sink,
+            config: config.clone(),
+        }),
+        None => sink,
+    }
+}
+
+struct ChaosSource {
+    inner: Box<dyn Source>,
+    config: ChaosConfig,
+}
+
+/// Damage an update the way a buggy upstream could: missing payloads and scrambled data
+fn corrupt(update: &mut SubscribeUpdate) {
+    match &mut update.update_oneof {
+        Some(UpdateOneof::Account(account)) => match &mut account.account {
+            Some(info) if !info.data.is_empty() => {
+                let index = rand::thread_rng().gen_range(0..info.data.len());
+                info.data[index] ^= 0xff;
+            },
+            _ => account.account = None,
+        },
+        Some(UpdateOneof::Transaction(transaction)) => transaction.transaction = None,
+        Some(UpdateOneof::BlockMeta(block_meta)) => block_meta.blockhash = "chaos".to_string(),
+        _ => {},
+    }
+}
+
+#[async_trait]
+impl Source for ChaosSource {
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+        if roll(self.config.disconnect_probability) {
+            return Some(Err(anyhow::anyhow!("chaos: injected disconnect")));
+        }
+        let mut update = self.inner.next().await?;
+        if let Ok(update) = &mut update {
+            if roll(self.config.corrupt_probability) {
+                corrupt(update);
+            }
+        }
+        Some(update)
+    }
+
+    async fn watermarks(&mut self) -> Option<Watermarks> {
+        self.inner.watermarks().await
+    }
+
+    async fn update_request(&mut self, request: SubscribeRequest) -> anyhow::Result<()> {
+        self.inner.update_request(request).await
+    }
+}
+
+struct ChaosSink {
+    inner: Box<dyn Sink>,
+    config: ChaosConfig,
+}
+
+#[async_trait]
+impl Sink for ChaosSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        self.send_at(key, payload, None).await
+    }
+
+    async fn send_at(&self, key: String, payload: Vec<u8>, slot: Option<u64>) -> anyhow::Result<Delivery> {
+        let delivery = self.inner.send_at(key, payload, slot).await?;
+        if !roll(self.config.ack_delay_probability) {
+            return Ok(delivery);
+        }
+        let delay = self.config.ack_delay;
+        Ok(async move {
+            tokio::time::sleep(delay).await;
+            delivery.await
+        }
+        .boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.inner.flush().await
+    }
+
+    async fn prune(&self, cutoff: &RetentionCutoff) -> anyhow::Result<Option<u64>> {
+        self.inner.prune(cutoff).await
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::sinks::Delivery;
+
+/// Checkpoint settings
+#[derive(Debug, Clone)]
+pub struct CheckpointConfig {
+    pub path: String,
+    pub interval_secs: u64,
+    pub max_catchup_slots: u64,
+}
+
+/// Slot to resume from, every record of the slots before it was acknowledged, persisted as JSON
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Checkpoint {
+    slot: u64,
+    updated_at: i64,
+}
+
+/// Where the subscription starts after a restart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartPosition {
+    /// No checkpoint, start at the tip
+    Tip,
+    /// Replay from this slot, the checkpoint is within the catch-up budget. The checkpoint slot
+    /// itself is replayed, some of its records may be published twice
+    Resume(u64),
+    /// The checkpoint is too old, start at the tip and report the skipped range
+    Skip { from_slot: u64, to_slot: u64 },
+}
+
+impl StartPosition {
+    /// Resume from the checkpoint if it is at most `max_catchup_slots` behind `tip`
+    pub fn decide(checkpoint: Option<u64>, tip: u64, max_catchup_slots: u64) -> Self {
+        match checkpoint {
+            None => Self::Tip,
+            Some(slot) if tip.saturating_sub(slot) <= max_catchup_slots => Self::Resume(slot),
+            Some(slot) => Self::Skip {
+                from_slot: slot,
+                to_slot: tip,
+            },
+        }
+    }
+}
+
+/// Deliveries of each slot not acknowledged yet, so the checkpoint never passes a slot whose
+/// records could still be lost
+#[derive(Default)]
+pub struct SlotAcks {
+    state: Arc<Mutex<AckState>>,
+    // Deliveries not awaited by an in-flight limit, polled here so they still count
+    unawaited: FuturesUnordered<Delivery>,
+}
+
+#[derive(Default)]
+struct AckState {
+    pending: BTreeMap<u64, usize>,
+    // Oldest slot with a failed delivery, the checkpoint stays before it until restart
+    failed: Option<u64>,
+}
+
+impl SlotAcks {
+    /// Count `delivery` against `slot` until it resolves
+    pub fn track(&self, slot: u64, delivery: Delivery) -> Delivery {
+        *self.lock().pending.entry(slot).or_default() += 1;
+        let state = Arc::clone(&self.state);
+        async move {
+            let result = delivery.await;
+            let mut state = state.lock().expect("slot acks lock");
+            if result.is_err() {
+                state.failed = Some(state.failed.map_or(slot, |failed| failed.min(slot)));
+            }
+            if let Some(count) = state.pending.get_mut(&slot) {
+                *count -= 1;
+                if *count == 0 {
+                    state.pending.remove(&slot);
+                }
+            }
+            result
+        }
+        .boxed()
+    }
+
+    /// Poll a tracked delivery nobody else awaits
+    pub fn push_unawaited(&mut self, delivery: Delivery) {
+        self.unawaited.push(delivery);
+    }
+
+    /// Slot to resume from once `slot` is reached: `slot` itself, or the oldest slot with
+    /// deliveries pending or failed
+    pub fn resume_slot(&mut self, slot: u64) -> u64 {
+        while let Some(Some(result)) = self.unawaited.next().now_or_never() {
+            if let Err(e) = result {
+                error!("Record delivery failed, holding the checkpoint: {:?}", e);
+            }
+        }
+        let state = self.lock();
+        state.pending.keys().next().copied().into_iter().chain(state.failed).fold(slot, u64::min)
+    }
+
+    /// Wait for the deliveries nobody else awaits, at shutdown
+    pub async fn drain(&mut self) {
+        while let Some(result) = self.unawaited.next().await {
+            if let Err(e) = result {
+                error!("Record delivery failed, holding the checkpoint: {:?}", e);
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, AckState> {
+        self.state.lock().expect("slot acks lock")
+    }
+}
+
+/// Periodically persists the slot to resume from
+pub struct CheckpointStore {
+    path: PathBuf,
+    interval: Duration,
+    last_write: Instant,
+    slot: Option<u64>,
+    written: Option<u64>,
+}
+
+impl CheckpointStore {
+    pub fn new(path: impl Into<PathBuf>, interval_secs: u64) -> Self {
+        Self {
+            path: path.into(),
+            interval: Duration::from_secs(interval_secs),
+            last_write: Instant::now(),
+            slot: None,
+            written: None,
+        }
+    }
+
+    /// Slot of the stored checkpoint, `None` if there is none yet
+    pub fn load(&self) -> anyhow::Result<Option<u64>> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        let contents = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read checkpoint {}", self.path.display()))?;
+        let checkpoint: Checkpoint = serde_json::from_str(&contents)
+            .with_context(|| format!("invalid checkpoint {}", self.path.display()))?;
+        Ok(Some(checkpoint.slot))
+    }
+
+    /// Record the slot to resume from, returns the slot when a checkpoint was written. It moves
+    /// back when an older slot has deliveries pending again, e.g. late account updates
+    pub async fn record(&mut self, slot: u64) -> anyhow::Result<Option<u64>> {
+        self.slot = Some(slot);
+        if self.last_write.elapsed() < self.interval {
+            return Ok(None);
+        }
+        self.flush().await
+    }
+
+    /// Keep the checkpoint at or before `slot`, whose updates were not all published, so they are
+    /// replayed on restart
+    pub fn rewind(&mut self, slot: u64) {
+        self.slot = self.slot.map(|current| current.min(slot));
+    }
+
+    /// Write the latest recorded slot if it changed since the last write
+    pub async fn flush(&mut self) -> anyhow::Result<Option<u64>> {
+        self.last_write = Instant::now();
+        let Some(slot) = self.slot.filter(|slot| Some(*slot) != self.written) else {
+            return Ok(None);
+        };
+
+        let checkpoint = Checkpoint {
+            slot,
+            updated_at: Utc::now().timestamp_millis(),
+        };
+        // Write to a temporary file first so a crash never leaves a truncated checkpoint
+        let tmp_path = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_vec(&checkpoint)?).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        self.written = Some(slot);
+        Ok(Some(slot))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, future};
+
+    use super::*;
+
+    #[test]
+    fn resumes_at_the_checkpoint_slot() {
+        assert_eq!(StartPosition::decide(None, 100, 10), StartPosition::Tip);
+        assert_eq!(StartPosition::decide(Some(95), 100, 10), StartPosition::Resume(95));
+        assert_eq!(
+            StartPosition::decide(Some(50), 100, 10),
+            StartPosition::Skip {
+                from_slot: 50,
+                to_slot: 100
+            }
+        );
+    }
+
+    #[test]
+    fn holds_the_resume_slot_at_pending_deliveries() {
+        let mut acks = SlotAcks::default();
+        let (tx, rx) = futures::channel::oneshot::channel::<()>();
+        let pending = acks.track(10, async move { rx.await.map_err(anyhow::Error::from) }.boxed());
+        let acked = acks.track(11, future::ok(()).boxed());
+        assert_eq!(acks.resume_slot(12), 10);
+
+        block_on(acked).unwrap();
+        assert_eq!(acks.resume_slot(12), 10);
+        tx.send(()).unwrap();
+        block_on(pending).unwrap();
+        assert_eq!(acks.resume_slot(12), 12);
+    }
+
+    #[test]
+    fn holds_the_resume_slot_at_failed_deliveries() {
+        let mut acks = SlotAcks::default();
+        acks.push_unawaited(acks.track(7, future::err(anyhow::anyhow!("rejected")).boxed()));
+        block_on(acks.drain());
+        assert_eq!(acks.resume_slot(20), 7);
+        assert_eq!(acks.resume_slot(5), 5);
+    }
+}
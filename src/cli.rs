@@ -0,0 +1,95 @@
+use clap::{Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[clap(author, version, about = "Yellowstone gRPC to Fluvio Streamer")]
+pub(crate) struct Args {
+    /// Path to the config file (`.yaml`, `.toml` or `.json`)
+    #[clap(long, default_value = "config.yaml")]
+    pub config: String,
+
+    /// Print a commented example config with every field and exit
+    #[clap(long)]
+    pub print_example_config: bool,
+
+    /// Create the topics required by the config and exit
+    #[clap(long)]
+    pub provision_only: bool,
+
+    /// Time the decode, format, serialize and produce stages of a sample of records, logging a
+    /// summary on shutdown
+    #[clap(long)]
+    pub profile: bool,
+
+    /// Time one in this many runs of each stage in `--profile` mode
+    #[clap(long, default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..))]
+    pub profile_sample_rate: u64,
+
+    /// Write the profile as folded stacks to this file, for `inferno-flamegraph` or `flamegraph.pl`
+    #[clap(long, requires = "profile")]
+    pub profile_output: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub(crate) enum Command {
+    /// Publish a historical slot range fetched from the configured history source
+    Backfill {
+        /// First slot to fetch (inclusive)
+        #[clap(long)]
+        from_slot: u64,
+
+        /// Last slot to fetch (inclusive)
+        #[clap(long)]
+        to_slot: u64,
+
+        /// JSON-RPC endpoint, overrides `rpc_endpoint` and `history.endpoint` from the config
+        #[clap(long)]
+        rpc_url: Option<String>,
+    },
+    /// Print the records the formatters build from the block of one slot, for debugging
+    FetchSlot {
+        /// Slot of the block
+        slot: u64,
+
+        /// JSON-RPC endpoint, overrides `rpc_endpoint` and `history.endpoint` from the config
+        #[clap(long)]
+        rpc_url: Option<String>,
+
+        /// Publish the block through the configured sinks, like a backfill of the slot, instead
+        /// of printing its records
+        #[clap(long)]
+        publish: bool,
+    },
+    /// Publish archived records of a slot range to a topic again, after downstream data loss
+    Republish {
+        /// Archive to read, repeatable: JSON lines written by `sink: stdout` (`-` for stdin,
+        /// `s3://bucket/prefix` for the objects under a prefix), or a `.sqlite` / `.duckdb` capture
+        /// file
+        #[clap(long, required = true)]
+        input: Vec<String>,
+
+        /// First slot to republish (inclusive)
+        #[clap(long)]
+        from_slot: u64,
+
+        /// Last slot to republish (inclusive)
+        #[clap(long)]
+        to_slot: u64,
+
+        /// Topic the records are published to
+        #[clap(long)]
+        topic: String,
+
+        /// Only republish the JSON lines archived from this topic
+        #[clap(long)]
+        source_topic: Option<String>,
+    },
+    /// Write the JSON Schema of every record type to a directory, for client codegen, and exit
+    ExportSchemas {
+        /// Directory the `<record>.schema.json` files are written to
+        #[clap(long, default_value = "schemas")]
+        dir: String,
+    },
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use serde_json::json;
+use solana_sdk::system_program;
+use yellowstone_grpc_proto::prelude::SubscribeUpdateAccount;
+
+use crate::{codec, config::YellowstoneGrpcConfig, derived::DerivedStream, sinks::SinkConnector};
+
+/// Account closure event settings
+#[derive(Debug, Clone)]
+pub struct AccountClosuresConfig {
+    pub topic: String,
+    /// Accounts whose last owner is remembered to detect reassignments to the system program
+    pub max_accounts: usize,
+}
+
+struct TrackedAccount {
+    owner: Vec<u8>,
+    closed: bool,
+}
+
+/// Detects the closures in account updates and publishes them as explicit `account_closed`
+/// events: the lamports of an account dropping to 0, or a program account being reassigned to
+/// the system program. Consumers of raw updates cannot tell a closure from an ordinary update
+/// without tracking every account themselves.
+pub struct AccountClosures {
+    stream: DerivedStream,
+    config: AccountClosuresConfig,
+    system_program: Vec<u8>,
+    accounts: HashMap<Vec<u8>, TrackedAccount>,
+}
+
+impl AccountClosures {
+    /// Publish an `account_closed` event when an account update closes the account
+    pub async fn observe(&mut self, update: &SubscribeUpdateAccount) {
+        let Some(account) = update.account.as_ref() else {
+            return;
+        };
+        let previous = self.accounts.get(&account.pubkey);
+        let reason = if account.lamports == 0 {
+            // Closed accounts keep being updated with 0 lamports until they are reused
+            (!previous.is_some_and(|previous| previous.closed)).then_some("zero_lamports")
+        } else {
+            previous
+                .filter(|previous| previous.owner != self.system_program && account.owner == self.system_program)
+                .map(|_| "assigned_to_system")
+        };
+        let previous_owner = previous.map(|previous| codec::encode_bs58(&previous.owner));
+        self.track(&account.pubkey, &account.owner, account.lamports == 0);
+
+        let Some(reason) = reason else {
+            return;
+        };
+        let pubkey = codec::encode_bs58(&account.pubkey);
+        let event = json!({
+            "event": "account_closed",
+            "reason": reason,
+            "pubkey": pubkey,
+            "owner": codec::encode_bs58(&account.owner),
+            "previousOwner": previous_owner,
+            "lamports": account.lamports,
+            "slot": update.slot,
+            "writeVersion": account.write_version,
+            "signature": account.txn_signature.as_ref().map(|signature| codec::encode_bs58(signature)),
+        });
+        self.stream.send(pubkey, &event).await;
+    }
+
+    fn track(&mut self, pubkey: &[u8], owner: &[u8], closed: bool) {
+        if let Some(tracked) = self.accounts.get_mut(pubkey) {
+            tracked.owner = owner.to_vec();
+            tracked.closed = closed;
+            return;
+        }
+        if self.accounts.len() >= self.config.max_accounts {
+            // Evict an arbitrary account, a reassignment of it is only detected after its next update
+            if let Some(evicted) = self.accounts.keys().next().cloned() {
+                self.accounts.remove(&evicted);
+            }
+        }
+        self.accounts.insert(
+            pubkey.to_vec(),
+            TrackedAccount {
+                owner: owner.to_vec(),
+                closed,
+            },
+        );
+    }
+
+    /// Flush the closure events, at shutdown
+    pub async fn finish(&self) {
+        self.stream.flush().await;
+    }
+}
+
+/// Connect the account closure topic, `suffix` is appended to it in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<AccountClosures>> {
+    let Some(closures_config) = config.get_account_closures_config() else {
+        return Ok(None);
+    };
+
+    Ok(Some(AccountClosures {
+        stream: DerivedStream::connect(sinks, config, "account closures", &closures_config.topic, suffix).await?,
+        config: closures_config,
+        system_program: system_program::id().to_bytes().to_vec(),
+        accounts: HashMap::new(),
+    }))
+}
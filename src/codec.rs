@@ -0,0 +1,50 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Longest base58 encoding of 64 bytes, the size of a signature
+const MAX_BS58_LEN: usize = 88;
+/// Pubkeys kept by the per-thread cache before it is cleared
+const PUBKEY_CACHE_CAPACITY: usize = 4096;
+
+thread_local! {
+    static PUBKEYS: RefCell<HashMap<[u8; 32], Box<str>>> = RefCell::new(HashMap::new());
+}
+
+/// base58 of a pubkey or signature, encoded on the stack and allocated once at its final size
+pub fn encode_bs58(bytes: &[u8]) -> String {
+    let mut buffer = [0u8; MAX_BS58_LEN];
+    match bs58::encode(bytes).onto(&mut buffer[..]) {
+        Ok(len) => std::str::from_utf8(&buffer[..len]).expect("base58 is ascii").to_owned(),
+        // Longer than a signature
+        Err(_) => bs58::encode(bytes).into_string(),
+    }
+}
+
+/// base58 of a pubkey that repeats across updates, such as account owners and programs, cached
+/// per thread
+pub fn encode_pubkey_cached(pubkey: &[u8]) -> String {
+    let Ok(key) = <[u8; 32]>::try_from(pubkey) else {
+        return encode_bs58(pubkey);
+    };
+
+    PUBKEYS.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if let Some(encoded) = cache.get(&key) {
+            return encoded.to_string();
+        }
+        if cache.len() >= PUBKEY_CACHE_CAPACITY {
+            cache.clear();
+        }
+        let encoded = encode_bs58(pubkey);
+        cache.insert(key, encoded.as_str().into());
+        encoded
+    })
+}
+
+/// base64 of account or record data, allocated once at its final size
+pub fn encode_base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    STANDARD.encode_string(data, &mut encoded);
+    encoded
+}
@@ -0,0 +1,222 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{info, warn};
+use serde_json::{json, Value};
+
+use crate::control;
+
+/// Magic number starting the dictionaries trained by zstd, followed by their id
+const DICTIONARY_MAGIC: u32 = 0xEC30_A437;
+
+/// Dictionary training settings
+#[derive(Debug, Clone)]
+pub struct DictionaryConfig {
+    /// Records of a topic sampled before its dictionary is trained
+    pub samples: usize,
+    /// Largest dictionary size in bytes
+    pub max_size: usize,
+    /// Directory the dictionary of each topic is kept in, `<topic>.zdict`, reused on restart
+    pub dir: Option<PathBuf>,
+}
+
+/// Payload compression settings
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    pub level: i32,
+    pub dictionary: Option<DictionaryConfig>,
+}
+
+enum State {
+    Plain,
+    /// Compressing without a dictionary while sampling records
+    Training(Vec<Vec<u8>>),
+    /// Compressing without a dictionary while it is trained from the samples
+    Building,
+    Trained {
+        id: u32,
+        dictionary: Vec<u8>,
+        compressor: zstd::bulk::Compressor<'static>,
+    },
+}
+
+/// Compresses the payloads of one topic with zstd into a JSON envelope. With a dictionary, the
+/// first records are sampled to train one for the topic, which compresses its repetitive JSON
+/// far better; the envelope carries the id of the dictionary a payload needs.
+pub struct Compressor {
+    topic: String,
+    level: i32,
+    dictionary: Option<DictionaryConfig>,
+    state: Mutex<State>,
+}
+
+impl Compressor {
+    pub fn new(topic: &str, config: CompressionConfig) -> anyhow::Result<Self> {
+        let state = match &config.dictionary {
+            None => State::Plain,
+            Some(dictionary) => match dictionary.dir.as_ref().map(|dir| dir.join(format!("{}.zdict", topic))) {
+                Some(path) if path.exists() => {
+                    let dictionary = std::fs::read(&path)?;
+                    info!("Loaded compression dictionary {:?} of topic '{}'", path, topic);
+                    State::Trained {
+                        id: dictionary_id(&dictionary),
+                        compressor: zstd::bulk::Compressor::with_dictionary(config.level, &dictionary)?,
+                        dictionary,
+                    }
+                },
+                _ => State::Training(Vec::with_capacity(dictionary.samples)),
+            },
+        };
+        Ok(Self {
+            topic: topic.to_owned(),
+            level: config.level,
+            dictionary: config.dictionary,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// The `compression_dictionary` control record of the dictionary in use, `None` without one.
+    /// Published at startup for a dictionary loaded from `dir`, as consumers starting after it was
+    /// trained need it too.
+    pub fn dictionary_record(&self) -> Option<Value> {
+        match &*self.state.lock().expect("compressor lock") {
+            State::Trained { id, dictionary, .. } => Some(dictionary_record(&self.topic, *id, dictionary)),
+            State::Plain | State::Training(_) | State::Building => None,
+        }
+    }
+
+    /// Compress `payload` into an envelope, also returning the `compression_dictionary` control
+    /// record when the dictionary of the topic was just trained
+    pub async fn compress(&self, payload: &[u8]) -> anyhow::Result<(Vec<u8>, Option<Value>)> {
+        let (envelope, samples) = {
+            let mut state = self.state.lock().expect("compressor lock");
+            let (compressed, id) = match &mut *state {
+                State::Trained { id, compressor, .. } => (compressor.compress(payload)?, Some(*id)),
+                State::Plain | State::Training(_) | State::Building => {
+                    (zstd::bulk::compress(payload, self.level)?, None)
+                },
+            };
+            let envelope = json!({
+                "alg": "zstd",
+                "dictionaryId": id,
+                "payload": STANDARD.encode(compressed),
+            });
+
+            let samples = match (&mut *state, &self.dictionary) {
+                (State::Training(samples), Some(config)) => {
+                    samples.push(payload.to_vec());
+                    (samples.len() >= config.samples).then(|| std::mem::take(samples))
+                },
+                _ => None,
+            };
+            if samples.is_some() {
+                *state = State::Building;
+            }
+            (envelope, samples)
+        };
+
+        let record = match samples {
+            Some(samples) => {
+                let (next, record) = self.train(samples).await;
+                *self.state.lock().expect("compressor lock") = next;
+                record
+            },
+            None => None,
+        };
+        Ok((envelope.to_string().into_bytes(), record))
+    }
+
+    /// Train the dictionary of the topic, compressing without one from now on if it fails.
+    /// Training takes a blocking thread, the lock is not held meanwhile.
+    async fn train(&self, samples: Vec<Vec<u8>>) -> (State, Option<Value>) {
+        let config = self.dictionary.as_ref().expect("dictionary settings");
+        let (count, max_size, level) = (samples.len(), config.max_size, self.level);
+        let trained = tokio::task::spawn_blocking(move || {
+            let dictionary = zstd::dict::from_samples(&samples, max_size)?;
+            let compressor = zstd::bulk::Compressor::with_dictionary(level, &dictionary)?;
+            anyhow::Ok((dictionary, compressor))
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|trained| trained);
+        let (dictionary, compressor) = match trained {
+            Ok(trained) => trained,
+            Err(e) => {
+                warn!("Failed to train the compression dictionary of topic '{}': {:?}", self.topic, e);
+                return (State::Plain, None);
+            },
+        };
+
+        let id = dictionary_id(&dictionary);
+        info!(
+            "Trained compression dictionary {} of {} bytes for topic '{}' from {} records",
+            id,
+            dictionary.len(),
+            self.topic,
+            count
+        );
+        if let Some(dir) = &config.dir {
+            let path = dir.join(format!("{}.zdict", self.topic));
+            if let Err(e) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, &dictionary)) {
+                warn!("Failed to save compression dictionary {:?}: {:?}", path, e);
+            }
+        }
+        let record = dictionary_record(&self.topic, id, &dictionary);
+        (State::Trained { id, dictionary, compressor }, Some(record))
+    }
+}
+
+fn dictionary_record(topic: &str, id: u32, dictionary: &[u8]) -> Value {
+    control::control_record(
+        "compression_dictionary",
+        json!({ "topic": topic, "dictionaryId": id, "dictionary": STANDARD.encode(dictionary) }),
+    )
+}
+
+/// Id zstd stores in the header of a trained dictionary, 0 for raw content dictionaries
+fn dictionary_id(dictionary: &[u8]) -> u32 {
+    let word = |offset: usize| {
+        let bytes = dictionary.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    match word(0) {
+        Some(DICTIONARY_MAGIC) => word(4).unwrap_or(0),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trains_then_reloads_and_republishes_the_dictionary() {
+        let dir = std::env::temp_dir().join(format!("dictionaries-{}", std::process::id()));
+        let config = CompressionConfig {
+            level: 3,
+            dictionary: Some(DictionaryConfig {
+                samples: 2000,
+                max_size: 4096,
+                dir: Some(dir.clone()),
+            }),
+        };
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+
+        let compressor = Compressor::new("solana", config.clone()).unwrap();
+        assert!(compressor.dictionary_record().is_none());
+        let mut trained = None;
+        for index in 0..2000u64 {
+            let payload = format!(r#"{{"slot":{},"signature":"sig{}","fee":{}}}"#, index, index * 7, 5000 + index);
+            let (_, record) = runtime.block_on(compressor.compress(payload.as_bytes())).unwrap();
+            trained = trained.or(record);
+        }
+        let trained = trained.expect("dictionary trained from the samples");
+        let dictionary = |record: Value| (record["dictionaryId"].clone(), record["dictionary"].clone());
+        assert_eq!(compressor.dictionary_record().map(dictionary), Some(dictionary(trained.clone())));
+
+        // Loaded from `dir` on restart, with the same dictionary
+        let reloaded = Compressor::new("solana", config).unwrap();
+        assert_eq!(reloaded.dictionary_record().map(dictionary), Some(dictionary(trained)));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use chrono::Utc;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use yellowstone_grpc_proto::prelude::{
+    subscribe_request_filter_accounts_filter::Filter as AccountsFilterOneof,
+    subscribe_request_filter_accounts_filter_lamports::Cmp as AccountsFilterLamports,
+    subscribe_request_filter_accounts_filter_memcmp::Data as AccountsFilterMemcmpOneof, CommitmentLevel,
+    SubscribeRequest, SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterTransactions,
+};
+
+use crate::sources::SourceEvent;
+
+/// Control record with the event `type` and the time it was emitted
+pub fn control_record(event: &str, fields: Value) -> Value {
+    let mut record = json!({
+        "type": event,
+        "timestamp": Utc::now().timestamp_millis(),
+    });
+    if let (Some(record), Value::Object(fields)) = (record.as_object_mut(), fields) {
+        record.extend(fields);
+    }
+    record
+}
+
+/// Control record for a source event
+pub fn source_event_record(event: &SourceEvent) -> Value {
+    match event {
+        SourceEvent::Reconnect { attempt, reason } => control_record(
+            "reconnect",
+            json!({
+                "attempt": attempt,
+                "reason": reason,
+            }),
+        ),
+        SourceEvent::Gap { from_slot, to_slot } => control_record(
+            "gap_detected",
+            json!({
+                "fromSlot": from_slot,
+                "toSlot": to_slot,
+                "reason": "reconnect",
+            }),
+        ),
+    }
+}
+
+/// Describe the active subscription so consumers know what the stream contains.
+/// The endpoint is hashed to avoid leaking provider URLs and embedded tokens.
+pub fn describe_subscription(request: &SubscribeRequest, endpoint: &str) -> Value {
+    let accounts: serde_json::Map<String, Value> = request
+        .accounts
+        .iter()
+        .map(|(name, filter)| {
+            let description = json!({
+                "account": filter.account,
+                "owner": filter.owner,
+                "filters": filter.filters.iter().filter_map(describe_accounts_filter).collect::<Vec<_>>(),
+                "nonemptyTxnSignature": filter.nonempty_txn_signature,
+            });
+            (name.clone(), description)
+        })
+        .collect();
+
+    let slots: serde_json::Map<String, Value> = request
+        .slots
+        .iter()
+        .map(|(name, filter)| {
+            let description = json!({
+                "filterByCommitment": filter.filter_by_commitment,
+                "interslotUpdates": filter.interslot_updates,
+            });
+            (name.clone(), description)
+        })
+        .collect();
+
+    let blocks: serde_json::Map<String, Value> = request
+        .blocks
+        .iter()
+        .map(|(name, filter)| {
+            let description = json!({
+                "accountInclude": filter.account_include,
+                "includeTransactions": filter.include_transactions,
+                "includeAccounts": filter.include_accounts,
+                "includeEntries": filter.include_entries,
+            });
+            (name.clone(), description)
+        })
+        .collect();
+
+    let data_slices: Vec<Value> = request
+        .accounts_data_slice
+        .iter()
+        .map(|slice| json!({ "offset": slice.offset, "length": slice.length }))
+        .collect();
+
+    let commitment = request
+        .commitment
+        .and_then(|commitment| CommitmentLevel::try_from(commitment).ok())
+        .map(|commitment| commitment.as_str_name());
+
+    control_record(
+        "subscription",
+        json!({
+            "endpointHash": hex::encode(Sha256::digest(endpoint.as_bytes())),
+            "commitment": commitment,
+            "fromSlot": request.from_slot,
+            "filters": {
+                "accounts": accounts,
+                "slots": slots,
+                "transactions": describe_transactions(request.transactions.iter()),
+                "transactionsStatus": describe_transactions(request.transactions_status.iter()),
+                "entry": request.entry.keys().collect::<Vec<_>>(),
+                "blocks": blocks,
+                "blocksMeta": request.blocks_meta.keys().collect::<Vec<_>>(),
+            },
+            "accountsDataSlice": data_slices,
+        }),
+    )
+}
+
+fn describe_transactions<'a>(
+    filters: impl Iterator<Item = (&'a String, &'a SubscribeRequestFilterTransactions)>,
+) -> Value {
+    filters
+        .map(|(name, filter)| {
+            let description = json!({
+                "vote": filter.vote,
+                "failed": filter.failed,
+                "signature": filter.signature,
+                "accountInclude": filter.account_include,
+                "accountExclude": filter.account_exclude,
+                "accountRequired": filter.account_required,
+            });
+            (name.clone(), description)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+fn describe_accounts_filter(filter: &SubscribeRequestFilterAccountsFilter) -> Option<Value> {
+    Some(match filter.filter.as_ref()? {
+        AccountsFilterOneof::Memcmp(memcmp) => {
+            let data = match memcmp.data.as_ref()? {
+                AccountsFilterMemcmpOneof::Bytes(data) => json!({ "bytes": data }),
+                AccountsFilterMemcmpOneof::Base58(data) => json!({ "base58": data }),
+                AccountsFilterMemcmpOneof::Base64(data) => json!({ "base64": data }),
+            };
+            json!({ "memcmp": { "offset": memcmp.offset, "data": data } })
+        },
+        AccountsFilterOneof::Datasize(datasize) => json!({ "datasize": datasize }),
+        AccountsFilterOneof::TokenAccountState(state) => json!({ "tokenAccountState": state }),
+        AccountsFilterOneof::Lamports(lamports) => {
+            let cmp = match lamports.cmp.as_ref()? {
+                AccountsFilterLamports::Eq(value) => json!({ "eq": value }),
+                AccountsFilterLamports::Ne(value) => json!({ "ne": value }),
+                AccountsFilterLamports::Lt(value) => json!({ "lt": value }),
+                AccountsFilterLamports::Gt(value) => json!({ "gt": value }),
+            };
+            json!({ "lamports": cmp })
+        },
+    })
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+/// Slots of block info and released signatures kept for late arrivals
+const KEEP_SLOTS: u64 = 512;
+
+/// Block fields stamped on transactions
+#[derive(Debug, Clone)]
+struct BlockInfo {
+    blockhash: String,
+    block_time: Option<i64>,
+    block_height: Option<u64>,
+}
+
+impl BlockInfo {
+    fn stamp(&self, tx: &mut Value) {
+        tx["blockhash"] = json!(self.blockhash);
+        tx["blockTime"] = json!(self.block_time);
+        tx["blockHeight"] = json!(self.block_height);
+    }
+}
+
+/// Transactions of a slot waiting for its block metadata
+struct PendingSlot {
+    since: Instant,
+    transactions: Vec<(String, Value)>,
+}
+
+/// Holds transactions until the block metadata of their slot arrives and stamps them with its
+/// `blockhash`, `blockTime` and `blockHeight`.
+///
+/// Transactions waiting longer than `max_wait` are released without block fields, and a
+/// `block_enrichment` record listing their signatures is returned once the block shows up.
+pub struct BlockCorrelator {
+    max_wait: Duration,
+    pending: BTreeMap<u64, PendingSlot>,
+    blocks: BTreeMap<u64, BlockInfo>,
+    released: BTreeMap<u64, Vec<String>>,
+}
+
+impl BlockCorrelator {
+    pub fn new(max_wait: Duration) -> Self {
+        Self {
+            max_wait,
+            pending: BTreeMap::new(),
+            blocks: BTreeMap::new(),
+            released: BTreeMap::new(),
+        }
+    }
+
+    /// Buffer a transaction, returned stamped right away when its block is already known
+    pub fn transaction(&mut self, slot: u64, key: String, mut tx: Value) -> Option<(String, Value)> {
+        if let Some(block) = self.blocks.get(&slot) {
+            block.stamp(&mut tx);
+            return Some((key, tx));
+        }
+
+        self.pending
+            .entry(slot)
+            .or_insert_with(|| PendingSlot {
+                since: Instant::now(),
+                transactions: Vec::new(),
+            })
+            .transactions
+            .push((key, tx));
+        None
+    }
+
+    /// Record a block, returning its stamped transactions and the late enrichment record for
+    /// transactions already released without block fields
+    pub fn block(
+        &mut self,
+        slot: u64,
+        blockhash: String,
+        block_time: Option<i64>,
+        block_height: Option<u64>,
+    ) -> (Vec<(String, Value)>, Option<Value>) {
+        let block = BlockInfo {
+            blockhash,
+            block_time,
+            block_height,
+        };
+
+        let mut transactions = self.pending.remove(&slot).map(|pending| pending.transactions).unwrap_or_default();
+        for (_, tx) in &mut transactions {
+            block.stamp(tx);
+        }
+
+        let late = self.released.remove(&slot).map(|signatures| {
+            json!({
+                "type": "block_enrichment",
+                "slot": slot,
+                "blockhash": block.blockhash,
+                "blockTime": block.block_time,
+                "blockHeight": block.block_height,
+                "signatures": signatures,
+            })
+        });
+
+        self.blocks.insert(slot, block);
+        let oldest = slot.saturating_sub(KEEP_SLOTS);
+        self.blocks = self.blocks.split_off(&oldest);
+        self.released = self.released.split_off(&oldest);
+
+        (transactions, late)
+    }
+
+    /// Transactions that waited longer than `max_wait`, released without block fields
+    pub fn expired(&mut self) -> Vec<(String, Value)> {
+        let expired: Vec<u64> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.since.elapsed() >= self.max_wait)
+            .map(|(slot, _)| *slot)
+            .collect();
+
+        expired.into_iter().flat_map(|slot| self.release(slot)).collect()
+    }
+
+    /// Release every buffered transaction, e.g. on shutdown
+    pub fn drain(&mut self) -> Vec<(String, Value)> {
+        let slots: Vec<u64> = self.pending.keys().copied().collect();
+        slots.into_iter().flat_map(|slot| self.release(slot)).collect()
+    }
+
+    /// Oldest slot with buffered transactions
+    pub fn oldest_pending(&self) -> Option<u64> {
+        self.pending.keys().next().copied()
+    }
+
+    fn release(&mut self, slot: u64) -> Vec<(String, Value)> {
+        let transactions = self.pending.remove(&slot).map(|pending| pending.transactions).unwrap_or_default();
+        self.released
+            .entry(slot)
+            .or_default()
+            .extend(transactions.iter().map(|(key, _)| key.clone()));
+        transactions
+    }
+}
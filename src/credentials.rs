@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::sources::GrpcEndpoint;
+
+/// How often the usage is saved and the active profile re-evaluated
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Provider credentials, the configured `endpoint` and `x_token` fill in what is not set
+#[derive(Debug, Clone)]
+pub struct CredentialProfile {
+    pub name: String,
+    pub endpoint: Option<String>,
+    pub x_token: Option<String>,
+    /// Updates the profile may receive per calendar month (UTC)
+    pub monthly_quota: Option<u64>,
+}
+
+/// Credential rotation settings
+#[derive(Debug, Clone)]
+pub struct CredentialsConfig {
+    pub profiles: Vec<CredentialProfile>,
+    /// Each profile is used in turn for this long, in order until its quota is spent when `None`
+    pub rotate_every: Option<Duration>,
+    /// File the monthly usage is kept in across restarts
+    pub state_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageState {
+    /// `YYYY-MM`, the usage is reset when the month changes
+    month: String,
+    active: Option<String>,
+    /// Updates received per profile this month
+    usage: HashMap<String, u64>,
+}
+
+/// Rotates the gRPC connections across credential profiles, on a schedule or once a profile
+/// received its monthly quota of updates, so long-running deployments do not need manual
+/// credential swaps. Sources re-subscribe with the new profile when it changes.
+pub struct Credentials {
+    config: CredentialsConfig,
+    state: Mutex<UsageState>,
+    // Updates received with the active profile since the last check
+    pending: AtomicU64,
+    exhausted: AtomicBool,
+    active: watch::Sender<usize>,
+}
+
+static CREDENTIALS: OnceLock<Arc<Credentials>> = OnceLock::new();
+
+/// Rotate the credentials of the gRPC connections opened from now on across `config.profiles`
+pub fn install(config: CredentialsConfig) -> anyhow::Result<()> {
+    let state = match &config.state_path {
+        Some(path) if path.exists() => serde_json::from_slice(&std::fs::read(path)?)
+            .with_context(|| format!("invalid credentials state {:?}", path))?,
+        _ => UsageState::default(),
+    };
+    let credentials = Arc::new(Credentials {
+        config,
+        state: Mutex::new(state),
+        pending: AtomicU64::new(0),
+        exhausted: AtomicBool::new(false),
+        active: watch::channel(0).0,
+    });
+    let active = credentials.select(&mut credentials.state.lock().expect("credentials lock"));
+    credentials.active.send_replace(active);
+    info!("Using credential profile '{}'", credentials.config.profiles[active].name);
+    credentials.check();
+
+    let _ = CREDENTIALS.set(Arc::clone(&credentials));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            credentials.check();
+        }
+    });
+    Ok(())
+}
+
+/// Installed credential rotation, `None` without profiles
+pub fn installed() -> Option<&'static Arc<Credentials>> {
+    CREDENTIALS.get()
+}
+
+/// Name of the active profile, `None` without profiles
+pub fn active_profile() -> Option<&'static str> {
+    let credentials = installed()?;
+    let active = *credentials.active.borrow();
+    Some(&credentials.config.profiles[active].name)
+}
+
+/// `endpoint` with the endpoint and x-token of the active profile
+pub fn resolve(endpoint: &GrpcEndpoint) -> GrpcEndpoint {
+    let mut endpoint = endpoint.clone();
+    if let Some(credentials) = installed() {
+        let profile = &credentials.config.profiles[*credentials.active.borrow()];
+        if let Some(url) = &profile.endpoint {
+            endpoint.endpoint = url.clone();
+        }
+        if profile.x_token.is_some() {
+            endpoint.x_token = profile.x_token.clone();
+        }
+    }
+    endpoint
+}
+
+impl Credentials {
+    /// Count updates received with the active profile
+    pub fn consumed(&self, updates: u64) {
+        self.pending.fetch_add(updates, Ordering::Relaxed);
+    }
+
+    /// Notified with the index of the active profile whenever it changes
+    pub fn subscribe(&self) -> watch::Receiver<usize> {
+        self.active.subscribe()
+    }
+
+    fn exhausted(&self, state: &UsageState, index: usize) -> bool {
+        let profile = &self.config.profiles[index];
+        profile
+            .monthly_quota
+            .is_some_and(|quota| state.usage.get(&profile.name).copied().unwrap_or(0) >= quota)
+    }
+
+    /// Profile to use now: the scheduled one, or the next one with quota left
+    fn select(&self, state: &mut UsageState) -> usize {
+        let month = Utc::now().format("%Y-%m").to_string();
+        if state.month != month {
+            state.month = month;
+            state.usage.clear();
+        }
+
+        let count = self.config.profiles.len();
+        let start = match self.config.rotate_every {
+            Some(every) => (Utc::now().timestamp().max(0) as u64 / every.as_secs().max(1)) as usize % count,
+            None => 0,
+        };
+        let available = (0..count)
+            .map(|offset| (start + offset) % count)
+            .find(|index| !self.exhausted(state, *index));
+        match available {
+            Some(index) => {
+                self.exhausted.store(false, Ordering::Relaxed);
+                index
+            },
+            None => {
+                if !self.exhausted.swap(true, Ordering::Relaxed) {
+                    warn!("Every credential profile received its monthly quota, staying on the scheduled one");
+                }
+                start
+            },
+        }
+    }
+
+    /// Fold in the usage since the last check, switch profiles if needed and save the state
+    fn check(&self) {
+        let mut state = self.state.lock().expect("credentials lock");
+        let active = *self.active.borrow();
+        let profile = &self.config.profiles[active];
+        *state.usage.entry(profile.name.clone()).or_default() += self.pending.swap(0, Ordering::Relaxed);
+
+        let next = self.select(&mut state);
+        if next != active {
+            let used = state.usage.get(&profile.name).copied().unwrap_or(0);
+            match profile.monthly_quota.filter(|_| self.exhausted(&state, active)) {
+                Some(quota) => warn!(
+                    "Credential profile '{}' received {} updates of its monthly quota of {}, switching to '{}'",
+                    profile.name, used, quota, self.config.profiles[next].name
+                ),
+                None => info!("Rotating credential profile '{}' to '{}'", profile.name, self.config.profiles[next].name),
+            }
+            self.active.send_replace(next);
+        }
+        state.active = Some(self.config.profiles[next].name.clone());
+
+        if let Some(path) = &self.config.state_path {
+            let saved = serde_json::to_vec_pretty(&*state)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| Ok(std::fs::write(path, contents)?));
+            if let Err(e) = saved {
+                error!("Failed to save credentials state {:?}: {:?}", path, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use serde_json::{json, Value};
+use solana_sdk::{bpf_loader_upgradeable, loader_upgradeable_instruction::UpgradeableLoaderInstruction};
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+use crate::{
+    codec, config::YellowstoneGrpcConfig, derived::DerivedStream, instructions, outbox::Outbox, sinks::SinkConnector,
+};
+
+/// Normalized event of an upgradeable loader instruction, from its data and account positions
+fn program_event(instruction: UpgradeableLoaderInstruction, account: impl Fn(usize) -> Option<String>) -> Option<Value> {
+    let event = match instruction {
+        UpgradeableLoaderInstruction::DeployWithMaxDataLen { max_data_len } => json!({
+            "event": "deploy",
+            "programId": account(2),
+            "programData": account(1),
+            "buffer": account(3),
+            "authority": account(7),
+            "maxDataLen": max_data_len,
+        }),
+        UpgradeableLoaderInstruction::Upgrade => json!({
+            "event": "upgrade",
+            "programId": account(1),
+            "programData": account(0),
+            "buffer": account(2),
+            "authority": account(6),
+        }),
+        UpgradeableLoaderInstruction::ExtendProgram { additional_bytes } => json!({
+            "event": "extend",
+            "programId": account(1),
+            "programData": account(0),
+            "additionalBytes": additional_bytes,
+        }),
+        // The account is a buffer or the program data of a program
+        UpgradeableLoaderInstruction::SetAuthority | UpgradeableLoaderInstruction::SetAuthorityChecked => json!({
+            "event": "set_authority",
+            "account": account(0),
+            "authority": account(1),
+            // Missing when the program is made immutable
+            "newAuthority": account(2),
+        }),
+        UpgradeableLoaderInstruction::Close => json!({
+            "event": "close",
+            // The program is only passed when closing its program data
+            "programId": account(3),
+            "account": account(0),
+            "recipient": account(1),
+            "authority": account(2),
+        }),
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// Program lifecycle events (deploy, upgrade, extend, set authority, close) extracted from the
+/// top-level and inner BPF Loader Upgradeable instructions of successful transactions
+pub struct ProgramDeploys {
+    stream: DerivedStream,
+    loader_program: Vec<u8>,
+}
+
+impl ProgramDeploys {
+    /// Publish the program lifecycle events of a transaction, or hold them in `outbox` until its
+    /// record is acknowledged
+    pub async fn observe(&self, update: &SubscribeUpdateTransaction, outbox: Option<&Outbox>) {
+        let signature = update.transaction.as_ref().map(|info| codec::encode_bs58(&info.signature)).unwrap_or_default();
+        for instruction in instructions::program_instructions(update, &self.loader_program) {
+            let Ok(loader_instruction) = bincode::deserialize::<UpgradeableLoaderInstruction>(instruction.data) else {
+                continue;
+            };
+            let Some(mut event) = program_event(loader_instruction, |position| instruction.account(position)) else {
+                continue;
+            };
+
+            event["signature"] = json!(signature);
+            event["slot"] = json!(update.slot);
+            event["cpi"] = json!(instruction.cpi);
+            // Keyed by program, so the lifecycle of a program stays in order
+            let key = event["programId"]
+                .as_str()
+                .or(event["account"].as_str())
+                .unwrap_or(&signature)
+                .to_owned();
+            self.stream.emit(outbox, &signature, key, event).await;
+        }
+    }
+
+    /// Flush the program events, at shutdown
+    pub async fn finish(&self) {
+        self.stream.flush().await;
+    }
+}
+
+/// Connect the program event topic, `suffix` is appended to it in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<ProgramDeploys>> {
+    let Some(topic) = config.get_program_deploys_topic() else {
+        return Ok(None);
+    };
+
+    Ok(Some(ProgramDeploys {
+        stream: DerivedStream::connect(sinks, config, "program deployments and upgrades", &topic, suffix).await?,
+        loader_program: bpf_loader_upgradeable::id().to_bytes().to_vec(),
+    }))
+}
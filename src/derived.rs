@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use log::{error, info};
+use serde_json::Value;
+
+use crate::{config::YellowstoneGrpcConfig, outbox::Outbox, publisher::Publisher, sinks::SinkConnector};
+
+/// Topic of records derived from the update stream (votes, SOL transfers, bloom filters...).
+///
+/// Derived records follow the failure policy of the main topic: a record that cannot be
+/// published stops the streamer, so the checkpoint is not saved past it and consumers never see
+/// a silent gap in a derived topic.
+#[derive(Clone)]
+pub struct DerivedStream {
+    /// What the topic carries, for the logs
+    name: &'static str,
+    topic: Arc<str>,
+    publisher: Arc<Publisher>,
+}
+
+impl DerivedStream {
+    /// Connect `topic`, `suffix` is appended to it in `dual_commitment` mode
+    pub async fn connect(
+        sinks: &SinkConnector,
+        config: &YellowstoneGrpcConfig,
+        name: &'static str,
+        topic: &str,
+        suffix: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let topic = match suffix {
+            Some(suffix) => format!("{}-{}", topic, suffix),
+            None => topic.to_owned(),
+        };
+        info!("Publishing {} to topic '{}'", name, topic);
+        Ok(Self {
+            name,
+            publisher: Arc::new(Publisher::connect(sinks, config, &topic).await?),
+            topic: topic.into(),
+        })
+    }
+
+    pub async fn send(&self, key: String, record: &Value) {
+        if let Err(e) = self.publisher.send(key, record).await {
+            self.fatal(e);
+        }
+    }
+
+    /// Publish an event derived from the transaction `parent` (its signature), or hold it in
+    /// `outbox` until the transaction record is acknowledged
+    pub async fn emit(&self, outbox: Option<&Outbox>, parent: &str, key: String, record: Value) {
+        match outbox {
+            Some(outbox) => outbox.hold(parent, record["slot"].as_u64().unwrap_or_default(), self, key, record),
+            None => self.send(key, &record).await,
+        }
+    }
+
+    /// Publish a tombstone deleting `key` on a compacted topic
+    pub async fn send_tombstone(&self, key: String, slot: u64) {
+        if let Err(e) = self.publisher.send_tombstone(key, slot).await {
+            self.fatal(e);
+        }
+    }
+
+    pub async fn flush(&self) {
+        if let Err(e) = self.publisher.flush().await {
+            self.fatal(e);
+        }
+    }
+
+    fn fatal(&self, e: anyhow::Error) -> ! {
+        error!("Error publishing {} to topic '{}': {:?}", self.name, self.topic, e);
+        error!("Fatal error publishing {}. Exiting...", self.name);
+        std::process::exit(1);
+    }
+}
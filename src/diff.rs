@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::codec;
+
+/// Changed ranges closer than this are merged, a range costs more than a few unchanged bytes
+const MERGE_GAP: usize = 16;
+
+/// Account diff settings
+#[derive(Debug, Clone)]
+pub struct AccountDiffConfig {
+    /// Publish the full data every this many updates of an account
+    pub snapshot_every: u32,
+    /// Accounts with less data are always published in full
+    pub min_data_size: usize,
+    /// Accounts whose data is cached for diffing
+    pub max_accounts: usize,
+}
+
+struct CachedAccount {
+    data: Vec<u8>,
+    slot: u64,
+    write_version: u64,
+    since_snapshot: u32,
+}
+
+/// How an account update is published
+pub enum AccountDiff {
+    /// The full data, for the first update of an account, size changes and periodic snapshots
+    Snapshot,
+    /// Only the byte ranges changed since the update at `base_slot` and `base_write_version`
+    Changes {
+        base_slot: u64,
+        base_write_version: u64,
+        ranges: Vec<(usize, Vec<u8>)>,
+    },
+}
+
+impl AccountDiff {
+    /// Mark the formatted account record as a snapshot, or replace its `data` with the changes
+    pub fn apply(self, record: &mut Value) {
+        match self {
+            AccountDiff::Snapshot => record["snapshot"] = json!(true),
+            AccountDiff::Changes {
+                base_slot,
+                base_write_version,
+                ranges,
+            } => {
+                let Some(object) = record.as_object_mut() else {
+                    return;
+                };
+                object.remove("data");
+                object.insert("snapshot".to_owned(), json!(false));
+                object.insert("baseSlot".to_owned(), json!(base_slot));
+                object.insert("baseWriteVersion".to_owned(), json!(base_write_version));
+                let changes: Vec<Value> = ranges
+                    .iter()
+                    .map(|(offset, bytes)| json!({ "offset": offset, "data": codec::encode_base64(bytes) }))
+                    .collect();
+                object.insert("changes".to_owned(), Value::Array(changes));
+            },
+        }
+    }
+}
+
+/// Diffs account data against the previous update of the same pubkey, so large accounts that
+/// change a few bytes at a time (e.g. orderbooks) are published as the changed ranges only
+pub struct AccountDiffer {
+    config: AccountDiffConfig,
+    accounts: HashMap<Vec<u8>, CachedAccount>,
+}
+
+impl AccountDiffer {
+    pub fn new(config: AccountDiffConfig) -> Self {
+        Self {
+            config,
+            accounts: HashMap::new(),
+        }
+    }
+
+    /// Compare `data` to the cached data of `pubkey` and cache it for the next update. Updates
+    /// of an account are ordered by slot, then write version within a slot
+    pub fn diff(&mut self, pubkey: &[u8], data: &[u8], slot: u64, write_version: u64) -> AccountDiff {
+        if data.len() < self.config.min_data_size {
+            return AccountDiff::Snapshot;
+        }
+
+        if let Some(cached) = self.accounts.get_mut(pubkey) {
+            let diffable = cached.data.len() == data.len()
+                && (cached.slot, cached.write_version) < (slot, write_version)
+                && cached.since_snapshot + 1 < self.config.snapshot_every;
+            if diffable {
+                let ranges = changed_ranges(&cached.data, data);
+                let (base_slot, base_write_version) = (cached.slot, cached.write_version);
+                cached.data.copy_from_slice(data);
+                cached.slot = slot;
+                cached.write_version = write_version;
+                cached.since_snapshot += 1;
+                return AccountDiff::Changes {
+                    base_slot,
+                    base_write_version,
+                    ranges,
+                };
+            }
+        } else if self.accounts.len() >= self.config.max_accounts {
+            // Evict an arbitrary account, its next update is published in full
+            if let Some(evicted) = self.accounts.keys().next().cloned() {
+                self.accounts.remove(&evicted);
+            }
+        }
+
+        self.accounts.insert(
+            pubkey.to_vec(),
+            CachedAccount {
+                data: data.to_vec(),
+                slot,
+                write_version,
+                since_snapshot: 0,
+            },
+        );
+        AccountDiff::Snapshot
+    }
+}
+
+/// Byte ranges of `new` that differ from `old`, both of the same length
+fn changed_ranges(old: &[u8], new: &[u8]) -> Vec<(usize, Vec<u8>)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    let mut index = 0;
+    while index < new.len() {
+        if old[index] == new[index] {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        while index < new.len() && old[index] != new[index] {
+            index += 1;
+        }
+        match ranges.last_mut() {
+            Some((_, end)) if start - *end <= MERGE_GAP => *end = index,
+            _ => ranges.push((start, index)),
+        }
+    }
+    ranges.into_iter().map(|(start, end)| (start, new[start..end].to_vec())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn differ() -> AccountDiffer {
+        AccountDiffer::new(AccountDiffConfig {
+            snapshot_every: 10,
+            min_data_size: 4,
+            max_accounts: 10,
+        })
+    }
+
+    #[test]
+    fn merges_close_ranges() {
+        let old = [0u8; 64];
+        let mut new = old;
+        new[1] = 1;
+        new[10] = 1;
+        new[40] = 1;
+        new[41] = 1;
+        assert_eq!(changed_ranges(&old, &new), [(1, new[1..11].to_vec()), (40, vec![1, 1])]);
+        assert!(changed_ranges(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn diffs_against_the_previous_write_of_the_pubkey() {
+        let mut differ = differ();
+        assert!(matches!(differ.diff(b"a", &[0; 8], 5, 1), AccountDiff::Snapshot));
+        // Another account of the same size has its own base
+        assert!(matches!(differ.diff(b"b", &[1; 8], 5, 2), AccountDiff::Snapshot));
+        match differ.diff(b"a", &[0, 0, 7, 0, 0, 0, 0, 0], 5, 3) {
+            AccountDiff::Changes {
+                base_slot,
+                base_write_version,
+                ranges,
+            } => {
+                assert_eq!((base_slot, base_write_version), (5, 1));
+                assert_eq!(ranges, [(2, vec![7])]);
+            },
+            AccountDiff::Snapshot => panic!("expected changes"),
+        }
+        // An older write of the same slot is published in full
+        assert!(matches!(differ.diff(b"a", &[0; 8], 5, 2), AccountDiff::Snapshot));
+    }
+
+    #[test]
+    fn records_the_base_write_version() {
+        let mut record = json!({ "pubkey": "a", "data": "AAAA" });
+        AccountDiff::Changes {
+            base_slot: 5,
+            base_write_version: 9,
+            ranges: vec![(2, vec![7])],
+        }
+        .apply(&mut record);
+        assert_eq!(
+            record,
+            json!({
+                "pubkey": "a",
+                "snapshot": false,
+                "baseSlot": 5,
+                "baseWriteVersion": 9,
+                "changes": [{ "offset": 2, "data": "Bw==" }],
+            })
+        );
+    }
+}
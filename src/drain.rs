@@ -0,0 +1,40 @@
+use std::{future::Future, time::Duration};
+
+use log::{error, warn};
+use tokio::sync::watch;
+
+/// Shutdown drain settings
+#[derive(Debug, Clone)]
+pub struct DrainConfig {
+    /// Time the processors have to publish the queued updates
+    pub timeout: Duration,
+    /// Time they then have to stop and write their checkpoint before the process exits anyway
+    pub exit_timeout: Duration,
+}
+
+/// Wait for the processors to drain their queues, within the drain timeout. Returns whether
+/// they drained everything; when the timeout expires `expired` tells them to stop, leaving the
+/// rest queued behind the checkpoint, and the process exits if they do not within the exit
+/// timeout, e.g. stuck on a sink that stopped acknowledging.
+pub async fn drain(
+    processors: impl Future<Output = ()>,
+    config: Option<DrainConfig>,
+    expired: &watch::Sender<bool>,
+) -> bool {
+    let Some(config) = config else {
+        processors.await;
+        return true;
+    };
+    tokio::pin!(processors);
+    if tokio::time::timeout(config.timeout, &mut processors).await.is_ok() {
+        return true;
+    }
+
+    warn!("Queued updates not drained within {:?}, stopping at the checkpoint", config.timeout);
+    expired.send_replace(true);
+    if tokio::time::timeout(config.exit_timeout, &mut processors).await.is_err() {
+        error!("Processors did not stop within {:?}, exiting", config.exit_timeout);
+        std::process::exit(1);
+    }
+    false
+}
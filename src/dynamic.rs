@@ -0,0 +1,211 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::watch;
+use yellowstone_grpc_proto::prelude::{SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterTransactions};
+
+/// How often expired filters are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Dynamic filter settings
+#[derive(Debug, Clone)]
+pub struct DynamicFiltersConfig {
+    /// Longest TTL a filter can be registered with
+    pub max_ttl: Duration,
+    /// Filters registered at the same time
+    pub max_filters: usize,
+    /// Accounts of one filter
+    pub max_accounts: usize,
+}
+
+/// What a dynamic filter subscribes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DynamicFilterKind {
+    /// Updates of the accounts
+    Accounts,
+    /// Transactions referencing one of the accounts
+    Transactions,
+}
+
+#[derive(Debug, Clone)]
+struct DynamicFilter {
+    kind: DynamicFilterKind,
+    accounts: Vec<String>,
+    expires: Instant,
+}
+
+/// Temporary filters registered through the admin server and merged into the live subscriptions
+/// until their TTL expires.
+///
+/// Each filter is added to the subscribe request under its own name, `dynamic-<id>`, next to
+/// the configured `client` filters.
+pub struct DynamicFilters {
+    config: DynamicFiltersConfig,
+    filters: Mutex<BTreeMap<u64, DynamicFilter>>,
+    next_id: AtomicU64,
+    // Bumped on every change of the filters
+    version: watch::Sender<u64>,
+}
+
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+impl DynamicFilters {
+    /// Register a filter for `ttl`, returning its id
+    pub fn register(&self, kind: DynamicFilterKind, accounts: Vec<String>, ttl: Duration) -> anyhow::Result<u64> {
+        if accounts.is_empty() {
+            bail!("a filter needs at least one account");
+        }
+        if accounts.len() > self.config.max_accounts {
+            bail!("a filter has at most {} accounts", self.config.max_accounts);
+        }
+        if ttl.is_zero() || ttl > self.config.max_ttl {
+            bail!("ttl must be between 1 and {} seconds", self.config.max_ttl.as_secs());
+        }
+
+        let mut filters = self.filters.lock().expect("dynamic filters lock");
+        if filters.len() >= self.config.max_filters {
+            bail!("{} dynamic filters are already registered", self.config.max_filters);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        filters.insert(id, DynamicFilter {
+            kind,
+            accounts,
+            expires: Instant::now() + ttl,
+        });
+        drop(filters);
+
+        info!("Registered dynamic filter {} for {}s", id, ttl.as_secs());
+        self.version.send_modify(|version| *version += 1);
+        Ok(id)
+    }
+
+    /// Remove a filter before it expires, `false` if it is not registered
+    pub fn remove(&self, id: u64) -> bool {
+        let removed = self.filters.lock().expect("dynamic filters lock").remove(&id).is_some();
+        if removed {
+            self.version.send_modify(|version| *version += 1);
+        }
+        removed
+    }
+
+    /// Registered filters with their remaining TTL
+    pub fn list(&self) -> Vec<Value> {
+        let now = Instant::now();
+        self.filters
+            .lock()
+            .expect("dynamic filters lock")
+            .iter()
+            .map(|(id, filter)| {
+                json!({
+                    "id": id,
+                    "kind": filter.kind,
+                    "accounts": filter.accounts,
+                    "expiresInSecs": filter.expires.saturating_duration_since(now).as_secs(),
+                })
+            })
+            .collect()
+    }
+
+    fn prune(&self) {
+        let now = Instant::now();
+        let mut filters = self.filters.lock().expect("dynamic filters lock");
+        let before = filters.len();
+        filters.retain(|id, filter| {
+            let expired = filter.expires <= now;
+            if expired {
+                info!("Dynamic filter {} expired", id);
+            }
+            !expired
+        });
+        let pruned = filters.len() != before;
+        drop(filters);
+
+        if pruned {
+            self.version.send_modify(|version| *version += 1);
+        }
+    }
+
+    /// Receiver notified whenever a filter is added or removed
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+
+    /// Add the registered filters to a subscribe request
+    pub fn merge(&self, request: &mut SubscribeRequest) {
+        for (id, filter) in self.filters.lock().expect("dynamic filters lock").iter() {
+            let name = format!("dynamic-{}", id);
+            match filter.kind {
+                DynamicFilterKind::Accounts => {
+                    request.accounts.insert(name, SubscribeRequestFilterAccounts {
+                        account: filter.accounts.clone(),
+                        ..Default::default()
+                    });
+                },
+                DynamicFilterKind::Transactions => {
+                    request.transactions.insert(name, SubscribeRequestFilterTransactions {
+                        account_include: filter.accounts.clone(),
+                        ..Default::default()
+                    });
+                },
+            }
+        }
+    }
+}
+
+/// Start expiring the dynamic filters
+pub fn start(config: DynamicFiltersConfig) -> Arc<DynamicFilters> {
+    let (version, _) = watch::channel(0);
+    let dynamic = Arc::new(DynamicFilters {
+        config,
+        filters: Mutex::new(BTreeMap::new()),
+        next_id: AtomicU64::new(0),
+        version,
+    });
+
+    let pruned = Arc::clone(&dynamic);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            pruned.prune();
+        }
+    });
+
+    dynamic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_the_accounts_of_a_filter() {
+        let dynamic = DynamicFilters {
+            config: DynamicFiltersConfig {
+                max_ttl: Duration::from_secs(60),
+                max_filters: 1,
+                max_accounts: 2,
+            },
+            filters: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(0),
+            version: watch::channel(0).0,
+        };
+        let accounts = |count: usize| vec!["account".to_string(); count];
+        let ttl = Duration::from_secs(10);
+
+        assert!(dynamic.register(DynamicFilterKind::Accounts, accounts(3), ttl).is_err());
+        assert_eq!(dynamic.register(DynamicFilterKind::Accounts, accounts(2), ttl).unwrap(), 1);
+        // The filter limit still applies
+        assert!(dynamic.register(DynamicFilterKind::Transactions, accounts(1), ttl).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key,
+};
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::platform;
+
+/// Payload encryption settings
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct EncryptionConfig {
+    /// Identifier of the key, published in every envelope so consumers can pick the right key
+    pub key_id: String,
+    /// Base64 encoded 256-bit key
+    pub key: Option<String>,
+    /// Environment variable holding the base64 key
+    pub key_env: Option<String>,
+    /// Command printing the base64 key to stdout, e.g. a KMS decrypt call, run with `sh -c`
+    /// (`cmd /C` on Windows)
+    pub key_command: Option<String>,
+}
+
+/// Encrypts payloads with AES-256-GCM into a JSON envelope
+pub struct Encryptor {
+    key_id: String,
+    cipher: Aes256Gcm,
+}
+
+impl Encryptor {
+    pub fn new(config: &EncryptionConfig) -> anyhow::Result<Self> {
+        let encoded = if let Some(key) = &config.key {
+            key.clone()
+        } else if let Some(var) = &config.key_env {
+            std::env::var(var).with_context(|| format!("encryption key variable {} is not set", var))?
+        } else if let Some(command) = &config.key_command {
+            let output = platform::shell_command(command)
+                .output()
+                .context("failed to run encryption key_command")?;
+            anyhow::ensure!(output.status.success(), "encryption key_command exited with {}", output.status);
+            String::from_utf8(output.stdout).context("encryption key_command output is not UTF-8")?
+        } else {
+            anyhow::bail!("encryption requires one of key, key_env or key_command");
+        };
+
+        let key = STANDARD.decode(encoded.trim()).context("encryption key is not valid base64")?;
+        anyhow::ensure!(key.len() == 32, "encryption key must be 32 bytes, got {}", key.len());
+
+        Ok(Self {
+            key_id: config.key_id.clone(),
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    /// Encrypt `payload` and wrap it with the key id and nonce
+    pub fn seal(&self, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|_| anyhow::anyhow!("failed to encrypt payload"))?;
+
+        let envelope = json!({
+            "keyId": self.key_id,
+            "alg": "AES-256-GCM",
+            "nonce": STANDARD.encode(nonce),
+            "ciphertext": STANDARD.encode(ciphertext),
+        });
+        Ok(envelope.to_string().into_bytes())
+    }
+}
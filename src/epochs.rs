@@ -0,0 +1,175 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use log::info;
+use tokio::sync::Mutex;
+
+use crate::{
+    provision::ProvisionConfig,
+    retention::RetentionCutoff,
+    sinks::{Sink, SinkConnector},
+};
+
+/// Epoch topic settings
+#[derive(Debug, Clone)]
+pub struct EpochTopicsConfig {
+    pub slots_per_epoch: u64,
+    /// Slots before an epoch boundary at which the topic of the next epoch is created
+    pub lead_slots: u64,
+}
+
+#[derive(Default)]
+struct OpenTopics {
+    sinks: BTreeMap<u64, Arc<dyn Sink>>,
+    // Newest epoch a record was published to
+    current: Option<u64>,
+    // Unsplit topic of the records without a slot published before the first epoch is known
+    unsplit: Option<Arc<dyn Sink>>,
+}
+
+/// Open epoch a record of an epoch older than `current` goes to: its topic while still open, else
+/// the oldest open one. `None` when `epoch` is not older than `current`
+fn late_epoch<T>(open: &BTreeMap<u64, T>, current: Option<u64>, epoch: u64) -> Option<u64> {
+    current.filter(|current| epoch < *current)?;
+    if open.contains_key(&epoch) {
+        return Some(epoch);
+    }
+    open.keys().next().copied()
+}
+
+/// Main topic split into one topic per epoch, `<topic>-epoch-<epoch>`, for retention and
+/// compaction policies set per epoch. The topic of the next epoch is created `lead_slots`
+/// before the boundary, the topic of the previous epoch stays open for late records (block
+/// correlation, finalized pipelines) and older ones are closed: records of a closed epoch go to
+/// the oldest open topic instead of reopening theirs.
+pub struct EpochTopics {
+    connector: Arc<SinkConnector>,
+    topic: String,
+    config: EpochTopicsConfig,
+    provision: ProvisionConfig,
+    open: Mutex<OpenTopics>,
+}
+
+impl EpochTopics {
+    pub fn new(
+        connector: Arc<SinkConnector>,
+        topic: &str,
+        config: EpochTopicsConfig,
+        provision: ProvisionConfig,
+    ) -> Self {
+        Self {
+            connector,
+            topic: topic.to_owned(),
+            config,
+            provision,
+            open: Mutex::new(OpenTopics::default()),
+        }
+    }
+
+    fn topic(&self, epoch: u64) -> String {
+        format!("{}-epoch-{}", self.topic, epoch)
+    }
+
+    async fn open_epoch(&self, epoch: u64) -> anyhow::Result<Arc<dyn Sink>> {
+        let topic = self.topic(epoch);
+        self.connector.provision(&[topic.clone()], &self.provision).await?;
+        info!("Opened topic '{}' of epoch {}", topic, epoch);
+        Ok(Arc::from(self.connector.open(&topic).await?))
+    }
+
+    /// Unsplit topic, for the records without a slot published before the first epoch is known
+    async fn unsplit(&self, open: &mut OpenTopics) -> anyhow::Result<Arc<dyn Sink>> {
+        if let Some(sink) = &open.unsplit {
+            return Ok(Arc::clone(sink));
+        }
+        self.connector.provision(&[self.topic.clone()], &self.provision).await?;
+        info!("Opened topic '{}' for records without a slot", self.topic);
+        let sink: Arc<dyn Sink> = Arc::from(self.connector.open(&self.topic).await?);
+        open.unsplit = Some(Arc::clone(&sink));
+        Ok(sink)
+    }
+
+    /// Sink of the epoch of `slot`, of the current epoch for records without a slot, or of the
+    /// unsplit topic before the first epoch is known
+    pub async fn sink(&self, slot: Option<u64>) -> anyhow::Result<Arc<dyn Sink>> {
+        let mut open = self.open.lock().await;
+        let epoch = match (slot, open.current) {
+            (Some(slot), _) => slot / self.config.slots_per_epoch,
+            (None, Some(current)) => current,
+            (None, None) => return self.unsplit(&mut open).await,
+        };
+        if let Some(late) = late_epoch(&open.sinks, open.current, epoch) {
+            return Ok(Arc::clone(&open.sinks[&late]));
+        }
+        let sink = match open.sinks.get(&epoch) {
+            Some(sink) => Arc::clone(sink),
+            None => {
+                let sink = self.open_epoch(epoch).await?;
+                open.sinks.insert(epoch, Arc::clone(&sink));
+                sink
+            },
+        };
+        if open.current.map_or(true, |current| epoch > current) {
+            open.current = Some(epoch);
+            // Late records of the previous epoch still have their topic
+            let closed: Vec<u64> = open.sinks.range(..epoch.saturating_sub(1)).map(|(epoch, _)| *epoch).collect();
+            for epoch in closed {
+                if let Some(sink) = open.sinks.remove(&epoch) {
+                    sink.flush().await?;
+                    info!("Closed topic '{}'", self.topic(epoch));
+                }
+            }
+        }
+
+        // Consumers can subscribe to the next topic before its first record
+        let until_boundary = slot.map(|slot| self.config.slots_per_epoch - slot % self.config.slots_per_epoch);
+        if until_boundary.is_some_and(|until_boundary| until_boundary <= self.config.lead_slots)
+            && !open.sinks.contains_key(&(epoch + 1))
+        {
+            let next = self.open_epoch(epoch + 1).await?;
+            open.sinks.insert(epoch + 1, next);
+        }
+        Ok(sink)
+    }
+
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        let sinks: Vec<Arc<dyn Sink>> = {
+            let open = self.open.lock().await;
+            open.sinks.values().chain(&open.unsplit).cloned().collect()
+        };
+        for sink in sinks {
+            sink.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Prune the open topics, `None` when their sink does not prune
+    pub async fn prune(&self, cutoff: &RetentionCutoff) -> anyhow::Result<Option<u64>> {
+        let sinks: Vec<Arc<dyn Sink>> = {
+            let open = self.open.lock().await;
+            open.sinks.values().chain(&open.unsplit).cloned().collect()
+        };
+        let mut pruned = None;
+        for sink in sinks {
+            if let Some(count) = sink.prune(cutoff).await? {
+                pruned = Some(pruned.unwrap_or(0) + count);
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_late_records_to_open_topics() {
+        let open = BTreeMap::from([(9, ()), (10, ())]);
+        assert_eq!(late_epoch(&BTreeMap::<u64, ()>::new(), None, 5), None);
+        assert_eq!(late_epoch(&open, Some(10), 10), None);
+        assert_eq!(late_epoch(&open, Some(10), 11), None);
+        assert_eq!(late_epoch(&open, Some(10), 9), Some(9));
+        // Closed epochs are not reopened
+        assert_eq!(late_epoch(&open, Some(10), 3), Some(9));
+    }
+}
@@ -0,0 +1,181 @@
+use schemars::{
+    schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec},
+    schema_for,
+};
+use serde_json::Value;
+
+use crate::config::YellowstoneGrpcConfig;
+
+/// Commented example `config.yaml` generated from the config structs.
+/// Required fields are set to an example value, optional fields are commented out.
+pub(crate) fn example_config() -> String {
+    let root = schema_for!(YellowstoneGrpcConfig);
+    let mut out = String::from(
+        "# Example config generated by `--print-example-config`\n\
+         # Required fields are set, uncomment optional fields to override their defaults\n",
+    );
+    Printer { root: &root }.object(&root.schema, 0, false, false, &mut out);
+    out
+}
+
+struct Printer<'a> {
+    root: &'a RootSchema,
+}
+
+impl<'a> Printer<'a> {
+    /// Print the properties of an object schema, starting with `- ` when it is a list item
+    fn object(&self, schema: &'a SchemaObject, indent: usize, commented: bool, list_item: bool, out: &mut String) {
+        let Some(object) = &schema.object else {
+            return;
+        };
+
+        for (index, (name, property)) in object.properties.iter().enumerate() {
+            let Schema::Object(property) = property else {
+                continue;
+            };
+            if indent == 1 && index > 0 {
+                out.push('\n');
+            }
+            let required = object.required.contains(name);
+            self.field(name, property, indent, commented || !required, list_item && index == 0, out);
+        }
+    }
+
+    fn field(&self, name: &str, schema: &'a SchemaObject, indent: usize, commented: bool, dash: bool, out: &mut String) {
+        let resolved = self.resolve(schema);
+        let pad = "  ".repeat(indent);
+
+        if let Some(description) = description(schema).or_else(|| description(resolved)) {
+            for line in description.lines() {
+                out.push_str(&format!("{}# {}\n", pad, line.trim()));
+            }
+        }
+        let allowed = self.allowed_values(resolved);
+        if allowed.len() > 1 {
+            let allowed: Vec<String> = allowed.iter().map(scalar).collect();
+            out.push_str(&format!("{}# one of: {}\n", pad, allowed.join(" | ")));
+        }
+
+        let mut prefix = if dash { "  ".repeat(indent - 1) } else { pad };
+        if commented {
+            prefix.push_str("# ");
+        }
+        if dash {
+            prefix.push_str("- ");
+        }
+
+        let item = self.array_item(resolved).filter(|item| item.object.is_some());
+        if resolved.object.is_some() {
+            let has_required = resolved.object.as_ref().is_some_and(|object| !object.required.is_empty());
+            // An object without required fields needs an explicit empty value to stay valid YAML
+            let value = if commented || has_required { "" } else { " {}" };
+            out.push_str(&format!("{}{}:{}\n", prefix, name, value));
+            self.object(resolved, indent + 1, commented, false, out);
+        } else if let Some(item) = item {
+            out.push_str(&format!("{}{}:\n", prefix, name));
+            self.object(item, indent + 2, commented, true, out);
+        } else {
+            out.push_str(&format!("{}{}: {}\n", prefix, name, self.example(schema, resolved)));
+        }
+    }
+
+    /// Follow `$ref`s and `allOf` / `anyOf` wrappers, skipping the `null` arm of optional fields
+    /// and preferring the structured arm of untagged enums
+    fn resolve(&self, schema: &'a SchemaObject) -> &'a SchemaObject {
+        if let Some(reference) = &schema.reference {
+            let name = reference.trim_start_matches("#/definitions/");
+            if let Some(Schema::Object(definition)) = self.root.definitions.get(name) {
+                return self.resolve(definition);
+            }
+        }
+
+        if let Some(subschemas) = &schema.subschemas {
+            let variants: Vec<&'a SchemaObject> = [&subschemas.all_of, &subschemas.any_of]
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|variant| match variant {
+                    Schema::Object(variant) => Some(self.resolve(variant)),
+                    Schema::Bool(_) => None,
+                })
+                .filter(|variant| !is_null(variant))
+                .collect();
+            if let Some(variant) = variants.iter().find(|variant| variant.object.is_some()).or(variants.first()) {
+                return *variant;
+            }
+        }
+
+        schema
+    }
+
+    fn array_item(&self, schema: &'a SchemaObject) -> Option<&'a SchemaObject> {
+        match schema.array.as_ref()?.items.as_ref()? {
+            SingleOrVec::Single(item) => match item.as_ref() {
+                Schema::Object(item) => Some(self.resolve(item)),
+                Schema::Bool(_) => None,
+            },
+            SingleOrVec::Vec(_) => None,
+        }
+    }
+
+    /// Values of a string enum, in declaration order so the default variant comes first
+    fn allowed_values(&self, schema: &SchemaObject) -> Vec<Value> {
+        if let Some(values) = &schema.enum_values {
+            return values.clone();
+        }
+
+        schema
+            .subschemas
+            .iter()
+            .flat_map(|subschemas| subschemas.one_of.iter().flatten())
+            .filter_map(|variant| match variant {
+                Schema::Object(variant) => variant.enum_values.as_ref(),
+                Schema::Bool(_) => None,
+            })
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    fn example(&self, schema: &SchemaObject, resolved: &'a SchemaObject) -> String {
+        let examples = [schema, resolved]
+            .into_iter()
+            .find_map(|schema| schema.metadata.as_ref()?.examples.first());
+        if let Some(example) = examples {
+            return scalar(example);
+        }
+        if let Some(value) = self.allowed_values(resolved).first() {
+            return scalar(value);
+        }
+
+        match instance_type(resolved) {
+            Some(InstanceType::Boolean) => "false".to_string(),
+            Some(InstanceType::Integer | InstanceType::Number) => "0".to_string(),
+            Some(InstanceType::Array) => match self.array_item(resolved) {
+                Some(item) => format!("[{}]", self.example(item, item)),
+                None => "[]".to_string(),
+            },
+            _ => "\"\"".to_string(),
+        }
+    }
+}
+
+fn description(schema: &SchemaObject) -> Option<&str> {
+    schema.metadata.as_ref()?.description.as_deref()
+}
+
+fn instance_type(schema: &SchemaObject) -> Option<InstanceType> {
+    match schema.instance_type.as_ref()? {
+        SingleOrVec::Single(instance_type) => Some(**instance_type),
+        SingleOrVec::Vec(instance_types) => instance_types.iter().copied().find(|t| *t != InstanceType::Null),
+    }
+}
+
+fn is_null(schema: &SchemaObject) -> bool {
+    matches!(&schema.instance_type, Some(SingleOrVec::Single(t)) if **t == InstanceType::Null)
+}
+
+/// JSON scalars are valid YAML flow values
+fn scalar(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
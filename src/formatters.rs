@@ -6,25 +6,29 @@ use yellowstone_grpc_proto::{convert_from, geyser::{
     CommitmentLevel, SubscribeUpdateAccount, SubscribeUpdateAccountInfo, SubscribeUpdateBlock, SubscribeUpdateBlockMeta, SubscribeUpdateEntry, SubscribeUpdateSlot, SubscribeUpdateTransaction, SubscribeUpdateTransactionInfo, SubscribeUpdateTransactionStatus
 }};
 use log::info;
-use base64;
 
-use crate::EPOCH_SIZE;
+use crate::{
+    codec,
+    profiling::{self, Stage},
+    EPOCH_SIZE,
+};
 
 pub fn format_account(update: SubscribeUpdateAccount) -> anyhow::Result<Value> {
+    let _span = profiling::span(Stage::Format);
     let Some(account_info) = update.account else {
         return Err(anyhow::anyhow!("Missing account info"));
     };
 
-    let base64_data = base64::encode(&account_info.data);
+    let base64_data = codec::encode_base64(&account_info.data);
 
     Ok(json!({
-        "pubkey": bs58::encode(&account_info.pubkey).into_string(),
+        "pubkey": codec::encode_bs58(&account_info.pubkey),
         "lamports": account_info.lamports,
-        "owner": bs58::encode(&account_info.owner).into_string(),
+        "owner": codec::encode_pubkey_cached(&account_info.owner),
         "rent_epoch": account_info.rent_epoch,
         "slot": update.slot,
         "data": base64_data,
-        "txn_signature": account_info.txn_signature.as_ref().map(|sig| bs58::encode(sig).into_string()),
+        "txn_signature": account_info.txn_signature.as_ref().map(|sig| codec::encode_bs58(sig)),
     }))
 }
 
@@ -38,13 +42,17 @@ pub fn format_slot(msg: SubscribeUpdateSlot) -> anyhow::Result<Value> {
 }
 
 pub fn format_transaction(msg: SubscribeUpdateTransaction) -> anyhow::Result<Value> {
+    let _span = profiling::span(Stage::Format);
     let tx = msg
         .transaction
         .ok_or(anyhow::anyhow!("no transaction in the message"))?;
 
-    let encoded = convert_from::create_tx_with_meta(tx)
+    let decode_span = profiling::span(Stage::Decode);
+    let decoded = convert_from::create_tx_with_meta(tx)
         .map_err(|error| anyhow::anyhow!(error))
-        .context("invalid tx with meta")?
+        .context("invalid tx with meta")?;
+    drop(decode_span);
+    let encoded = decoded
         .encode(UiTransactionEncoding::JsonParsed, Some(u8::MAX), true)
         .context("failed to encode transaction")?;
 
@@ -60,24 +68,113 @@ pub fn format_transaction(msg: SubscribeUpdateTransaction) -> anyhow::Result<Val
     Ok(value)
 }
 
-// pub fn format_transaction_status(msg: SubscribeUpdateTransactionStatus) -> anyhow::Result<Value> {
-//     Ok(json!({
-//         "slot": msg.slot,
-//         "epoch": msg.slot / EPOCH_SIZE,
-//         "signature": Signature::try_from(msg.signature.as_slice()).context("invalid signature")?.to_string(),
-//         "isVote": msg.is_vote,
-//         "index": msg.index,
-//         "err": if let Some(err) = msg.err {
-//             convert_from::create_tx_error(Some(&err))
-//                 .map_err(|error| anyhow::anyhow!(error))
-//                 .context("invalid error")?
-//         } else {
-//             convert_from::create_tx_error(None)
-//                 .map_err(|error| anyhow::anyhow!(error))
-//                 .context("invalid error")?
-//         },
-//     }))
-// }
+/// Replace `meta.innerInstructions` with `flatInstructions`, every instruction in execution
+/// order with its `index` in the list, `depth` (0 for top-level), `parent` (index of the invoking
+/// instruction, `null` for top-level) and `topLevelIndex`, like explorer views
+pub fn flatten_instructions(tx: &mut Value) {
+    let inner = tx["meta"]
+        .as_object_mut()
+        .and_then(|meta| meta.remove("innerInstructions"))
+        .unwrap_or(Value::Null);
+    let top_level = tx["transaction"]["message"]["instructions"].as_array().cloned().unwrap_or_default();
+
+    let mut flat = Vec::new();
+    for (top_level_index, instruction) in top_level.into_iter().enumerate() {
+        let inner_instructions = inner
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|inner| inner["index"].as_u64() == Some(top_level_index as u64))
+            .flat_map(|inner| inner["instructions"].as_array().cloned().unwrap_or_default());
+
+        // Indices of the instructions on the current invocation stack, by depth
+        let mut stack: Vec<usize> = Vec::new();
+        for (position, instruction) in std::iter::once(instruction).chain(inner_instructions).enumerate() {
+            // stackHeight is 1 for top-level instructions, missing before it was recorded
+            let depth = match instruction["stackHeight"].as_u64() {
+                Some(height) => height.saturating_sub(1) as usize,
+                None => usize::from(position > 0),
+            };
+            stack.truncate(depth);
+            let parent = stack.last().copied();
+            let index = flat.len();
+
+            let mut entry = match instruction {
+                Value::Object(object) => object,
+                other => serde_json::Map::from_iter([("instruction".to_string(), other)]),
+            };
+            entry.insert("index".to_string(), json!(index));
+            entry.insert("depth".to_string(), json!(depth));
+            entry.insert("parent".to_string(), json!(parent));
+            entry.insert("topLevelIndex".to_string(), json!(top_level_index));
+            flat.push(Value::Object(entry));
+            stack.push(index);
+        }
+    }
+    tx["flatInstructions"] = Value::Array(flat);
+}
+
+/// Compact transaction record built from a JsonParsed transaction record
+pub fn summarize_transaction(tx: &Value) -> Value {
+    let message = &tx["transaction"]["message"];
+    let meta = &tx["meta"];
+
+    let instructions: Vec<&Value> = match tx["flatInstructions"].as_array() {
+        Some(flat) => flat.iter().collect(),
+        None => message["instructions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .chain(
+                meta["innerInstructions"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|inner| inner["instructions"].as_array().into_iter().flatten()),
+            )
+            .collect(),
+    };
+
+    let mut programs: Vec<&str> = instructions
+        .iter()
+        .filter_map(|instruction| instruction["programId"].as_str())
+        .collect();
+    programs.sort_unstable();
+    programs.dedup();
+
+    let token_transfers = instructions
+        .iter()
+        .filter(|instruction| matches!(instruction["program"].as_str(), Some("spl-token" | "spl-token-2022")))
+        .filter(|instruction| matches!(instruction["parsed"]["type"].as_str(), Some("transfer" | "transferChecked")))
+        .count();
+
+    json!({
+        "signature": tx["transaction"]["signatures"][0],
+        "slot": tx["slot"],
+        "blockTime": tx["blockTime"],
+        "status": if meta["err"].is_null() { "success" } else { "failed" },
+        "fee": meta["fee"],
+        // JsonParsed account keys are objects, other encodings plain strings
+        "feePayer": message["accountKeys"][0].get("pubkey").unwrap_or(&message["accountKeys"][0]),
+        "programs": programs,
+        "tokenTransfers": token_transfers,
+    })
+}
+
+pub fn format_transaction_status(msg: SubscribeUpdateTransactionStatus) -> anyhow::Result<Value> {
+    let err = convert_from::create_tx_error(msg.err.as_ref())
+        .map_err(|error| anyhow::anyhow!(error))
+        .context("invalid error")?;
+
+    Ok(json!({
+        "slot": msg.slot,
+        "epoch": msg.slot / EPOCH_SIZE,
+        "signature": Signature::try_from(msg.signature.as_slice()).context("invalid signature")?.to_string(),
+        "isVote": msg.is_vote,
+        "index": msg.index,
+        "err": err,
+    }))
+}
 
 // pub fn format_entry(msg: SubscribeUpdateEntry) -> anyhow::Result<Value> {
 //     Ok(json!({
@@ -89,6 +186,7 @@ pub fn format_transaction(msg: SubscribeUpdateTransaction) -> anyhow::Result<Val
 // }
 
 pub fn format_block_meta(msg: SubscribeUpdateBlockMeta) -> anyhow::Result<Value> {
+    let _span = profiling::span(Stage::Format);
     Ok(json!({
         "slot": msg.slot,
         "blockhash": msg.blockhash,
@@ -133,7 +231,7 @@ fn create_pretty_account(account: SubscribeUpdateAccountInfo) -> anyhow::Result<
         "rentEpoch": account.rent_epoch,
         "data": hex::encode(account.data),
         "writeVersion": account.write_version,
-        "txnSignature": account.txn_signature.map(|sig| bs58::encode(sig).into_string()),
+        "txnSignature": account.txn_signature.map(|sig| codec::encode_bs58(&sig)),
     }))
 }
 
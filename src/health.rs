@@ -0,0 +1,311 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use log::info;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{metrics::Metrics, pause};
+
+/// Transitions of a stream kept for `/status`
+const HISTORY_LEN: usize = 16;
+
+/// Health thresholds
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    /// A stream without any update (pings included) for this long is degraded
+    pub stale_after: Duration,
+    /// A stream disconnected or without any update for this long is dead
+    pub dead_after: Duration,
+}
+
+/// Health of the streamer, in Kubernetes probe terms: degraded fails readiness, dead fails
+/// liveness as well so the pod is restarted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    /// Reconnecting or not receiving updates, expected to recover on its own
+    Degraded,
+    /// Stopped or unable to recover within `dead_after`
+    Dead,
+}
+
+/// Lifecycle of a subscription stream. Sources and the stream task move it through the states,
+/// every transition is logged, counted in the metrics and kept for `/status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// Subscribing for the first time
+    Connecting,
+    /// Subscribed, waiting for the first update
+    Subscribed,
+    /// Receiving updates
+    Streaming,
+    /// Subscribed but without any update (pings included) for `stale_after`
+    Degraded,
+    /// A subscription of the stream was lost and is being re-established
+    Reconnecting,
+    /// The stream ended and will not produce updates anymore
+    Stopped,
+}
+
+impl ConnectionState {
+    pub const ALL: [ConnectionState; 6] = [
+        ConnectionState::Connecting,
+        ConnectionState::Subscribed,
+        ConnectionState::Streaming,
+        ConnectionState::Degraded,
+        ConnectionState::Reconnecting,
+        ConnectionState::Stopped,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Subscribed => "subscribed",
+            ConnectionState::Streaming => "streaming",
+            ConnectionState::Degraded => "degraded",
+            ConnectionState::Reconnecting => "reconnecting",
+            ConnectionState::Stopped => "stopped",
+        }
+    }
+}
+
+struct Lifecycle {
+    since: Instant,
+    transitions: u64,
+    history: VecDeque<Value>,
+}
+
+/// Health of one subscription stream, updated by its source and stream task
+pub struct StreamHealth {
+    name: String,
+    started: Instant,
+    // Milliseconds since `started`
+    last_update_ms: AtomicU64,
+    disconnected_since_ms: AtomicU64,
+    // Inner subscriptions currently down, a multiplexed stream has several
+    disconnected: AtomicUsize,
+    finished: AtomicBool,
+    // `ConnectionState` index, read without locking `lifecycle` on every update
+    state: AtomicU8,
+    lifecycle: Mutex<Lifecycle>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl StreamHealth {
+    fn elapsed_ms(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        ConnectionState::ALL[self.state.load(Ordering::Relaxed) as usize]
+    }
+
+    /// Move to `to` from one of the states `from`, ignored from any other state
+    fn transition(&self, from: &[ConnectionState], to: ConnectionState) {
+        let mut lifecycle = self.lifecycle.lock().expect("health lock");
+        let current = self.state();
+        if current == to || !from.contains(&current) {
+            return;
+        }
+        self.state.store(to as u8, Ordering::Relaxed);
+        info!("Stream '{}' {} -> {}", self.name, current.name(), to.name());
+
+        lifecycle.since = Instant::now();
+        lifecycle.transitions += 1;
+        if lifecycle.history.len() == HISTORY_LEN {
+            lifecycle.history.pop_front();
+        }
+        lifecycle.history.push_back(json!({ "from": current, "to": to, "at": Utc::now().to_rfc3339() }));
+        if let Some(metrics) = &self.metrics {
+            metrics.record_state_transition(Some(current), to);
+        }
+    }
+
+    /// The first subscription of the stream was established
+    pub fn subscribed(&self) {
+        self.transition(&[ConnectionState::Connecting], ConnectionState::Subscribed);
+    }
+
+    /// An update (or ping) was received
+    pub fn updated(&self) {
+        self.last_update_ms.store(self.elapsed_ms(), Ordering::Relaxed);
+        if matches!(self.state(), ConnectionState::Subscribed | ConnectionState::Degraded) {
+            self.transition(&[ConnectionState::Subscribed, ConnectionState::Degraded], ConnectionState::Streaming);
+        }
+    }
+
+    /// A subscription of the stream was lost and is reconnecting
+    pub fn disconnected(&self) {
+        if self.disconnected.fetch_add(1, Ordering::Relaxed) == 0 {
+            self.disconnected_since_ms.store(self.elapsed_ms(), Ordering::Relaxed);
+            let connected = &ConnectionState::ALL[..ConnectionState::Reconnecting.index()];
+            self.transition(connected, ConnectionState::Reconnecting);
+        }
+    }
+
+    /// A lost subscription was re-established
+    pub fn reconnected(&self) {
+        let down = self
+            .disconnected
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| count.checked_sub(1));
+        if down == Ok(1) {
+            self.transition(&[ConnectionState::Reconnecting], ConnectionState::Subscribed);
+        }
+    }
+
+    /// The stream ended and will not produce updates anymore
+    pub fn finished(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+        let running = &ConnectionState::ALL[..ConnectionState::Stopped.index()];
+        self.transition(running, ConnectionState::Stopped);
+    }
+
+    /// Degrade a subscribed stream without any update for `stale_after`, unless the subscriptions
+    /// are paused
+    fn tick(&self, config: &HealthConfig) {
+        if pause::subscription_paused() {
+            return;
+        }
+        let idle = Duration::from_millis(self.elapsed_ms().saturating_sub(self.last_update_ms.load(Ordering::Relaxed)));
+        if idle >= config.stale_after {
+            self.transition(&[ConnectionState::Subscribed, ConnectionState::Streaming], ConnectionState::Degraded);
+        }
+    }
+
+    fn status(&self) -> Value {
+        let lifecycle = self.lifecycle.lock().expect("health lock");
+        json!({
+            "name": self.name,
+            "state": self.state(),
+            "stateSecs": lifecycle.since.elapsed().as_secs(),
+            "transitions": lifecycle.transitions,
+            "history": lifecycle.history,
+        })
+    }
+
+    fn report(&self, config: &HealthConfig) -> (HealthStatus, Value) {
+        let now = self.elapsed_ms();
+        let idle = if pause::subscription_paused() {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(now.saturating_sub(self.last_update_ms.load(Ordering::Relaxed)))
+        };
+        let disconnected = self.disconnected.load(Ordering::Relaxed) > 0;
+        let down = Duration::from_millis(now.saturating_sub(self.disconnected_since_ms.load(Ordering::Relaxed)));
+
+        let status = if self.finished.load(Ordering::Relaxed)
+            || idle >= config.dead_after
+            || (disconnected && down >= config.dead_after)
+        {
+            HealthStatus::Dead
+        } else if disconnected || idle >= config.stale_after {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        };
+
+        let report = json!({
+            "name": self.name,
+            "status": status,
+            "state": self.state(),
+            "reconnecting": disconnected,
+            "idleMs": idle.as_millis() as u64,
+        });
+        (status, report)
+    }
+}
+
+/// Health of every stream of the pipeline, served by the admin probes and `/status`
+pub struct Health {
+    config: HealthConfig,
+    streams: Mutex<Vec<Arc<StreamHealth>>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Health {
+    pub fn new(config: HealthConfig, metrics: Option<Arc<Metrics>>) -> Self {
+        Self {
+            config,
+            streams: Mutex::new(Vec::new()),
+            metrics,
+        }
+    }
+
+    /// Track a new stream, connecting and healthy until its thresholds are exceeded
+    pub fn register(&self, name: impl Into<String>) -> Arc<StreamHealth> {
+        let stream = Arc::new(StreamHealth {
+            name: name.into(),
+            started: Instant::now(),
+            last_update_ms: AtomicU64::new(0),
+            disconnected_since_ms: AtomicU64::new(0),
+            disconnected: AtomicUsize::new(0),
+            finished: AtomicBool::new(false),
+            state: AtomicU8::new(ConnectionState::Connecting as u8),
+            lifecycle: Mutex::new(Lifecycle {
+                since: Instant::now(),
+                transitions: 0,
+                history: VecDeque::with_capacity(HISTORY_LEN),
+            }),
+            metrics: self.metrics.clone(),
+        });
+        info!("Stream '{}' {}", stream.name, ConnectionState::Connecting.name());
+        if let Some(metrics) = &self.metrics {
+            metrics.record_state_transition(None, ConnectionState::Connecting);
+        }
+        self.streams.lock().expect("health lock").push(Arc::clone(&stream));
+        stream
+    }
+
+    /// Check the streams for the `degraded` state every second, in the background
+    pub fn monitor(self: &Arc<Self>) {
+        let health = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                ticker.tick().await;
+                let streams = health.streams.lock().expect("health lock").clone();
+                for stream in streams {
+                    stream.tick(&health.config);
+                }
+            }
+        });
+    }
+
+    /// Connection state of every stream with its recent transitions
+    pub fn status(&self) -> Value {
+        let streams = self.streams.lock().expect("health lock");
+        let (status, _) = self.report_streams(&streams);
+        json!({ "status": status, "streams": streams.iter().map(|stream| stream.status()).collect::<Vec<_>>() })
+    }
+
+    /// Worst status of the streams with a JSON report of each
+    pub fn report(&self) -> (HealthStatus, Value) {
+        let streams = self.streams.lock().expect("health lock");
+        self.report_streams(&streams)
+    }
+
+    fn report_streams(&self, streams: &[Arc<StreamHealth>]) -> (HealthStatus, Value) {
+        let mut status = HealthStatus::Healthy;
+        let mut reports = Vec::with_capacity(streams.len());
+        for stream in streams.iter() {
+            let (stream_status, report) = stream.report(&self.config);
+            status = status.max(stream_status);
+            reports.push(report);
+        }
+        (status, json!({ "status": status, "streams": reports }))
+    }
+}
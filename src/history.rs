@@ -0,0 +1,471 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::warn;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use solana_sdk::{
+    message::VersionedMessage,
+    reward_type::RewardType,
+    transaction::{TransactionError, VersionedTransaction},
+};
+#[cfg(feature = "bigtable")]
+use solana_storage_bigtable::{CredentialType, LedgerStorage, LedgerStorageConfig};
+#[cfg(feature = "bigtable")]
+use solana_transaction_status::{BlockEncodingOptions, TransactionDetails, UiTransactionEncoding};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedTransactionWithStatusMeta, Reward, UiConfirmedBlock,
+    UiInnerInstructions, UiInstruction, UiLoadedAddresses, UiTransactionReturnData, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+use yellowstone_grpc_proto::prelude::{self as proto, subscribe_update::UpdateOneof};
+
+use crate::{config::HistoryConfig, rpc::RpcClient};
+
+/// Where historical blocks are read from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HistorySourceKind {
+    /// Any Solana JSON-RPC node with the range in its ledger, including the Old Faithful
+    /// archive server `faithful-cli rpc`
+    #[default]
+    Rpc,
+    /// Google Bigtable ledger storage
+    Bigtable,
+}
+
+/// Source of confirmed historical blocks
+#[async_trait]
+pub trait BlockProvider: Send + Sync {
+    /// Fetch the block for `slot`, `None` if the slot was skipped
+    async fn get_block(&self, slot: u64) -> anyhow::Result<Option<UiConfirmedBlock>>;
+}
+
+#[async_trait]
+impl BlockProvider for RpcClient {
+    async fn get_block(&self, slot: u64) -> anyhow::Result<Option<UiConfirmedBlock>> {
+        RpcClient::get_block(self, slot).await
+    }
+}
+
+/// Reads blocks from Bigtable ledger storage, requires the `bigtable` feature
+#[cfg(feature = "bigtable")]
+pub struct BigtableProvider {
+    storage: LedgerStorage,
+}
+
+#[cfg(feature = "bigtable")]
+impl BigtableProvider {
+    pub async fn new(config: &HistoryConfig) -> anyhow::Result<Self> {
+        let default_config = LedgerStorageConfig::default();
+        let storage = LedgerStorage::new_with_config(LedgerStorageConfig {
+            read_only: true,
+            credential_type: CredentialType::Filepath(config.bigtable_credentials.clone()),
+            instance_name: config.bigtable_instance.clone().unwrap_or(default_config.instance_name.clone()),
+            app_profile_id: config.bigtable_app_profile.clone().unwrap_or(default_config.app_profile_id.clone()),
+            ..default_config
+        })
+        .await?;
+
+        Ok(Self { storage })
+    }
+}
+
+#[cfg(feature = "bigtable")]
+#[async_trait]
+impl BlockProvider for BigtableProvider {
+    async fn get_block(&self, slot: u64) -> anyhow::Result<Option<UiConfirmedBlock>> {
+        let block = match self.storage.get_confirmed_block(slot).await {
+            Ok(block) => block,
+            Err(solana_storage_bigtable::Error::BlockNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let options = BlockEncodingOptions {
+            transaction_details: TransactionDetails::Full,
+            show_rewards: true,
+            max_supported_transaction_version: Some(0),
+        };
+        Ok(Some(block.encode_with_options(UiTransactionEncoding::Base64, options)?))
+    }
+}
+
+/// Build the block provider selected in the `history` config, `endpoint` is used by JSON-RPC sources
+pub async fn block_provider(
+    config: Option<&HistoryConfig>,
+    endpoint: Option<String>,
+) -> anyhow::Result<Box<dyn BlockProvider>> {
+    let source = config.and_then(|config| config.source).unwrap_or_default();
+
+    Ok(match source {
+        HistorySourceKind::Rpc => {
+            let endpoint = endpoint
+                .ok_or_else(|| anyhow::anyhow!("{:?} history source requires an RPC endpoint", source))?;
+            Box::new(RpcClient::new(endpoint))
+        },
+        #[cfg(feature = "bigtable")]
+        HistorySourceKind::Bigtable => {
+            let config = config.ok_or_else(|| anyhow::anyhow!("bigtable history source requires a history config"))?;
+            Box::new(BigtableProvider::new(config).await?)
+        },
+        #[cfg(not(feature = "bigtable"))]
+        HistorySourceKind::Bigtable => anyhow::bail!("built without the `bigtable` feature"),
+    })
+}
+
+/// Convert a historical block into the updates a live subscription would have produced.
+///
+/// Transactions that cannot be converted are skipped with a warning, the rest of the block is kept.
+pub fn block_updates(slot: u64, block: UiConfirmedBlock) -> Vec<UpdateOneof> {
+    let transactions = block.transactions.unwrap_or_default();
+    let mut updates = Vec::with_capacity(transactions.len() + 1);
+
+    let executed_transaction_count = transactions.len() as u64;
+    for (index, tx) in transactions.into_iter().enumerate() {
+        let info = match create_transaction_info(tx, index as u64) {
+            Ok(info) => info,
+            Err(e) => {
+                warn!("Skipping invalid transaction {} in slot {}: {:?}", index, slot, e);
+                continue;
+            },
+        };
+        updates.push(UpdateOneof::Transaction(proto::SubscribeUpdateTransaction {
+            transaction: Some(info),
+            slot,
+        }));
+    }
+
+    updates.push(UpdateOneof::BlockMeta(proto::SubscribeUpdateBlockMeta {
+        slot,
+        blockhash: block.blockhash,
+        rewards: block.rewards.map(|rewards| proto::Rewards {
+            rewards: rewards.into_iter().map(create_reward).collect(),
+            num_partitions: None,
+        }),
+        block_time: block.block_time.map(|timestamp| proto::UnixTimestamp { timestamp }),
+        block_height: block.block_height.map(|block_height| proto::BlockHeight { block_height }),
+        parent_slot: block.parent_slot,
+        parent_blockhash: block.previous_blockhash,
+        executed_transaction_count,
+        entries_count: 0,
+    }));
+
+    updates
+}
+
+/// Keep the `updates` of a historical block that a live subscription with `request` would have
+/// streamed, each tagged with the names of the filters it matched, so filter groups route them
+pub fn filter_updates(request: &proto::SubscribeRequest, updates: Vec<UpdateOneof>) -> Vec<proto::SubscribeUpdate> {
+    updates
+        .into_iter()
+        .filter_map(|update| {
+            let filters: Vec<String> = match &update {
+                UpdateOneof::Transaction(msg) => {
+                    let Some(info) = &msg.transaction else {
+                        return None;
+                    };
+                    request
+                        .transactions
+                        .iter()
+                        .filter(|(_, filter)| transaction_matches(filter, info))
+                        .map(|(name, _)| name.clone())
+                        .collect()
+                },
+                UpdateOneof::BlockMeta(_) => request.blocks_meta.keys().cloned().collect(),
+                _ => Vec::new(),
+            };
+            (!filters.is_empty()).then(|| proto::SubscribeUpdate {
+                filters,
+                update_oneof: Some(update),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Whether the Geyser plugin would stream `info` for a transaction `filter`
+fn transaction_matches(
+    filter: &proto::SubscribeRequestFilterTransactions,
+    info: &proto::SubscribeUpdateTransactionInfo,
+) -> bool {
+    let failed = info.meta.as_ref().is_some_and(|meta| meta.err.is_some());
+    if filter.vote.is_some_and(|vote| vote != info.is_vote) || filter.failed.is_some_and(|f| f != failed) {
+        return false;
+    }
+    if filter.signature.as_ref().is_some_and(|signature| *signature != bs58::encode(&info.signature).into_string()) {
+        return false;
+    }
+
+    let static_keys = info
+        .transaction
+        .as_ref()
+        .and_then(|tx| tx.message.as_ref())
+        .map(|message| message.account_keys.as_slice())
+        .unwrap_or_default();
+    let loaded_keys = info
+        .meta
+        .iter()
+        .flat_map(|meta| meta.loaded_writable_addresses.iter().chain(&meta.loaded_readonly_addresses));
+    let accounts: HashSet<String> =
+        static_keys.iter().chain(loaded_keys).map(|key| bs58::encode(key).into_string()).collect();
+    (filter.account_include.is_empty() || filter.account_include.iter().any(|account| accounts.contains(account)))
+        && !filter.account_exclude.iter().any(|account| accounts.contains(account))
+        && filter.account_required.iter().all(|account| accounts.contains(account))
+}
+
+fn create_transaction_info(
+    tx: EncodedTransactionWithStatusMeta,
+    index: u64,
+) -> anyhow::Result<proto::SubscribeUpdateTransactionInfo> {
+    let versioned = tx.transaction.decode().context("failed to decode transaction")?;
+    let signature = versioned
+        .signatures
+        .first()
+        .context("transaction without signatures")?
+        .as_ref()
+        .to_vec();
+    let account_keys = versioned.message.static_account_keys();
+    let is_vote = versioned.message.instructions().iter().any(|ix| {
+        account_keys.get(ix.program_id_index as usize) == Some(&solana_sdk::vote::program::id())
+    });
+    let meta = tx.meta.map(create_transaction_meta).transpose()?;
+
+    Ok(proto::SubscribeUpdateTransactionInfo {
+        signature,
+        is_vote,
+        transaction: Some(create_transaction(&versioned)),
+        meta,
+        index,
+    })
+}
+
+fn create_transaction(tx: &VersionedTransaction) -> proto::Transaction {
+    let message = &tx.message;
+    let header = message.header();
+
+    proto::Transaction {
+        signatures: tx.signatures.iter().map(|signature| signature.as_ref().to_vec()).collect(),
+        message: Some(proto::Message {
+            header: Some(proto::MessageHeader {
+                num_required_signatures: header.num_required_signatures as u32,
+                num_readonly_signed_accounts: header.num_readonly_signed_accounts as u32,
+                num_readonly_unsigned_accounts: header.num_readonly_unsigned_accounts as u32,
+            }),
+            account_keys: message.static_account_keys().iter().map(|key| key.as_ref().to_vec()).collect(),
+            recent_blockhash: message.recent_blockhash().as_ref().to_vec(),
+            instructions: message
+                .instructions()
+                .iter()
+                .map(|ix| proto::CompiledInstruction {
+                    program_id_index: ix.program_id_index as u32,
+                    accounts: ix.accounts.clone(),
+                    data: ix.data.clone(),
+                })
+                .collect(),
+            versioned: matches!(message, VersionedMessage::V0(_)),
+            address_table_lookups: message
+                .address_table_lookups()
+                .unwrap_or_default()
+                .iter()
+                .map(|lookup| proto::MessageAddressTableLookup {
+                    account_key: lookup.account_key.as_ref().to_vec(),
+                    writable_indexes: lookup.writable_indexes.clone(),
+                    readonly_indexes: lookup.readonly_indexes.clone(),
+                })
+                .collect(),
+        }),
+    }
+}
+
+fn create_transaction_meta(meta: UiTransactionStatusMeta) -> anyhow::Result<proto::TransactionStatusMeta> {
+    // Round-trip through JSON so both the plain and the UI error representations are accepted
+    let err: Option<TransactionError> = serde_json::from_value(serde_json::to_value(&meta.err)?)
+        .context("invalid transaction error")?;
+    let err = err
+        .map(|err| bincode::serialize(&err).map(|err| proto::TransactionError { err }))
+        .transpose()?;
+
+    let inner_instructions: Option<Vec<UiInnerInstructions>> = meta.inner_instructions.into();
+    let log_messages: Option<Vec<String>> = meta.log_messages.into();
+    let rewards: Option<Vec<Reward>> = meta.rewards.into();
+    let loaded_addresses: Option<UiLoadedAddresses> = meta.loaded_addresses.into();
+    let return_data: Option<UiTransactionReturnData> = meta.return_data.into();
+    let (loaded_writable, loaded_readonly) = loaded_addresses
+        .map(|addresses| (addresses.writable, addresses.readonly))
+        .unwrap_or_default();
+
+    Ok(proto::TransactionStatusMeta {
+        err,
+        fee: meta.fee,
+        pre_balances: meta.pre_balances,
+        post_balances: meta.post_balances,
+        inner_instructions_none: inner_instructions.is_none(),
+        inner_instructions: inner_instructions
+            .unwrap_or_default()
+            .into_iter()
+            .map(create_inner_instructions)
+            .collect::<anyhow::Result<_>>()?,
+        log_messages_none: log_messages.is_none(),
+        log_messages: log_messages.unwrap_or_default(),
+        pre_token_balances: create_token_balances(meta.pre_token_balances),
+        post_token_balances: create_token_balances(meta.post_token_balances),
+        rewards: rewards.unwrap_or_default().into_iter().map(create_reward).collect(),
+        loaded_writable_addresses: decode_pubkeys(&loaded_writable)?,
+        loaded_readonly_addresses: decode_pubkeys(&loaded_readonly)?,
+        return_data_none: return_data.is_none(),
+        return_data: return_data
+            .map(|return_data| -> anyhow::Result<_> {
+                Ok(proto::ReturnData {
+                    program_id: bs58::decode(&return_data.program_id).into_vec()?,
+                    data: STANDARD.decode(&return_data.data.0)?,
+                })
+            })
+            .transpose()?,
+        compute_units_consumed: meta.compute_units_consumed.into(),
+        ..Default::default()
+    })
+}
+
+fn create_inner_instructions(ixs: UiInnerInstructions) -> anyhow::Result<proto::InnerInstructions> {
+    let mut instructions = Vec::with_capacity(ixs.instructions.len());
+    for ix in ixs.instructions {
+        match ix {
+            UiInstruction::Compiled(ix) => instructions.push(proto::InnerInstruction {
+                program_id_index: ix.program_id_index as u32,
+                accounts: ix.accounts,
+                data: bs58::decode(&ix.data).into_vec()?,
+                stack_height: ix.stack_height,
+            }),
+            UiInstruction::Parsed(_) => anyhow::bail!("unexpected parsed inner instruction"),
+        }
+    }
+
+    Ok(proto::InnerInstructions {
+        index: ixs.index as u32,
+        instructions,
+    })
+}
+
+fn create_token_balances(balances: OptionSerializer<Vec<UiTransactionTokenBalance>>) -> Vec<proto::TokenBalance> {
+    Option::<Vec<_>>::from(balances)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|balance| proto::TokenBalance {
+            account_index: balance.account_index as u32,
+            mint: balance.mint,
+            ui_token_amount: Some(proto::UiTokenAmount {
+                ui_amount: balance.ui_token_amount.ui_amount.unwrap_or_default(),
+                decimals: balance.ui_token_amount.decimals as u32,
+                amount: balance.ui_token_amount.amount,
+                ui_amount_string: balance.ui_token_amount.ui_amount_string,
+            }),
+            owner: Option::<String>::from(balance.owner).unwrap_or_default(),
+            program_id: Option::<String>::from(balance.program_id).unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn create_reward(reward: Reward) -> proto::Reward {
+    let reward_type = match reward.reward_type {
+        None => proto::RewardType::Unspecified,
+        Some(RewardType::Fee) => proto::RewardType::Fee,
+        Some(RewardType::Rent) => proto::RewardType::Rent,
+        Some(RewardType::Staking) => proto::RewardType::Staking,
+        Some(RewardType::Voting) => proto::RewardType::Voting,
+    };
+
+    proto::Reward {
+        pubkey: reward.pubkey,
+        lamports: reward.lamports,
+        post_balance: reward.post_balance,
+        reward_type: reward_type as i32,
+        commission: reward.commission.map(|commission| commission.to_string()).unwrap_or_default(),
+    }
+}
+
+fn decode_pubkeys(pubkeys: &[String]) -> anyhow::Result<Vec<Vec<u8>>> {
+    pubkeys
+        .iter()
+        .map(|pubkey| bs58::decode(pubkey).into_vec().context("invalid pubkey"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use solana_sdk::pubkey::Pubkey;
+
+    use super::*;
+
+    fn transaction(keys: &[Pubkey], is_vote: bool, failed: bool) -> UpdateOneof {
+        UpdateOneof::Transaction(proto::SubscribeUpdateTransaction {
+            transaction: Some(proto::SubscribeUpdateTransactionInfo {
+                is_vote,
+                transaction: Some(proto::Transaction {
+                    message: Some(proto::Message {
+                        account_keys: keys.iter().map(|key| key.to_bytes().to_vec()).collect(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                meta: Some(proto::TransactionStatusMeta {
+                    err: failed.then(proto::TransactionError::default),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            slot: 1,
+        })
+    }
+
+    #[test]
+    fn keeps_and_tags_the_updates_matched_by_the_filters() {
+        let program = Pubkey::new_unique();
+        let request = proto::SubscribeRequest {
+            transactions: HashMap::from([
+                ("client".to_string(), proto::SubscribeRequestFilterTransactions {
+                    vote: Some(false),
+                    failed: Some(false),
+                    account_include: vec![program.to_string()],
+                    ..Default::default()
+                }),
+                ("failed".to_string(), proto::SubscribeRequestFilterTransactions {
+                    failed: Some(true),
+                    ..Default::default()
+                }),
+            ]),
+            ..Default::default()
+        };
+        let updates = vec![
+            transaction(&[Pubkey::new_unique(), program], false, false),
+            transaction(&[program], true, false),
+            transaction(&[program], false, true),
+            transaction(&[Pubkey::new_unique()], false, false),
+            // No `blocks_meta` filter
+            UpdateOneof::BlockMeta(proto::SubscribeUpdateBlockMeta::default()),
+        ];
+
+        let filters: Vec<Vec<String>> =
+            filter_updates(&request, updates).into_iter().map(|update| update.filters).collect();
+        assert_eq!(filters, [["client"], ["failed"]]);
+    }
+
+    #[test]
+    fn skips_invalid_transactions() {
+        let block: UiConfirmedBlock = serde_json::from_value(serde_json::json!({
+            "previousBlockhash": "11111111111111111111111111111111",
+            "blockhash": "11111111111111111111111111111111",
+            "parentSlot": 0,
+            "transactions": [{ "transaction": ["not base64!", "base64"], "meta": null }],
+            "rewards": [],
+        }))
+        .unwrap();
+
+        let updates = block_updates(1, block);
+        assert_eq!(updates.len(), 1);
+        assert!(matches!(&updates[0], UpdateOneof::BlockMeta(meta) if meta.executed_transaction_count == 1));
+    }
+}
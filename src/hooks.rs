@@ -0,0 +1,152 @@
+use std::{collections::HashMap, time::Duration};
+
+use log::{error, info};
+use reqwest::Client;
+use serde_json::json;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest, SubscribeRequestFilterSlots,
+};
+
+use crate::{
+    platform,
+    sources::{GrpcEndpoint, ReconnectingSource, Source},
+};
+
+/// Milestones fired at once after a jump, older ones are skipped
+const MAX_MILESTONES_PER_UPDATE: u64 = 16;
+
+/// Slot milestone hook settings
+#[derive(Debug, Clone)]
+pub struct SlotHookConfig {
+    pub commitment: CommitmentLevel,
+    /// Fire each time the watermark reaches a multiple of this many slots
+    pub every: u64,
+    pub command: Option<String>,
+    pub url: Option<String>,
+}
+
+/// A slot range whose slots all reached the hook's commitment
+#[derive(Debug, Clone, Copy)]
+struct Milestone {
+    slot: u64,
+    from_slot: u64,
+    to_slot: u64,
+}
+
+struct SlotHook {
+    config: SlotHookConfig,
+    last_slot: Option<u64>,
+}
+
+impl SlotHook {
+    /// Milestones crossed by moving the watermark to `slot`, none for the first observed slot
+    fn observe(&mut self, slot: u64) -> Vec<Milestone> {
+        let Some(last_slot) = self.last_slot.filter(|last_slot| slot > *last_slot) else {
+            self.last_slot.get_or_insert(slot);
+            return Vec::new();
+        };
+        self.last_slot = Some(slot);
+
+        let every = self.config.every;
+        let first = (last_slot / every + 1).max((slot / every).saturating_sub(MAX_MILESTONES_PER_UPDATE - 1));
+        (first..=slot / every)
+            .map(|index| Milestone {
+                slot: index * every,
+                from_slot: (index * every).saturating_sub(every),
+                to_slot: (index * every).saturating_sub(1),
+            })
+            .collect()
+    }
+}
+
+/// Follow the slot watermark of every hooked commitment level and call the hooks at their
+/// milestones, e.g. to start batch jobs for each completed range of finalized slots
+pub fn start(hooks: Vec<SlotHookConfig>, endpoint: GrpcEndpoint) {
+    let mut by_commitment: HashMap<CommitmentLevel, Vec<SlotHook>> = HashMap::new();
+    for config in hooks {
+        info!("Slot hook every {} {:?} slots", config.every, config.commitment);
+        by_commitment.entry(config.commitment).or_default().push(SlotHook {
+            config,
+            last_slot: None,
+        });
+    }
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("Failed to create HTTP client");
+    for (commitment, hooks) in by_commitment {
+        tokio::spawn(follow(commitment, hooks, endpoint.clone(), client.clone()));
+    }
+}
+
+async fn follow(commitment: CommitmentLevel, mut hooks: Vec<SlotHook>, endpoint: GrpcEndpoint, client: Client) {
+    let request = SubscribeRequest {
+        slots: HashMap::from([(
+            "slot_hooks".to_owned(),
+            SubscribeRequestFilterSlots {
+                filter_by_commitment: Some(true),
+                ..Default::default()
+            },
+        )]),
+        commitment: Some(commitment as i32),
+        ..Default::default()
+    };
+
+    let mut source = loop {
+        match ReconnectingSource::connect(endpoint.clone(), request.clone()).await {
+            Ok(source) => break source,
+            Err(e) => {
+                error!("Error subscribing to {:?} slots for hooks: {:?}", commitment, e);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            },
+        }
+    };
+
+    while let Some(update) = source.next().await {
+        let Ok(update) = update else {
+            continue;
+        };
+        let Some(UpdateOneof::Slot(slot)) = update.update_oneof else {
+            continue;
+        };
+        for hook in &mut hooks {
+            for milestone in hook.observe(slot.slot) {
+                tokio::spawn(fire(hook.config.clone(), milestone, client.clone()));
+            }
+        }
+    }
+}
+
+async fn fire(config: SlotHookConfig, milestone: Milestone, client: Client) {
+    let commitment = config.commitment.as_str_name();
+    info!("{} slot milestone {} reached", commitment, milestone.slot);
+
+    if let Some(url) = &config.url {
+        let payload = json!({
+            "commitment": commitment,
+            "slot": milestone.slot,
+            "fromSlot": milestone.from_slot,
+            "toSlot": milestone.to_slot,
+        });
+        let result = client.post(url).json(&payload).send().await.and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            error!("Slot hook {} failed for slot {}: {:?}", url, milestone.slot, e);
+        }
+    }
+
+    if let Some(command) = config.command {
+        let mut shell = platform::shell_command(&command);
+        shell
+            .env("SLOT", milestone.slot.to_string())
+            .env("FROM_SLOT", milestone.from_slot.to_string())
+            .env("TO_SLOT", milestone.to_slot.to_string())
+            .env("COMMITMENT", commitment);
+        match tokio::task::spawn_blocking(move || shell.status()).await {
+            Ok(Ok(status)) if status.success() => {},
+            Ok(Ok(status)) => error!("Slot hook command exited with {} for slot {}", status, milestone.slot),
+            Ok(Err(e)) => error!("Failed to run slot hook command: {:?}", e),
+            Err(e) => error!("Slot hook command task failed: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+/// Instruction of a program, with its accounts resolved
+pub struct ProgramInstruction<'a> {
+    pub data: &'a [u8],
+    /// Accounts by position, `None` where an index is outside the transaction keys
+    pub accounts: Vec<Option<&'a [u8]>>,
+    /// Invoked by another program rather than from the transaction
+    pub cpi: bool,
+}
+
+impl ProgramInstruction<'_> {
+    /// Base58 account at `position`
+    pub fn account(&self, position: usize) -> Option<String> {
+        self.accounts.get(position).copied().flatten().map(crate::codec::encode_bs58)
+    }
+}
+
+/// Top-level and inner instructions of a successful transaction invoking `program`, in execution
+/// order: the inner instructions of a top-level instruction follow it
+pub fn program_instructions<'a>(update: &'a SubscribeUpdateTransaction, program: &[u8]) -> Vec<ProgramInstruction<'a>> {
+    let Some(info) = &update.transaction else {
+        return Vec::new();
+    };
+    let Some(message) = info.transaction.as_ref().and_then(|transaction| transaction.message.as_ref()) else {
+        return Vec::new();
+    };
+    let meta = info.meta.as_ref();
+    if meta.is_some_and(|meta| meta.err.is_some()) {
+        return Vec::new();
+    }
+
+    // Inner instructions index lookup table addresses after the static keys
+    let keys: Vec<&[u8]> = message
+        .account_keys
+        .iter()
+        .chain(meta.into_iter().flat_map(|meta| meta.loaded_writable_addresses.iter()))
+        .chain(meta.into_iter().flat_map(|meta| meta.loaded_readonly_addresses.iter()))
+        .map(Vec::as_slice)
+        .collect();
+    let inner = |position: usize| {
+        meta.into_iter()
+            .flat_map(|meta| meta.inner_instructions.iter())
+            .filter(move |inner| inner.index as usize == position)
+            .flat_map(|inner| inner.instructions.iter())
+            .map(|instruction| (instruction.program_id_index, &instruction.accounts, &instruction.data, true))
+    };
+    let executed = message.instructions.iter().enumerate().flat_map(|(position, instruction)| {
+        std::iter::once((instruction.program_id_index, &instruction.accounts, &instruction.data, false))
+            .chain(inner(position))
+    });
+
+    executed
+        .filter(|(program_id_index, ..)| keys.get(*program_id_index as usize) == Some(&program))
+        .map(|(_, accounts, data, cpi)| ProgramInstruction {
+            data,
+            accounts: accounts.iter().map(|index| keys.get(*index as usize).copied()).collect(),
+            cpi,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use yellowstone_grpc_proto::prelude::{
+        CompiledInstruction, InnerInstruction, InnerInstructions, Message, SubscribeUpdateTransactionInfo, Transaction,
+        TransactionStatusMeta,
+    };
+
+    use super::*;
+
+    fn inner(index: u32, data: u8) -> InnerInstructions {
+        InnerInstructions {
+            index,
+            instructions: vec![InnerInstruction {
+                program_id_index: 1,
+                accounts: vec![0],
+                data: vec![data],
+                stack_height: Some(2),
+            }],
+        }
+    }
+
+    #[test]
+    fn interleaves_inner_instructions_and_keeps_account_positions() {
+        let update = SubscribeUpdateTransaction {
+            transaction: Some(SubscribeUpdateTransactionInfo {
+                transaction: Some(Transaction {
+                    message: Some(Message {
+                        account_keys: vec![vec![7; 32], vec![1; 32], vec![2; 32]],
+                        instructions: vec![
+                            CompiledInstruction {
+                                program_id_index: 1,
+                                accounts: vec![9, 2],
+                                data: vec![1],
+                            },
+                            CompiledInstruction {
+                                program_id_index: 2,
+                                accounts: vec![],
+                                data: vec![3],
+                            },
+                        ],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }),
+                meta: Some(TransactionStatusMeta {
+                    inner_instructions: vec![inner(1, 4), inner(0, 2)],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let instructions = program_instructions(&update, &[1; 32]);
+        let order: Vec<(u8, bool)> =
+            instructions.iter().map(|instruction| (instruction.data[0], instruction.cpi)).collect();
+        assert_eq!(order, [(1, false), (2, true), (4, true)]);
+        // The first account index is outside the keys, the second keeps its position
+        assert_eq!(instructions[0].account(0), None);
+        assert_eq!(instructions[0].account(1), Some(crate::codec::encode_bs58(&[2; 32])));
+    }
+}
@@ -0,0 +1,160 @@
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    canonical::{self, NumberFormat},
+    control,
+};
+
+/// Field holding the hash of a record
+const HASH_FIELD: &str = "integrityHash";
+
+/// Hashes every published record and builds a Merkle root over each batch of `batch_size` hashes.
+///
+/// A record's hash is the SHA-256 of its canonical JSON (object keys sorted, no whitespace, see
+/// `canonical::to_vec`) without the hash field, in hex, with numbers written as published. Roots
+/// are built like RFC 6962 trees: a leaf is the SHA-256 of `0x00` and a record hash, a node the
+/// SHA-256 of `0x01` and its two children, and the last node of an odd level is promoted as is.
+pub struct IntegrityChain {
+    batch_size: usize,
+    batch: u64,
+    hashes: Vec<[u8; 32]>,
+    numbers: NumberFormat,
+    // Topic of the records, on the roots of route records
+    topic: Option<String>,
+}
+
+impl IntegrityChain {
+    pub fn new(batch_size: usize, numbers: NumberFormat) -> Self {
+        Self {
+            batch_size: batch_size.max(1),
+            batch: 0,
+            hashes: Vec::new(),
+            numbers,
+            topic: None,
+        }
+    }
+
+    /// Chain of the records of the route `topic`, named on its roots
+    pub fn for_topic(self, topic: &str) -> Self {
+        Self {
+            topic: Some(topic.to_owned()),
+            ..self
+        }
+    }
+
+    /// Add the hash of `record` to it, returning the `integrity_root` control record when the
+    /// batch is complete
+    pub fn stamp(&mut self, record: &mut Value) -> Option<Value> {
+        if let Some(object) = record.as_object_mut() {
+            object.remove(HASH_FIELD);
+        }
+        let hash: [u8; 32] = Sha256::digest(canonical::to_vec(record, self.numbers)).into();
+        record[HASH_FIELD] = json!(hex::encode(hash));
+
+        self.hashes.push(hash);
+        (self.hashes.len() >= self.batch_size).then(|| self.root())
+    }
+
+    /// Root of the incomplete batch, if any, e.g. on shutdown
+    pub fn finish(&mut self) -> Option<Value> {
+        (!self.hashes.is_empty()).then(|| self.root())
+    }
+
+    fn root(&mut self) -> Value {
+        let hashes = std::mem::take(&mut self.hashes);
+        let mut root = json!({
+            "batch": self.batch,
+            "count": hashes.len(),
+            "firstHash": hex::encode(hashes[0]),
+            "lastHash": hex::encode(hashes[hashes.len() - 1]),
+            "root": hex::encode(merkle_root(&hashes)),
+        });
+        if let Some(topic) = &self.topic {
+            root["topic"] = json!(topic);
+        }
+        self.batch += 1;
+        control::control_record("integrity_root", root)
+    }
+}
+
+/// Leaves and nodes are hashed with distinct prefixes, so a node cannot pass for a record
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+fn merkle_root(hashes: &[[u8; 32]]) -> [u8; 32] {
+    let mut level: Vec<[u8; 32]> = hashes.iter().map(|hash| prefixed(LEAF_PREFIX, &[hash])).collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => prefixed(NODE_PREFIX, &[left, right]),
+                [odd] => *odd,
+                _ => unreachable!("chunks of two"),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+fn prefixed(prefix: u8, parts: &[&[u8; 32]]) -> [u8; 32] {
+    parts.iter().fold(Sha256::new().chain_update([prefix]), |hasher, part| hasher.chain_update(part)).finalize().into()
+}
+
+/// Key identifying a record across retried and replayed publishes, `<key>:<slot>:<hash>` with the
+/// first 16 bytes of the SHA-256 of its canonical JSON, numbers written as published, in hex, for
+/// consumers deduplicating writes
+pub fn idempotency_key(key: &str, record: &Value, numbers: NumberFormat) -> String {
+    let hash = Sha256::digest(canonical::to_vec(record, numbers));
+    format!("{}:{}:{}", key, record["slot"].as_u64().unwrap_or_default(), hex::encode(&hash[..16]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canonical::LargeIntegers;
+
+    fn leaf(hash: [u8; 32]) -> [u8; 32] {
+        Sha256::new().chain_update([0x00]).chain_update(hash).finalize().into()
+    }
+
+    fn node(left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        Sha256::new().chain_update([0x01]).chain_update(left).chain_update(right).finalize().into()
+    }
+
+    #[test]
+    fn separates_leaves_from_nodes() {
+        let hashes = [[1; 32], [2; 32], [3; 32]];
+        assert_eq!(merkle_root(&hashes[..1]), leaf(hashes[0]));
+        assert_eq!(merkle_root(&hashes), node(node(leaf(hashes[0]), leaf(hashes[1])), leaf(hashes[2])));
+        // A batch of the two nodes of the level above does not give the same root
+        let nodes = [node(leaf(hashes[0]), leaf(hashes[1])), leaf(hashes[2])];
+        assert_ne!(merkle_root(&nodes), merkle_root(&hashes));
+    }
+
+    #[test]
+    fn names_the_route_topic_on_roots() {
+        let mut chain = IntegrityChain::new(1, NumberFormat::default()).for_topic("summaries");
+        let root = chain.stamp(&mut json!({ "slot": 1 })).unwrap();
+        assert_eq!(root["topic"], json!("summaries"));
+    }
+
+    #[test]
+    fn hashes_numbers_as_published() {
+        let record = json!({ "slot": 1, "lamports": u64::MAX });
+        let strings = NumberFormat {
+            large_integers: LargeIntegers::String,
+            big_numbers: false,
+        };
+        let published = Sha256::digest(canonical::to_vec(&record, strings));
+        assert_eq!(
+            idempotency_key("key", &record, strings),
+            format!("key:1:{}", hex::encode(&published[..16]))
+        );
+        assert_ne!(idempotency_key("key", &record, strings), idempotency_key("key", &record, NumberFormat::default()));
+
+        let mut stamped = record.clone();
+        IntegrityChain::new(10, strings).stamp(&mut stamped);
+        assert_eq!(stamped[HASH_FIELD], json!(hex::encode(published)));
+    }
+}
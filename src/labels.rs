@@ -0,0 +1,52 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Context;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Human readable label for a pubkey, e.g. `Raydium AMM` / `dex`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Label {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+/// Pubkey labels loaded from a JSON or YAML file mapping pubkeys to labels
+#[derive(Debug)]
+pub struct Labels {
+    labels: HashMap<String, Label>,
+}
+
+impl Labels {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).with_context(|| format!("failed to read labels file {}", path))?;
+        let is_json = Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let labels: HashMap<String, Label> = if is_json {
+            serde_json::from_str(&contents).with_context(|| format!("invalid labels file {}", path))?
+        } else {
+            serde_yaml::from_str(&contents).with_context(|| format!("invalid labels file {}", path))?
+        };
+        info!("Loaded {} pubkey labels from {}", labels.len(), path);
+
+        Ok(Self { labels })
+    }
+
+    /// Add `accountLabels` with the labels of the transaction's account keys
+    pub fn annotate_transaction(&self, tx: &mut Value) {
+        let mut account_labels = Map::new();
+        if let Some(account_keys) = tx["transaction"]["message"]["accountKeys"].as_array() {
+            for account_key in account_keys {
+                // JsonParsed keys are objects, other encodings plain strings
+                let pubkey = account_key["pubkey"].as_str().or_else(|| account_key.as_str());
+                if let Some((pubkey, label)) = pubkey.and_then(|pubkey| self.labels.get_key_value(pubkey)) {
+                    account_labels.insert(pubkey.clone(), json!(label));
+                }
+            }
+        }
+        tx["accountLabels"] = Value::Object(account_labels);
+    }
+}
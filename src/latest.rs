@@ -0,0 +1,191 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use log::{debug, error, info, warn};
+use serde_json::{json, Value};
+
+use crate::{
+    config::YellowstoneGrpcConfig, derived::DerivedStream, redact::Redactor, rpc::RpcClient, sinks::SinkConnector,
+};
+
+/// How often the tracked accounts are saved to `state_path`
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Accounts per `getMultipleAccounts` call, the RPC limit
+const RECONCILE_BATCH: usize = 100;
+
+/// Latest account state topic settings
+#[derive(Debug, Clone)]
+pub struct LatestAccountsConfig {
+    pub topic: String,
+    /// Slots the newest version of an account is remembered for, updates reordered by more are
+    /// not detected
+    pub retain_slots: u64,
+    /// File the accounts with a state on the topic are kept in across restarts
+    pub state_path: Option<PathBuf>,
+    /// Client checking the tracked accounts at startup, `None` without `reconcile_on_startup`
+    pub reconcile: Option<RpcClient>,
+}
+
+/// Account updates keyed by pubkey on a topic meant for key compaction, so it converges to the
+/// latest state of every account.
+///
+/// Compaction keeps the last record of a key, so an update is only published when it is newer,
+/// by slot then write version, than the last one published for its account: reordered updates,
+/// e.g. from sharded subscriptions or after a reconnect, would otherwise win. Closed accounts
+/// (0 lamports) are published as tombstones, an empty payload, deleting their key.
+///
+/// With a `state_path`, the accounts with a state on the topic are remembered across restarts,
+/// so the states of accounts closed while the streamer was down can be deleted at startup.
+pub struct LatestAccounts {
+    stream: DerivedStream,
+    config: LatestAccountsConfig,
+    // Slot and write version last published for each account
+    versions: HashMap<String, (u64, u64)>,
+    pruned_at: u64,
+    // Accounts with a state on the topic, only kept with a `state_path`
+    tracked: HashSet<String>,
+    saved_at: Instant,
+}
+
+impl LatestAccounts {
+    /// Publish the record of an account update at `slot` and `write_version`, unless a newer
+    /// version of the account was already published. Redacted like the records of the main topic.
+    pub async fn observe(&mut self, slot: u64, write_version: u64, record: &Value, redactor: Option<&Redactor>) {
+        let Some(pubkey) = record["pubkey"].as_str() else {
+            return;
+        };
+        if self.versions.get(pubkey).is_some_and(|newest| *newest >= (slot, write_version)) {
+            debug!("Skipping stale update of account {} at slot {} for the latest state", pubkey, slot);
+            return;
+        }
+        self.versions.insert(pubkey.to_owned(), (slot, write_version));
+
+        let closed = record["lamports"].as_u64() == Some(0);
+        if closed {
+            self.stream.send_tombstone(pubkey.to_owned(), slot).await;
+        } else {
+            let mut record = record.clone();
+            record["writeVersion"] = json!(write_version);
+            if let Some(redactor) = redactor {
+                redactor.apply(&mut record);
+            }
+            self.stream.send(pubkey.to_owned(), &record).await;
+        }
+        if self.config.state_path.is_some() {
+            if closed {
+                self.tracked.remove(pubkey);
+            } else if !self.tracked.contains(pubkey) {
+                self.tracked.insert(pubkey.to_owned());
+            }
+        }
+        self.prune(slot);
+        if self.saved_at.elapsed() >= SAVE_INTERVAL {
+            self.save();
+            self.saved_at = Instant::now();
+        }
+    }
+
+    /// Delete the states of the tracked accounts that no longer exist on chain
+    async fn reconcile(&mut self, rpc: &RpcClient) -> anyhow::Result<()> {
+        let mut accounts: Vec<String> = self.tracked.iter().cloned().collect();
+        accounts.sort();
+        let mut deleted = 0;
+        for batch in accounts.chunks(RECONCILE_BATCH) {
+            let exist = rpc.accounts_exist(batch).await?;
+            for (pubkey, _) in batch.iter().zip(exist).filter(|(_, exists)| !exists) {
+                self.stream.send_tombstone(pubkey.clone(), 0).await;
+                self.tracked.remove(pubkey);
+                deleted += 1;
+            }
+        }
+        self.stream.flush().await;
+        info!(
+            "Reconciled the latest state of {} accounts, deleted {} that no longer exist",
+            accounts.len(),
+            deleted
+        );
+        self.save();
+        Ok(())
+    }
+
+    /// Write the tracked accounts to `state_path`
+    fn save(&self) {
+        let Some(path) = &self.config.state_path else {
+            return;
+        };
+        let saved = serde_json::to_vec(&self.tracked)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(std::fs::write(path, contents)?));
+        if let Err(e) = saved {
+            error!("Failed to save latest account state {:?}: {:?}", path, e);
+        }
+    }
+
+    /// Forget the accounts not updated for `retain_slots`
+    fn prune(&mut self, slot: u64) {
+        if slot < self.pruned_at + self.config.retain_slots {
+            return;
+        }
+        let retain_slots = self.config.retain_slots;
+        self.versions.retain(|_, (updated, _)| *updated + retain_slots >= slot);
+        self.pruned_at = slot;
+    }
+
+    /// Flush the latest account states, at shutdown
+    pub async fn finish(&self) {
+        self.stream.flush().await;
+        self.save();
+    }
+}
+
+/// `path` with `-<suffix>` appended to its file stem, `latest.json` to `latest-finalized.json`
+fn suffixed_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    match path.extension() {
+        Some(extension) => path.with_file_name(format!("{}-{}.{}", stem, suffix, extension.to_string_lossy())),
+        None => path.with_file_name(format!("{}-{}", stem, suffix)),
+    }
+}
+
+/// Connect the latest account state topic, `suffix` is appended to it in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<LatestAccounts>> {
+    let Some(mut latest_config) = config.get_latest_accounts_config() else {
+        return Ok(None);
+    };
+    if let Some(suffix) = suffix {
+        latest_config.state_path = latest_config.state_path.map(|path| suffixed_path(&path, suffix));
+    }
+    let name = "the latest state of each account";
+    let stream = DerivedStream::connect(sinks, config, name, &latest_config.topic, suffix).await?;
+
+    let tracked = match &latest_config.state_path {
+        Some(path) if path.exists() => serde_json::from_slice(&std::fs::read(path)?)
+            .with_context(|| format!("invalid latest account state {:?}", path))?,
+        _ => HashSet::new(),
+    };
+    let reconcile = latest_config.reconcile.take();
+    let mut latest = LatestAccounts {
+        stream,
+        config: latest_config,
+        versions: HashMap::new(),
+        pruned_at: 0,
+        tracked,
+        saved_at: Instant::now(),
+    };
+    if let Some(rpc) = reconcile {
+        // The stream still delivers the accounts closed from now on
+        if let Err(e) = latest.reconcile(&rpc).await {
+            warn!("Failed to reconcile the latest account states, keeping them: {:?}", e);
+        }
+    }
+    Ok(Some(latest))
+}
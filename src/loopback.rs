@@ -0,0 +1,44 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use fluvio::{consumer::ConsumerConfigExtBuilder, Fluvio, Offset};
+use futures::StreamExt;
+use log::{error, info};
+
+use crate::metrics::Metrics;
+
+/// Wait before reconnecting the consumer after an error
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Consume the tail of `topic` with a client of its own, as a consumer would, and report how
+/// long records take from the producer to being readable and how fast the end offset grows.
+/// Proves records are consumable, not only acknowledged by the producer.
+pub fn start(topic: String, metrics: Arc<Metrics>) {
+    info!("Starting loopback consumer on topic '{}'", topic);
+    tokio::spawn(async move {
+        loop {
+            match consume(&topic, &metrics).await {
+                Ok(()) => error!("Loopback consumer stream on '{}' ended", topic),
+                Err(e) => error!("Loopback consumer error on '{}': {:?}", topic, e),
+            }
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+    });
+}
+
+async fn consume(topic: &str, metrics: &Metrics) -> anyhow::Result<()> {
+    let fluvio = Fluvio::connect().await?;
+    let config = ConsumerConfigExtBuilder::default()
+        .topic(topic.to_string())
+        .offset_start(Offset::end())
+        .build()?;
+    let mut stream = fluvio.consumer_with_config(config).await?;
+
+    while let Some(record) = stream.next().await {
+        let record = record?;
+        // Producer timestamp in milliseconds, both clocks are this host's
+        let latency_ms = Utc::now().timestamp_millis().saturating_sub(record.timestamp()).max(0) as u64;
+        metrics.record_loopback(latency_ms, record.offset().max(0) as u64);
+    }
+    Ok(())
+}
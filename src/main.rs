@@ -3,69 +3,256 @@ use std::{
     time::{Duration, Instant}, 
 };
 use tokio::sync::mpsc;
-use fluvio::{Fluvio, RecordKey, TopicProducerPool, metadata::topic::TopicSpec};
-use futures::{sink::SinkExt, stream::StreamExt};
-use log::{info, error};
+use log::{info, warn, error};
+use clap::Parser;
 use serde_json;
-use serde_yaml;
 //use anyhow::Result;
 
 const EPOCH_SIZE: u64 = 432000;
 const CHANNEL_SIZE: usize = 50_000;
 
 // Internal modules
+mod activity;
+#[cfg(feature = "admin")]
+mod admin;
+#[cfg(feature = "rkyv")]
+mod archive;
+mod backfill;
+mod batching;
+mod blobs;
+mod bloom;
+mod build_info;
+mod canonical;
+mod cardinality;
+#[cfg(feature = "chaos")]
+mod chaos;
+mod checkpoint;
+mod cli;
+mod closures;
+mod codec;
+#[cfg(feature = "zstd")]
+mod compression;
 mod config;
+mod control;
+mod correlate;
+mod credentials;
+mod deploys;
+mod derived;
+mod diff;
+mod drain;
+mod dynamic;
+mod envelope;
+mod epochs;
+mod example;
 mod formatters;
+mod health;
+mod history;
+mod hooks;
+mod instructions;
+mod integrity;
+mod labels;
+mod latest;
+#[cfg(feature = "fluvio")]
+mod loopback;
 mod metrics;
-use config::YellowstoneGrpcConfig;
-use metrics::{Metrics, MetricsReporter};
+mod mints;
+mod outbox;
+mod pause;
+mod platform;
+mod pricing;
+mod priority_fee;
+mod profiling;
+mod producers;
+mod program_filter;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+mod provision;
+mod publisher;
+mod quota;
+mod redact;
+mod relay;
+mod reload;
+mod remote;
+mod republish;
+mod retention;
+mod routing;
+mod rpc;
+mod schemas;
+mod shedding;
+mod sinks;
+mod stake;
+mod sources;
+mod staleness;
+mod stateful;
+mod stats;
+mod statuses;
+mod subscription;
+mod timeseries;
+mod transfers;
+mod unknown;
+mod volume;
+mod votes;
+mod watch;
+mod web;
+use cli::{Args, Command};
+use activity::AccountActivityMonitor;
+#[cfg(feature = "admin")]
+use admin::AdminState;
+use batching::BatchController;
+use blobs::BlobStore;
+use bloom::SignatureBloom;
+use closures::AccountClosures;
+use checkpoint::{CheckpointStore, SlotAcks, StartPosition};
+use correlate::BlockCorrelator;
+use diff::AccountDiffer;
+use health::{Health, StreamHealth};
+use integrity::IntegrityChain;
+use config::{CommitmentTopic, Filters, YellowstoneGrpcConfig};
+use labels::Labels;
+use metrics::Metrics;
+#[cfg(feature = "metrics")]
+use metrics::MetricsReporter;
+use outbox::Outbox;
+use pricing::PriceOracle;
+use producers::ProducerEnricher;
+use profiling::ProfilingConfig;
+use priority_fee::PriorityFeeFilter;
+use program_filter::TopLevelProgramFilter;
+use publisher::{InFlight, Publisher, RecordFormat};
+use redact::Redactor;
+use relay::Relay;
+use retention::{Pruner, Retention};
+use routing::{GroupMatch, GroupRouter, GroupedRecords, RecordKind, Route};
+use shedding::LoadShedder;
+use staleness::StalenessGuard;
+use sinks::SinkConnector;
+use sources::Source;
+use latest::LatestAccounts;
+use deploys::ProgramDeploys;
+use mints::MintEvents;
+use stake::StakeEvents;
+use stats::StatsStream;
+use statuses::StatusStream;
+use transfers::TransferStream;
+use unknown::UnknownUpdates;
+use stateful::StatefulStage;
+use volume::VolumeBudget;
+use votes::VoteStream;
+use web::{WebStream, WebTap};
 //use yellowstone_grpc_proto::prost::Message;
 
 // Yellowstone-specific imports
-use yellowstone_grpc_client::GeyserGrpcClient;
 use yellowstone_grpc_proto::
     prelude::{
-        subscribe_update::UpdateOneof, CommitmentLevel,
+        subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
         SubscribeUpdateTransaction, SubscribeUpdateAccount,
-        SubscribeUpdateBlockMeta
+        SubscribeUpdateBlockMeta, SubscribeUpdateTransactionStatus
     }
 ;
 
 #[derive(Debug)]
-enum ProcessingMessage {
+pub(crate) enum ProcessingMessage {
     Transaction(SubscribeUpdateTransaction),
     Account(SubscribeUpdateAccount),
     BlockMetadata(SubscribeUpdateBlockMeta),
+    TransactionStatus(SubscribeUpdateTransactionStatus),
+    /// Update matched by filter groups with their own topic
+    Grouped(Box<ProcessingMessage>, GroupMatch),
     Shutdown,
 }
 
+impl ProcessingMessage {
+    /// Map a subscription update to a message, giving back updates of unhandled types
+    pub(crate) fn from_update(update: UpdateOneof) -> Result<Self, UpdateOneof> {
+        match update {
+            UpdateOneof::Transaction(msg) => Ok(Self::Transaction(msg)),
+            UpdateOneof::Account(msg) => Ok(Self::Account(msg)),
+            UpdateOneof::BlockMeta(msg) => Ok(Self::BlockMetadata(msg)),
+            UpdateOneof::TransactionStatus(msg) => Ok(Self::TransactionStatus(msg)),
+            other => Err(other),
+        }
+    }
+
+    /// Slot the message belongs to
+    fn slot(&self) -> Option<u64> {
+        match self {
+            Self::Transaction(msg) => Some(msg.slot),
+            Self::Account(msg) => Some(msg.slot),
+            Self::BlockMetadata(msg) => Some(msg.slot),
+            Self::TransactionStatus(msg) => Some(msg.slot),
+            Self::Grouped(msg, _) => msg.slot(),
+            Self::Shutdown => None,
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let _ = env_logger::try_init();
-    info!("Starting Yellowstone gRPC to Fluvio Streamer");
+    info!(
+        "Starting Yellowstone gRPC to Fluvio Streamer {} (commit {}, built {})",
+        build_info::VERSION,
+        build_info::GIT_SHA,
+        build_info::BUILD_TIMESTAMP
+    );
+    let args = Args::parse();
+    if args.print_example_config {
+        print!("{}", example::example_config());
+        return Ok(());
+    }
+    if let Some(Command::ExportSchemas { dir }) = &args.command {
+        return schemas::export(dir);
+    }
 
-    let config_yaml = match std::fs::read_to_string("config.yaml") {
+    let config_contents = match std::fs::read_to_string(&args.config) {
         Ok(contents) => contents,
         Err(e) => {
             error!("Failed to read config file: {:?}", e);
             std::process::exit(1);
         }
     };
-    let config: YellowstoneGrpcConfig = serde_yaml::from_str(&config_yaml)?;
+    let config: YellowstoneGrpcConfig = config::parse_config(&args.config, &config_contents)?;
 
-    println!("Loaded config: {:?}", config);
+    // Logged rather than printed, stdout may be the sink
+    info!("Loaded config: {:?}", config);
+    if RecordFormat::parse(&config.yellowstone_grpc.format).is_none() {
+        warn!("Unknown `format: {}`, publishing JSON records", config.yellowstone_grpc.format);
+    }
+    #[cfg(feature = "chaos")]
+    if let Some(chaos_config) = config.get_chaos_config() {
+        chaos::install(chaos_config);
+    }
 
-    // Connect to Fluvio
-    let fluvio = Fluvio::connect().await?; 
-    let topic_name = &config.yellowstone_grpc.topic_name;  
-    ensure_topic_exists(&fluvio, topic_name).await?;
-    let producer = Arc::new(fluvio.topic_producer(topic_name).await.expect("Failed to create producer"));
+    // Reported when `main` returns
+    let _profile = args.profile.then(|| {
+        profiling::install(ProfilingConfig {
+            sample_rate: args.profile_sample_rate,
+            output: args.profile_output.clone().map(Into::into),
+        })
+    });
 
-    let runtime = tokio::runtime::Runtime::new()?; 
-    let _guard = runtime.enter(); 
+    match args.command {
+        Some(Command::Backfill { from_slot, to_slot, rpc_url }) => {
+            return backfill::run(&config, from_slot, to_slot, rpc_url).await;
+        },
+        Some(Command::FetchSlot { slot, rpc_url, publish }) => {
+            return backfill::fetch_slot(&config, slot, rpc_url, publish).await;
+        },
+        Some(Command::Republish { input, from_slot, to_slot, topic, source_topic }) => {
+            return republish::run(&config, input, from_slot, to_slot, topic, source_topic).await;
+        },
+        Some(Command::ExportSchemas { .. }) | None => {},
+    }
+
+    if let Some(credentials_config) = config.get_credentials_config() {
+        credentials::install(credentials_config)?;
+    }
+    quota::install(config.get_byte_quota_config())?;
 
     // Initialize metrics
+    #[cfg(feature = "metrics")]
     let metrics_config = config.get_metrics_config(); 
+    #[cfg(feature = "metrics")]
     let metrics = if metrics_config.enabled {
         info!("Metrics enabled, sending to: {}", metrics_config.endpoint);
         let metrics = Arc::new(Metrics::new());
@@ -76,227 +263,1024 @@ async fn main() -> anyhow::Result<()> {
         info!("Metrics disabled");
         None
     };
+    #[cfg(not(feature = "metrics"))]
+    let metrics: Option<Arc<Metrics>> = None;
 
-    // ✅ Connect to Yellowstone gRPC
-    let mut client = GeyserGrpcClient::build_from_shared(config.yellowstone_grpc.endpoint.clone())?
-        .x_token(config.yellowstone_grpc.x_token.clone())?
-        .connect_timeout(Duration::from_secs(10))
-        .timeout(Duration::from_secs(10))
-        .max_decoding_message_size(config.yellowstone_grpc.max_decoding_message_size as usize)
-        .connect()
-        .await?;
-    
+    // Connect to the sink backend
+    let sinks = Arc::new(SinkConnector::connect(&config, metrics.clone()).await?);
+    provision::provision(&sinks, &config).await?;
+    if args.provision_only {
+        return Ok(());
+    }
+    #[cfg(feature = "fluvio")]
+    if let (Some(topic), Some(metrics)) = (config.get_loopback_topic(), &metrics) {
+        loopback::start(topic, Arc::clone(metrics));
+    }
+    let targets = config.get_commitment_topics();
+    let mut publishers = Vec::with_capacity(targets.len());
+    for target in &targets {
+        publishers.push(Arc::new(Publisher::connect_main(&sinks, &config, &target.topic).await.expect("Failed to create producer")));
+    }
+    // Control records and activity warnings go through the first pipeline
+    let publisher = Arc::clone(&publishers[0]);
+    let startup = control::control_record(
+        "startup",
+        serde_json::json!({ "version": env!("CARGO_PKG_VERSION"), "topic": config.yellowstone_grpc.topic_name }),
+    );
+    if let Err(e) = publisher.send_control(&startup).await {
+        error!("Error publishing startup event: {:?}", e);
+    }
+    let unknown_updates = Arc::new(unknown::connect(&sinks, &config, metrics.clone()).await?);
 
-    // ✅ Subscribe to the gRPC stream
-    let (mut subscribe_tx, mut stream) = client.subscribe().await?;
+    #[cfg_attr(not(feature = "admin"), allow(unused_variables))]
+    let signature_watch = match config.get_signature_watch_config() {
+        Some(watch_config) => {
+            let watch_publisher = Publisher::connect(&sinks, &config, &watch_config.topic).await?;
+            Some(watch::start(watch_config, (&config.yellowstone_grpc).into(), watch_publisher))
+        },
+        None => None,
+    };
 
-    let commitment = config
-        .yellowstone_grpc.commitment
-        .as_ref()
-        .map(|s| CommitmentLevel::from_str_name(s).unwrap_or(CommitmentLevel::Processed));
+    let dynamic_filters = config
+        .get_dynamic_filters_config()
+        .filter(|_| sources::is_live(&config.yellowstone_grpc))
+        .map(dynamic::start);
 
-    let subscribe_request = config::get_subscribe_request(&config.yellowstone_grpc.filters, commitment).await?;
+    let slot_hooks = config.get_slot_hooks();
+    if !slot_hooks.is_empty() {
+        hooks::start(slot_hooks, (&config.yellowstone_grpc).into());
+    }
 
-    subscribe_tx.send(subscribe_request).await?;
+    let health = Arc::new(Health::new(config.get_health_config(), metrics.clone()));
+    health.monitor();
 
-    // Create channels for different message types
-    let (tx_sender, tx_receiver) = mpsc::channel::<ProcessingMessage>(CHANNEL_SIZE);
+    #[cfg(feature = "admin")]
+    if let Some(admin_config) = config.get_admin_config() {
+        let mut state = AdminState::new((&config.yellowstone_grpc).into()).with_health(Arc::clone(&health));
+        if let Some(signature_watch) = &signature_watch {
+            state = state.with_signature_watch(Arc::clone(signature_watch));
+        }
+        if let Some(dynamic_filters) = &dynamic_filters {
+            state = state.with_dynamic_filters(Arc::clone(dynamic_filters));
+        }
+        admin::start(admin_config, state).await?;
+    }
 
-    // Spawn processor tasks
-    let tx_handle = tokio::spawn(transaction_processor(
-        tx_receiver,
-        Arc::clone(&producer),
-        config.yellowstone_grpc.format.clone(),
-        metrics,
-    ));
+    #[cfg(feature = "relay")]
+    let relay = match config.get_relay_config() {
+        Some(relay_config) => {
+            let commitments: Vec<_> = targets.iter().map(|target| target.commitment).collect();
+            let relay = Arc::new(Relay::new(&commitments, relay_config.buffer));
+            relay::start(relay_config, Arc::clone(&relay), &(&config.yellowstone_grpc).into()).await?;
+            Some(relay)
+        },
+        None => None,
+    };
+    #[cfg(not(feature = "relay"))]
+    let relay: Option<Arc<Relay>> = None;
 
-    let mut last_slot_check = Instant::now();
+    #[cfg(feature = "web")]
+    let web_stream = match config.get_web_stream_config() {
+        Some(web_config) => {
+            let web_stream = WebStream::new(web_config.buffer);
+            web::start(web_config, Arc::clone(&web_stream)).await?;
+            Some(web_stream)
+        },
+        None => None,
+    };
+    #[cfg(not(feature = "web"))]
+    let web_stream: Option<Arc<WebStream>> = None;
 
-    // Main processing loop with graceful shutdown handling
-    let processing = async {
-        while let Some(message) = stream.next().await {
-            match message {
-                Ok(update) => match update.update_oneof {
-                    Some(UpdateOneof::BlockMeta(msg)) => {
-                        if last_slot_check.elapsed() >= Duration::from_secs(5) {
-                            let slot = msg.slot;
-
-                            // Get all slot info, handling potential errors
-                            let processed = client.get_slot(Some(CommitmentLevel::Processed)).await.ok();
-                            let confirmed = client.get_slot(Some(CommitmentLevel::Confirmed)).await.ok();
-                            let finalized = client.get_slot(Some(CommitmentLevel::Finalized)).await.ok();
-
-                            if let (Some(processed), Some(confirmed), Some(finalized)) = (processed, confirmed, finalized) {
-                                let processed_diff = processed.slot as i64 - slot as i64;
-                                let confirmed_diff = confirmed.slot as i64 - slot as i64;
-                                let finalized_diff = finalized.slot as i64 - slot as i64;
-
-                                info!(
-                                    "Last slot processed: {}, Mainnet watermarks: [P: {}, C: {}, F: {}], Deltas: [P: {}, C: {}, F: {}]", 
-                                    format_slot_yellow(slot), 
-                                    format_slot(processed.slot), format_slot(confirmed.slot), format_slot(finalized.slot),
-                                    format_delta(processed_diff), format_delta(confirmed_diff), format_delta(finalized_diff)
-                                );
-                            }
-                            last_slot_check = Instant::now();
-                        }
+    let runtime = tokio::runtime::Runtime::new()?; 
+    let _guard = runtime.enter(); 
 
-                        if tx_sender.send(ProcessingMessage::BlockMetadata(msg)).await.is_err() {
-                            error!("Block Metadata channel closed, shutting down");
-                            break;
-                        }
-                    },
-                    Some(UpdateOneof::Transaction(msg)) => {
-                        if last_slot_check.elapsed() >= Duration::from_secs(5) {
-                            let slot = msg.slot;
-
-                            // Get all slot info, handling potential errors
-                            let processed = client.get_slot(Some(CommitmentLevel::Processed)).await.ok();
-                            let confirmed = client.get_slot(Some(CommitmentLevel::Confirmed)).await.ok();
-                            let finalized = client.get_slot(Some(CommitmentLevel::Finalized)).await.ok();
-
-                            if let (Some(processed), Some(confirmed), Some(finalized)) = (processed, confirmed, finalized) {
-                                let processed_diff = processed.slot as i64 - slot as i64;
-                                let confirmed_diff = confirmed.slot as i64 - slot as i64;
-                                let finalized_diff = finalized.slot as i64 - slot as i64;
-
-                                info!(
-                                    "Last slot processed: {}, Mainnet watermarks: [P: {}, C: {}, F: {}], Deltas: [P: {}, C: {}, F: {}]", 
-                                    format_slot_yellow(slot), 
-                                    format_slot(processed.slot), format_slot(confirmed.slot), format_slot(finalized.slot),
-                                    format_delta(processed_diff), format_delta(confirmed_diff), format_delta(finalized_diff)
-                                );
-                            }
-                            last_slot_check = Instant::now();
-                        }
+    // Initialize account activity analyzer
+    let activity = config.get_activity_config().map(|activity_config| {
+        let monitor = Arc::new(AccountActivityMonitor::new(activity_config, metrics.clone()));
+        Arc::clone(&monitor).start(Arc::clone(&publisher));
+        monitor
+    });
 
-                        if tx_sender.send(ProcessingMessage::Transaction(msg)).await.is_err() {
-                            error!("Block Metadata channel closed, shutting down");
-                            break;
-                        }
-                    },
-                    Some(UpdateOneof::Account(msg)) => {
-                        if last_slot_check.elapsed() >= Duration::from_secs(5) {
-                            let slot = msg.slot;
-
-                            // Get all slot info, handling potential errors
-                            let processed = client.get_slot(Some(CommitmentLevel::Processed)).await.ok();
-                            let confirmed = client.get_slot(Some(CommitmentLevel::Confirmed)).await.ok();
-                            let finalized = client.get_slot(Some(CommitmentLevel::Finalized)).await.ok();
-
-                            if let (Some(processed), Some(confirmed), Some(finalized)) = (processed, confirmed, finalized) {
-                                let processed_diff = processed.slot as i64 - slot as i64;
-                                let confirmed_diff = confirmed.slot as i64 - slot as i64;
-                                let finalized_diff = finalized.slot as i64 - slot as i64;
-
-                                info!(
-                                    "Last slot processed: {}, Mainnet watermarks: [P: {}, C: {}, F: {}], Deltas: [P: {}, C: {}, F: {}]", 
-                                    format_slot_yellow(slot), 
-                                    format_slot(processed.slot), format_slot(confirmed.slot), format_slot(finalized.slot),
-                                    format_delta(processed_diff), format_delta(confirmed_diff), format_delta(finalized_diff)
-                                );
-                            }
-                            last_slot_check = Instant::now();
-                        }
+    let labels = config.get_labels()?.map(Arc::new);
+    let producers = config.get_producer_enrichment().map(Arc::new);
+    let pricing = config.get_price_oracle().map(Arc::new);
 
-                        if tx_sender.send(ProcessingMessage::Account(msg)).await.is_err() {
-                            error!("Accounts channel closed, shutting down");
-                            break;
-                        }
-                    },
-                    // Other types can go here 
-                    _ => {},
-                },
-                Err(e) => {
-                    error!("Error: {:?}", e);
+    let (filters, reloaded_filters) = match config.get_remote_filters_config() {
+        Some(remote_config) => {
+            let (filters, remote_filters) = remote::start(remote_config, config.yellowstone_grpc.filters.clone()).await?;
+            (filters, Some(remote_filters).filter(|_| sources::is_live(&config.yellowstone_grpc)))
+        },
+        None => {
+            let reloaded_filters = config
+                .get_config_reload()
+                .filter(|_| sources::is_live(&config.yellowstone_grpc))
+                .map(|interval| reload::watch_filters(args.config.clone(), interval, config.yellowstone_grpc.filters.clone()));
+            (config.yellowstone_grpc.filters.clone(), reloaded_filters)
+        },
+    };
+
+    let mut senders = Vec::with_capacity(targets.len());
+    let mut handles = Vec::with_capacity(targets.len());
+    let (drain_expired, _) = tokio::sync::watch::channel(false);
+    let mut streams = Vec::with_capacity(targets.len());
+    let loaded_filters = filters.load_files().await?;
+    for (index, (target, publisher)) in targets.iter().zip(&publishers).enumerate() {
+        let (mut subscribe_request, warnings) = config::get_subscribe_request(&loaded_filters, target.commitment)?;
+        // The same for every commitment
+        if index == 0 {
+            for warning in warnings {
+                warn!("{}", warning);
+            }
+            let cardinality = config.get_owner_cardinality_config().filter(|_| sources::is_live(&config.yellowstone_grpc));
+            if let Some(cardinality) = cardinality {
+                cardinality::check(&cardinality, &subscribe_request).await?;
+            }
+        }
+        let checkpoint = config.get_checkpoint_config().map(|checkpoint_config| {
+            let path = match target.commitment {
+                Some(commitment) if targets.len() > 1 => {
+                    format!("{}.{}", checkpoint_config.path, commitment.as_str_name().to_lowercase())
                 },
+                _ => checkpoint_config.path.clone(),
+            };
+            (CheckpointStore::new(path, checkpoint_config.interval_secs), checkpoint_config.max_catchup_slots)
+        });
+        if let Some((store, max_catchup_slots)) = &checkpoint {
+            if sources::is_live(&config.yellowstone_grpc) {
+                subscribe_request.from_slot = start_from_checkpoint(&config, target, store, *max_catchup_slots, publisher).await?;
             }
         }
-    };
 
-    processing.await; 
+        let description = control::describe_subscription(&subscribe_request, &config.yellowstone_grpc.endpoint);
+        if let Err(e) = publisher.send_control(&description).await {
+            error!("Error publishing subscription description: {:?}", e);
+        }
+
+        let volume = config.get_volume_budget_config().map(|budget| Arc::new(VolumeBudget::new(budget)));
+        let refresh_interval = filters.refresh_interval();
+        let disables = volume.as_ref().is_some_and(|volume| volume.disables());
+        let request_updates = if sources::is_live(&config.yellowstone_grpc)
+            && (refresh_interval.is_some() || reloaded_filters.is_some() || dynamic_filters.is_some() || disables)
+        {
+            let filters = reloaded_filters
+                .clone()
+                .unwrap_or_else(|| tokio::sync::watch::channel(filters.clone()).1);
+            Some(subscription::spawn_refresh(
+                filters,
+                target.commitment,
+                refresh_interval,
+                subscribe_request.clone(),
+                config.yellowstone_grpc.endpoint.clone(),
+                Arc::clone(publisher),
+                dynamic_filters.clone(),
+                volume.clone(),
+            ))
+        } else {
+            None
+        };
+
+        // ✅ Connect the configured source (Yellowstone gRPC by default)
+        let transactions_subscribed =
+            !subscribe_request.transactions.is_empty() || sources::includes_block_transactions(&subscribe_request);
+        let stream_health = health.register(target.topic.clone());
+        let mut source = sources::build(&config.yellowstone_grpc, subscribe_request).await?;
+        stream_health.subscribed();
+        source.attach_health(Arc::clone(&stream_health));
+        if let Some(metrics) = &metrics {
+            source.attach_metrics(Arc::clone(metrics));
+        }
+        info!("Streaming {:?} updates to topic '{}'", target.commitment, target.topic);
+
+        let suffix = target.commitment.filter(|_| targets.len() > 1).map(|commitment| commitment.as_str_name().to_lowercase());
+        let routes = routing::connect(&sinks, &config, suffix.as_deref()).await?;
+        let bloom = bloom::connect(&sinks, &config, suffix.as_deref()).await?;
+        let votes = votes::connect(&sinks, &config, suffix.as_deref()).await?;
+        let stake_events = stake::connect(&sinks, &config, suffix.as_deref()).await?;
+        let mint_events = mints::connect(&sinks, &config, suffix.as_deref()).await?;
+        let program_deploys = deploys::connect(&sinks, &config, suffix.as_deref()).await?;
+        let transfers = transfers::connect(&sinks, &config, suffix.as_deref()).await?;
+        let stats = stats::connect(&config, suffix.as_deref()).await?;
+        let statuses = statuses::connect(&sinks, &config, suffix.as_deref(), transactions_subscribed).await?;
+        let latest_accounts = latest::connect(&sinks, &config, suffix.as_deref()).await?;
+        let account_closures = closures::connect(&sinks, &config, suffix.as_deref()).await?;
+        let blobs = blobs::connect(&sinks, &config, suffix.as_deref()).await?;
+
+        // Create channels for different message types
+        let (tx_sender, tx_receiver) = mpsc::channel::<ProcessingMessage>(CHANNEL_SIZE);
+
+        // Spawn processor tasks
+        handles.push(tokio::spawn(transaction_processor(tx_receiver, ProcessorOptions {
+            publisher: Arc::clone(publisher),
+            format: config.get_record_format(),
+            metrics: metrics.clone(),
+            historical: source.historical(),
+            activity: if index == 0 { activity.clone() } else { None },
+            redactor: config.get_redactor(),
+            labels: labels.clone(),
+            producers: producers.clone(),
+            checkpoint: checkpoint.map(|(store, _)| store),
+            correlator: config.get_block_correlator(),
+            pricing: pricing.clone(),
+            routes,
+            max_in_flight: config.yellowstone_grpc.max_in_flight,
+            batching: config
+                .get_batching_config()
+                .map(|batching| BatchController::new(batching, CHANNEL_SIZE, metrics.clone())),
+            bloom,
+            account_diff: config.get_account_differ(),
+            stateful: stateful::build(&config.get_stateful_processors()),
+            integrity: config.get_integrity_chain(),
+            retention: config.get_retention(),
+            program_filter: filters.top_level_program_filter(),
+            priority_fee_filter: filters.priority_fee_filter(),
+            reloaded_filters: reloaded_filters.clone(),
+            flatten_instructions: config.yellowstone_grpc.flatten_instructions.unwrap_or(false),
+            idempotency_keys: config.yellowstone_grpc.idempotency_keys.unwrap_or(false),
+            drain_expired: drain_expired.subscribe(),
+            votes,
+            stake_events,
+            mint_events,
+            program_deploys,
+            transfers,
+            outbox: config.get_outbox_config().map(Outbox::new),
+            stats,
+            statuses,
+            latest_accounts,
+            account_closures,
+            blobs,
+            web: web_stream.as_ref().map(|web_stream| web_stream.tap(&target.topic)),
+        })));
+        let shedder = config
+            .get_latency_budget()
+            .map(|budget| LoadShedder::new(budget, metrics.clone()));
+        let staleness = config
+            .get_staleness_config()
+            .map(|staleness| StalenessGuard::new(staleness, metrics.clone()));
+        streams.push(stream_updates(
+            source,
+            tx_sender.clone(),
+            Arc::clone(publisher),
+            target.commitment,
+            shedder,
+            staleness,
+            relay.clone(),
+            request_updates,
+            stream_health,
+            volume,
+            GroupRouter::new(&config),
+            Arc::clone(&unknown_updates),
+        ));
+        senders.push(tx_sender);
+    }
+
+    // Main processing loop with graceful shutdown handling
+    let reason = tokio::select! {
+        _ = futures::future::join_all(streams) => "source closed",
+        reason = platform::shutdown_signal() => reason,
+        reason = quota::exceeded() => reason,
+    };
 
-    info!("Initiating graceful shutdown"); 
-    let _ = tx_sender.send(ProcessingMessage::Shutdown).await; 
+    info!("Initiating graceful shutdown ({})", reason);
+    let processors = async {
+        for tx_sender in senders {
+            let _ = tx_sender.send(ProcessingMessage::Shutdown).await;
+        }
+        for tx_handle in handles {
+            let _ = tx_handle.await;
+        }
+    };
+    let drained = drain::drain(processors, config.get_drain_config(), &drain_expired).await;
 
-    let _ = tx_handle.await;
+    let shutdown = control::control_record("shutdown", serde_json::json!({ "reason": reason, "drained": drained }));
+    if let Err(e) = publisher.send_control(&shutdown).await {
+        error!("Error publishing shutdown event: {:?}", e);
+    }
+    for publisher in &publishers {
+        publisher.flush().await?;
+    }
+    unknown_updates.finish().await;
 
     Ok(())
 }
 
-async fn ensure_topic_exists(fluvio: &Fluvio, topic_name: &str) -> anyhow::Result<()> {
-    let admin = fluvio.admin().await;
-    
-    // Get all topics and check if ours exists
-    let topics = admin.all::<TopicSpec>().await?;
-    let topic_exists = topics.iter().any(|t| t.name == topic_name);
+/// Decide where the subscription starts from the stored checkpoint, returning the `from_slot`
+/// to replay from and publishing a `gap_detected` control record when the checkpoint is too old
+async fn start_from_checkpoint(
+    config: &YellowstoneGrpcConfig,
+    target: &CommitmentTopic,
+    store: &CheckpointStore,
+    max_catchup_slots: u64,
+    publisher: &Publisher,
+) -> anyhow::Result<Option<u64>> {
+    let Some(checkpoint) = store.load()? else {
+        return Ok(None);
+    };
+    let tip = sources::tip_slot(&config.yellowstone_grpc, target.commitment).await?;
 
-    if topic_exists {
-        info!("Topic '{}' already exists. Skipping creation.", topic_name);
-    } else {
-        info!("Topic '{}' does not exist. Creating it now...", topic_name);
-        let topic_spec = TopicSpec::new_computed(1, 1, None); // 1 partition, 1 replica
-        admin.create(topic_name.to_string(), false, topic_spec).await?;
-        info!("Topic '{}' created successfully!", topic_name);
+    match StartPosition::decide(Some(checkpoint), tip, max_catchup_slots) {
+        StartPosition::Tip => Ok(None),
+        StartPosition::Resume(from_slot) => {
+            info!("Resuming '{}' from checkpoint slot {} ({} slots behind tip)", target.topic, checkpoint, tip.saturating_sub(checkpoint));
+            Ok(Some(from_slot))
+        },
+        StartPosition::Skip { from_slot, to_slot } => {
+            warn!(
+                "Checkpoint for '{}' is {} slots behind tip (max {}), starting at the tip",
+                target.topic, tip.saturating_sub(checkpoint), max_catchup_slots
+            );
+            let gap = control::control_record(
+                "gap_detected",
+                serde_json::json!({ "fromSlot": from_slot, "toSlot": to_slot, "reason": "max_catchup_exceeded" }),
+            );
+            if let Err(e) = publisher.send_control(&gap).await {
+                error!("Error publishing gap event: {:?}", e);
+            }
+            Ok(None)
+        },
     }
+}
 
-    Ok(())
+/// Forward updates from `source` to the processor, logging lag against the upstream watermarks,
+/// shedding low-priority updates when over the latency budget, dropping stale updates, relaying
+/// the others and applying refreshed filters
+async fn stream_updates(
+    mut source: Box<dyn Source>,
+    tx_sender: mpsc::Sender<ProcessingMessage>,
+    publisher: Arc<Publisher>,
+    commitment: Option<CommitmentLevel>,
+    mut shedder: Option<LoadShedder>,
+    mut staleness: Option<StalenessGuard>,
+    relay: Option<Arc<Relay>>,
+    mut request_updates: Option<mpsc::Receiver<SubscribeRequest>>,
+    health: Arc<StreamHealth>,
+    volume: Option<Arc<VolumeBudget>>,
+    groups: Option<GroupRouter>,
+    unknown_updates: Arc<UnknownUpdates>,
+) {
+    let mut last_slot_check = Instant::now();
+
+    loop {
+        if pause::subscription_paused() {
+            pause::subscription_resumed().await;
+            // Not idle while paused
+            health.updated();
+        }
+        let message = tokio::select! {
+            message = source.next() => message,
+            Some(request) = next_request(&mut request_updates) => {
+                if let Err(e) = source.update_request(request).await {
+                    error!("Error updating subscription: {:?}", e);
+                }
+                continue;
+            },
+        };
+        let Some(message) = message else {
+            break;
+        };
+        if message.is_ok() {
+            health.updated();
+        }
+
+        for event in source.take_events() {
+            if let Err(e) = publisher.send_control(&control::source_event_record(&event)).await {
+                error!("Error publishing {:?} event: {:?}", event, e);
+            }
+        }
+
+        match message {
+            Ok(mut update) => {
+                if let Some(volume) = &volume {
+                    for record in volume.observe(update.update_oneof.as_ref(), &update.filters) {
+                        if let Err(e) = publisher.send_control(&record).await {
+                            error!("Error publishing filter budget event: {:?}", e);
+                        }
+                    }
+                }
+                let created_at = update.created_at;
+                let matched = groups.as_ref().and_then(|groups| groups.route(&update.filters));
+                let message = match update.update_oneof.take().map(ProcessingMessage::from_update) {
+                    Some(Ok(message)) => message,
+                    unhandled => {
+                        update.update_oneof = unhandled.and_then(Result::err);
+                        unknown_updates.observe(&update).await;
+                        continue;
+                    },
+                };
+
+                if last_slot_check.elapsed() >= Duration::from_secs(5) {
+                    if let (Some(slot), Some(watermarks)) = (message.slot(), source.watermarks().await) {
+                        let processed_diff = watermarks.processed as i64 - slot as i64;
+                        let confirmed_diff = watermarks.confirmed as i64 - slot as i64;
+                        let finalized_diff = watermarks.finalized as i64 - slot as i64;
+
+                        info!(
+                            "Last slot processed: {}, Mainnet watermarks: [P: {}, C: {}, F: {}], Deltas: [P: {}, C: {}, F: {}]",
+                            format_slot_yellow(slot),
+                            format_slot(watermarks.processed), format_slot(watermarks.confirmed), format_slot(watermarks.finalized),
+                            format_delta(processed_diff), format_delta(confirmed_diff), format_delta(finalized_diff)
+                        );
+
+                        if let Some(shedder) = &mut shedder {
+                            let watermark = match commitment {
+                                Some(CommitmentLevel::Finalized) => watermarks.finalized,
+                                Some(CommitmentLevel::Confirmed) => watermarks.confirmed,
+                                _ => watermarks.processed,
+                            };
+                            shedder.observe_lag(watermark.saturating_sub(slot));
+                        }
+                    }
+                    last_slot_check = Instant::now();
+                }
+
+                if shedder.as_ref().is_some_and(|shedder| shedder.shed(&message)) {
+                    continue;
+                }
+                if staleness.as_mut().is_some_and(|staleness| staleness.stale(&message, created_at.as_ref())) {
+                    continue;
+                }
+                if let Some(relay) = &relay {
+                    relay.publish(commitment, &message);
+                }
+                let message = match matched {
+                    Some(matched) => ProcessingMessage::Grouped(Box::new(message), matched),
+                    None => message,
+                };
+
+                if tx_sender.send(message).await.is_err() {
+                    error!("Processing channel closed, shutting down");
+                    break;
+                }
+            },
+            Err(e) => {
+                error!("Error: {:?}", e);
+            },
+        }
+    }
+    health.finished();
+}
+
+/// Shared sinks and optional steps of a processing pipeline
+pub(crate) struct ProcessorOptions {
+    pub publisher: Arc<Publisher>,
+    pub format: RecordFormat,
+    pub metrics: Option<Arc<Metrics>>,
+    /// Flag records as `historical`, for backfills
+    pub historical: bool,
+    pub activity: Option<Arc<AccountActivityMonitor>>,
+    pub redactor: Option<Redactor>,
+    pub labels: Option<Arc<Labels>>,
+    pub producers: Option<Arc<ProducerEnricher>>,
+    pub checkpoint: Option<CheckpointStore>,
+    pub correlator: Option<BlockCorrelator>,
+    pub pricing: Option<Arc<PriceOracle>>,
+    /// Extra destination topics with their own output profiles
+    pub routes: Vec<Route>,
+    /// Records awaiting acknowledgement at a time, `None` to not wait for acknowledgements
+    pub max_in_flight: Option<usize>,
+    pub batching: Option<BatchController>,
+    pub bloom: Option<SignatureBloom>,
+    pub account_diff: Option<AccountDiffer>,
+    pub stateful: Vec<Box<dyn StatefulStage>>,
+    pub integrity: Option<IntegrityChain>,
+    pub retention: Option<Retention>,
+    /// Drop transactions not invoking these programs from a top-level instruction
+    pub program_filter: Option<TopLevelProgramFilter>,
+    pub priority_fee_filter: Option<PriorityFeeFilter>,
+    /// New versions of the filters, replacing the client-side filters above
+    pub reloaded_filters: Option<tokio::sync::watch::Receiver<Filters>>,
+    pub flatten_instructions: bool,
+    pub idempotency_keys: bool,
+    /// Set when the drain timeout expired at shutdown, the queued updates are left unpublished
+    pub drain_expired: tokio::sync::watch::Receiver<bool>,
+    pub votes: Option<VoteStream>,
+    pub stake_events: Option<StakeEvents>,
+    pub mint_events: Option<MintEvents>,
+    pub program_deploys: Option<ProgramDeploys>,
+    pub transfers: Option<TransferStream>,
+    /// Holds derived events until their transaction record is acknowledged
+    pub outbox: Option<Outbox>,
+    pub stats: Option<StatsStream>,
+    pub statuses: Option<StatusStream>,
+    pub latest_accounts: Option<LatestAccounts>,
+    pub account_closures: Option<AccountClosures>,
+    /// Stores the data of large accounts, published as a pointer
+    pub blobs: Option<BlobStore>,
+    pub web: Option<WebTap>,
 }
 
 /// Process transactions & send to Fluvio
-async fn transaction_processor(
-    mut rx: mpsc::Receiver<ProcessingMessage>,
-    producer: Arc<TopicProducerPool>,
-    _format: String,
-    _metrics: Option<Arc<Metrics>>,
-) {
+pub(crate) async fn transaction_processor(mut rx: mpsc::Receiver<ProcessingMessage>, options: ProcessorOptions) {
+    let ProcessorOptions {
+        publisher,
+        format: _format,
+        metrics: _metrics,
+        historical,
+        activity,
+        redactor,
+        labels,
+        producers,
+        mut checkpoint,
+        mut correlator,
+        pricing,
+        routes,
+        max_in_flight,
+        mut batching,
+        mut bloom,
+        mut account_diff,
+        mut stateful,
+        integrity,
+        mut retention,
+        mut program_filter,
+        mut priority_fee_filter,
+        mut reloaded_filters,
+        flatten_instructions,
+        idempotency_keys,
+        drain_expired,
+        mut votes,
+        stake_events,
+        mint_events,
+        program_deploys,
+        transfers,
+        outbox,
+        stats,
+        statuses,
+        mut latest_accounts,
+        mut account_closures,
+        blobs,
+        web,
+    } = options;
+    let pruner = retention.is_some().then(|| Pruner::start(Arc::clone(&publisher)));
+    let mut output = Output {
+        publisher: Arc::clone(&publisher),
+        routes,
+        historical,
+        idempotency_keys,
+        redactor,
+        in_flight: max_in_flight.map(InFlight::new),
+        acks: checkpoint.is_some().then(SlotAcks::default),
+        slot: None,
+        integrity,
+        web,
+        outbox,
+        statuses,
+        grouped: GroupedRecords::default(),
+    };
+
     while let Some(msg) = rx.recv().await {
+        if *drain_expired.borrow() {
+            abandon(msg, &mut rx, checkpoint.as_mut());
+            break;
+        }
+        let reloaded = reloaded_filters.as_mut().filter(|reloaded| reloaded.has_changed().unwrap_or(false));
+        if let Some(reloaded) = reloaded {
+            let filters = reloaded.borrow_and_update();
+            program_filter = filters.top_level_program_filter();
+            priority_fee_filter = filters.priority_fee_filter();
+        }
+        let (msg, matched) = match msg {
+            ProcessingMessage::Grouped(msg, matched) => (*msg, Some(matched)),
+            msg => (msg, None),
+        };
+        let slot = msg.slot();
+        output.slot = slot;
+        let mut records = Vec::with_capacity(1);
+        // Published after the record of the message itself
+        let state_records: Vec<_> = stateful
+            .iter_mut()
+            .flat_map(|stage| stage.process(&msg))
+            .map(|(key, record)| (key, record, RecordKind::State))
+            .collect();
+        let mut vote_only = false;
+        if let (Some(votes), ProcessingMessage::Transaction(tx)) = (&mut votes, &msg) {
+            vote_only = votes.observe(tx).await && votes.excludes_transactions();
+        }
+        if let (Some(stake_events), ProcessingMessage::Transaction(tx)) = (&stake_events, &msg) {
+            stake_events.observe(tx, output.outbox.as_ref()).await;
+        }
+        if let (Some(mint_events), ProcessingMessage::Transaction(tx)) = (&mint_events, &msg) {
+            mint_events.observe(tx, output.outbox.as_ref()).await;
+        }
+        if let (Some(program_deploys), ProcessingMessage::Transaction(tx)) = (&program_deploys, &msg) {
+            program_deploys.observe(tx, output.outbox.as_ref()).await;
+        }
+        if let (Some(transfers), ProcessingMessage::Transaction(tx)) = (&transfers, &msg) {
+            transfers.observe(tx, output.outbox.as_ref()).await;
+        }
+        if let (Some(stats), ProcessingMessage::Transaction(tx)) = (&stats, &msg) {
+            stats.observe(tx);
+        }
         match msg {
+            // Filtered out, still checkpointed
+            ProcessingMessage::Transaction(tx) if vote_only => output.discard_derived(&tx).await,
+            ProcessingMessage::Transaction(tx) if program_filter.as_ref().is_some_and(|filter| !filter.matches(&tx)) => {
+                output.discard_derived(&tx).await
+            },
+            ProcessingMessage::Transaction(tx) if priority_fee_filter.as_ref().is_some_and(|filter| !filter.matches(&tx)) => {
+                output.discard_derived(&tx).await
+            },
             ProcessingMessage::Transaction(tx) => {
-                if let Some(transaction) = tx.transaction.clone() {
-                    let key: RecordKey = bs58::encode(&transaction.signature).into_string().into();
-                    let json_value = formatters::format_transaction(tx).unwrap_or_else(|_| serde_json::json!({}));
-
-                    if let Err(e) = producer.send(key, json_value.to_string().into_bytes()).await {
-                        error!("Error processing transaction: {:?}", e);
-                        error!("Fatal error processing transaction. Exiting...");
-                        std::process::exit(1);
-                    }
+                let Some(transaction) = tx.transaction.as_ref() else {
+                    continue;
+                };
+                let key = codec::encode_bs58(&transaction.signature);
+                if let Some(matched) = matched {
+                    output.route_to(RecordKind::Transaction, &key, tx.slot, matched);
+                }
+                let tx_slot = tx.slot;
+                if let Some(bloom) = &mut bloom {
+                    bloom.observe(tx_slot, &transaction.signature);
+                }
+                let mut json_value = formatters::format_transaction(tx).unwrap_or_else(|_| serde_json::json!({}));
+                if flatten_instructions {
+                    formatters::flatten_instructions(&mut json_value);
+                }
+                if let Some(labels) = &labels {
+                    labels.annotate_transaction(&mut json_value);
+                }
+                if let Some(pricing) = &pricing {
+                    pricing.enrich(&mut json_value).await;
+                }
+                match &mut correlator {
+                    Some(correlator) => {
+                        if let Some((key, json_value)) = correlator.transaction(tx_slot, key, json_value) {
+                            records.push((key, json_value, RecordKind::Transaction));
+                        }
+                    },
+                    None => records.push((key, json_value, RecordKind::Transaction)),
                 }
             }
             ProcessingMessage::BlockMetadata(block_meta) => {
-                let key: RecordKey = bs58::encode(&block_meta.blockhash).into_string().into();
-                let json_value = formatters::format_block_meta(block_meta).unwrap_or_else(|_| serde_json::json!({}));
-
-                if let Err(e) = producer.send(key, json_value.to_string().into_bytes()).await {
-                    error!("Error processing block metadata: {:?}", e);
-                    error!("Fatal error processing block metadata. Exiting...");
-                    std::process::exit(1);
+                let key = bs58::encode(&block_meta.blockhash).into_string();
+                if let Some(matched) = matched {
+                    output.route_to(RecordKind::BlockMeta, &key, block_meta.slot, matched);
+                }
+                let block_slot = block_meta.slot;
+                let blockhash = block_meta.blockhash.clone();
+                let block_time = block_meta.block_time.as_ref().map(|block_time| block_time.timestamp);
+                let block_height = block_meta.block_height.as_ref().map(|block_height| block_height.block_height);
+                let mut json_value = formatters::format_block_meta(block_meta).unwrap_or_else(|_| serde_json::json!({}));
+                if let Some(producers) = &producers {
+                    producers.enrich(&mut json_value, block_slot).await;
+                }
+                if let Some(correlator) = &mut correlator {
+                    let (transactions, late) = correlator.block(block_slot, blockhash, block_time, block_height);
+                    // Lets consumers know every buffered transaction of the block was published
+                    json_value["correlatedTransactions"] = serde_json::json!(transactions.len());
+                    records.extend(transactions.into_iter().map(|(key, tx)| (key, tx, RecordKind::Transaction)));
+                    if let Some(late) = late {
+                        records.push((key.clone(), late, RecordKind::BlockEnrichment));
+                    }
                 }
+                records.push((key, json_value, RecordKind::BlockMeta));
             }
             ProcessingMessage::Account(account) => {
                 // Make sure `account.account` is present
-                if let Some(account_info) = account.account.as_ref() {
-                    let key = if let Some(signature) = &account_info.txn_signature {
-                        RecordKey::from(bs58::encode(signature).into_string())
-                    } else {
-                        RecordKey::from(bs58::encode(&account_info.pubkey).into_string())
-                    };
-            
-                    let json_value = formatters::format_account(account)
-                        .unwrap_or_else(|_| serde_json::json!({}));
-            
-                    if let Err(e) = producer.send(key, json_value.to_string().into_bytes()).await {
-                        error!("Error processing account update: {:?}", e);
-                        error!("Fatal error processing account update. Exiting...");
-                        std::process::exit(1);
-                    }
-                } else {
+                let Some(account_info) = account.account.as_ref() else {
                     error!("Received SubscribeUpdateAccount without account info");
+                    continue;
+                };
+                let pubkey = codec::encode_bs58(&account_info.pubkey);
+                if let Some(pricing) = &pricing {
+                    pricing.observe_account(&pubkey, &account_info.data);
+                }
+                if let Some(warning) = activity.as_ref().and_then(|activity| activity.record(&pubkey)) {
+                    if let Err(e) = publisher.send(pubkey.clone(), &warning).await {
+                        error!("Error publishing account activity warning: {:?}", e);
+                    }
                 }
+
+                if let Some(matched) = matched {
+                    output.route_to(RecordKind::Account, &pubkey, account.slot, matched);
+                }
+                // Diffed accounts stay keyed by pubkey, so their records share a partition with
+                // the snapshot they apply to
+                let key = match &account_info.txn_signature {
+                    Some(signature) if account_diff.is_none() => codec::encode_bs58(signature),
+                    _ => pubkey,
+                };
+                let (slot, write_version) = (account.slot, account_info.write_version);
+                let diff = account_diff
+                    .as_mut()
+                    .map(|differ| differ.diff(&account_info.pubkey, &account_info.data, slot, write_version));
+                if let Some(account_closures) = &mut account_closures {
+                    account_closures.observe(&account).await;
+                }
+                let blob = match &blobs {
+                    Some(blobs) => blobs.offload(&account_info.data).await,
+                    None => None,
+                };
+                let mut json_value = formatters::format_account(account)
+                    .unwrap_or_else(|_| serde_json::json!({}));
+                if let Some(pointer) = blob {
+                    BlobStore::attach(&mut json_value, pointer);
+                }
+                if let Some(latest_accounts) = &mut latest_accounts {
+                    latest_accounts.observe(slot, write_version, &json_value, output.redactor.as_ref()).await;
+                }
+                if let Some(diff) = diff {
+                    diff.apply(&mut json_value);
+                }
+                records.push((key, json_value, RecordKind::Account));
             }
+            ProcessingMessage::TransactionStatus(status) => {
+                if let Some(statuses) = &mut output.statuses {
+                    statuses.observe(status).await;
+                }
+            },
+            // Unwrapped above
+            ProcessingMessage::Grouped(..) => continue,
             ProcessingMessage::Shutdown => break,
+        };
+        records.extend(state_records);
+
+        if let Some(correlator) = &mut correlator {
+            records.extend(correlator.expired().into_iter().map(|(key, tx)| (key, tx, RecordKind::Transaction)));
+        }
+        for (key, json_value, kind) in records {
+            output.publish(key, json_value, kind).await;
+        }
+        if let (Some(statuses), Some(slot)) = (&mut output.statuses, slot) {
+            statuses.expire(slot).await;
+        }
+        if let Some(bloom) = &mut bloom {
+            bloom.publish_closed().await;
+        }
+        if batching.as_mut().is_some_and(|batching| batching.should_flush(rx.len())) {
+            output.flush().await;
+        }
+
+        if let (Some(store), Some(slot)) = (&mut checkpoint, slot) {
+            // Transactions still waiting for their block are not published yet
+            let pending = correlator.as_ref().and_then(BlockCorrelator::oldest_pending);
+            let slot = pending.map_or(slot, |pending| slot.min(pending));
+            let slot = output.acks.as_mut().map_or(slot, |acks| acks.resume_slot(slot));
+            // Records dropped by a publishing pause are replayed on restart
+            let slot = pause::held_slot().map_or(slot, |held| slot.min(held));
+            let written = store.record(slot).await;
+            publish_checkpoint(&publisher, written).await;
+        }
+        if let Some(cutoff) = retention.as_mut().zip(slot).and_then(|(retention, slot)| retention.due(slot)) {
+            if !pruner.as_ref().is_some_and(|pruner| pruner.request(cutoff)) {
+                retention = None;
+            }
+        }
+    }
+
+    if let Some(correlator) = &mut correlator {
+        for (key, json_value) in correlator.drain() {
+            output.publish(key, json_value, RecordKind::Transaction).await;
+        }
+    }
+    output.finish().await;
+    if let Some(bloom) = &mut bloom {
+        bloom.finish().await;
+    }
+    if let Some(votes) = &votes {
+        votes.finish().await;
+    }
+    if let Some(stake_events) = &stake_events {
+        stake_events.finish().await;
+    }
+    if let Some(mint_events) = &mint_events {
+        mint_events.finish().await;
+    }
+    if let Some(program_deploys) = &program_deploys {
+        program_deploys.finish().await;
+    }
+    if let Some(transfers) = &transfers {
+        transfers.finish().await;
+    }
+    if let Some(stats) = &stats {
+        stats.finish().await;
+    }
+    if let Some(latest_accounts) = &latest_accounts {
+        latest_accounts.finish().await;
+    }
+    if let Some(account_closures) = &account_closures {
+        account_closures.finish().await;
+    }
+    if let Some(blobs) = &blobs {
+        blobs.finish().await;
+    }
+    if let Some(store) = &mut checkpoint {
+        // Deliveries failed while draining are replayed on restart
+        if let Some(acks) = &mut output.acks {
+            store.rewind(acks.resume_slot(u64::MAX));
+        }
+        let written = store.flush().await;
+        publish_checkpoint(&publisher, written).await;
+    }
+}
+
+/// Final steps shared by every record of a pipeline
+struct Output {
+    publisher: Arc<Publisher>,
+    routes: Vec<Route>,
+    historical: bool,
+    idempotency_keys: bool,
+    redactor: Option<Redactor>,
+    in_flight: Option<InFlight>,
+    // Acknowledgements per slot, with a checkpoint
+    acks: Option<SlotAcks>,
+    // Slot of the update being processed
+    slot: Option<u64>,
+    integrity: Option<IntegrityChain>,
+    web: Option<WebTap>,
+    outbox: Option<Outbox>,
+    statuses: Option<StatusStream>,
+    // Destinations of the records of updates matched by filter groups, until they are published
+    grouped: GroupedRecords,
+}
+
+impl Output {
+    /// Publish the record `key` of `kind` of `slot` to the topics of the filter groups it matched,
+    /// account records by their pubkey
+    fn route_to(&mut self, kind: RecordKind, key: &str, slot: u64, matched: GroupMatch) {
+        self.grouped.insert(kind, key, slot, matched);
+    }
+
+    /// Flag, redact and publish a record to the main topic and matching routes, exiting on publish errors
+    async fn publish(&mut self, key: String, mut json_value: serde_json::Value, kind: RecordKind) {
+        // Before redaction, which can mask the pubkey
+        let matched = self.grouped.take(kind, &key, &json_value);
+        // From the content of the record only, before the fields of this run are added
+        if self.idempotency_keys {
+            json_value["idempotencyKey"] = serde_json::json!(integrity::idempotency_key(&key, &json_value, self.publisher.numbers()));
+        }
+        json_value["streamerVersion"] = serde_json::json!(build_info::FULL_VERSION);
+        if self.historical {
+            json_value["historical"] = serde_json::json!(true);
+        }
+        if let Some(redactor) = &self.redactor {
+            redactor.apply(&mut json_value);
         }
+        // Routes stamp the records they publish themselves
+        let unstamped = (self.integrity.is_some() && !self.routes.is_empty()).then(|| json_value.clone());
+        let integrity_root = self.integrity.as_mut().and_then(|integrity| integrity.stamp(&mut json_value));
+        if let Some(web) = &self.web {
+            web.publish(&key, kind, &json_value);
+        }
+
+        let mut deliveries = Vec::with_capacity(1 + self.routes.len());
+        if matched.as_ref().map_or(true, GroupMatch::main) {
+            match self.publisher.send_tracked(key.clone(), &json_value).await {
+                // Transaction records are keyed by signature, their derived events and status follow them
+                Ok(delivery) => {
+                    if let (Some(statuses), RecordKind::Transaction) = (&mut self.statuses, kind) {
+                        statuses.release(&key, json_value["slot"].as_u64().unwrap_or_default(), self.outbox.as_ref()).await;
+                    }
+                    match (&self.outbox, kind) {
+                        (Some(outbox), RecordKind::Transaction) => deliveries.push(outbox.release(&key, delivery).await),
+                        _ => deliveries.push(delivery),
+                    }
+                },
+                Err(e) => fatal(kind, e),
+            }
+        } else if kind == RecordKind::Transaction {
+            if let Some(outbox) = &self.outbox {
+                outbox.discard(&key);
+            }
+            if let Some(statuses) = &mut self.statuses {
+                statuses.release(&key, json_value["slot"].as_u64().unwrap_or_default(), None).await;
+            }
+        }
+        let route_record = unstamped.as_ref().unwrap_or(&json_value);
+        for route in self.routes.iter_mut().filter(|route| route.matches(kind, matched.as_ref())) {
+            match route.send(key.clone(), route_record, kind).await {
+                Ok(delivery) => deliveries.push(delivery),
+                Err(e) => fatal(kind, e),
+            }
+        }
+
+        // Derived records of earlier slots hold the checkpoint at their own slot
+        let slot = json_value["slot"].as_u64().into_iter().chain(self.slot).min();
+        for delivery in deliveries {
+            let delivery = match (&self.acks, slot) {
+                (Some(acks), Some(slot)) => acks.track(slot, delivery),
+                _ => delivery,
+            };
+            // Without an in-flight limit acknowledgements are only awaited for the checkpoint
+            match (&mut self.in_flight, &mut self.acks) {
+                (Some(in_flight), _) => {
+                    if let Err(e) = in_flight.push(delivery).await {
+                        fatal(kind, e);
+                    }
+                },
+                (None, Some(acks)) => acks.push_unawaited(delivery),
+                (None, None) => {},
+            }
+        }
+        if let Some(root) = integrity_root {
+            self.publish_root(&root).await;
+        }
+    }
+
+    /// Drop the derived events held for a transaction filtered out of the main topic, its status
+    /// is published alone
+    async fn discard_derived(&mut self, tx: &SubscribeUpdateTransaction) {
+        let Some(transaction) = &tx.transaction else {
+            return;
+        };
+        let signature = codec::encode_bs58(&transaction.signature);
+        if let Some(outbox) = &self.outbox {
+            outbox.discard(&signature);
+        }
+        if let Some(statuses) = &mut self.statuses {
+            statuses.release(&signature, tx.slot, None).await;
+        }
+    }
+
+    async fn publish_root(&self, root: &serde_json::Value) {
+        if let Err(e) = self.publisher.send_control(root).await {
+            error!("Error publishing integrity root: {:?}", e);
+        }
+    }
+
+    /// Flush the main topic and route producers
+    async fn flush(&self) {
+        if let Err(e) = self.publisher.flush().await {
+            error!("Error flushing records: {:?}", e);
+        }
+        for route in &self.routes {
+            if let Err(e) = route.flush().await {
+                error!("Error flushing route: {:?}", e);
+            }
+        }
+    }
+
+    /// Wait for outstanding acknowledgements and flush the routes
+    async fn finish(&mut self) {
+        if let Some(root) = self.integrity.as_mut().and_then(IntegrityChain::finish) {
+            self.publish_root(&root).await;
+        }
+        if let Some(in_flight) = &mut self.in_flight {
+            if let Err(e) = in_flight.drain().await {
+                error!("Error awaiting record acknowledgements: {:?}", e);
+            }
+        }
+        if let Some(acks) = &mut self.acks {
+            acks.drain().await;
+        }
+        if let Some(outbox) = &self.outbox {
+            outbox.finish().await;
+        }
+        if let Some(statuses) = &mut self.statuses {
+            statuses.finish().await;
+        }
+        for route in &mut self.routes {
+            if let Err(e) = route.finish().await {
+                error!("Error flushing route: {:?}", e);
+            }
+        }
+    }
+}
+
+fn fatal(kind: RecordKind, e: anyhow::Error) -> ! {
+    error!("Error processing {}: {:?}", kind, e);
+    error!("Fatal error processing {}. Exiting...", kind);
+    std::process::exit(1);
+}
+
+/// Stop at `msg` once the drain timeout expired, keeping the checkpoint before the slots of the
+/// updates left in the queue so they are replayed on restart
+fn abandon(msg: ProcessingMessage, rx: &mut mpsc::Receiver<ProcessingMessage>, checkpoint: Option<&mut CheckpointStore>) {
+    rx.close();
+    let mut left = 0;
+    let mut first_slot = None;
+    for msg in std::iter::once(msg).chain(std::iter::from_fn(|| rx.try_recv().ok())) {
+        if let Some(slot) = msg.slot() {
+            left += 1;
+            first_slot = Some(first_slot.map_or(slot, |first: u64| first.min(slot)));
+        }
+    }
+    let Some(first_slot) = first_slot else {
+        return;
+    };
+    warn!("Leaving {} queued updates unpublished, from slot {}", left, first_slot);
+    if let Some(store) = checkpoint {
+        store.rewind(first_slot);
+    }
+}
+
+/// Publish a `checkpoint` control record after a checkpoint write
+async fn publish_checkpoint(publisher: &Publisher, written: anyhow::Result<Option<u64>>) {
+    match written {
+        Ok(Some(slot)) => {
+            let record = control::control_record("checkpoint", serde_json::json!({ "slot": slot }));
+            if let Err(e) = publisher.send_control(&record).await {
+                error!("Error publishing checkpoint event: {:?}", e);
+            }
+        },
+        Ok(None) => {},
+        Err(e) => error!("Error writing checkpoint: {:?}", e),
+    }
+}
+
+/// Next refreshed subscribe request, pending forever without a refresh task
+async fn next_request(request_updates: &mut Option<mpsc::Receiver<SubscribeRequest>>) -> Option<SubscribeRequest> {
+    match request_updates {
+        Some(request_updates) => request_updates.recv().await,
+        None => futures::future::pending().await,
     }
 }
 
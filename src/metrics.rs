@@ -1,12 +1,24 @@
+#[cfg(feature = "metrics")]
 use chrono::Utc;
+#[cfg(feature = "metrics")]
 use reqwest::{Client, header};
+#[cfg(feature = "metrics")]
 use serde_json::{json, Value};
 use std::time::Duration;
+#[cfg(feature = "metrics")]
 use tokio::time;
+#[cfg(feature = "metrics")]
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use yellowstone_grpc_proto::tonic::Code;
+#[cfg(feature = "metrics")]
 use log::{info, warn, error, debug};
 
+#[cfg(feature = "metrics")]
+use crate::build_info;
+use crate::health::ConnectionState;
+use crate::sinks::SinkKind;
+
 /// Metrics configuration
 #[derive(Debug, Clone)]
 pub struct MetricsConfig {
@@ -31,20 +43,159 @@ impl Default for MetricsConfig {
     }
 }
 
+/// Kind of a producer send or acknowledgement error, telling sink-side issues apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProducerErrorKind {
+    /// The connection to the broker (Fluvio SPU) was reset or closed
+    ConnectionReset,
+    Timeout,
+    RecordTooLarge,
+    Other,
+}
+
+// Only classified by the Fluvio sink, only reported with the `metrics` feature
+#[cfg_attr(not(all(feature = "fluvio", feature = "metrics")), allow(dead_code))]
+impl ProducerErrorKind {
+    const ALL: [ProducerErrorKind; 4] = [
+        ProducerErrorKind::ConnectionReset,
+        ProducerErrorKind::Timeout,
+        ProducerErrorKind::RecordTooLarge,
+        ProducerErrorKind::Other,
+    ];
+
+    /// Classify an error from its message, producer errors do not share a type across sinks
+    pub fn classify(error: &anyhow::Error) -> Self {
+        let message = format!("{:#}", error).to_lowercase();
+        if ["connection", "socket", "broken pipe", "reset by peer", "eof"]
+            .iter()
+            .any(|pattern| message.contains(pattern))
+        {
+            ProducerErrorKind::ConnectionReset
+        } else if message.contains("timeout") || message.contains("timed out") {
+            ProducerErrorKind::Timeout
+        } else if message.contains("too large") || message.contains("toolarge") {
+            ProducerErrorKind::RecordTooLarge
+        } else {
+            ProducerErrorKind::Other
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ProducerErrorKind::ConnectionReset => "connection_reset",
+            ProducerErrorKind::Timeout => "timeout",
+            ProducerErrorKind::RecordTooLarge => "record_too_large",
+            ProducerErrorKind::Other => "other",
+        }
+    }
+}
+
+/// gRPC status codes, `Ok` (0) to `Unauthenticated` (16), indexing the stream error counters
+const GRPC_CODES: usize = 17;
+
+/// Name of a gRPC status code in the metric names
+#[cfg(feature = "metrics")]
+fn code_name(code: Code) -> &'static str {
+    match code {
+        Code::Ok => "ok",
+        Code::Cancelled => "cancelled",
+        Code::Unknown => "unknown",
+        Code::InvalidArgument => "invalid_argument",
+        Code::DeadlineExceeded => "deadline_exceeded",
+        Code::NotFound => "not_found",
+        Code::AlreadyExists => "already_exists",
+        Code::PermissionDenied => "permission_denied",
+        Code::ResourceExhausted => "resource_exhausted",
+        Code::FailedPrecondition => "failed_precondition",
+        Code::Aborted => "aborted",
+        Code::OutOfRange => "out_of_range",
+        Code::Unimplemented => "unimplemented",
+        Code::Internal => "internal",
+        Code::Unavailable => "unavailable",
+        Code::DataLoss => "data_loss",
+        Code::Unauthenticated => "unauthenticated",
+    }
+}
+
 /// Metric type for storing counter values
 #[derive(Debug)]
 pub struct Metrics {
     processed_transactions: AtomicU64,
     processed_accounts: AtomicU64,
     errors: AtomicU64,
+    activity_warnings: AtomicU64,
+    shed_votes: AtomicU64,
+    shed_accounts: AtomicU64,
+    stale_dropped: AtomicU64,
+    batch_flushes: AtomicU64,
+    batch_linger_ms: AtomicU64,
+    producer_errors: [AtomicU64; ProducerErrorKind::ALL.len()],
+    producer_retries: AtomicU64,
+    producer_acks: AtomicU64,
+    producer_ack_latency_us: AtomicU64,
+    loopback_records: AtomicU64,
+    loopback_latency_ms: AtomicU64,
+    loopback_end_offset: AtomicU64,
+    fanout_complete: AtomicU64,
+    fanout_partial: AtomicU64,
+    fanout_failed: AtomicU64,
+    sink_registered: [AtomicBool; SinkKind::ALL.len()],
+    sink_pending: [AtomicU64; SinkKind::ALL.len()],
+    sink_dropped: [AtomicU64; SinkKind::ALL.len()],
+    sink_open_circuits: [AtomicU64; SinkKind::ALL.len()],
+    stream_errors: [AtomicU64; GRPC_CODES],
+    stream_closed: AtomicU64,
+    reconnect_attempts: AtomicU64,
+    reconnect_successes: AtomicU64,
+    resubscribe_latency_ms: AtomicU64,
+    stream_states: [AtomicU64; ConnectionState::ALL.len()],
+    state_transitions: AtomicU64,
+    received_bytes: AtomicU64,
+    unknown_updates: AtomicU64,
 }
 
+// Without the `metrics` feature counters are kept but never reported, producer and loopback
+// counters are only updated with the `fluvio` feature
+#[cfg_attr(not(all(feature = "metrics", feature = "fluvio")), allow(dead_code))]
 impl Metrics {
     pub fn new() -> Self {
         Self {
             processed_transactions: AtomicU64::new(0),
             processed_accounts: AtomicU64::new(0),
             errors: AtomicU64::new(0),
+            activity_warnings: AtomicU64::new(0),
+            shed_votes: AtomicU64::new(0),
+            shed_accounts: AtomicU64::new(0),
+            stale_dropped: AtomicU64::new(0),
+            batch_flushes: AtomicU64::new(0),
+            batch_linger_ms: AtomicU64::new(0),
+            producer_errors: std::array::from_fn(|_| AtomicU64::new(0)),
+            producer_retries: AtomicU64::new(0),
+            producer_acks: AtomicU64::new(0),
+            producer_ack_latency_us: AtomicU64::new(0),
+            loopback_records: AtomicU64::new(0),
+            loopback_latency_ms: AtomicU64::new(0),
+            loopback_end_offset: AtomicU64::new(0),
+            fanout_complete: AtomicU64::new(0),
+            fanout_partial: AtomicU64::new(0),
+            fanout_failed: AtomicU64::new(0),
+            sink_registered: std::array::from_fn(|_| AtomicBool::new(false)),
+            sink_pending: std::array::from_fn(|_| AtomicU64::new(0)),
+            sink_dropped: std::array::from_fn(|_| AtomicU64::new(0)),
+            sink_open_circuits: std::array::from_fn(|_| AtomicU64::new(0)),
+            stream_errors: std::array::from_fn(|_| AtomicU64::new(0)),
+            stream_closed: AtomicU64::new(0),
+            reconnect_attempts: AtomicU64::new(0),
+            reconnect_successes: AtomicU64::new(0),
+            resubscribe_latency_ms: AtomicU64::new(0),
+            stream_states: std::array::from_fn(|_| AtomicU64::new(0)),
+            state_transitions: AtomicU64::new(0),
+            received_bytes: AtomicU64::new(0),
+            unknown_updates: AtomicU64::new(0),
         }
     }
 
@@ -63,6 +214,138 @@ impl Metrics {
         self.errors.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Increment the account activity warnings counter
+    pub fn increment_activity_warnings(&self) {
+        self.activity_warnings.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Increment the counter of vote transactions dropped by load shedding
+    pub fn increment_shed_votes(&self) {
+        self.shed_votes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Increment the counter of account updates dropped by load shedding
+    pub fn increment_shed_accounts(&self) {
+        self.shed_accounts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Increment the counter of updates dropped as stale
+    pub fn increment_stale_dropped(&self) {
+        self.stale_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Increment the counter of flushes decided by adaptive batching
+    pub fn increment_batch_flushes(&self) {
+        self.batch_flushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Set the linger currently chosen by adaptive batching
+    pub fn set_batch_linger_ms(&self, linger_ms: u64) {
+        self.batch_linger_ms.store(linger_ms, Ordering::Relaxed);
+    }
+
+    /// Increment the counter of producer errors of `kind`
+    pub fn increment_producer_errors(&self, kind: ProducerErrorKind) {
+        self.producer_errors[kind.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Increment the counter of producer sends retried by a fan-out lane
+    pub fn increment_producer_retries(&self) {
+        self.producer_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the outcome of a record fanned out to `sinks` sinks, `delivered` of which took it
+    pub fn record_fanout(&self, delivered: usize, sinks: usize) {
+        let counter = match delivered {
+            0 => &self.fanout_failed,
+            delivered if delivered < sinks => &self.fanout_partial,
+            _ => &self.fanout_complete,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Report the queue of a fanned out sink of `kind`
+    pub fn register_sink(&self, kind: SinkKind) {
+        self.sink_registered[kind.index()].store(true, Ordering::Relaxed);
+    }
+
+    /// Count a record queued for a fanned out sink of `kind`
+    pub fn add_sink_pending(&self, kind: SinkKind) {
+        self.sink_pending[kind.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a record acknowledged, failed or dropped by a fanned out sink of `kind`
+    pub fn remove_sink_pending(&self, kind: SinkKind) {
+        self.sink_pending[kind.index()].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Increment the counter of records a fanned out sink of `kind` skipped, on a full queue or
+    /// an open circuit
+    pub fn increment_sink_dropped(&self, kind: SinkKind) {
+        self.sink_dropped[kind.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the circuit of a fanned out sink of `kind` opening or closing
+    pub fn set_sink_circuit(&self, kind: SinkKind, open: bool) {
+        if open {
+            self.sink_open_circuits[kind.index()].fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.sink_open_circuits[kind.index()].fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Record an acknowledged record and the time it took from send to acknowledgement
+    pub fn record_producer_ack(&self, latency: Duration) {
+        self.producer_acks.fetch_add(1, Ordering::Relaxed);
+        self.producer_ack_latency_us.fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Record a record read back by the loopback consumer at `offset`
+    pub fn record_loopback(&self, latency_ms: u64, offset: u64) {
+        self.loopback_records.fetch_add(1, Ordering::Relaxed);
+        self.loopback_latency_ms.fetch_add(latency_ms, Ordering::Relaxed);
+        self.loopback_end_offset.fetch_max(offset + 1, Ordering::Relaxed);
+    }
+
+    /// Increment the counter of gRPC subscriptions failed with `code`, or closed by the server
+    /// without a status when `None`
+    pub fn increment_stream_errors(&self, code: Option<Code>) {
+        match code {
+            Some(code) => self.stream_errors[code as usize].fetch_add(1, Ordering::Relaxed),
+            None => self.stream_closed.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    /// Increment the counter of attempts to re-establish a gRPC subscription
+    pub fn increment_reconnect_attempts(&self) {
+        self.reconnect_attempts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a re-established gRPC subscription and the time it was down for
+    pub fn record_reconnect(&self, latency: Duration) {
+        self.reconnect_successes.fetch_add(1, Ordering::Relaxed);
+        self.resubscribe_latency_ms.fetch_add(latency.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Count bytes received from the gRPC streams
+    pub fn add_received_bytes(&self, bytes: u64) {
+        self.received_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Count an update of a type the pipeline does not handle
+    pub fn increment_unknown_updates(&self) {
+        self.unknown_updates.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a stream moving from `from`, `None` when registered, to `to`
+    pub fn record_state_transition(&self, from: Option<ConnectionState>, to: ConnectionState) {
+        if let Some(from) = from {
+            self.stream_states[from.index()].fetch_sub(1, Ordering::Relaxed);
+            self.state_transitions.fetch_add(1, Ordering::Relaxed);
+        }
+        self.stream_states[to.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Get current transaction count
     pub fn transactions(&self) -> u64 {
         self.processed_transactions.load(Ordering::Relaxed)
@@ -77,8 +360,165 @@ impl Metrics {
     pub fn errors(&self) -> u64 {
         self.errors.load(Ordering::Relaxed)
     }
+
+    /// Get current account activity warnings count
+    pub fn activity_warnings(&self) -> u64 {
+        self.activity_warnings.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of shed vote transactions
+    pub fn shed_votes(&self) -> u64 {
+        self.shed_votes.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of shed account updates
+    pub fn shed_accounts(&self) -> u64 {
+        self.shed_accounts.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of updates dropped as stale
+    pub fn stale_dropped(&self) -> u64 {
+        self.stale_dropped.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of adaptive batching flushes
+    pub fn batch_flushes(&self) -> u64 {
+        self.batch_flushes.load(Ordering::Relaxed)
+    }
+
+    /// Get the current adaptive batching linger
+    pub fn batch_linger_ms(&self) -> u64 {
+        self.batch_linger_ms.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of producer errors of `kind`
+    pub fn producer_errors(&self, kind: ProducerErrorKind) -> u64 {
+        self.producer_errors[kind.index()].load(Ordering::Relaxed)
+    }
+
+    /// Get current count of retried producer sends
+    pub fn producer_retries(&self) -> u64 {
+        self.producer_retries.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of fanned out records delivered to every sink
+    pub fn fanout_complete(&self) -> u64 {
+        self.fanout_complete.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of fanned out records delivered to some of the sinks
+    pub fn fanout_partial(&self) -> u64 {
+        self.fanout_partial.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of fanned out records no sink took
+    pub fn fanout_failed(&self) -> u64 {
+        self.fanout_failed.load(Ordering::Relaxed)
+    }
+
+    /// Whether fanned out sinks of `kind` are running
+    pub fn sink_registered(&self, kind: SinkKind) -> bool {
+        self.sink_registered[kind.index()].load(Ordering::Relaxed)
+    }
+
+    /// Get the records queued for or in flight to fanned out sinks of `kind`
+    pub fn sink_pending(&self, kind: SinkKind) -> u64 {
+        self.sink_pending[kind.index()].load(Ordering::Relaxed)
+    }
+
+    /// Get current count of records skipped by fanned out sinks of `kind`
+    pub fn sink_dropped(&self, kind: SinkKind) -> u64 {
+        self.sink_dropped[kind.index()].load(Ordering::Relaxed)
+    }
+
+    /// Get the open circuits of fanned out sinks of `kind`, one per topic
+    pub fn sink_open_circuits(&self, kind: SinkKind) -> u64 {
+        self.sink_open_circuits[kind.index()].load(Ordering::Relaxed)
+    }
+
+    /// Get current count of acknowledged records
+    pub fn producer_acks(&self) -> u64 {
+        self.producer_acks.load(Ordering::Relaxed)
+    }
+
+    /// Get the total acknowledgement latency of the acknowledged records
+    pub fn producer_ack_latency_us(&self) -> u64 {
+        self.producer_ack_latency_us.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of records read back by the loopback consumer
+    pub fn loopback_records(&self) -> u64 {
+        self.loopback_records.load(Ordering::Relaxed)
+    }
+
+    /// Get the total publish-to-availability latency of the records read back
+    pub fn loopback_latency_ms(&self) -> u64 {
+        self.loopback_latency_ms.load(Ordering::Relaxed)
+    }
+
+    /// Get the end offset of the topic as last seen by the loopback consumer
+    pub fn loopback_end_offset(&self) -> u64 {
+        self.loopback_end_offset.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of gRPC subscriptions failed with `code`
+    pub fn stream_errors(&self, code: Code) -> u64 {
+        self.stream_errors[code as usize].load(Ordering::Relaxed)
+    }
+
+    /// Get current count of gRPC subscriptions closed by the server without a status
+    pub fn stream_closed(&self) -> u64 {
+        self.stream_closed.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of reconnect attempts
+    pub fn reconnect_attempts(&self) -> u64 {
+        self.reconnect_attempts.load(Ordering::Relaxed)
+    }
+
+    /// Get current count of re-established subscriptions
+    pub fn reconnect_successes(&self) -> u64 {
+        self.reconnect_successes.load(Ordering::Relaxed)
+    }
+
+    /// Get the total time the re-established subscriptions were down for
+    pub fn resubscribe_latency_ms(&self) -> u64 {
+        self.resubscribe_latency_ms.load(Ordering::Relaxed)
+    }
+
+    /// Get the streams currently in `state`
+    pub fn streams_in_state(&self, state: ConnectionState) -> u64 {
+        self.stream_states[state.index()].load(Ordering::Relaxed)
+    }
+
+    /// Get current count of stream state transitions
+    pub fn state_transitions(&self) -> u64 {
+        self.state_transitions.load(Ordering::Relaxed)
+    }
+
+    /// Get the bytes received from the gRPC streams
+    pub fn received_bytes(&self) -> u64 {
+        self.received_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Get the updates of types the pipeline does not handle
+    pub fn unknown_updates(&self) -> u64 {
+        self.unknown_updates.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "metrics")]
+/// Change of a counter since the previous report, stored in `last` (handles counter resets)
+fn delta(current: u64, last: &AtomicU64) -> u64 {
+    let last = last.swap(current, Ordering::Relaxed);
+    if current >= last {
+        current - last
+    } else {
+        current
+    }
 }
 
+#[cfg(feature = "metrics")]
 /// BetterStack metrics reporter
 pub struct MetricsReporter {
     metrics: Arc<Metrics>,
@@ -87,8 +527,33 @@ pub struct MetricsReporter {
     last_transactions: AtomicU64,
     last_accounts: AtomicU64,
     last_errors: AtomicU64,
+    last_activity_warnings: AtomicU64,
+    last_shed_votes: AtomicU64,
+    last_shed_accounts: AtomicU64,
+    last_stale_dropped: AtomicU64,
+    last_batch_flushes: AtomicU64,
+    last_producer_errors: [AtomicU64; ProducerErrorKind::ALL.len()],
+    last_producer_retries: AtomicU64,
+    last_producer_acks: AtomicU64,
+    last_producer_ack_latency_us: AtomicU64,
+    last_loopback_records: AtomicU64,
+    last_loopback_latency_ms: AtomicU64,
+    last_loopback_end_offset: AtomicU64,
+    last_fanout_complete: AtomicU64,
+    last_fanout_partial: AtomicU64,
+    last_fanout_failed: AtomicU64,
+    last_sink_dropped: [AtomicU64; SinkKind::ALL.len()],
+    last_stream_errors: [AtomicU64; GRPC_CODES],
+    last_stream_closed: AtomicU64,
+    last_reconnect_attempts: AtomicU64,
+    last_reconnect_successes: AtomicU64,
+    last_resubscribe_latency_ms: AtomicU64,
+    last_state_transitions: AtomicU64,
+    last_received_bytes: AtomicU64,
+    last_unknown_updates: AtomicU64,
 }
 
+#[cfg(feature = "metrics")]
 impl MetricsReporter {
     /// Create a new metrics reporter
     pub fn new(metrics: Arc<Metrics>, config: MetricsConfig) -> Self {
@@ -104,6 +569,30 @@ impl MetricsReporter {
             last_transactions: AtomicU64::new(0),
             last_accounts: AtomicU64::new(0),
             last_errors: AtomicU64::new(0),
+            last_activity_warnings: AtomicU64::new(0),
+            last_shed_votes: AtomicU64::new(0),
+            last_shed_accounts: AtomicU64::new(0),
+            last_stale_dropped: AtomicU64::new(0),
+            last_batch_flushes: AtomicU64::new(0),
+            last_producer_errors: std::array::from_fn(|_| AtomicU64::new(0)),
+            last_producer_retries: AtomicU64::new(0),
+            last_producer_acks: AtomicU64::new(0),
+            last_producer_ack_latency_us: AtomicU64::new(0),
+            last_loopback_records: AtomicU64::new(0),
+            last_loopback_latency_ms: AtomicU64::new(0),
+            last_loopback_end_offset: AtomicU64::new(0),
+            last_fanout_complete: AtomicU64::new(0),
+            last_fanout_partial: AtomicU64::new(0),
+            last_fanout_failed: AtomicU64::new(0),
+            last_sink_dropped: std::array::from_fn(|_| AtomicU64::new(0)),
+            last_stream_errors: std::array::from_fn(|_| AtomicU64::new(0)),
+            last_stream_closed: AtomicU64::new(0),
+            last_reconnect_attempts: AtomicU64::new(0),
+            last_reconnect_successes: AtomicU64::new(0),
+            last_resubscribe_latency_ms: AtomicU64::new(0),
+            last_state_transitions: AtomicU64::new(0),
+            last_received_bytes: AtomicU64::new(0),
+            last_unknown_updates: AtomicU64::new(0),
         }
     }
 
@@ -135,36 +624,21 @@ impl MetricsReporter {
         let timestamp = Utc::now().format("%Y-%m-%d %T UTC").to_string();
         
         // Calculate deltas since last report
-        let current_transactions = metrics.transactions();
-        let current_accounts = metrics.accounts();
-        let current_errors = metrics.errors();
-        
-        let last_transactions = self.last_transactions.swap(current_transactions, Ordering::Relaxed);
-        let last_accounts = self.last_accounts.swap(current_accounts, Ordering::Relaxed);
-        let last_errors = self.last_errors.swap(current_errors, Ordering::Relaxed);
-        
-        // Calculate deltas (handle case where counters might reset)
-        let transactions_delta = if current_transactions >= last_transactions {
-            current_transactions - last_transactions
-        } else {
-            current_transactions
-        };
-        
-        let accounts_delta = if current_accounts >= last_accounts {
-            current_accounts - last_accounts
-        } else {
-            current_accounts
-        };
-        
-        let errors_delta = if current_errors >= last_errors {
-            current_errors - last_errors
-        } else {
-            current_errors
-        };
+        let transactions_delta = delta(metrics.transactions(), &self.last_transactions);
+        let accounts_delta = delta(metrics.accounts(), &self.last_accounts);
+        let errors_delta = delta(metrics.errors(), &self.last_errors);
+        let activity_warnings_delta = delta(metrics.activity_warnings(), &self.last_activity_warnings);
+        let shed_votes_delta = delta(metrics.shed_votes(), &self.last_shed_votes);
+        let shed_accounts_delta = delta(metrics.shed_accounts(), &self.last_shed_accounts);
+        let stale_dropped_delta = delta(metrics.stale_dropped(), &self.last_stale_dropped);
+        let batch_flushes_delta = delta(metrics.batch_flushes(), &self.last_batch_flushes);
         
         debug!("Reporting metrics - transactions delta: {}, accounts delta: {}, errors delta: {}", 
                transactions_delta, accounts_delta, errors_delta);
         
+        // Report the running build so metrics can be correlated with deployments
+        self.send_build_info(&timestamp).await?;
+
         // Report transactions metric
         self.send_metric(
             "yellowstone_processed_transactions",
@@ -185,6 +659,178 @@ impl MetricsReporter {
             errors_delta,
             &timestamp,
         ).await?;
+
+        // Report account activity warnings metric
+        self.send_metric(
+            "yellowstone_account_activity_warnings",
+            activity_warnings_delta,
+            &timestamp,
+        ).await?;
+
+        // Report load shedding metrics
+        self.send_metric(
+            "yellowstone_shed_votes",
+            shed_votes_delta,
+            &timestamp,
+        ).await?;
+        self.send_metric(
+            "yellowstone_shed_account_updates",
+            shed_accounts_delta,
+            &timestamp,
+        ).await?;
+
+        // Report updates dropped as stale
+        self.send_metric(
+            "yellowstone_stale_dropped",
+            stale_dropped_delta,
+            &timestamp,
+        ).await?;
+
+        // Report adaptive batching decisions
+        self.send_metric(
+            "yellowstone_batch_flushes",
+            batch_flushes_delta,
+            &timestamp,
+        ).await?;
+        self.send_metric(
+            "yellowstone_batch_linger_ms",
+            metrics.batch_linger_ms(),
+            &timestamp,
+        ).await?;
+
+        // Report producer health, separating sink-side issues from gRPC-side ones
+        for kind in ProducerErrorKind::ALL {
+            let errors_delta = delta(metrics.producer_errors(kind), &self.last_producer_errors[kind.index()]);
+            self.send_metric(
+                &format!("yellowstone_producer_errors_{}", kind.name()),
+                errors_delta,
+                &timestamp,
+            ).await?;
+        }
+        self.send_metric(
+            "yellowstone_producer_retries",
+            delta(metrics.producer_retries(), &self.last_producer_retries),
+            &timestamp,
+        ).await?;
+        let acks_delta = delta(metrics.producer_acks(), &self.last_producer_acks);
+        let ack_latency_delta = delta(metrics.producer_ack_latency_us(), &self.last_producer_ack_latency_us);
+        self.send_metric(
+            "yellowstone_producer_ack_latency_us",
+            ack_latency_delta.checked_div(acks_delta).unwrap_or(0),
+            &timestamp,
+        ).await?;
+
+        // Report what the loopback consumer could read back, zero when it is not running
+        let loopback_records_delta = delta(metrics.loopback_records(), &self.last_loopback_records);
+        let loopback_latency_delta = delta(metrics.loopback_latency_ms(), &self.last_loopback_latency_ms);
+        self.send_metric(
+            "yellowstone_loopback_latency_ms",
+            loopback_latency_delta.checked_div(loopback_records_delta).unwrap_or(0),
+            &timestamp,
+        ).await?;
+        self.send_metric(
+            "yellowstone_loopback_offset_growth",
+            delta(metrics.loopback_end_offset(), &self.last_loopback_end_offset),
+            &timestamp,
+        ).await?;
+
+        // Report the combined delivery status of records fanned out to several sinks
+        self.send_metric(
+            "yellowstone_fanout_complete",
+            delta(metrics.fanout_complete(), &self.last_fanout_complete),
+            &timestamp,
+        ).await?;
+        self.send_metric(
+            "yellowstone_fanout_partial",
+            delta(metrics.fanout_partial(), &self.last_fanout_partial),
+            &timestamp,
+        ).await?;
+        self.send_metric(
+            "yellowstone_fanout_failed",
+            delta(metrics.fanout_failed(), &self.last_fanout_failed),
+            &timestamp,
+        ).await?;
+
+        // Report which fanned out sink is behind
+        for kind in SinkKind::ALL.into_iter().filter(|kind| metrics.sink_registered(*kind)) {
+            self.send_metric(
+                &format!("yellowstone_sink_lag_{}", kind.name()),
+                metrics.sink_pending(kind),
+                &timestamp,
+            ).await?;
+            self.send_metric(
+                &format!("yellowstone_sink_dropped_{}", kind.name()),
+                delta(metrics.sink_dropped(kind), &self.last_sink_dropped[kind.index()]),
+                &timestamp,
+            ).await?;
+            self.send_metric(
+                &format!("yellowstone_sink_open_circuits_{}", kind.name()),
+                metrics.sink_open_circuits(kind),
+                &timestamp,
+            ).await?;
+        }
+
+        // Report the health of the gRPC subscriptions, only the status codes seen so far
+        for code in (0..GRPC_CODES as i32).map(Code::from_i32) {
+            if metrics.stream_errors(code) == 0 {
+                continue;
+            }
+            self.send_metric(
+                &format!("yellowstone_stream_errors_{}", code_name(code)),
+                delta(metrics.stream_errors(code), &self.last_stream_errors[code as usize]),
+                &timestamp,
+            ).await?;
+        }
+        self.send_metric(
+            "yellowstone_stream_closed",
+            delta(metrics.stream_closed(), &self.last_stream_closed),
+            &timestamp,
+        ).await?;
+        self.send_metric(
+            "yellowstone_reconnect_attempts",
+            delta(metrics.reconnect_attempts(), &self.last_reconnect_attempts),
+            &timestamp,
+        ).await?;
+        let reconnects_delta = delta(metrics.reconnect_successes(), &self.last_reconnect_successes);
+        let resubscribe_latency_delta = delta(metrics.resubscribe_latency_ms(), &self.last_resubscribe_latency_ms);
+        self.send_metric(
+            "yellowstone_reconnect_successes",
+            reconnects_delta,
+            &timestamp,
+        ).await?;
+        self.send_metric(
+            "yellowstone_resubscribe_latency_ms",
+            resubscribe_latency_delta.checked_div(reconnects_delta).unwrap_or(0),
+            &timestamp,
+        ).await?;
+
+        // Report the connection state of the streams, for orchestration to decide on restarts
+        for state in ConnectionState::ALL {
+            self.send_metric(
+                &format!("yellowstone_streams_{}", state.name()),
+                metrics.streams_in_state(state),
+                &timestamp,
+            ).await?;
+        }
+        self.send_metric(
+            "yellowstone_stream_state_transitions",
+            delta(metrics.state_transitions(), &self.last_state_transitions),
+            &timestamp,
+        ).await?;
+
+        // Report the bytes received from the provider, for quota accounting
+        self.send_metric(
+            "yellowstone_received_bytes",
+            delta(metrics.received_bytes(), &self.last_received_bytes),
+            &timestamp,
+        ).await?;
+
+        // Report updates dropped for their type, e.g. new variants after a provider upgrade
+        self.send_metric(
+            "yellowstone_unknown_updates",
+            delta(metrics.unknown_updates(), &self.last_unknown_updates),
+            &timestamp,
+        ).await?;
         
         Ok(())
     }
@@ -198,7 +844,27 @@ impl MetricsReporter {
                 "value": value
             }
         });
+        self.post(&payload).await
+    }
+
+    /// Send `yellowstone_build_info`, always 1, tagged with the running build
+    async fn send_build_info(&self, timestamp: &str) -> Result<(), reqwest::Error> {
+        let payload = json!({
+            "dt": timestamp,
+            "name": "yellowstone_build_info",
+            "gauge": {
+                "value": 1
+            },
+            "tags": {
+                "version": build_info::VERSION,
+                "commit": build_info::GIT_SHA,
+                "built_at": build_info::BUILD_TIMESTAMP,
+            }
+        });
+        self.post(&payload).await
+    }
 
+    async fn post(&self, payload: &Value) -> Result<(), reqwest::Error> {
         let mut headers = header::HeaderMap::new();
         let auth_value = format!("Bearer {}", self.config.api_token);
         headers.insert(
@@ -209,7 +875,7 @@ impl MetricsReporter {
         self.client
             .post(&self.config.endpoint)
             .headers(headers)
-            .json(&payload)
+            .json(payload)
             .send()
             .await?;
 
@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+use crate::{
+    codec,
+    config::{YellowstoneGrpcConfig, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    derived::DerivedStream,
+    instructions::{self, ProgramInstruction},
+    outbox::Outbox,
+    sinks::SinkConnector,
+};
+
+/// Pubkey at `offset` of instruction data
+fn pubkey_at(data: &[u8], offset: usize) -> Option<String> {
+    data.get(offset..offset + 32).map(codec::encode_bs58)
+}
+
+/// `COption<Pubkey>` at `offset` of instruction data, a tag byte then the pubkey when set
+fn optional_pubkey_at(data: &[u8], offset: usize) -> Option<String> {
+    match data.get(offset)? {
+        1 => pubkey_at(data, offset + 1),
+        _ => None,
+    }
+}
+
+/// Event of a token instruction creating a mint or a token account, from the instruction layout
+/// shared by the Token and Token-2022 programs
+fn creation_event(instruction: &ProgramInstruction) -> Option<Value> {
+    let (tag, data) = instruction.data.split_first()?;
+    let event = match tag {
+        // InitializeMint, InitializeMint2
+        0 | 20 => json!({
+            "event": "new_mint",
+            "mint": instruction.account(0),
+            "decimals": data.first()?,
+            "mintAuthority": pubkey_at(data, 1)?,
+            "freezeAuthority": optional_pubkey_at(data, 33),
+        }),
+        // InitializeAccount
+        1 => json!({
+            "event": "new_token_account",
+            "account": instruction.account(0),
+            "mint": instruction.account(1),
+            "owner": instruction.account(2),
+        }),
+        // InitializeAccount2, InitializeAccount3: the owner is in the data
+        16 | 18 => json!({
+            "event": "new_token_account",
+            "account": instruction.account(0),
+            "mint": instruction.account(1),
+            "owner": pubkey_at(data, 0)?,
+        }),
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// Mints and token accounts created by the top-level and inner Token and Token-2022
+/// instructions of successful transactions, for token discovery
+pub struct MintEvents {
+    stream: DerivedStream,
+    token_programs: Vec<(Vec<u8>, &'static str)>,
+}
+
+impl MintEvents {
+    /// Publish the token creations of a transaction, or hold them in `outbox` until its record is
+    /// acknowledged
+    pub async fn observe(&self, update: &SubscribeUpdateTransaction, outbox: Option<&Outbox>) {
+        let signature = update.transaction.as_ref().map(|info| codec::encode_bs58(&info.signature)).unwrap_or_default();
+        for (program, program_id) in &self.token_programs {
+            for instruction in instructions::program_instructions(update, program) {
+                let Some(mut event) = creation_event(&instruction) else {
+                    continue;
+                };
+
+                event["program"] = json!(program_id);
+                event["signature"] = json!(signature);
+                event["slot"] = json!(update.slot);
+                event["cpi"] = json!(instruction.cpi);
+                // Keyed by mint, so the accounts of a mint follow its creation
+                let key = event["mint"].as_str().unwrap_or(&signature).to_owned();
+                self.stream.emit(outbox, &signature, key, event).await;
+            }
+        }
+    }
+
+    /// Flush the token creations, at shutdown
+    pub async fn finish(&self) {
+        self.stream.flush().await;
+    }
+}
+
+/// Connect the token creation topic, `suffix` is appended to it in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<MintEvents>> {
+    let Some(topic) = config.get_mint_events_topic() else {
+        return Ok(None);
+    };
+
+    let token_programs = [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID]
+        .into_iter()
+        .map(|program_id| Ok((Pubkey::from_str(program_id)?.to_bytes().to_vec(), program_id)))
+        .collect::<anyhow::Result<_>>()?;
+    Ok(Some(MintEvents {
+        stream: DerivedStream::connect(sinks, config, "mint and token account creations", &topic, suffix).await?,
+        token_programs,
+    }))
+}
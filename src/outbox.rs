@@ -0,0 +1,189 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use futures::FutureExt;
+use log::warn;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{derived::DerivedStream, publisher::Delivery};
+
+/// Derived event outbox settings
+#[derive(Debug, Clone)]
+pub struct OutboxConfig {
+    /// Transactions whose derived events wait for the acknowledgement of their record
+    pub queue_size: usize,
+    /// Slots after which held events whose transaction is still unpublished are dropped
+    pub max_held_slots: u64,
+}
+
+/// Derived event held until its parent transaction record is acknowledged
+struct Held {
+    stream: DerivedStream,
+    key: String,
+    record: Value,
+}
+
+/// Events held for a transaction, with its slot
+struct Parent {
+    slot: u64,
+    events: Vec<Held>,
+}
+
+/// Held events by transaction signature
+#[derive(Default)]
+struct Pending {
+    parents: HashMap<String, Parent>,
+    /// Newest slot held
+    newest: u64,
+}
+
+impl Pending {
+    /// Drop the events of transactions older than `cutoff`, returning how many were dropped
+    fn expire(&mut self, cutoff: u64) -> usize {
+        let mut dropped = 0;
+        self.parents.retain(|_, parent| {
+            let keep = parent.slot >= cutoff;
+            if !keep {
+                dropped += parent.events.len();
+            }
+            keep
+        });
+        dropped
+    }
+}
+
+enum Command {
+    Release {
+        parent: Delivery,
+        held: Vec<Held>,
+        ack: oneshot::Sender<anyhow::Result<()>>,
+    },
+    Drain(oneshot::Sender<()>),
+}
+
+/// Holds the events derived from a transaction (SOL transfers, stake events) until the
+/// transaction record is acknowledged on the main topic, so consumers never see a derived event
+/// without its parent. None of the sinks commits records to several topics atomically, the
+/// outbox orders them instead: released events are published in the order of their parents,
+/// events of a parent that failed or was filtered out are dropped, and so are events whose
+/// parent is still unpublished `max_held_slots` later. Held events are only kept in memory: they
+/// are lost on a crash, and replayed with their transaction when the stream resumes from the
+/// checkpoint.
+pub struct Outbox {
+    held: Mutex<Pending>,
+    relay: mpsc::Sender<Command>,
+    max_held_slots: u64,
+}
+
+impl Outbox {
+    pub fn new(config: OutboxConfig) -> Self {
+        let (relay, commands) = mpsc::channel(config.queue_size);
+        tokio::spawn(run_relay(commands));
+        Self {
+            held: Mutex::new(Pending::default()),
+            relay,
+            max_held_slots: config.max_held_slots,
+        }
+    }
+
+    /// Hold an event derived from the transaction `parent` (its signature) of `slot` for `stream`
+    pub fn hold(&self, parent: &str, slot: u64, stream: &DerivedStream, key: String, record: Value) {
+        let mut pending = self.held.lock().expect("outbox lock");
+        if slot > pending.newest {
+            pending.newest = slot;
+            let dropped = pending.expire(slot.saturating_sub(self.max_held_slots));
+            if dropped > 0 {
+                warn!(
+                    "Dropping {} derived events whose transaction was not published within {} slots",
+                    dropped, self.max_held_slots
+                );
+            }
+        }
+        let parent = pending.parents.entry(parent.to_owned()).or_insert_with(|| Parent {
+            slot,
+            events: Vec::new(),
+        });
+        parent.events.push(Held {
+            stream: stream.clone(),
+            key,
+            record,
+        });
+    }
+
+    /// Drop the events of a transaction that is not published
+    pub fn discard(&self, parent: &str) {
+        self.held.lock().expect("outbox lock").parents.remove(parent);
+    }
+
+    /// Queue the events of `parent` behind the delivery of its record, returning the delivery
+    /// to await in its place
+    pub async fn release(&self, parent: &str, delivery: Delivery) -> Delivery {
+        let Some(Parent { events: held, .. }) = self.held.lock().expect("outbox lock").parents.remove(parent) else {
+            return delivery;
+        };
+        let (ack, acked) = oneshot::channel();
+        let command = Command::Release {
+            parent: delivery,
+            held,
+            ack,
+        };
+        if self.relay.send(command).await.is_err() {
+            return futures::future::ready(Err(anyhow::anyhow!("outbox relay stopped"))).boxed();
+        }
+        async move { acked.await? }.boxed()
+    }
+
+    /// Wait until the released events are published, at shutdown
+    pub async fn finish(&self) {
+        let (done, drained) = oneshot::channel();
+        if self.relay.send(Command::Drain(done)).await.is_ok() {
+            let _ = drained.await;
+        }
+        let orphans: usize =
+            self.held.lock().expect("outbox lock").parents.values().map(|parent| parent.events.len()).sum();
+        if orphans > 0 {
+            warn!("Dropping {} derived events whose transaction was never published", orphans);
+        }
+    }
+}
+
+/// Publish the events of each released transaction once its record is acknowledged, in release
+/// order
+async fn run_relay(mut commands: mpsc::Receiver<Command>) {
+    while let Some(command) = commands.recv().await {
+        match command {
+            Command::Release { parent, held, ack } => {
+                let result = parent.await;
+                match &result {
+                    Ok(()) => {
+                        for Held { stream, key, record } in held {
+                            stream.send(key, &record).await;
+                        }
+                    },
+                    Err(e) => warn!("Dropping {} derived events, their transaction record failed: {:?}", held.len(), e),
+                }
+                let _ = ack.send(result);
+            },
+            Command::Drain(done) => {
+                let _ = done.send(());
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_transactions_by_slot() {
+        let mut pending = Pending::default();
+        for (signature, slot) in [("old", 10), ("cutoff", 20), ("new", 30)] {
+            pending.parents.insert(signature.to_string(), Parent { slot, events: Vec::new() });
+        }
+        pending.expire(20);
+        let mut kept: Vec<_> = pending.parents.keys().map(String::as_str).collect();
+        kept.sort_unstable();
+        assert_eq!(kept, ["cutoff", "new"]);
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Instant,
+};
+
+use futures::FutureExt;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::watch;
+
+use crate::sinks::Delivery;
+
+/// What a pause holds back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PauseMode {
+    /// Keep reading and processing the stream, dropping the records instead of publishing them.
+    /// The checkpoint stays before the first dropped slot until restart, which replays them
+    #[default]
+    Publishing,
+    /// Stop reading the subscriptions, updates queue up on the connections until resumed
+    Subscription,
+}
+
+struct Pause {
+    mode: watch::Sender<Option<PauseMode>>,
+    since: Mutex<Option<Instant>>,
+    // Records dropped since the pause started
+    dropped: AtomicU64,
+    // Oldest slot with a dropped record since startup
+    held: Mutex<Option<u64>>,
+}
+
+static PAUSE: OnceLock<Pause> = OnceLock::new();
+
+fn state() -> &'static Pause {
+    PAUSE.get_or_init(|| Pause {
+        mode: watch::channel(None).0,
+        since: Mutex::new(None),
+        dropped: AtomicU64::new(0),
+        held: Mutex::new(None),
+    })
+}
+
+/// Hold publishing or the subscriptions until `resume`, without closing the connections
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+pub fn pause(mode: PauseMode) {
+    let state = state();
+    let previous = state.mode.send_replace(Some(mode));
+    if previous.is_none() {
+        *state.since.lock().expect("pause lock") = Some(Instant::now());
+        state.dropped.store(0, Ordering::Relaxed);
+    }
+    info!("Paused {:?}", mode);
+}
+
+/// Resume publishing and the subscriptions, `false` if they were not paused
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+pub fn resume() -> bool {
+    let state = state();
+    let Some(mode) = state.mode.send_replace(None) else {
+        return false;
+    };
+    let since = state.since.lock().expect("pause lock").take();
+    info!(
+        "Resumed {:?} after {}s, {} records dropped",
+        mode,
+        since.map_or(0, |since| since.elapsed().as_secs()),
+        state.dropped.load(Ordering::Relaxed)
+    );
+    true
+}
+
+/// Current pause, for `/status` and the admin endpoints
+#[cfg_attr(not(feature = "admin"), allow(dead_code))]
+pub fn report() -> Value {
+    let state = state();
+    let mode = *state.mode.borrow();
+    let since = *state.since.lock().expect("pause lock");
+    json!({
+        "paused": mode.is_some(),
+        "mode": mode,
+        "pausedSecs": since.map(|since| since.elapsed().as_secs()),
+        "droppedRecords": state.dropped.load(Ordering::Relaxed),
+        "checkpointHeldAt": *state.held.lock().expect("pause lock"),
+    })
+}
+
+/// Whether the subscriptions are paused
+pub fn subscription_paused() -> bool {
+    *state().mode.borrow() == Some(PauseMode::Subscription)
+}
+
+/// Resolves once the subscriptions are not paused
+pub async fn subscription_resumed() {
+    let mut mode = state().mode.subscribe();
+    let _ = mode.wait_for(|mode| *mode != Some(PauseMode::Subscription)).await;
+}
+
+/// Acknowledgement of a record of `slot` dropped while publishing is paused, `None` when not
+/// paused
+pub fn dropped(slot: Option<u64>) -> Option<Delivery> {
+    let state = state();
+    if *state.mode.borrow() != Some(PauseMode::Publishing) {
+        return None;
+    }
+    state.dropped.fetch_add(1, Ordering::Relaxed);
+    if let Some(slot) = slot {
+        let mut held = state.held.lock().expect("pause lock");
+        *held = Some(held.map_or(slot, |held| held.min(slot)));
+    }
+    Some(futures::future::ready(Ok(())).boxed())
+}
+
+/// Oldest slot with a record dropped by a publishing pause: the checkpoint stays before it until
+/// restart, so the dropped records are replayed then rather than lost
+pub fn held_slot() -> Option<u64> {
+    *state().held.lock().expect("pause lock")
+}
@@ -0,0 +1,59 @@
+use std::process::Command;
+
+/// Wait for a shutdown request, returning the reason recorded in the `shutdown` control record.
+///
+/// Besides Ctrl-C this handles SIGTERM on Unix, sent by container runtimes, and Ctrl-Break or a
+/// closed console on Windows.
+pub async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "interrupted",
+                _ = terminate.recv() => "terminated",
+            },
+            Err(e) => {
+                log::warn!("Failed to listen for SIGTERM, only Ctrl-C stops the streamer: {:?}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                "interrupted"
+            },
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        use tokio::signal::windows::{ctrl_break, ctrl_close};
+
+        match (ctrl_break(), ctrl_close()) {
+            (Ok(mut ctrl_break), Ok(mut ctrl_close)) => tokio::select! {
+                _ = tokio::signal::ctrl_c() => "interrupted",
+                _ = ctrl_break.recv() => "interrupted",
+                _ = ctrl_close.recv() => "terminated",
+            },
+            _ => {
+                let _ = tokio::signal::ctrl_c().await;
+                "interrupted"
+            },
+        }
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "interrupted"
+    }
+}
+
+/// Command running `command` through the platform shell, `sh -c` or `cmd /C` on Windows
+pub fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    let (shell, flag) = ("cmd", "/C");
+    #[cfg(not(windows))]
+    let (shell, flag) = ("sh", "-c");
+
+    let mut shell = Command::new(shell);
+    shell.arg(flag).arg(command);
+    shell
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::RwLock,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use log::warn;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+/// Magic number at the start of legacy Pyth price accounts
+const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+/// Legacy Pyth account type of price accounts
+const PYTH_PRICE_ACCOUNT: u32 = 3;
+
+/// USD price of a token
+#[derive(Debug, Clone, Copy)]
+pub struct Price {
+    pub usd: f64,
+    /// Unix timestamp the price was published at
+    pub published_at: i64,
+}
+
+/// Source of USD prices by token mint
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    /// Latest known price of a mint
+    async fn price(&self, mint: &str) -> Option<Price>;
+
+    /// Account update seen in the stream, for sources reading on-chain price accounts
+    fn observe_account(&self, _pubkey: &str, _data: &[u8]) {}
+}
+
+/// Prices decoded from Pyth price account updates already in the stream
+pub struct PythPriceSource {
+    /// Price account to the mint it prices
+    feeds: HashMap<String, String>,
+    prices: RwLock<HashMap<String, Price>>,
+    price_update_discriminator: [u8; 8],
+}
+
+impl PythPriceSource {
+    /// `feeds` maps mints to their Pyth price account
+    pub fn new(feeds: &HashMap<String, String>) -> Self {
+        let digest = Sha256::digest(b"account:PriceUpdateV2");
+        let mut price_update_discriminator = [0; 8];
+        price_update_discriminator.copy_from_slice(&digest[..8]);
+
+        Self {
+            feeds: feeds.iter().map(|(mint, account)| (account.clone(), mint.clone())).collect(),
+            prices: RwLock::new(HashMap::new()),
+            price_update_discriminator,
+        }
+    }
+
+    fn decode(&self, data: &[u8]) -> Option<Price> {
+        if read_u32(data, 0) == Some(PYTH_MAGIC) {
+            return decode_legacy_price(data);
+        }
+        if data.get(..8) == Some(&self.price_update_discriminator[..]) {
+            return decode_price_update(data);
+        }
+        None
+    }
+}
+
+#[async_trait]
+impl PriceSource for PythPriceSource {
+    async fn price(&self, mint: &str) -> Option<Price> {
+        self.prices.read().expect("pyth prices lock").get(mint).copied()
+    }
+
+    fn observe_account(&self, pubkey: &str, data: &[u8]) {
+        let Some(mint) = self.feeds.get(pubkey) else {
+            return;
+        };
+        match self.decode(data) {
+            Some(price) => {
+                self.prices.write().expect("pyth prices lock").insert(mint.clone(), price);
+            },
+            None => warn!("Failed to decode Pyth price account {}", pubkey),
+        }
+    }
+}
+
+/// Legacy push oracle price account, aggregate price and its publish time
+fn decode_legacy_price(data: &[u8]) -> Option<Price> {
+    if read_u32(data, 8)? != PYTH_PRICE_ACCOUNT {
+        return None;
+    }
+    let exponent = read_i32(data, 20)?;
+    let published_at = read_i64(data, 96)?;
+    let price = read_i64(data, 208)?;
+    // Only aggregate prices with the `trading` status are meaningful
+    if read_u32(data, 224)? != 1 {
+        return None;
+    }
+
+    Some(Price {
+        usd: price as f64 * 10f64.powi(exponent),
+        published_at,
+    })
+}
+
+/// Pull oracle `PriceUpdateV2` account written by the Pyth receiver program
+fn decode_price_update(data: &[u8]) -> Option<Price> {
+    // Discriminator, write authority, then a `Partial { num_signatures }` or `Full` verification level
+    let offset = match data.get(40)? {
+        0 => 42,
+        1 => 41,
+        _ => return None,
+    };
+    // Price feed message: feed id, price, confidence, exponent, publish time
+    let price = read_i64(data, offset + 32)?;
+    let exponent = read_i32(data, offset + 48)?;
+    let published_at = read_i64(data, offset + 52)?;
+
+    Some(Price {
+        usd: price as f64 * 10f64.powi(exponent),
+        published_at,
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_i32(data: &[u8], offset: usize) -> Option<i32> {
+    Some(i32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_i64(data: &[u8], offset: usize) -> Option<i64> {
+    Some(i64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+/// Response of the HTTP oracle
+#[derive(Debug, Deserialize)]
+struct HttpPrice {
+    price: f64,
+    /// Unix timestamp, the fetch time when omitted
+    timestamp: Option<i64>,
+}
+
+/// Prices fetched from an HTTP oracle answering `{"price": 1.23, "timestamp": 1718000000}`
+pub struct HttpPriceSource {
+    client: Client,
+    /// URL with a `{mint}` placeholder
+    url: String,
+    refresh: Duration,
+    cache: Mutex<HashMap<String, (Instant, Option<Price>)>>,
+}
+
+impl HttpPriceSource {
+    pub fn new(url: String, refresh: Duration) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self {
+            client,
+            url,
+            refresh,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn fetch(&self, mint: &str) -> anyhow::Result<Price> {
+        let response: HttpPrice = self
+            .client
+            .get(self.url.replace("{mint}", mint))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(Price {
+            usd: response.price,
+            published_at: response.timestamp.unwrap_or_else(unix_now),
+        })
+    }
+}
+
+#[async_trait]
+impl PriceSource for HttpPriceSource {
+    async fn price(&self, mint: &str) -> Option<Price> {
+        let mut cache = self.cache.lock().await;
+        if let Some((fetched_at, price)) = cache.get(mint) {
+            if fetched_at.elapsed() < self.refresh {
+                return *price;
+            }
+        }
+
+        // Failures are cached too so an unknown mint is not fetched on every transaction
+        let price = self
+            .fetch(mint)
+            .await
+            .map_err(|e| warn!("Failed to fetch price of {}: {:?}", mint, e))
+            .ok();
+        cache.insert(mint.to_string(), (Instant::now(), price));
+        price
+    }
+}
+
+/// Attaches USD values to the token balance changes of transactions.
+///
+/// Sources are asked in order, prices older than `max_age` are treated as unknown.
+pub struct PriceOracle {
+    sources: Vec<Box<dyn PriceSource>>,
+    max_age: Duration,
+}
+
+impl PriceOracle {
+    pub fn new(sources: Vec<Box<dyn PriceSource>>, max_age: Duration) -> Self {
+        Self { sources, max_age }
+    }
+
+    /// Feed an account update to the price sources
+    pub fn observe_account(&self, pubkey: &str, data: &[u8]) {
+        for source in &self.sources {
+            source.observe_account(pubkey, data);
+        }
+    }
+
+    /// Fresh price of a mint from the first source that has one
+    async fn price(&self, mint: &str) -> Option<Price> {
+        let now = unix_now();
+        for source in &self.sources {
+            if let Some(price) = source.price(mint).await {
+                if now.saturating_sub(price.published_at) <= self.max_age.as_secs() as i64 {
+                    return Some(price);
+                }
+            }
+        }
+        None
+    }
+
+    /// Add `usdValues` with the USD value of each owner's token balance change, left out for
+    /// transactions without token balance changes
+    pub async fn enrich(&self, tx: &mut Value) {
+        let changes = token_balance_changes(&tx["meta"]);
+        if changes.is_empty() {
+            return;
+        }
+
+        let mut values = Vec::with_capacity(changes.len());
+        for ((owner, mint), amount) in changes {
+            let price = self.price(&mint).await;
+            values.push(json!({
+                "owner": owner,
+                "mint": mint,
+                "amount": amount,
+                "priceUsd": price.map(|price| price.usd),
+                "valueUsd": price.map(|price| price.usd * amount),
+                "pricePublishedAt": price.map(|price| price.published_at),
+            }));
+        }
+        tx["usdValues"] = Value::Array(values);
+    }
+}
+
+/// Net UI amount change per owner and mint from `preTokenBalances` / `postTokenBalances`
+fn token_balance_changes(meta: &Value) -> BTreeMap<(String, String), f64> {
+    let mut changes = BTreeMap::new();
+    for (balances, sign) in [(&meta["preTokenBalances"], -1.0), (&meta["postTokenBalances"], 1.0)] {
+        for balance in balances.as_array().into_iter().flatten() {
+            let (Some(owner), Some(mint)) = (balance["owner"].as_str(), balance["mint"].as_str()) else {
+                continue;
+            };
+            let amount = balance["uiTokenAmount"]["uiAmountString"]
+                .as_str()
+                .and_then(|amount| amount.parse::<f64>().ok())
+                .unwrap_or(0.0);
+            *changes.entry((owner.to_string(), mint.to_string())).or_insert(0.0) += sign * amount;
+        }
+    }
+    changes.retain(|_, amount| *amount != 0.0);
+    changes
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,93 @@
+use solana_sdk::compute_budget;
+use yellowstone_grpc_proto::prelude::{Message, SubscribeUpdateTransaction};
+
+/// Compute units requested per instruction without a `SetComputeUnitLimit` instruction
+const DEFAULT_INSTRUCTION_COMPUTE_UNITS: u64 = 200_000;
+/// Highest compute unit limit of a transaction
+const MAX_COMPUTE_UNITS: u64 = 1_400_000;
+
+/// Compute unit price (micro-lamports per compute unit) and limit requested by a transaction's
+/// compute budget instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComputeBudget {
+    pub compute_unit_price: u64,
+    pub compute_unit_limit: u64,
+}
+
+impl ComputeBudget {
+    /// Read the compute budget instructions of a message, as the runtime does
+    pub fn from_message(message: &Message) -> Self {
+        let program = compute_budget::id().to_bytes();
+        let mut compute_unit_price = None;
+        let mut compute_unit_limit = None;
+        let mut other_instructions = 0;
+
+        for instruction in &message.instructions {
+            let is_compute_budget = message
+                .account_keys
+                .get(instruction.program_id_index as usize)
+                .is_some_and(|key| key.as_slice() == program);
+            if !is_compute_budget {
+                other_instructions += 1;
+                continue;
+            }
+            // Borsh encoded: a one byte discriminant followed by the little-endian value
+            match instruction.data.split_first() {
+                Some((2, value)) if value.len() >= 4 => {
+                    compute_unit_limit = Some(u32::from_le_bytes(value[..4].try_into().unwrap()) as u64);
+                },
+                Some((3, value)) if value.len() >= 8 => {
+                    compute_unit_price = Some(u64::from_le_bytes(value[..8].try_into().unwrap()));
+                },
+                _ => {},
+            }
+        }
+
+        Self {
+            compute_unit_price: compute_unit_price.unwrap_or(0),
+            compute_unit_limit: compute_unit_limit
+                .unwrap_or(other_instructions * DEFAULT_INSTRUCTION_COMPUTE_UNITS)
+                .min(MAX_COMPUTE_UNITS),
+        }
+    }
+
+    /// Priority fee in lamports, the price of the requested compute units rounded up
+    pub fn priority_fee(&self) -> u64 {
+        let micro_lamports = self.compute_unit_price as u128 * self.compute_unit_limit as u128;
+        micro_lamports.div_ceil(1_000_000).min(u64::MAX as u128) as u64
+    }
+}
+
+/// Keeps the transactions paying at least the configured compute unit price and/or priority fee.
+///
+/// Both are computed from the compute budget instructions, transactions without them pay no
+/// priority fee. When both thresholds are set a transaction must reach both.
+pub struct PriorityFeeFilter {
+    min_compute_unit_price: Option<u64>,
+    min_priority_fee: Option<u64>,
+}
+
+impl PriorityFeeFilter {
+    pub fn new(min_compute_unit_price: Option<u64>, min_priority_fee: Option<u64>) -> Self {
+        Self {
+            min_compute_unit_price,
+            min_priority_fee,
+        }
+    }
+
+    /// Whether the transaction reaches the thresholds
+    pub fn matches(&self, update: &SubscribeUpdateTransaction) -> bool {
+        let Some(message) = update
+            .transaction
+            .as_ref()
+            .and_then(|info| info.transaction.as_ref())
+            .and_then(|transaction| transaction.message.as_ref())
+        else {
+            return false;
+        };
+
+        let budget = ComputeBudget::from_message(message);
+        self.min_compute_unit_price.map_or(true, |min| budget.compute_unit_price >= min)
+            && self.min_priority_fee.map_or(true, |min| budget.priority_fee() >= min)
+    }
+}
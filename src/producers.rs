@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use crate::{rpc::RpcClient, EPOCH_SIZE};
+
+/// Wait before retrying after the leader schedule or validator set could not be fetched
+const RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Stake and on-chain info of a validator identity
+#[derive(Debug, Clone)]
+struct ValidatorInfo {
+    vote_pubkey: String,
+    stake: u64,
+    commission: u8,
+    name: Option<String>,
+}
+
+/// Leader schedule and validator set of one epoch
+struct EpochProducers {
+    epoch: u64,
+    // Leader identity per slot index within the epoch
+    leaders: Vec<Option<Arc<str>>>,
+    validators: HashMap<String, ValidatorInfo>,
+}
+
+/// Adds the block producer identity and validator info to block records.
+///
+/// The leader schedule and the validator set are fetched over JSON-RPC once per epoch.
+pub struct ProducerEnricher {
+    rpc: RpcClient,
+    cache: Mutex<Option<EpochProducers>>,
+    last_failure: Mutex<Option<Instant>>,
+}
+
+impl ProducerEnricher {
+    pub fn new(rpc: RpcClient) -> Self {
+        Self {
+            rpc,
+            cache: Mutex::new(None),
+            last_failure: Mutex::new(None),
+        }
+    }
+
+    /// Add `producer` to a block record, left out when the producer is unknown
+    pub async fn enrich(&self, record: &mut Value, slot: u64) {
+        let epoch = slot / EPOCH_SIZE;
+        let mut cache = self.cache.lock().await;
+
+        if cache.as_ref().map(|producers| producers.epoch) != Some(epoch) {
+            let mut last_failure = self.last_failure.lock().await;
+            if last_failure.is_some_and(|failed_at| failed_at.elapsed() < RETRY_AFTER) {
+                return;
+            }
+            match self.fetch(epoch).await {
+                Ok(producers) => {
+                    *cache = Some(producers);
+                    *last_failure = None;
+                },
+                Err(e) => {
+                    warn!("Failed to fetch block producers for epoch {}: {:?}", epoch, e);
+                    *last_failure = Some(Instant::now());
+                    return;
+                },
+            }
+        }
+
+        let Some(producers) = cache.as_ref() else {
+            return;
+        };
+        let index = (slot - epoch * EPOCH_SIZE) as usize;
+        let Some(identity) = producers.leaders.get(index).cloned().flatten() else {
+            return;
+        };
+
+        let validator = producers.validators.get(identity.as_ref());
+        record["producer"] = json!({
+            "identity": identity.as_ref(),
+            "votePubkey": validator.map(|validator| &validator.vote_pubkey),
+            "name": validator.and_then(|validator| validator.name.as_ref()),
+            "stake": validator.map(|validator| validator.stake),
+            "commission": validator.map(|validator| validator.commission),
+        });
+    }
+
+    async fn fetch(&self, epoch: u64) -> anyhow::Result<EpochProducers> {
+        let schedule = self.rpc.get_leader_schedule(epoch * EPOCH_SIZE).await?;
+        let mut leaders = vec![None; EPOCH_SIZE as usize];
+        for (identity, slots) in schedule {
+            let identity: Arc<str> = identity.into();
+            for index in slots {
+                if let Some(leader) = leaders.get_mut(index as usize) {
+                    *leader = Some(Arc::clone(&identity));
+                }
+            }
+        }
+
+        let vote_accounts = self.rpc.get_vote_accounts().await?;
+        // Names are optional, a validator without published info still gets stake context
+        let names = self.rpc.get_validator_names().await.unwrap_or_else(|e| {
+            warn!("Failed to fetch validator names: {:?}", e);
+            HashMap::new()
+        });
+        let validators: HashMap<String, ValidatorInfo> = vote_accounts
+            .current
+            .into_iter()
+            .chain(vote_accounts.delinquent)
+            .map(|account| {
+                let info = ValidatorInfo {
+                    vote_pubkey: account.vote_pubkey,
+                    stake: account.activated_stake,
+                    commission: account.commission,
+                    name: names.get(&account.node_pubkey).cloned(),
+                };
+                (account.node_pubkey, info)
+            })
+            .collect();
+
+        info!("Loaded leader schedule and {} validators for epoch {}", validators.len(), epoch);
+        Ok(EpochProducers {
+            epoch,
+            leaders,
+            validators,
+        })
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    fmt::Write as _,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::Instant,
+};
+
+use log::{info, warn};
+
+/// Timings kept per stage, later samples overwrite earlier ones past it
+const MAX_SAMPLES: usize = 100_000;
+
+/// Pipeline stages timed in profiling mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Protobuf transactions converted to Solana types, part of `Format`
+    Decode,
+    /// Records built from the updates
+    Format,
+    /// Records encoded into payloads
+    Serialize,
+    /// Payloads compressed, encrypted and handed to the sinks
+    Produce,
+}
+
+impl Stage {
+    const ALL: [Stage; 4] = [Stage::Decode, Stage::Format, Stage::Serialize, Stage::Produce];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Decode => "decode",
+            Stage::Format => "format",
+            Stage::Serialize => "serialize",
+            Stage::Produce => "produce",
+        }
+    }
+
+    /// Stack of the stage in the folded output
+    fn stack(self) -> &'static str {
+        match self {
+            Stage::Decode => "pipeline;format;decode",
+            Stage::Format => "pipeline;format",
+            Stage::Serialize => "pipeline;serialize",
+            Stage::Produce => "pipeline;produce",
+        }
+    }
+}
+
+/// Profiling settings
+#[derive(Debug, Clone)]
+pub struct ProfilingConfig {
+    /// One in `sample_rate` runs of each stage is timed
+    pub sample_rate: u64,
+    /// File the folded stacks are written to on shutdown
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct StageTimings {
+    runs: AtomicU64,
+    sampled: AtomicU64,
+    total_us: AtomicU64,
+    samples_us: Mutex<Vec<u64>>,
+}
+
+impl StageTimings {
+    fn record(&self, micros: u64) {
+        let sampled = self.sampled.fetch_add(1, Ordering::Relaxed) as usize;
+        self.total_us.fetch_add(micros, Ordering::Relaxed);
+        let mut samples = self.samples_us.lock().expect("profiler lock");
+        if samples.len() < MAX_SAMPLES {
+            samples.push(micros);
+        } else {
+            samples[sampled % MAX_SAMPLES] = micros;
+        }
+    }
+
+    /// Time spent in the stage over every run, extrapolated from the sampled ones
+    fn estimated_total_us(&self) -> u64 {
+        let sampled = self.sampled.load(Ordering::Relaxed);
+        let total = self.total_us.load(Ordering::Relaxed) as u128;
+        (total * self.runs.load(Ordering::Relaxed) as u128).checked_div(sampled as u128).unwrap_or(0) as u64
+    }
+}
+
+struct Profiler {
+    config: ProfilingConfig,
+    stages: [StageTimings; Stage::ALL.len()],
+}
+
+static PROFILER: OnceLock<Profiler> = OnceLock::new();
+
+/// Reports the profile when dropped, on shutdown
+pub struct ProfileReport;
+
+impl Drop for ProfileReport {
+    fn drop(&mut self) {
+        report();
+    }
+}
+
+/// Enable profiling, returning the guard reporting it on shutdown
+pub fn install(config: ProfilingConfig) -> ProfileReport {
+    info!("Profiling mode enabled, timing one in {} runs of each stage", config.sample_rate);
+    let _ = PROFILER.set(Profiler {
+        config,
+        stages: Default::default(),
+    });
+    ProfileReport
+}
+
+/// Times a stage until dropped
+pub struct Span {
+    stage: Stage,
+    started: Instant,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(profiler) = PROFILER.get() {
+            profiler.stages[self.stage.index()].record(self.started.elapsed().as_micros() as u64);
+        }
+    }
+}
+
+/// Start timing a run of `stage`, `None` when profiling is off or the run is not sampled
+pub fn span(stage: Stage) -> Option<Span> {
+    let profiler = PROFILER.get()?;
+    let run = profiler.stages[stage.index()].runs.fetch_add(1, Ordering::Relaxed);
+    (run % profiler.config.sample_rate == 0).then(|| Span {
+        stage,
+        started: Instant::now(),
+    })
+}
+
+/// Log the timing summary of each stage and write the folded stacks
+fn report() {
+    let Some(profiler) = PROFILER.get() else {
+        return;
+    };
+    let timings = |stage: Stage| &profiler.stages[stage.index()];
+    let mut folded = String::new();
+    for stage in Stage::ALL {
+        let mut samples = timings(stage).samples_us.lock().expect("profiler lock").clone();
+        if samples.is_empty() {
+            continue;
+        }
+        samples.sort_unstable();
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+        let sampled = timings(stage).sampled.load(Ordering::Relaxed);
+        info!(
+            "Profile {:<9} runs {:>10}, sampled {:>8}, mean {:>7}us, p50 {:>7}us, p99 {:>7}us, max {:>7}us",
+            stage.name(),
+            timings(stage).runs.load(Ordering::Relaxed),
+            sampled,
+            timings(stage).total_us.load(Ordering::Relaxed) / sampled.max(1),
+            percentile(0.5),
+            percentile(0.99),
+            samples[samples.len() - 1]
+        );
+
+        // Folded stacks count the time of a frame itself, without its children
+        let mut self_us = timings(stage).estimated_total_us();
+        if stage == Stage::Format {
+            self_us = self_us.saturating_sub(timings(Stage::Decode).estimated_total_us());
+        }
+        let _ = writeln!(folded, "{} {}", stage.stack(), self_us);
+    }
+
+    if let Some(path) = &profiler.config.output {
+        match std::fs::write(path, folded) {
+            Ok(()) => info!("Wrote the profile to {:?}, render it with `inferno-flamegraph` or `flamegraph.pl`", path),
+            Err(e) => warn!("Failed to write the profile to {:?}: {:?}", path, e),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::HashSet;
+
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+/// Keeps the transactions invoking one of `programs` from a top-level instruction.
+///
+/// Yellowstone's `account_include` also matches transactions that only reference a program or
+/// reach it through CPI, this filter runs on the streamed transactions to drop those. Program
+/// ids are always static account keys, so lookup tables do not need to be resolved.
+pub struct TopLevelProgramFilter {
+    programs: HashSet<Vec<u8>>,
+}
+
+impl TopLevelProgramFilter {
+    pub fn new(programs: &[String]) -> Self {
+        Self {
+            programs: programs
+                .iter()
+                .filter_map(|program| bs58::decode(program).into_vec().ok())
+                .collect(),
+        }
+    }
+
+    /// Whether a top-level instruction of the transaction invokes one of the programs
+    pub fn matches(&self, update: &SubscribeUpdateTransaction) -> bool {
+        let Some(message) = update
+            .transaction
+            .as_ref()
+            .and_then(|info| info.transaction.as_ref())
+            .and_then(|transaction| transaction.message.as_ref())
+        else {
+            return false;
+        };
+
+        message.instructions.iter().any(|instruction| {
+            message
+                .account_keys
+                .get(instruction.program_id_index as usize)
+                .is_some_and(|program| self.programs.contains(program))
+        })
+    }
+}
@@ -0,0 +1,182 @@
+use prost::Message;
+use serde_json::{Map, Value};
+
+use self::records::{
+    json_value::Kind, record::Payload, AccountClosed, JsonArray, JsonObject, JsonValue, NullValue, ProgramDeploy,
+    Record, SolTransfer, StakeEvent, TokenCreation, Vote,
+};
+
+/// Messages generated from `proto/records.proto`
+pub mod records {
+    include!(concat!(env!("OUT_DIR"), "/yellowstone_grpc_json.records.v1.rs"));
+}
+
+/// Fields the streamer stamps on every record, carried by the envelope
+const STAMPS: [&str; 3] = ["streamerVersion", "historical", "idempotencyKey"];
+
+impl From<&Value> for JsonValue {
+    fn from(value: &Value) -> Self {
+        let kind = match value {
+            Value::Null => Kind::Null(NullValue::NullValue.into()),
+            Value::Bool(value) => Kind::Bool(*value),
+            Value::Number(number) => match (number.as_u64(), number.as_i64()) {
+                (Some(value), _) => Kind::Unsigned(value),
+                (None, Some(value)) => Kind::Signed(value),
+                (None, None) => Kind::Float(number.as_f64().unwrap_or_default()),
+            },
+            Value::String(value) => Kind::String(value.clone()),
+            Value::Array(values) => Kind::Array(JsonArray {
+                values: values.iter().map(JsonValue::from).collect(),
+            }),
+            Value::Object(fields) => Kind::Object(JsonObject {
+                fields: fields.iter().map(|(name, value)| (name.clone(), JsonValue::from(value))).collect(),
+            }),
+        };
+        JsonValue { kind: Some(kind) }
+    }
+}
+
+/// Fields of a record not moved into its message yet
+struct Fields(Map<String, Value>);
+
+impl Fields {
+    /// Move out the field `name` if it converts, `null` fields are dropped
+    fn take<T>(&mut self, name: &str, convert: impl FnOnce(&Value) -> Option<T>) -> Option<T> {
+        let converted = match self.0.get(name)? {
+            Value::Null => None,
+            value => Some(convert(value)?),
+        };
+        self.0.remove(name);
+        converted
+    }
+
+    fn string(&mut self, name: &str) -> Option<String> {
+        self.take(name, |value| value.as_str().map(str::to_owned))
+    }
+
+    fn u64(&mut self, name: &str) -> Option<u64> {
+        self.take(name, Value::as_u64)
+    }
+
+    fn u32(&mut self, name: &str) -> Option<u32> {
+        self.take(name, |value| value.as_u64().and_then(|value| u32::try_from(value).ok()))
+    }
+
+    fn bool(&mut self, name: &str) -> bool {
+        self.take(name, Value::as_bool).unwrap_or_default()
+    }
+
+    fn has(&self, names: &[&str]) -> bool {
+        names.iter().all(|name| self.0.contains_key(*name))
+    }
+}
+
+/// Message of a derived event, told apart by its `event` or its fields
+fn derived_event(fields: &mut Fields) -> Option<Payload> {
+    let event = fields.0.get("event").and_then(Value::as_str).map(str::to_owned);
+    let payload = match event.as_deref() {
+        Some("account_closed") => {
+            fields.0.remove("event");
+            Payload::AccountClosed(AccountClosed {
+                reason: fields.string("reason").unwrap_or_default(),
+                pubkey: fields.string("pubkey").unwrap_or_default(),
+                owner: fields.string("owner").unwrap_or_default(),
+                previous_owner: fields.string("previousOwner"),
+                lamports: fields.u64("lamports").unwrap_or_default(),
+                slot: fields.u64("slot").unwrap_or_default(),
+                write_version: fields.u64("writeVersion").unwrap_or_default(),
+                signature: fields.string("signature"),
+            })
+        },
+        Some("delegate" | "deactivate" | "withdraw" | "split" | "merge") => Payload::StakeEvent(StakeEvent {
+            event: fields.string("event").unwrap_or_default(),
+            stake_account: fields.string("stakeAccount"),
+            vote_account: fields.string("voteAccount"),
+            authority: fields.string("authority"),
+            destination: fields.string("destination"),
+            lamports: fields.u64("lamports"),
+            signature: fields.string("signature").unwrap_or_default(),
+            slot: fields.u64("slot").unwrap_or_default(),
+            cpi: fields.bool("cpi"),
+        }),
+        Some("new_mint" | "new_token_account") => Payload::TokenCreation(TokenCreation {
+            event: fields.string("event").unwrap_or_default(),
+            program: fields.string("program").unwrap_or_default(),
+            mint: fields.string("mint"),
+            decimals: fields.u32("decimals"),
+            mint_authority: fields.string("mintAuthority"),
+            freeze_authority: fields.string("freezeAuthority"),
+            account: fields.string("account"),
+            owner: fields.string("owner"),
+            signature: fields.string("signature").unwrap_or_default(),
+            slot: fields.u64("slot").unwrap_or_default(),
+            cpi: fields.bool("cpi"),
+        }),
+        Some("deploy" | "upgrade" | "extend" | "set_authority" | "close") => Payload::ProgramDeploy(ProgramDeploy {
+            event: fields.string("event").unwrap_or_default(),
+            program_id: fields.string("programId"),
+            program_data: fields.string("programData"),
+            buffer: fields.string("buffer"),
+            account: fields.string("account"),
+            authority: fields.string("authority"),
+            new_authority: fields.string("newAuthority"),
+            recipient: fields.string("recipient"),
+            max_data_len: fields.u64("maxDataLen"),
+            additional_bytes: fields.u32("additionalBytes"),
+            signature: fields.string("signature").unwrap_or_default(),
+            slot: fields.u64("slot").unwrap_or_default(),
+            cpi: fields.bool("cpi"),
+        }),
+        None if fields.has(&["voteAccount", "kind", "slots"]) => Payload::Vote(Vote {
+            vote_account: fields.string("voteAccount").unwrap_or_default(),
+            authority: fields.string("authority"),
+            signature: fields.string("signature").unwrap_or_default(),
+            kind: fields.string("kind").unwrap_or_default(),
+            slot: fields.u64("slot").unwrap_or_default(),
+            last_voted_slot: fields.u64("lastVotedSlot"),
+            latency: fields.u64("latency"),
+            slots: fields
+                .take("slots", |slots| slots.as_array()?.iter().map(Value::as_u64).collect())
+                .unwrap_or_default(),
+            root: fields.u64("root"),
+            timestamp: fields.take("timestamp", Value::as_i64),
+        }),
+        None if fields.has(&["from", "to", "lamports", "cpi"]) => Payload::SolTransfer(SolTransfer {
+            from: fields.string("from").unwrap_or_default(),
+            to: fields.string("to").unwrap_or_default(),
+            lamports: fields.u64("lamports").unwrap_or_default(),
+            signature: fields.string("signature").unwrap_or_default(),
+            slot: fields.u64("slot").unwrap_or_default(),
+            cpi: fields.bool("cpi"),
+        }),
+        _ => return None,
+    };
+    Some(payload)
+}
+
+/// Encode a record as a `Record` message: derived events as their own message with the fields
+/// it has no field for in `extra`, other records as their JSON tree
+pub fn encode(key: &str, value: &Value) -> Vec<u8> {
+    let mut record = Record {
+        key: key.to_owned(),
+        streamer_version: value["streamerVersion"].as_str().unwrap_or_default().to_owned(),
+        historical: value["historical"].as_bool().unwrap_or_default(),
+        idempotency_key: value["idempotencyKey"].as_str().map(str::to_owned),
+        ..Default::default()
+    };
+
+    let mut fields = Fields(value.as_object().cloned().unwrap_or_default());
+    match derived_event(&mut fields) {
+        Some(payload) => {
+            record.payload = Some(payload);
+            record.extra = fields
+                .0
+                .iter()
+                .filter(|(name, _)| !STAMPS.contains(&name.as_str()))
+                .map(|(name, value)| (name.clone(), JsonValue::from(value)))
+                .collect();
+        },
+        None => record.payload = Some(Payload::Json(JsonValue::from(value))),
+    }
+    record.encode_to_vec()
+}
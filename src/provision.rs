@@ -0,0 +1,96 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use log::info;
+
+use crate::{blobs::BlobTarget, config::YellowstoneGrpcConfig, publisher::OversizePolicy, sinks::SinkConnector};
+
+/// Topic provisioning settings
+#[derive(Debug, Clone)]
+pub struct ProvisionConfig {
+    /// Create missing topics, otherwise fail when one is missing
+    pub create: bool,
+    pub partitions: u32,
+    pub replicas: u32,
+    /// Retention time of created topics, from `retention.max_age_secs`
+    pub retention: Option<Duration>,
+}
+
+/// Every topic the config publishes to: the commitment topics with their routes and derived topics
+/// (bloom, votes, stake, token creations, program deployments, transfers, transaction statuses,
+/// account closures, blobs, latest account states), and the control, unknown update, oversize
+/// and signature watch topics. Epoch topics are created as the stream reaches their epoch.
+pub fn required_topics(config: &YellowstoneGrpcConfig) -> Vec<String> {
+    let targets = config.get_commitment_topics();
+    let epoch_topics = config.get_epoch_topics_config().is_some();
+    let mut topics = BTreeSet::new();
+    for target in &targets {
+        if !epoch_topics {
+            topics.insert(target.topic.clone());
+        }
+
+        // Same suffixes as the pipelines in `dual_commitment` mode
+        let suffix = target
+            .commitment
+            .filter(|_| targets.len() > 1)
+            .map(|commitment| commitment.as_str_name().to_lowercase());
+        let suffixed = |topic: String| match &suffix {
+            Some(suffix) => format!("{}-{}", topic, suffix),
+            None => topic,
+        };
+        topics.extend(config.get_routes().into_iter().map(|route| suffixed(route.topic)));
+        if let Some(bloom) = config.get_signature_bloom_config() {
+            topics.insert(suffixed(bloom.topic));
+        }
+        if let Some(votes) = config.get_vote_stream_config() {
+            topics.insert(suffixed(votes.topic));
+        }
+        if let Some(stake_topic) = config.get_stake_events_topic() {
+            topics.insert(suffixed(stake_topic));
+        }
+        if let Some(mint_topic) = config.get_mint_events_topic() {
+            topics.insert(suffixed(mint_topic));
+        }
+        if let Some(deploys_topic) = config.get_program_deploys_topic() {
+            topics.insert(suffixed(deploys_topic));
+        }
+        if let Some(transfers) = config.get_transfer_stream_config() {
+            topics.insert(suffixed(transfers.topic));
+        }
+        if let Some(statuses) = config.get_status_stream_config() {
+            topics.insert(suffixed(statuses.topic));
+        }
+        if let Some(closures) = config.get_account_closures_config() {
+            topics.insert(suffixed(closures.topic));
+        }
+        if let Some(blobs) = config.get_blob_config().filter(|blobs| blobs.target == BlobTarget::Topic) {
+            topics.insert(suffixed(blobs.topic));
+        }
+        if let Some(latest) = config.get_latest_accounts_config() {
+            topics.insert(suffixed(latest.topic));
+        }
+    }
+
+    topics.extend(config.get_control_topic());
+    topics.extend(config.get_unknown_updates_topic());
+    if let Some(guard) = config.get_size_guard().filter(|guard| guard.policy == OversizePolicy::Topic) {
+        topics.insert(guard.oversize_topic);
+    }
+    if let Some(signature_watch) = config.get_signature_watch_config() {
+        topics.insert(signature_watch.topic);
+    }
+    topics.into_iter().collect()
+}
+
+/// Declare every required topic on the sink backend in one pass, creating the missing ones.
+/// Safe to run repeatedly, existing topics are left as they are.
+pub async fn provision(sinks: &SinkConnector, config: &YellowstoneGrpcConfig) -> anyhow::Result<()> {
+    let topics = required_topics(config);
+    let created = sinks.provision(&topics, &config.get_provision_config()).await?;
+    info!(
+        "Provisioned {} topics ({} created): {}",
+        topics.len(),
+        created.len(),
+        topics.join(", ")
+    );
+    Ok(())
+}
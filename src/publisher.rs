@@ -0,0 +1,427 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
+#[cfg(feature = "zstd")]
+use log::error;
+use log::warn;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+#[cfg(feature = "rkyv")]
+use crate::archive;
+#[cfg(feature = "zstd")]
+use crate::compression::Compressor;
+#[cfg(feature = "protobuf")]
+use crate::protobuf;
+use crate::{
+    canonical::{self, NumberFormat},
+    config::YellowstoneGrpcConfig,
+    envelope::Encryptor,
+    epochs::EpochTopics,
+    pause,
+    profiling::{self, Stage},
+    retention::RetentionCutoff,
+    sinks::{Sink, SinkConnector, SinkKind},
+};
+
+pub use crate::sinks::Delivery;
+
+/// Bytes reserved for the wrapper fields of split parts
+const SPLIT_OVERHEAD: usize = 256;
+
+/// Encoding of the published records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Canonical JSON text, see `canonical::to_vec`
+    #[default]
+    Json,
+    /// rkyv archives read in place by Rust consumers, requires the `rkyv` feature
+    Rkyv,
+    /// `Record` messages of `proto/records.proto`, requires the `protobuf` feature
+    Protobuf,
+}
+
+impl RecordFormat {
+    /// Format named `name`, `None` for a name this build does not know
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "rkyv" => Some(Self::Rkyv),
+            "protobuf" => Some(Self::Protobuf),
+            _ => None,
+        }
+    }
+}
+
+/// How records larger than `max_record_size` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizePolicy {
+    /// Publish only the top-level scalar fields with `truncated: true`
+    #[default]
+    Truncate,
+    /// Publish the record as base64 parts with `part` / `parts` markers
+    Split,
+    /// Publish the full record to the oversize topic and a pointer record to the main topic
+    Topic,
+}
+
+/// Payload size guardrail settings
+#[derive(Debug, Clone)]
+pub struct SizeGuard {
+    pub max_record_size: usize,
+    pub policy: OversizePolicy,
+    pub oversize_topic: String,
+}
+
+/// Deliveries awaiting acknowledgement, at most `max` at a time
+pub struct InFlight {
+    max: usize,
+    deliveries: FuturesUnordered<Delivery>,
+}
+
+impl InFlight {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            deliveries: FuturesUnordered::new(),
+        }
+    }
+
+    /// Track a delivery, first waiting for earlier ones while the limit is reached
+    pub async fn push(&mut self, delivery: Delivery) -> anyhow::Result<()> {
+        while self.deliveries.len() >= self.max {
+            if let Some(result) = self.deliveries.next().await {
+                result?;
+            }
+        }
+        self.deliveries.push(delivery);
+        Ok(())
+    }
+
+    /// Wait for every outstanding delivery
+    pub async fn drain(&mut self) -> anyhow::Result<()> {
+        while let Some(result) = self.deliveries.next().await {
+            result?;
+        }
+        Ok(())
+    }
+}
+
+/// Destination of the records of the main topic
+enum Producer {
+    Topic(Arc<dyn Sink>),
+    /// One topic per epoch, picked from the `slot` of each record
+    Epochs(EpochTopics),
+}
+
+/// Publishes formatted records to a sink, applying the payload size guardrail and encryption.
+/// Records are dropped while publishing is paused, control records are always published.
+pub struct Publisher {
+    producer: Producer,
+    size_guard: Option<SizeGuard>,
+    oversize_producer: Option<Box<dyn Sink>>,
+    control_producer: Option<Box<dyn Sink>>,
+    format: RecordFormat,
+    numbers: NumberFormat,
+    #[cfg(feature = "zstd")]
+    compressor: Option<Compressor>,
+    encryptor: Option<Encryptor>,
+}
+
+impl Publisher {
+    /// Open the sinks of `topic` and the auxiliary topics
+    pub async fn connect(sinks: &SinkConnector, config: &YellowstoneGrpcConfig, topic: &str) -> anyhow::Result<Self> {
+        Self::connect_to(sinks, config, topic, None).await
+    }
+
+    /// Open the sinks of `topic` and the auxiliary topics on the backends of `kinds`, every
+    /// configured one when `None`
+    pub async fn connect_to(
+        sinks: &SinkConnector,
+        config: &YellowstoneGrpcConfig,
+        topic: &str,
+        kinds: Option<&[SinkKind]>,
+    ) -> anyhow::Result<Self> {
+        let producer = Producer::Topic(Arc::from(sinks.open_to(topic, kinds).await?));
+        Self::with_producer(sinks, config, topic, producer, kinds).await
+    }
+
+    /// Open the sinks of the main topic `topic`, split into one topic per epoch when
+    /// `epoch_topics` is enabled, and the auxiliary topics
+    pub async fn connect_main(
+        sinks: &Arc<SinkConnector>,
+        config: &YellowstoneGrpcConfig,
+        topic: &str,
+    ) -> anyhow::Result<Self> {
+        let Some(epoch_config) = config.get_epoch_topics_config() else {
+            return Self::connect(sinks, config, topic).await;
+        };
+        let epochs = EpochTopics::new(Arc::clone(sinks), topic, epoch_config, config.get_provision_config());
+        Self::with_producer(sinks, config, topic, Producer::Epochs(epochs), None).await
+    }
+
+    async fn with_producer(
+        sinks: &SinkConnector,
+        config: &YellowstoneGrpcConfig,
+        #[cfg_attr(not(feature = "zstd"), allow(unused_variables))] topic: &str,
+        producer: Producer,
+        kinds: Option<&[SinkKind]>,
+    ) -> anyhow::Result<Self> {
+        let size_guard = config.get_size_guard();
+        let encryptor = config.get_encryptor()?;
+
+        let oversize_producer = match &size_guard {
+            Some(guard) if guard.policy == OversizePolicy::Topic => Some(sinks.open_to(&guard.oversize_topic, kinds).await?),
+            _ => None,
+        };
+
+        let control_producer = match config.get_control_topic() {
+            Some(control_topic) => Some(sinks.open_to(&control_topic, kinds).await?),
+            None => None,
+        };
+
+        let publisher = Self {
+            producer,
+            size_guard,
+            oversize_producer,
+            control_producer,
+            format: config.get_record_format(),
+            numbers: config.get_number_format(),
+            #[cfg(feature = "zstd")]
+            compressor: config
+                .get_compression_config()
+                .map(|compression| Compressor::new(topic, compression))
+                .transpose()?,
+            encryptor,
+        };
+        // A dictionary loaded from disk, consumers starting after it was trained need it too
+        #[cfg(feature = "zstd")]
+        if let Some(dictionary) = publisher.compressor.as_ref().and_then(Compressor::dictionary_record) {
+            if let Err(e) = publisher.send_control(&dictionary).await {
+                error!("Error publishing compression dictionary: {:?}", e);
+            }
+        }
+        Ok(publisher)
+    }
+
+    /// How numbers are written in the published records
+    pub fn numbers(&self) -> NumberFormat {
+        self.numbers
+    }
+
+    /// Publish a record to the main topic without waiting for its acknowledgement
+    pub async fn send(&self, key: String, value: &Value) -> anyhow::Result<()> {
+        self.send_tracked(key, value).await.map(drop)
+    }
+
+    /// Publish a record to the main topic, returning its acknowledgement
+    pub async fn send_tracked(&self, key: String, value: &Value) -> anyhow::Result<Delivery> {
+        let slot = value["slot"].as_u64();
+        if let Some(delivery) = pause::dropped(slot) {
+            return Ok(delivery);
+        }
+        let payload = self.encode(&key, value)?;
+        let producer = match &self.producer {
+            Producer::Topic(sink) => Arc::clone(sink),
+            Producer::Epochs(epochs) => epochs.sink(slot).await?,
+        };
+
+        // The limit applies to the payload as published, once compressed and encrypted
+        let payload = self.package(payload).await?;
+        let Some(guard) = self.size_guard.as_ref().filter(|guard| payload.len() > guard.max_record_size) else {
+            return self.produce(producer.as_ref(), key, payload, slot).await;
+        };
+
+        warn!(
+            "Record of {} bytes exceeds max_record_size of {} bytes, applying {:?} policy",
+            payload.len(), guard.max_record_size, guard.policy
+        );
+        let mut deliveries = Vec::new();
+        match guard.policy {
+            OversizePolicy::Truncate => {
+                let mut truncated = scalar_fields(value);
+                truncated.insert("key".to_owned(), json!(key));
+                truncated.insert("truncated".to_owned(), json!(true));
+                truncated.insert("originalSize".to_owned(), json!(payload.len()));
+                let payload = self.package(self.encode(&key, &Value::Object(truncated))?).await?;
+                deliveries.push(self.produce(producer.as_ref(), key, payload, slot).await?);
+            },
+            OversizePolicy::Split => {
+                // Parts carry the published payload and are not compressed or encrypted again, so
+                // consumers join them before decrypting. base64 grows a part by 4/3, keep every
+                // part under the limit.
+                let chunk_size = (guard.max_record_size.saturating_sub(SPLIT_OVERHEAD) / 4 * 3).max(1);
+                let parts = payload.len().div_ceil(chunk_size);
+                for (part, chunk) in payload.chunks(chunk_size).enumerate() {
+                    let record = json!({
+                        "part": part,
+                        "parts": parts,
+                        "originalSize": payload.len(),
+                        "payload": STANDARD.encode(chunk),
+                    });
+                    let part = self.encode(&key, &record)?;
+                    deliveries.push(self.produce(producer.as_ref(), key.clone(), part, slot).await?);
+                }
+            },
+            OversizePolicy::Topic => {
+                let oversize_producer = self
+                    .oversize_producer
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("oversize producer is not initialized"))?;
+                let oversize = self.produce(oversize_producer.as_ref(), key.clone(), payload.clone(), slot).await?;
+                deliveries.push(oversize);
+
+                let mut pointer = scalar_fields(value);
+                pointer.insert("key".to_owned(), json!(key));
+                pointer.insert("oversize".to_owned(), json!(true));
+                pointer.insert("topic".to_owned(), json!(guard.oversize_topic));
+                pointer.insert("originalSize".to_owned(), json!(payload.len()));
+                let payload = self.package(self.encode(&key, &Value::Object(pointer))?).await?;
+                deliveries.push(self.produce(producer.as_ref(), key, payload, slot).await?);
+            },
+        }
+
+        Ok(delivery(deliveries))
+    }
+
+    /// Publish a tombstone, an empty payload deleting `key` from a compacted topic. It is neither
+    /// compressed nor encrypted, brokers only recognize empty payloads.
+    pub async fn send_tombstone(&self, key: String, slot: u64) -> anyhow::Result<Delivery> {
+        if let Some(delivery) = pause::dropped(Some(slot)) {
+            return Ok(delivery);
+        }
+        let producer = match &self.producer {
+            Producer::Topic(sink) => Arc::clone(sink),
+            Producer::Epochs(epochs) => epochs.sink(Some(slot)).await?,
+        };
+        producer.send(key, Vec::new()).await
+    }
+
+    /// Encode a record in the configured format
+    fn encode(
+        &self,
+        #[cfg_attr(not(any(feature = "rkyv", feature = "protobuf")), allow(unused_variables))] key: &str,
+        value: &Value,
+    ) -> anyhow::Result<Vec<u8>> {
+        let _span = profiling::span(Stage::Serialize);
+        match self.format {
+            RecordFormat::Json => Ok(canonical::to_vec(value, self.numbers)),
+            #[cfg(feature = "rkyv")]
+            RecordFormat::Rkyv => archive::encode(key, value),
+            #[cfg(not(feature = "rkyv"))]
+            RecordFormat::Rkyv => anyhow::bail!("rkyv records require the `rkyv` feature"),
+            #[cfg(feature = "protobuf")]
+            RecordFormat::Protobuf => Ok(protobuf::encode(key, value)),
+            #[cfg(not(feature = "protobuf"))]
+            RecordFormat::Protobuf => anyhow::bail!("protobuf records require the `protobuf` feature"),
+        }
+    }
+
+    /// Publish a control record keyed by its `type`, a no-op when the control topic is disabled
+    pub async fn send_control(&self, record: &Value) -> anyhow::Result<()> {
+        let Some(producer) = &self.control_producer else {
+            return Ok(());
+        };
+        let key = record["type"].as_str().unwrap_or("control").to_owned();
+        // Always JSON and not compressed, consumers read the compression dictionaries from it
+        producer.send(key, self.seal(canonical::to_vec(record, self.numbers))?).await?;
+        producer.flush().await?;
+        Ok(())
+    }
+
+    /// Send a payload, as returned by `package`, of a record of `slot` to `producer`
+    async fn produce(
+        &self,
+        producer: &dyn Sink,
+        key: String,
+        payload: Vec<u8>,
+        slot: Option<u64>,
+    ) -> anyhow::Result<Delivery> {
+        let _span = profiling::span(Stage::Produce);
+        producer.send_at(key, payload, slot).await
+    }
+
+    /// Compress then encrypt an encoded record when configured, into the payload published
+    async fn package(&self, payload: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        let _span = profiling::span(Stage::Produce);
+        #[cfg(feature = "zstd")]
+        let payload = match &self.compressor {
+            Some(compressor) => {
+                let (compressed, dictionary) = compressor.compress(&payload).await?;
+                if let Some(dictionary) = dictionary {
+                    if let Err(e) = self.send_control(&dictionary).await {
+                        error!("Error publishing compression dictionary: {:?}", e);
+                    }
+                }
+                compressed
+            },
+            None => payload,
+        };
+        self.seal(payload)
+    }
+
+    fn seal(&self, payload: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        match &self.encryptor {
+            Some(encryptor) => encryptor.seal(&payload),
+            None => Ok(payload),
+        }
+    }
+
+    /// Prune the main and oversize sinks, `None` when the main sink does not prune
+    pub async fn prune(&self, cutoff: &RetentionCutoff) -> anyhow::Result<Option<u64>> {
+        let pruned = match &self.producer {
+            Producer::Topic(sink) => sink.prune(cutoff).await?,
+            Producer::Epochs(epochs) => epochs.prune(cutoff).await?,
+        };
+        let Some(mut pruned) = pruned else {
+            return Ok(None);
+        };
+        if let Some(producer) = &self.oversize_producer {
+            pruned += producer.prune(cutoff).await?.unwrap_or(0);
+        }
+        Ok(Some(pruned))
+    }
+
+    /// Flush pending records on every producer
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        match &self.producer {
+            Producer::Topic(sink) => sink.flush().await?,
+            Producer::Epochs(epochs) => epochs.flush().await?,
+        }
+        if let Some(producer) = &self.oversize_producer {
+            producer.flush().await?;
+        }
+        if let Some(producer) = &self.control_producer {
+            producer.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Delivery resolved once every produced record is acknowledged
+fn delivery(deliveries: Vec<Delivery>) -> Delivery {
+    async move {
+        for delivery in deliveries {
+            delivery.await?;
+        }
+        Ok(())
+    }
+    .boxed()
+}
+
+/// Top-level fields that are neither objects nor arrays, used to identify a reduced record
+fn scalar_fields(value: &Value) -> Map<String, Value> {
+    value
+        .as_object()
+        .map(|object| {
+            object
+                .iter()
+                .filter(|(_, field)| !field.is_object() && !field.is_array())
+                .map(|(name, field)| (name.clone(), field.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
@@ -0,0 +1,185 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use log::{error, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::watch;
+
+/// How often the usage is saved and checked against the quota
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What happens once an endpoint received its monthly byte quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaAction {
+    /// Log a warning and keep streaming
+    #[default]
+    Warn,
+    /// Shut the streamer down gracefully
+    Stop,
+}
+
+/// Byte quota settings
+#[derive(Debug, Clone)]
+pub struct ByteQuotaConfig {
+    /// Bytes each endpoint or credential profile may receive per calendar month (UTC)
+    pub monthly_bytes: Option<u64>,
+    /// Share of the quota a warning is logged at
+    pub warn_ratio: f64,
+    pub action: QuotaAction,
+    /// File the monthly usage is kept in across restarts
+    pub state_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageState {
+    /// `YYYY-MM`, the usage is reset when the month changes
+    month: String,
+    /// Bytes received per endpoint or credential profile this month
+    bytes: HashMap<String, u64>,
+}
+
+/// Bytes received from the gRPC streams, per endpoint or credential profile and calendar month,
+/// as the protobuf size of the updates. Checked against the monthly quota every minute.
+struct ByteUsage {
+    config: ByteQuotaConfig,
+    state: Mutex<UsageState>,
+    // Endpoints already warned about this month, at the warning ratio and over the quota
+    warned: Mutex<HashSet<(String, bool)>>,
+    exceeded: watch::Sender<bool>,
+}
+
+static USAGE: OnceLock<ByteUsage> = OnceLock::new();
+
+/// Start accounting the bytes received, loading the usage of the month from `state_path`
+pub fn install(config: ByteQuotaConfig) -> anyhow::Result<()> {
+    let state = match &config.state_path {
+        Some(path) if path.exists() => serde_json::from_slice(&std::fs::read(path)?)
+            .with_context(|| format!("invalid byte usage state {:?}", path))?,
+        _ => UsageState::default(),
+    };
+    let usage = ByteUsage {
+        config,
+        state: Mutex::new(state),
+        warned: Mutex::new(HashSet::new()),
+        exceeded: watch::channel(false).0,
+    };
+    if USAGE.set(usage).is_err() {
+        return Ok(());
+    }
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Some(usage) = USAGE.get() {
+                usage.check();
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Count `bytes` received from `endpoint`, the endpoint URL or credential profile name
+pub fn record(endpoint: &str, bytes: u64) {
+    let Some(usage) = USAGE.get() else {
+        return;
+    };
+    let mut state = usage.state.lock().expect("byte usage lock");
+    match state.bytes.get_mut(endpoint) {
+        Some(total) => *total += bytes,
+        None => {
+            state.bytes.insert(endpoint.to_owned(), bytes);
+        },
+    }
+}
+
+/// Usage of the month per endpoint, for `/status`
+pub fn report() -> Value {
+    let Some(usage) = USAGE.get() else {
+        return Value::Null;
+    };
+    let state = usage.state.lock().expect("byte usage lock");
+    let quota = usage.config.monthly_bytes;
+    let mut endpoints: Vec<Value> = state
+        .bytes
+        .iter()
+        .map(|(name, bytes)| {
+            json!({
+                "name": name,
+                "bytes": bytes,
+                "quotaUsed": quota.map(|quota| *bytes as f64 / quota as f64),
+            })
+        })
+        .collect();
+    endpoints.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+    json!({ "month": state.month, "monthlyQuotaBytes": quota, "endpoints": endpoints })
+}
+
+/// Resolves once an endpoint went over its quota with `action: stop`, never otherwise
+pub async fn exceeded() -> &'static str {
+    match USAGE.get().filter(|usage| usage.config.action == QuotaAction::Stop) {
+        Some(usage) => {
+            let mut exceeded = usage.exceeded.subscribe();
+            let _ = exceeded.wait_for(|exceeded| *exceeded).await;
+            "byte quota exceeded"
+        },
+        None => futures::future::pending().await,
+    }
+}
+
+impl ByteUsage {
+    /// Roll the month over, warn about the endpoints near or over the quota and save the state
+    fn check(&self) {
+        let mut state = self.state.lock().expect("byte usage lock");
+        let month = Utc::now().format("%Y-%m").to_string();
+        if state.month != month {
+            state.month = month;
+            state.bytes.clear();
+            self.warned.lock().expect("byte usage lock").clear();
+        }
+
+        if let Some(quota) = self.config.monthly_bytes {
+            let mut warned = self.warned.lock().expect("byte usage lock");
+            for (name, bytes) in &state.bytes {
+                let over = *bytes >= quota;
+                if (*bytes as f64) < quota as f64 * self.config.warn_ratio || !warned.insert((name.clone(), over)) {
+                    continue;
+                }
+                match (over, self.config.action) {
+                    (true, QuotaAction::Stop) => {
+                        error!("'{}' received {} bytes, over its monthly quota of {}, stopping", name, bytes, quota);
+                        self.exceeded.send_replace(true);
+                    },
+                    (true, QuotaAction::Warn) => {
+                        warn!("'{}' received {} bytes, over its monthly quota of {}", name, bytes, quota)
+                    },
+                    (false, _) => warn!(
+                        "'{}' received {} bytes, {:.0}% of its monthly quota of {}",
+                        name,
+                        bytes,
+                        *bytes as f64 * 100.0 / quota as f64,
+                        quota
+                    ),
+                }
+            }
+        }
+
+        if let Some(path) = &self.config.state_path {
+            let saved = serde_json::to_vec_pretty(&*state)
+                .map_err(anyhow::Error::from)
+                .and_then(|contents| Ok(std::fs::write(path, contents)?));
+            if let Err(e) = saved {
+                error!("Failed to save byte usage state {:?}: {:?}", path, e);
+            }
+        }
+    }
+}
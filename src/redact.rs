@@ -0,0 +1,132 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// What to do with a matched field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactAction {
+    /// Drop the field from the record
+    #[default]
+    Remove,
+    /// Replace the field with `sha256:<hex>` of its JSON value
+    Hash,
+}
+
+/// A single redaction rule from the config
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub struct RedactRule {
+    /// Dot separated JSON path, `*` matches every array element or object value
+    /// (e.g. `meta.logMessages` or `transaction.message.instructions.*.data`)
+    pub path: String,
+    /// `remove` (default) or `hash`
+    pub action: Option<RedactAction>,
+}
+
+/// Removes or hashes configured fields before records are published
+#[derive(Debug)]
+pub struct Redactor {
+    rules: Vec<(Vec<String>, RedactAction)>,
+}
+
+impl Redactor {
+    pub fn new(rules: &[RedactRule]) -> Self {
+        Self {
+            rules: rules
+                .iter()
+                .map(|rule| {
+                    let path = rule.path.split('.').map(str::to_owned).collect();
+                    (path, rule.action.unwrap_or_default())
+                })
+                .collect(),
+        }
+    }
+
+    /// Apply every rule to `value` in place
+    pub fn apply(&self, value: &mut Value) {
+        for (path, action) in &self.rules {
+            redact_path(value, path, *action);
+        }
+    }
+}
+
+fn redact_path(value: &mut Value, path: &[String], action: RedactAction) {
+    let Some((segment, rest)) = path.split_first() else {
+        return;
+    };
+
+    if rest.is_empty() {
+        match value {
+            Value::Object(map) if segment == "*" => {
+                if action == RedactAction::Remove {
+                    map.clear();
+                } else {
+                    map.values_mut().for_each(hash_value);
+                }
+            },
+            Value::Object(map) => match action {
+                RedactAction::Remove => {
+                    map.remove(segment.as_str());
+                },
+                RedactAction::Hash => {
+                    if let Some(field) = map.get_mut(segment.as_str()) {
+                        hash_value(field);
+                    }
+                },
+            },
+            Value::Array(items) if segment == "*" => {
+                if action == RedactAction::Remove {
+                    items.clear();
+                } else {
+                    items.iter_mut().for_each(hash_value);
+                }
+            },
+            Value::Array(items) => {
+                if let Ok(index) = segment.parse::<usize>() {
+                    match action {
+                        RedactAction::Remove if index < items.len() => {
+                            items.remove(index);
+                        },
+                        RedactAction::Hash => {
+                            if let Some(item) = items.get_mut(index) {
+                                hash_value(item);
+                            }
+                        },
+                        _ => {},
+                    }
+                }
+            },
+            _ => {},
+        }
+        return;
+    }
+
+    match value {
+        Value::Object(map) if segment == "*" => {
+            map.values_mut().for_each(|child| redact_path(child, rest, action));
+        },
+        Value::Object(map) => {
+            if let Some(child) = map.get_mut(segment.as_str()) {
+                redact_path(child, rest, action);
+            }
+        },
+        Value::Array(items) if segment == "*" => {
+            items.iter_mut().for_each(|child| redact_path(child, rest, action));
+        },
+        Value::Array(items) => {
+            if let Some(child) = segment.parse::<usize>().ok().and_then(|index| items.get_mut(index)) {
+                redact_path(child, rest, action);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn hash_value(value: &mut Value) {
+    let digest = match value {
+        Value::String(s) => Sha256::digest(s.as_bytes()),
+        other => Sha256::digest(other.to_string().as_bytes()),
+    };
+    *value = Value::String(format!("sha256:{}", hex::encode(digest)));
+}
@@ -0,0 +1,82 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use tokio::sync::broadcast;
+use yellowstone_grpc_proto::{
+    prelude::{subscribe_update::UpdateOneof, CommitmentLevel, SubscribeUpdate},
+    prost_types::Timestamp,
+};
+
+use crate::ProcessingMessage;
+
+#[cfg(feature = "relay")]
+mod server;
+
+#[cfg(feature = "relay")]
+pub use server::start;
+
+/// Relay server settings
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "relay"), allow(dead_code))]
+pub struct RelayConfig {
+    pub listen: SocketAddr,
+    /// Token expected in the `x-token` header of subscribers
+    pub x_token: Option<String>,
+    /// Updates buffered per subscriber before it is disconnected as too slow
+    pub buffer: usize,
+}
+
+/// Updates streamed by the pipelines, re-exported by the relay server so downstream instances
+/// can subscribe to this one instead of the upstream provider.
+///
+/// The upstream subscription filters, load shedding and staleness are applied before updates
+/// are relayed.
+#[cfg_attr(not(feature = "relay"), allow(dead_code))]
+pub struct Relay {
+    pipelines: Vec<(Option<CommitmentLevel>, broadcast::Sender<Arc<SubscribeUpdate>>)>,
+}
+
+#[cfg_attr(not(feature = "relay"), allow(dead_code))]
+impl Relay {
+    pub fn new(commitments: &[Option<CommitmentLevel>], buffer: usize) -> Self {
+        Self {
+            pipelines: commitments
+                .iter()
+                .map(|commitment| (*commitment, broadcast::channel(buffer.max(1)).0))
+                .collect(),
+        }
+    }
+
+    /// Relay a message of the pipeline at `commitment`
+    pub fn publish(&self, commitment: Option<CommitmentLevel>, message: &ProcessingMessage) {
+        let Some((_, sender)) = self.pipelines.iter().find(|(pipeline, _)| *pipeline == commitment) else {
+            return;
+        };
+        if sender.receiver_count() == 0 {
+            return;
+        }
+
+        let update_oneof = match message {
+            ProcessingMessage::Transaction(msg) => UpdateOneof::Transaction(msg.clone()),
+            ProcessingMessage::Account(msg) => UpdateOneof::Account(msg.clone()),
+            ProcessingMessage::BlockMetadata(msg) => UpdateOneof::BlockMeta(msg.clone()),
+            ProcessingMessage::TransactionStatus(msg) => UpdateOneof::TransactionStatus(msg.clone()),
+            ProcessingMessage::Grouped(message, _) => return self.publish(commitment, message),
+            ProcessingMessage::Shutdown => return,
+        };
+        // Fails only without subscribers
+        let _ = sender.send(Arc::new(SubscribeUpdate {
+            update_oneof: Some(update_oneof),
+            created_at: Some(Timestamp::from(std::time::SystemTime::now())),
+            ..Default::default()
+        }));
+    }
+
+    /// Updates of the pipeline at `commitment`, any pipeline when `None`. A pipeline without a
+    /// commitment streams at the upstream default and serves every commitment
+    pub fn subscribe(&self, commitment: Option<CommitmentLevel>) -> Option<broadcast::Receiver<Arc<SubscribeUpdate>>> {
+        self.pipelines
+            .iter()
+            .find(|(pipeline, _)| commitment.is_none() || pipeline.is_none() || *pipeline == commitment)
+            .map(|(_, sender)| sender.subscribe())
+    }
+}
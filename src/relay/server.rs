@@ -0,0 +1,521 @@
+use std::{collections::HashSet, pin::Pin, sync::Arc, time::Duration};
+
+use futures::Stream;
+use log::{error, info, warn};
+use tokio::sync::{broadcast, mpsc};
+use tonic::{
+    metadata::AsciiMetadataValue,
+    transport::{server::TcpIncoming, Server},
+    Request, Response, Status, Streaming,
+};
+use yellowstone_grpc_proto::prelude::{
+    geyser_server::{Geyser, GeyserServer},
+    subscribe_update::UpdateOneof,
+    CommitmentLevel, GetBlockHeightRequest, GetBlockHeightResponse, GetLatestBlockhashRequest, GetLatestBlockhashResponse,
+    GetSlotRequest, GetSlotResponse, GetVersionRequest, GetVersionResponse, IsBlockhashValidRequest,
+    IsBlockhashValidResponse, PingRequest, PongResponse, SubscribeReplayInfoRequest, SubscribeReplayInfoResponse,
+    SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterTransactions, SubscribeUpdate,
+    SubscribeUpdateAccountInfo, SubscribeUpdatePing, SubscribeUpdatePong, SubscribeUpdateTransactionInfo,
+};
+
+use super::{Relay, RelayConfig};
+use crate::sources::{GrpcEndpoint, UnaryApi, UnaryPool};
+
+/// How often subscribers are pinged to keep idle connections open
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Geyser gRPC service over the relayed updates.
+///
+/// Subscribers receive the account, transaction, transaction status and block metadata updates
+/// matching the filters of their request, at the commitment of one of the pipelines. Slots,
+/// blocks, entries and account data filters are not relayed, requests using them are rejected.
+/// Unary calls are forwarded upstream.
+struct RelayService {
+    relay: Arc<Relay>,
+    unary: Arc<UnaryPool>,
+}
+
+impl RelayService {
+    /// Updates of the pipeline of a subscribe request
+    fn pipeline(&self, request: &SubscribeRequest) -> Result<broadcast::Receiver<Arc<SubscribeUpdate>>, Status> {
+        let commitment = request
+            .commitment
+            .map(|commitment| {
+                CommitmentLevel::try_from(commitment)
+                    .map_err(|_| Status::invalid_argument(format!("invalid commitment {}", commitment)))
+            })
+            .transpose()?;
+        self.relay
+            .subscribe(commitment)
+            .ok_or_else(|| Status::invalid_argument(format!("commitment {:?} is not relayed", commitment)))
+    }
+
+    async fn call<T>(
+        &self,
+        f: impl for<'a> FnOnce(&'a mut Box<dyn UnaryApi>) -> futures::future::BoxFuture<'a, anyhow::Result<T>>,
+    ) -> Result<Response<T>, Status> {
+        self.unary
+            .call(f)
+            .await
+            .map(Response::new)
+            .map_err(|e| Status::unavailable(format!("{:#}", e)))
+    }
+}
+
+/// Filters of a subscriber, evaluated like the upstream evaluates them
+#[derive(Default)]
+struct Selection {
+    accounts: Vec<(String, AccountFilter)>,
+    transactions: Vec<(String, TransactionFilter)>,
+    transactions_status: Vec<(String, TransactionFilter)>,
+    blocks_meta: Vec<String>,
+}
+
+impl Selection {
+    fn new(request: &SubscribeRequest) -> Result<Self, Status> {
+        let accounts = request
+            .accounts
+            .iter()
+            .map(|(name, filter)| Ok((name.clone(), AccountFilter::new(filter)?)))
+            .collect::<Result<_, Status>>()?;
+        let transactions = request
+            .transactions
+            .iter()
+            .map(|(name, filter)| Ok((name.clone(), TransactionFilter::new(filter)?)))
+            .collect::<Result<_, Status>>()?;
+        let transactions_status: Vec<(String, TransactionFilter)> = request
+            .transactions_status
+            .iter()
+            .map(|(name, filter)| Ok((name.clone(), TransactionFilter::new(filter)?)))
+            .collect::<Result<_, Status>>()?;
+        if transactions_status.iter().any(|(_, filter)| filter.has_accounts()) {
+            return Err(Status::unimplemented("transactions_status account filters are not supported by the relay"));
+        }
+        Ok(Self {
+            accounts,
+            transactions,
+            transactions_status,
+            blocks_meta: request.blocks_meta.keys().cloned().collect(),
+        })
+    }
+
+    /// The update tagged with the names of the filters it matched, `None` when it matched none
+    fn filter(&self, update: &SubscribeUpdate) -> Option<SubscribeUpdate> {
+        let filters: Vec<String> = match update.update_oneof.as_ref()? {
+            UpdateOneof::Account(update) => {
+                let account = update.account.as_ref()?;
+                matching(&self.accounts, |filter| filter.matches(account))
+            },
+            UpdateOneof::Transaction(update) => {
+                let tx = update.transaction.as_ref()?;
+                let keys = account_keys(tx);
+                let failed = tx.meta.as_ref().is_some_and(|meta| meta.err.is_some());
+                matching(&self.transactions, |filter| filter.matches(tx.is_vote, failed, &tx.signature, &keys))
+            },
+            UpdateOneof::TransactionStatus(status) => matching(&self.transactions_status, |filter| {
+                filter.matches(status.is_vote, status.err.is_some(), &status.signature, &[])
+            }),
+            UpdateOneof::BlockMeta(_) => self.blocks_meta.clone(),
+            _ => return None,
+        };
+        if filters.is_empty() {
+            return None;
+        }
+        Some(SubscribeUpdate {
+            filters,
+            ..update.clone()
+        })
+    }
+}
+
+/// Names of the filters matching an update
+fn matching<T>(filters: &[(String, T)], matches: impl Fn(&T) -> bool) -> Vec<String> {
+    filters.iter().filter(|(_, filter)| matches(filter)).map(|(name, _)| name.clone()).collect()
+}
+
+/// Static and loaded account keys of a transaction
+fn account_keys(transaction: &SubscribeUpdateTransactionInfo) -> Vec<&[u8]> {
+    let message = transaction.transaction.as_ref().and_then(|transaction| transaction.message.as_ref());
+    let static_keys = message.into_iter().flat_map(|message| &message.account_keys);
+    let loaded = transaction
+        .meta
+        .as_ref()
+        .into_iter()
+        .flat_map(|meta| meta.loaded_writable_addresses.iter().chain(&meta.loaded_readonly_addresses));
+    static_keys.chain(loaded).map(Vec::as_slice).collect()
+}
+
+/// Decode the base58 pubkeys of a filter field
+fn pubkeys(field: &str, pubkeys: &[String]) -> Result<HashSet<Vec<u8>>, Status> {
+    pubkeys
+        .iter()
+        .map(|pubkey| {
+            bs58::decode(pubkey)
+                .into_vec()
+                .map_err(|_| Status::invalid_argument(format!("invalid pubkey '{}' in {}", pubkey, field)))
+        })
+        .collect()
+}
+
+struct AccountFilter {
+    account: HashSet<Vec<u8>>,
+    owner: HashSet<Vec<u8>>,
+    nonempty_txn_signature: Option<bool>,
+}
+
+impl AccountFilter {
+    fn new(filter: &SubscribeRequestFilterAccounts) -> Result<Self, Status> {
+        if !filter.filters.is_empty() {
+            return Err(Status::unimplemented("account data filters are not supported by the relay"));
+        }
+        Ok(Self {
+            account: pubkeys("account", &filter.account)?,
+            owner: pubkeys("owner", &filter.owner)?,
+            nonempty_txn_signature: filter.nonempty_txn_signature,
+        })
+    }
+
+    fn matches(&self, account: &SubscribeUpdateAccountInfo) -> bool {
+        (self.account.is_empty() || self.account.contains(&account.pubkey))
+            && (self.owner.is_empty() || self.owner.contains(&account.owner))
+            && self.nonempty_txn_signature.map_or(true, |nonempty| nonempty == account.txn_signature.is_some())
+    }
+}
+
+struct TransactionFilter {
+    vote: Option<bool>,
+    failed: Option<bool>,
+    signature: Option<Vec<u8>>,
+    account_include: HashSet<Vec<u8>>,
+    account_exclude: HashSet<Vec<u8>>,
+    account_required: HashSet<Vec<u8>>,
+}
+
+impl TransactionFilter {
+    fn new(filter: &SubscribeRequestFilterTransactions) -> Result<Self, Status> {
+        let signature = filter
+            .signature
+            .as_ref()
+            .map(|signature| {
+                bs58::decode(signature)
+                    .into_vec()
+                    .map_err(|_| Status::invalid_argument(format!("invalid signature '{}'", signature)))
+            })
+            .transpose()?;
+        Ok(Self {
+            vote: filter.vote,
+            failed: filter.failed,
+            signature,
+            account_include: pubkeys("account_include", &filter.account_include)?,
+            account_exclude: pubkeys("account_exclude", &filter.account_exclude)?,
+            account_required: pubkeys("account_required", &filter.account_required)?,
+        })
+    }
+
+    fn has_accounts(&self) -> bool {
+        !self.account_include.is_empty() || !self.account_exclude.is_empty() || !self.account_required.is_empty()
+    }
+
+    fn matches(&self, is_vote: bool, failed: bool, signature: &[u8], keys: &[&[u8]]) -> bool {
+        self.vote.map_or(true, |vote| vote == is_vote)
+            && self.failed.map_or(true, |expected| expected == failed)
+            && self.signature.as_ref().map_or(true, |expected| expected == signature)
+            && (self.account_include.is_empty() || keys.iter().any(|key| self.account_include.contains(*key)))
+            && !keys.iter().any(|key| self.account_exclude.contains(*key))
+            && self.account_required.iter().all(|required| keys.contains(&required.as_slice()))
+    }
+}
+
+fn check_request(request: &SubscribeRequest) -> Result<(), Status> {
+    if request.from_slot.is_some() {
+        return Err(Status::unimplemented("from_slot is not supported by the relay"));
+    }
+    // The pipelines do not receive these updates, or receive account data unsliced
+    if !request.slots.is_empty() || !request.blocks.is_empty() || !request.entry.is_empty() {
+        return Err(Status::unimplemented("slots, blocks and entries are not relayed"));
+    }
+    if !request.accounts_data_slice.is_empty() {
+        return Err(Status::unimplemented("accounts_data_slice is not supported by the relay"));
+    }
+    Ok(())
+}
+
+/// Requests carrying only a ping keep the current filters
+fn is_ping_only(request: &SubscribeRequest) -> bool {
+    request.ping.is_some()
+        && request.accounts.is_empty()
+        && request.transactions.is_empty()
+        && request.blocks_meta.is_empty()
+        && request.slots.is_empty()
+        && request.blocks.is_empty()
+        && request.entry.is_empty()
+        && request.transactions_status.is_empty()
+}
+
+/// Forward the relayed updates to one subscriber, applying its requests as they arrive
+async fn serve_subscriber(
+    service: Arc<RelayService>,
+    mut requests: Streaming<SubscribeRequest>,
+    sender: mpsc::Sender<Result<SubscribeUpdate, Status>>,
+) {
+    let mut updates: Option<broadcast::Receiver<Arc<SubscribeUpdate>>> = None;
+    let mut selection = Selection::default();
+    let mut ping = tokio::time::interval(PING_INTERVAL);
+
+    loop {
+        let outgoing = tokio::select! {
+            request = requests.message() => {
+                let request = match request {
+                    Ok(Some(request)) => request,
+                    Ok(None) => return,
+                    Err(e) => {
+                        warn!("Relay subscriber stream failed: {}", e);
+                        return;
+                    },
+                };
+                if let Some(ping) = &request.ping {
+                    let pong = SubscribeUpdate {
+                        update_oneof: Some(UpdateOneof::Pong(SubscribeUpdatePong { id: ping.id })),
+                        ..Default::default()
+                    };
+                    if sender.send(Ok(pong)).await.is_err() {
+                        return;
+                    }
+                }
+                if is_ping_only(&request) {
+                    continue;
+                }
+                let subscribed = check_request(&request)
+                    .and_then(|_| Selection::new(&request))
+                    .and_then(|new_selection| Ok((service.pipeline(&request)?, new_selection)));
+                match subscribed {
+                    Ok((receiver, new_selection)) => {
+                        updates = Some(receiver);
+                        selection = new_selection;
+                    },
+                    Err(status) => {
+                        let _ = sender.send(Err(status)).await;
+                        return;
+                    },
+                }
+                continue;
+            },
+            update = next_update(&mut updates) => match update {
+                Ok(update) => match selection.filter(&update) {
+                    Some(update) => Ok(update),
+                    None => continue,
+                },
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    // Disconnected rather than silently missing updates, the subscriber re-subscribes
+                    Err(Status::resource_exhausted(format!("subscriber fell {} updates behind the relay", skipped)))
+                },
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = ping.tick() => Ok(SubscribeUpdate {
+                update_oneof: Some(UpdateOneof::Ping(SubscribeUpdatePing {})),
+                ..Default::default()
+            }),
+        };
+
+        let failed = outgoing.is_err();
+        if sender.send(outgoing).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+async fn next_update(
+    updates: &mut Option<broadcast::Receiver<Arc<SubscribeUpdate>>>,
+) -> Result<Arc<SubscribeUpdate>, broadcast::error::RecvError> {
+    match updates {
+        Some(updates) => updates.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Shared by the connections of the server
+struct SharedService(Arc<RelayService>);
+
+#[tonic::async_trait]
+impl Geyser for SharedService {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<SubscribeUpdate, Status>> + Send>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<Streaming<SubscribeRequest>>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let (sender, receiver) = mpsc::channel(16);
+        tokio::spawn(serve_subscriber(Arc::clone(&self.0), request.into_inner(), sender));
+
+        let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|update| (update, receiver))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn subscribe_replay_info(
+        &self,
+        _request: Request<SubscribeReplayInfoRequest>,
+    ) -> Result<Response<SubscribeReplayInfoResponse>, Status> {
+        Ok(Response::new(SubscribeReplayInfoResponse { first_available: None }))
+    }
+
+    async fn ping(&self, request: Request<PingRequest>) -> Result<Response<PongResponse>, Status> {
+        Ok(Response::new(PongResponse {
+            count: request.into_inner().count,
+        }))
+    }
+
+    async fn get_latest_blockhash(
+        &self,
+        request: Request<GetLatestBlockhashRequest>,
+    ) -> Result<Response<GetLatestBlockhashResponse>, Status> {
+        let commitment = commitment(request.into_inner().commitment);
+        self.0.call(|client| client.get_latest_blockhash(commitment)).await
+    }
+
+    async fn get_block_height(
+        &self,
+        request: Request<GetBlockHeightRequest>,
+    ) -> Result<Response<GetBlockHeightResponse>, Status> {
+        let commitment = commitment(request.into_inner().commitment);
+        self.0.call(|client| client.get_block_height(commitment)).await
+    }
+
+    async fn get_slot(&self, request: Request<GetSlotRequest>) -> Result<Response<GetSlotResponse>, Status> {
+        let commitment = commitment(request.into_inner().commitment);
+        self.0.call(|client| client.get_slot(commitment)).await
+    }
+
+    async fn is_blockhash_valid(
+        &self,
+        request: Request<IsBlockhashValidRequest>,
+    ) -> Result<Response<IsBlockhashValidResponse>, Status> {
+        let request = request.into_inner();
+        let commitment = commitment(request.commitment);
+        self.0
+            .call(|client| client.is_blockhash_valid(request.blockhash, commitment))
+            .await
+    }
+
+    async fn get_version(&self, _request: Request<GetVersionRequest>) -> Result<Response<GetVersionResponse>, Status> {
+        Ok(Response::new(GetVersionResponse {
+            version: serde_json::json!({ "relay": env!("CARGO_PKG_VERSION") }).to_string(),
+        }))
+    }
+}
+
+fn commitment(commitment: Option<i32>) -> Option<CommitmentLevel> {
+    commitment.and_then(|commitment| CommitmentLevel::try_from(commitment).ok())
+}
+
+/// Bind the relay server and serve it in the background, forwarding unary calls to `endpoint`
+pub async fn start(config: RelayConfig, relay: Arc<Relay>, endpoint: &GrpcEndpoint) -> anyhow::Result<()> {
+    if config.x_token.is_none() && !config.listen.ip().is_loopback() {
+        warn!(
+            "Relay server listens on {} without `relay.x_token`, anyone reaching it can subscribe to the stream",
+            config.listen
+        );
+    }
+    let service = SharedService(Arc::new(RelayService {
+        relay,
+        unary: UnaryPool::shared(endpoint),
+    }));
+    let x_token = config
+        .x_token
+        .map(|token| token.parse::<AsciiMetadataValue>())
+        .transpose()?;
+    let service = GeyserServer::with_interceptor(service, move |request: Request<()>| {
+        match &x_token {
+            Some(token) if request.metadata().get("x-token") != Some(token) => {
+                Err(Status::unauthenticated("invalid x-token"))
+            },
+            _ => Ok(request),
+        }
+    });
+
+    let listener = tokio::net::TcpListener::bind(config.listen).await?;
+    let incoming = TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow::anyhow!(e))?;
+    info!("Relay server listening on {}", config.listen);
+    tokio::spawn(async move {
+        if let Err(e) = Server::builder().add_service(service).serve_with_incoming(incoming).await {
+            error!("Relay server stopped: {:?}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use yellowstone_grpc_proto::prelude::{SubscribeRequestFilterSlots, SubscribeUpdateAccount};
+
+    use super::*;
+
+    fn account_update(pubkey: &[u8], owner: &[u8]) -> SubscribeUpdate {
+        SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Account(SubscribeUpdateAccount {
+                account: Some(SubscribeUpdateAccountInfo {
+                    pubkey: pubkey.to_vec(),
+                    owner: owner.to_vec(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn evaluates_account_filters() {
+        let owner = bs58::encode([2; 32]).into_string();
+        let request = SubscribeRequest {
+            accounts: HashMap::from([
+                ("all".to_string(), SubscribeRequestFilterAccounts::default()),
+                ("program".to_string(), SubscribeRequestFilterAccounts {
+                    owner: vec![owner],
+                    ..Default::default()
+                }),
+            ]),
+            ..Default::default()
+        };
+        let selection = Selection::new(&request).unwrap();
+
+        let mut filters = selection.filter(&account_update(&[1; 32], &[2; 32])).unwrap().filters;
+        filters.sort();
+        assert_eq!(filters, ["all", "program"]);
+        assert_eq!(selection.filter(&account_update(&[1; 32], &[3; 32])).unwrap().filters, ["all"]);
+    }
+
+    #[test]
+    fn evaluates_transaction_filters() {
+        let filter = TransactionFilter::new(&SubscribeRequestFilterTransactions {
+            vote: Some(false),
+            account_required: vec![bs58::encode([5; 32]).into_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        let keys: [&[u8]; 2] = [&[4; 32], &[5; 32]];
+        assert!(filter.matches(false, false, &[9; 64], &keys));
+        assert!(!filter.matches(true, false, &[9; 64], &keys));
+        assert!(!filter.matches(false, false, &[9; 64], &keys[..1]));
+    }
+
+    #[test]
+    fn rejects_what_is_not_relayed() {
+        let request = SubscribeRequest {
+            slots: HashMap::from([("slots".to_string(), SubscribeRequestFilterSlots::default())]),
+            ..Default::default()
+        };
+        assert_eq!(check_request(&request).unwrap_err().code(), tonic::Code::Unimplemented);
+        let request = SubscribeRequest {
+            accounts: HashMap::from([("bad".to_string(), SubscribeRequestFilterAccounts {
+                account: vec!["not base58 0OIl".to_string()],
+                ..Default::default()
+            })]),
+            ..Default::default()
+        };
+        assert_eq!(Selection::new(&request).err().map(|status| status.code()), Some(tonic::Code::InvalidArgument));
+    }
+}
@@ -0,0 +1,69 @@
+use std::{path::PathBuf, time::Duration};
+
+use log::{error, info, warn};
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+
+use crate::config::{self, Filters};
+
+/// Resolved target and digest of the config file, a change of either is a new version
+#[derive(PartialEq, Eq)]
+struct Fingerprint {
+    target: PathBuf,
+    digest: [u8; 32],
+}
+
+async fn read(path: &str) -> anyhow::Result<(Fingerprint, String)> {
+    let target = tokio::fs::canonicalize(path).await?;
+    let contents = tokio::fs::read_to_string(&target).await?;
+    let digest = Sha256::digest(contents.as_bytes()).into();
+    Ok((Fingerprint { target, digest }, contents))
+}
+
+/// Poll the config file every `interval` and publish the filters of each valid new version.
+///
+/// Kubernetes updates a mounted ConfigMap by atomically swapping the `..data` symlink of the
+/// mount, which file watches on the old target miss. Polling resolves the path again each time,
+/// so the swap is seen as a change of target. Invalid versions are logged and skipped, the
+/// previous filters stay in use. Settings other than `filters` need a restart.
+pub fn watch_filters(path: String, interval: Duration, filters: Filters) -> watch::Receiver<Filters> {
+    let (tx, rx) = watch::channel(filters);
+
+    tokio::spawn(async move {
+        let mut last = match read(&path).await {
+            Ok((fingerprint, _)) => Some(fingerprint),
+            Err(e) => {
+                warn!("Failed to read config file {} for reloads: {:?}", path, e);
+                None
+            },
+        };
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let (fingerprint, contents) = match read(&path).await {
+                Ok(version) => version,
+                Err(e) => {
+                    warn!("Failed to read config file {}: {:?}", path, e);
+                    continue;
+                },
+            };
+            if last.as_ref() == Some(&fingerprint) {
+                continue;
+            }
+
+            info!("Config file {} changed (resolves to {}), reloading filters", path, fingerprint.target.display());
+            last = Some(fingerprint);
+            match config::parse_config(&path, &contents) {
+                Ok(config) => {
+                    if tx.send(config.yellowstone_grpc.filters).is_err() {
+                        break;
+                    }
+                },
+                Err(e) => error!("Ignoring invalid config, keeping the previous filters: {:#}", e),
+            }
+        }
+    });
+
+    rx
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{error, info, warn};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::sync::watch;
+
+use crate::config::{self, Filters};
+
+/// Service the filters are fetched from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RemoteFiltersSource {
+    /// `GET url`, the body is the filters
+    #[default]
+    Http,
+    /// Value of `key` in the Consul KV store of the agent at `url`
+    Consul,
+    /// Value of `key` in etcd, through the v3 JSON gateway at `url`
+    Etcd,
+}
+
+/// Remote filter settings
+#[derive(Debug, Clone)]
+pub struct RemoteFiltersConfig {
+    pub source: RemoteFiltersSource,
+    pub url: String,
+    pub key: Option<String>,
+    /// Bearer token, or Consul ACL token
+    pub token: Option<String>,
+    pub interval: Duration,
+}
+
+/// Fetches the subscription filters, YAML or JSON in the format of `filters`, from a central
+/// service managing what many streamer instances subscribe to
+struct RemoteFilters {
+    config: RemoteFiltersConfig,
+    client: reqwest::Client,
+}
+
+impl RemoteFilters {
+    fn new(config: RemoteFiltersConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn origin(&self) -> String {
+        match &self.config.key {
+            Some(key) if self.config.source != RemoteFiltersSource::Http => format!("{}#{}", self.config.url, key),
+            _ => self.config.url.clone(),
+        }
+    }
+
+    async fn fetch_contents(&self) -> anyhow::Result<String> {
+        let url = self.config.url.trim_end_matches('/');
+        let key = || self.config.key.as_deref().context("`key` is required by consul and etcd");
+        let request = match self.config.source {
+            RemoteFiltersSource::Http => self.client.get(url),
+            RemoteFiltersSource::Consul => {
+                let request = self.client.get(format!("{}/v1/kv/{}?raw", url, key()?.trim_start_matches('/')));
+                match &self.config.token {
+                    Some(token) => request.header("X-Consul-Token", token),
+                    None => request,
+                }
+            },
+            RemoteFiltersSource::Etcd => {
+                self.client.post(format!("{}/v3/kv/range", url)).json(&json!({ "key": STANDARD.encode(key()?) }))
+            },
+        };
+        let request = match (&self.config.token, self.config.source) {
+            (Some(token), RemoteFiltersSource::Http | RemoteFiltersSource::Etcd) => request.bearer_auth(token),
+            _ => request,
+        };
+        let response = request.timeout(Duration::from_secs(10)).send().await?.error_for_status()?;
+
+        if self.config.source != RemoteFiltersSource::Etcd {
+            return Ok(response.text().await?);
+        }
+        let body: Value = response.json().await?;
+        let value = body["kvs"][0]["value"].as_str().with_context(|| format!("etcd key {} not found", self.origin()))?;
+        Ok(String::from_utf8(STANDARD.decode(value)?)?)
+    }
+}
+
+/// Parse remote filters, rejecting what cannot be applied on reload: fields that are not filters,
+/// e.g. `routes`, and filter groups with a topic not declared in the `local` filters, whose topics
+/// are provisioned and routed at startup
+fn parse(origin: &str, contents: &str, local: &Filters) -> anyhow::Result<Filters> {
+    let filters = config::parse_filters(origin, contents)?;
+    let mut errors = unsupported_fields(contents)?;
+    filters.check_remote_groups(local, &mut errors);
+    if !errors.is_empty() {
+        anyhow::bail!("{}: unsupported remote filters:\n  {}", origin, errors.join("\n  "));
+    }
+    Ok(filters)
+}
+
+/// Fields of the remote document, and of its groups, that `filters` does not have
+fn unsupported_fields(contents: &str) -> anyhow::Result<Vec<String>> {
+    let root = schemars::schema_for!(Filters);
+    let known: Vec<&str> =
+        root.schema.object.iter().flat_map(|object| object.properties.keys()).map(String::as_str).collect();
+    let unknown = |mapping: &serde_yaml::Mapping, prefix: &str, extra: &[&str]| -> Vec<String> {
+        mapping
+            .keys()
+            .filter_map(|key| key.as_str())
+            .filter(|key| !known.contains(key) && !extra.contains(key))
+            .map(|key| format!("{}.{}: not a filter field", prefix, key))
+            .collect()
+    };
+
+    let document: serde_yaml::Value = serde_yaml::from_str(contents)?;
+    let Some(mapping) = document.as_mapping() else {
+        return Ok(Vec::new());
+    };
+    let mut errors = unknown(mapping, "filters", &[]);
+    let groups = mapping.get("groups").and_then(serde_yaml::Value::as_sequence).into_iter().flatten();
+    for (index, group) in groups.enumerate() {
+        if let Some(group) = group.as_mapping() {
+            errors.extend(unknown(group, &format!("filters.groups[{}]", index), &["name", "topic", "sinks"]));
+        }
+    }
+    Ok(errors)
+}
+
+/// Fetch the filters at startup, failing when they cannot be, then every `interval`, publishing
+/// each valid new version. Later fetch errors and invalid versions are logged, the previous
+/// filters stay in use. `local` are the filters of the config file.
+pub async fn start(
+    remote_config: RemoteFiltersConfig,
+    local: Filters,
+) -> anyhow::Result<(Filters, watch::Receiver<Filters>)> {
+    let remote = RemoteFilters::new(remote_config);
+    let contents = remote
+        .fetch_contents()
+        .await
+        .with_context(|| format!("failed to fetch filters from {}", remote.origin()))?;
+    let filters = parse(&remote.origin(), &contents, &local)?;
+    info!("Fetched filters from {}", remote.origin());
+    let (tx, rx) = watch::channel(filters.clone());
+
+    tokio::spawn(async move {
+        let mut last: [u8; 32] = Sha256::digest(contents.as_bytes()).into();
+        let mut ticker = tokio::time::interval(remote.config.interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let contents = match remote.fetch_contents().await {
+                Ok(contents) => contents,
+                Err(e) => {
+                    warn!("Failed to fetch filters from {}: {:?}", remote.origin(), e);
+                    continue;
+                },
+            };
+            let digest: [u8; 32] = Sha256::digest(contents.as_bytes()).into();
+            if digest == last {
+                continue;
+            }
+            last = digest;
+
+            match parse(&remote.origin(), &contents, &local) {
+                Ok(filters) => {
+                    info!("Filters at {} changed, reloading them", remote.origin());
+                    if tx.send(filters).is_err() {
+                        break;
+                    }
+                },
+                Err(e) => error!("Ignoring invalid remote filters, keeping the previous ones: {:#}", e),
+            }
+        }
+    });
+
+    Ok((filters, rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local() -> Filters {
+        config::parse_filters("config.yaml", "transactions: true\ngroups: [{name: swaps, topic: swaps}]").unwrap()
+    }
+
+    #[test]
+    fn rejects_fields_that_are_not_filters() {
+        let contents = "transactions: true\nroutes: []\ngroups: [{name: swaps, profile: slim}]";
+        let error = parse("remote", contents, &local()).unwrap_err().to_string();
+        assert!(error.contains("filters.routes: not a filter field"), "{}", error);
+        assert!(error.contains("filters.groups[0].profile: not a filter field"), "{}", error);
+    }
+
+    #[test]
+    fn accepts_groups_with_the_local_topics_only() {
+        assert!(parse("remote", "groups: [{name: swaps, topic: swaps, accounts: true}]", &local()).is_ok());
+        assert!(parse("remote", "groups: [{name: wallets, accounts: true}]", &local()).is_ok());
+
+        let error = parse("remote", "groups: [{name: swaps, topic: other}]", &local()).unwrap_err().to_string();
+        assert!(error.contains("group `swaps` with a topic is not declared"), "{}", error);
+        assert!(parse("remote", "groups: [{name: wallets, topic: wallets}]", &local()).is_err());
+    }
+}
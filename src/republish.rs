@@ -0,0 +1,214 @@
+use std::path::Path;
+
+use log::{info, warn};
+use serde::Deserialize;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader},
+    sync::mpsc,
+};
+
+use crate::{
+    config::YellowstoneGrpcConfig,
+    publisher::InFlight,
+    sinks::{ArchivedLine, SinkConnector},
+    CHANNEL_SIZE,
+};
+
+/// Acknowledgements awaited at a time without `max_in_flight`
+const DEFAULT_IN_FLIGHT: usize = 1000;
+
+/// Record read back from an archive, with the key and payload it was published with and its slot
+type Archived = (String, Vec<u8>, u64);
+
+/// Slot of a JSON record, for archived lines written without their slot
+#[derive(Deserialize)]
+struct Slotted {
+    slot: Option<u64>,
+}
+
+/// Publish the archived records of `from_slot..=to_slot` to `topic` as they were published, for
+/// recovering from downstream data loss. Inputs are read in order: JSON lines written by
+/// `sink: stdout` from a file, stdin (`-`) or the objects of an `s3://bucket/prefix`, or SQLite /
+/// DuckDB capture files.
+pub(crate) async fn run(
+    config: &YellowstoneGrpcConfig,
+    inputs: Vec<String>,
+    from_slot: u64,
+    to_slot: u64,
+    topic: String,
+    source_topic: Option<String>,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(from_slot <= to_slot, "--from-slot must not be greater than --to-slot");
+    anyhow::ensure!(!inputs.is_empty(), "give at least one --input");
+
+    let sinks = SinkConnector::connect(config, None).await?;
+    sinks.provision(&[topic.clone()], &config.get_provision_config()).await?;
+    // Archived payloads are sent as they are, already size guarded and encrypted
+    let sink = sinks.open(&topic).await?;
+    let mut in_flight = InFlight::new(config.yellowstone_grpc.max_in_flight.unwrap_or(DEFAULT_IN_FLIGHT));
+
+    let (sender, mut receiver) = mpsc::channel::<Archived>(CHANNEL_SIZE);
+    let reader = tokio::spawn(async move {
+        for input in inputs {
+            info!("Reading slots {} to {} from {}", from_slot, to_slot, input);
+            read_archive(&input, from_slot, to_slot, source_topic.as_deref(), &sender).await?;
+        }
+        anyhow::Ok(())
+    });
+
+    let mut republished = 0u64;
+    while let Some((key, payload, slot)) = receiver.recv().await {
+        in_flight.push(sink.send_at(key, payload, Some(slot)).await?).await?;
+        republished += 1;
+    }
+    reader.await??;
+    in_flight.drain().await?;
+    sink.flush().await?;
+
+    info!("Republished {} records of slots {} to {} to topic '{}'", republished, from_slot, to_slot, topic);
+    Ok(())
+}
+
+async fn read_archive(
+    input: &str,
+    from_slot: u64,
+    to_slot: u64,
+    source_topic: Option<&str>,
+    sender: &mpsc::Sender<Archived>,
+) -> anyhow::Result<()> {
+    if input == "-" {
+        return read_lines(BufReader::new(tokio::io::stdin()), from_slot, to_slot, source_topic, sender).await;
+    }
+    if let Some(location) = input.strip_prefix("s3://") {
+        return read_s3(location, from_slot, to_slot, source_topic, sender).await;
+    }
+    let path = Path::new(input);
+    if path.extension().is_some_and(|extension| extension == "sqlite" || extension == "db" || extension == "duckdb") {
+        return read_capture(path, from_slot, to_slot, source_topic, sender).await;
+    }
+    let file = tokio::fs::File::open(path).await?;
+    read_lines(BufReader::new(file), from_slot, to_slot, source_topic, sender).await
+}
+
+/// Lines of `sink: stdout`, see `ArchivedLine`, keeping those of `source_topic`. Payloads are
+/// passed on byte for byte.
+async fn read_lines(
+    reader: impl AsyncBufRead + Unpin,
+    from_slot: u64,
+    to_slot: u64,
+    source_topic: Option<&str>,
+    sender: &mpsc::Sender<Archived>,
+) -> anyhow::Result<()> {
+    let mut lines = reader.lines();
+    let mut number = 0;
+    while let Some(line) = lines.next_line().await? {
+        number += 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(line) = serde_json::from_str::<ArchivedLine>(&line) else {
+            warn!("Skipping line {}, not an archived record", number);
+            continue;
+        };
+        if source_topic.is_some_and(|source_topic| line.topic != source_topic) {
+            continue;
+        }
+        // Lines written before the slot was archived next to the record carry it in the record,
+        // unless it is compressed or encrypted
+        let slot = line.slot.or_else(|| {
+            let record = line.record.as_ref()?;
+            serde_json::from_str::<Slotted>(record.get()).ok()?.slot
+        });
+        let Some(slot) = slot.filter(|slot| (from_slot..=to_slot).contains(slot)) else {
+            continue;
+        };
+        let payload = match line.payload() {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Skipping line {}: {:?}", number, e);
+                continue;
+            },
+        };
+        if sender.send((line.key, payload, slot)).await.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Lines of `sink: stdout` stored in the objects under `bucket/prefix`, read in key order, so
+/// archives shipped to S3 in rotated files are read from the oldest
+#[cfg(feature = "s3")]
+async fn read_s3(
+    location: &str,
+    from_slot: u64,
+    to_slot: u64,
+    source_topic: Option<&str>,
+    sender: &mpsc::Sender<Archived>,
+) -> anyhow::Result<()> {
+    let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+    let client = aws_sdk_s3::Client::new(&crate::sinks::aws_sdk_config(None).await);
+
+    let mut keys = Vec::new();
+    let mut pages = client.list_objects_v2().bucket(bucket).prefix(prefix).into_paginator().send();
+    while let Some(page) = pages.next().await {
+        keys.extend(page?.contents().iter().filter_map(|object| object.key().map(str::to_owned)));
+    }
+    keys.sort();
+    anyhow::ensure!(!keys.is_empty(), "no archive under s3://{}", location);
+
+    for key in keys {
+        info!("Reading s3://{}/{}", bucket, key);
+        let object = client.get_object().bucket(bucket).key(&key).send().await?;
+        let reader = BufReader::new(object.body.into_async_read());
+        read_lines(reader, from_slot, to_slot, source_topic, sender).await?;
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "s3"))]
+async fn read_s3(
+    location: &str,
+    _from_slot: u64,
+    _to_slot: u64,
+    _source_topic: Option<&str>,
+    _sender: &mpsc::Sender<Archived>,
+) -> anyhow::Result<()> {
+    anyhow::bail!("reading s3://{} requires the `s3` feature", location)
+}
+
+#[cfg(any(feature = "sqlite", feature = "duckdb"))]
+async fn read_capture(
+    path: &Path,
+    from_slot: u64,
+    to_slot: u64,
+    source_topic: Option<&str>,
+    sender: &mpsc::Sender<Archived>,
+) -> anyhow::Result<()> {
+    use crate::sinks::{scan_capture, LocalDbEngine};
+
+    if source_topic.is_some() {
+        warn!("--source-topic is ignored for capture files, which hold a single topic");
+    }
+    let engine = LocalDbEngine::from_path(path)
+        .ok_or_else(|| anyhow::anyhow!("built without the engine of {:?}, enable the `sqlite` or `duckdb` feature", path))?;
+    let path = path.to_owned();
+    let sender = sender.clone();
+    tokio::task::spawn_blocking(move || {
+        scan_capture(engine, &path, from_slot, to_slot, |key, slot, payload| {
+            sender.blocking_send((key, payload, slot)).map_err(|_| anyhow::anyhow!("republish stopped"))
+        })
+    })
+    .await?
+}
+
+#[cfg(not(any(feature = "sqlite", feature = "duckdb")))]
+async fn read_capture(
+    path: &Path,
+    _from_slot: u64,
+    _to_slot: u64,
+    _source_topic: Option<&str>,
+    _sender: &mpsc::Sender<Archived>,
+) -> anyhow::Result<()> {
+    anyhow::bail!("reading {:?} requires the `sqlite` or `duckdb` feature", path)
+}
@@ -0,0 +1,122 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use log::{error, info};
+use tokio::sync::watch;
+
+use crate::publisher::Publisher;
+
+/// Bounded retention settings
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    /// Keep records of the last `max_slots` slots
+    pub max_slots: Option<u64>,
+    /// Keep records published within `max_age`
+    pub max_age: Option<Duration>,
+    /// Time between two pruning passes
+    pub interval: Duration,
+}
+
+/// Records older than either bound are pruned
+#[derive(Debug, Clone, Copy)]
+// Only read by sinks storing records locally
+#[cfg_attr(not(any(feature = "sqlite", feature = "duckdb")), allow(dead_code))]
+pub struct RetentionCutoff {
+    pub before_slot: Option<u64>,
+    /// Milliseconds since the epoch
+    pub before_timestamp_ms: Option<i64>,
+}
+
+/// Decides when a pipeline prunes its sink and up to where
+pub struct Retention {
+    config: RetentionConfig,
+    newest_slot: u64,
+    last_run: Instant,
+}
+
+impl Retention {
+    pub fn new(config: RetentionConfig) -> Self {
+        Self {
+            config,
+            newest_slot: 0,
+            last_run: Instant::now(),
+        }
+    }
+
+    /// Cutoff to prune once `interval` elapsed, after observing `slot`
+    pub fn due(&mut self, slot: u64) -> Option<RetentionCutoff> {
+        self.newest_slot = self.newest_slot.max(slot);
+        if self.last_run.elapsed() < self.config.interval {
+            return None;
+        }
+        self.last_run = Instant::now();
+
+        Some(RetentionCutoff {
+            before_slot: self
+                .config
+                .max_slots
+                .map(|max_slots| self.newest_slot.saturating_sub(max_slots)),
+            before_timestamp_ms: self
+                .config
+                .max_age
+                .map(|max_age| Utc::now().timestamp_millis() - max_age.as_millis() as i64),
+        })
+    }
+}
+
+/// Prunes the sinks of a publisher in the background, one pass at a time, so the pipeline never
+/// waits for a pass. Cutoffs due while a pass runs are coalesced into the latest one.
+pub struct Pruner {
+    cutoffs: watch::Sender<Option<RetentionCutoff>>,
+}
+
+impl Pruner {
+    pub fn start(publisher: Arc<Publisher>) -> Self {
+        let (cutoffs, mut due) = watch::channel(None);
+        tokio::spawn(async move {
+            while due.changed().await.is_ok() {
+                let Some(cutoff) = *due.borrow_and_update() else {
+                    continue;
+                };
+                match publisher.prune(&cutoff).await {
+                    Ok(Some(pruned)) => info!("Pruned {} records older than {:?}", pruned, cutoff),
+                    Ok(None) => {
+                        info!("Sink does not prune records, retention is left to the broker");
+                        return;
+                    },
+                    Err(e) => error!("Error pruning records: {:?}", e),
+                }
+            }
+        });
+        Self { cutoffs }
+    }
+
+    /// Prune up to `cutoff` once the running pass is done, `false` when the sink does not prune
+    pub fn request(&self, cutoff: RetentionCutoff) -> bool {
+        self.cutoffs.send(Some(cutoff)).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_off_behind_the_newest_slot() {
+        let mut retention = Retention::new(RetentionConfig {
+            max_slots: Some(100),
+            max_age: None,
+            interval: Duration::ZERO,
+        });
+        assert_eq!(retention.due(50).and_then(|cutoff| cutoff.before_slot), Some(0));
+        assert_eq!(retention.due(1_000).and_then(|cutoff| cutoff.before_slot), Some(900));
+        // Late slots do not move the cutoff back
+        assert_eq!(retention.due(10).and_then(|cutoff| cutoff.before_slot), Some(900));
+
+        retention.config.interval = Duration::from_secs(3600);
+        assert!(retention.due(2_000).is_none());
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use log::{error, info};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    config::YellowstoneGrpcConfig,
+    formatters,
+    integrity::IntegrityChain,
+    publisher::{Delivery, Publisher},
+    sinks::{SinkConnector, SinkKind},
+};
+
+/// Kind of a published record
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Transaction,
+    Account,
+    BlockMeta,
+    BlockEnrichment,
+    /// Records of the stateful processors
+    State,
+}
+
+impl fmt::Display for RecordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RecordKind::Transaction => "transaction",
+            RecordKind::Account => "account update",
+            RecordKind::BlockMeta => "block metadata",
+            RecordKind::BlockEnrichment => "block enrichment",
+            RecordKind::State => "state record",
+        })
+    }
+}
+
+/// Shape of the records published to a route
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputProfile {
+    /// The record as published to the main topic
+    #[default]
+    Full,
+    /// Compact transaction records (signature, slot, status, fee, fee payer, programs and
+    /// token transfer count), other kinds as published to the main topic
+    Summary,
+}
+
+/// Routing rule settings
+#[derive(Debug, Clone)]
+pub struct RouteConfig {
+    pub topic: String,
+    pub kinds: Option<Vec<RecordKind>>,
+    pub profile: OutputProfile,
+    pub fields: Option<Vec<String>>,
+    /// Backends of the route when fanning out, all of them when `None`
+    pub sinks: Option<Vec<SinkKind>>,
+    /// Filter group whose records the route receives instead of the records of the main topic
+    pub group: Option<String>,
+}
+
+/// Filter groups with their own topic an update was matched by
+#[derive(Debug, Clone)]
+pub struct GroupMatch {
+    groups: Vec<String>,
+    /// Also matched by a filter of the main topic
+    main: bool,
+}
+
+impl GroupMatch {
+    /// Whether the record is published to the main topic and its routes
+    pub fn main(&self) -> bool {
+        self.main
+    }
+}
+
+/// Tells the updates matched by filter groups with their own topic from those of the main topic
+#[derive(Debug, Clone)]
+pub struct GroupRouter {
+    groups: HashSet<String>,
+}
+
+impl GroupRouter {
+    /// Router of the filter groups with a topic, `None` without any
+    pub fn new(config: &YellowstoneGrpcConfig) -> Option<Self> {
+        let groups: HashSet<String> = config.get_routes().into_iter().filter_map(|route| route.group).collect();
+        (!groups.is_empty()).then_some(Self { groups })
+    }
+
+    /// Groups of the filters an update matched, `None` when it only goes to the main topic
+    pub fn route(&self, filters: &[String]) -> Option<GroupMatch> {
+        let groups: Vec<String> = filters.iter().filter(|filter| self.groups.contains(*filter)).cloned().collect();
+        if groups.is_empty() {
+            return None;
+        }
+        // `client`, dynamic filters and groups without a topic feed the main topic
+        let main = groups.len() < filters.len();
+        Some(GroupMatch { groups, main })
+    }
+}
+
+/// Slots after which the destinations of a record that was never published are dropped, e.g.
+/// when its transaction was filtered out of every topic
+const MAX_GROUPED_SLOTS: u64 = 150;
+
+/// Destinations of the records of updates matched by filter groups, until they are published.
+/// Records are told apart by kind, slot and key, the pubkey for account updates, whose records of
+/// a transaction share its signature as key.
+#[derive(Default)]
+pub struct GroupedRecords {
+    matches: HashMap<(RecordKind, String, u64), GroupMatch>,
+    /// Newest slot inserted
+    newest: u64,
+}
+
+impl GroupedRecords {
+    /// Route the record of `kind` with `key` of `slot` to the groups of `matched`
+    pub fn insert(&mut self, kind: RecordKind, key: &str, slot: u64, matched: GroupMatch) {
+        if slot > self.newest {
+            self.newest = slot;
+            let cutoff = slot.saturating_sub(MAX_GROUPED_SLOTS);
+            self.matches.retain(|(_, _, slot), _| *slot >= cutoff);
+        }
+        self.matches.insert((kind, key.to_owned(), slot), matched);
+    }
+
+    /// Destinations of a record about to be published with `key`, `None` for the main topic
+    pub fn take(&mut self, kind: RecordKind, key: &str, record: &Value) -> Option<GroupMatch> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let key = match kind {
+            RecordKind::Account => record["pubkey"].as_str().unwrap_or(key),
+            _ => key,
+        };
+        self.matches.remove(&(kind, key.to_owned(), record["slot"].as_u64().unwrap_or_default()))
+    }
+}
+
+/// Extra destination topic receiving the records of some kinds in its own output profile
+pub struct Route {
+    publisher: Publisher,
+    kinds: Option<Vec<RecordKind>>,
+    profile: OutputProfile,
+    fields: Option<Vec<String>>,
+    group: Option<String>,
+    // Stamps the records as published to the route, after their profile and projection
+    integrity: Option<IntegrityChain>,
+}
+
+impl Route {
+    /// Whether records of `kind`, matched by the filter groups `matched`, are published to this
+    /// route: the records of its group, or else those of the main topic
+    pub fn matches(&self, kind: RecordKind, matched: Option<&GroupMatch>) -> bool {
+        let selected = match &self.group {
+            Some(group) => matched.is_some_and(|matched| matched.groups.contains(group)),
+            None => matched.map_or(true, GroupMatch::main),
+        };
+        selected && self.kinds.as_ref().map_or(true, |kinds| kinds.contains(&kind))
+    }
+
+    /// Publish a record to the route in its output profile, returning its acknowledgement.
+    /// `record` is not stamped, the route stamps its own rendering of it
+    pub async fn send(&mut self, key: String, record: &Value, kind: RecordKind) -> anyhow::Result<Delivery> {
+        let rendered = match (self.profile, kind) {
+            (OutputProfile::Summary, RecordKind::Transaction) => formatters::summarize_transaction(record),
+            _ => record.clone(),
+        };
+        let mut rendered = match &self.fields {
+            Some(fields) => project(&rendered, fields),
+            None => rendered,
+        };
+        let integrity_root = self.integrity.as_mut().and_then(|integrity| integrity.stamp(&mut rendered));
+        let delivery = self.publisher.send_tracked(key, &rendered).await?;
+        if let Some(root) = integrity_root {
+            self.publish_root(&root).await;
+        }
+        Ok(delivery)
+    }
+
+    pub async fn flush(&self) -> anyhow::Result<()> {
+        self.publisher.flush().await
+    }
+
+    /// Publish the root of the last partial integrity batch and flush, on shutdown
+    pub async fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some(root) = self.integrity.as_mut().and_then(IntegrityChain::finish) {
+            self.publish_root(&root).await;
+        }
+        self.flush().await
+    }
+
+    async fn publish_root(&self, root: &Value) {
+        if let Err(e) = self.publisher.send_control(root).await {
+            error!("Error publishing integrity root: {:?}", e);
+        }
+    }
+}
+
+/// Connect the configured routes, `suffix` is appended to their topics in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Vec<Route>> {
+    let mut routes = Vec::new();
+    for route in config.get_routes() {
+        let topic = match suffix {
+            Some(suffix) => format!("{}-{}", route.topic, suffix),
+            None => route.topic,
+        };
+        match &route.group {
+            Some(group) => info!("Routing records of filter group '{}' to topic '{}'", group, topic),
+            None => info!("Routing {:?} records to topic '{}' as {:?}", route.kinds, topic, route.profile),
+        }
+        routes.push(Route {
+            publisher: Publisher::connect_to(sinks, config, &topic, route.sinks.as_deref()).await?,
+            kinds: route.kinds,
+            profile: route.profile,
+            fields: route.fields,
+            group: route.group,
+            integrity: config.get_integrity_chain().map(|integrity| integrity.for_topic(&topic)),
+        });
+    }
+    Ok(routes)
+}
+
+/// Keep only the dotted `fields` paths of a record, e.g. `meta.fee`
+fn project(record: &Value, fields: &[String]) -> Value {
+    let mut projected = Value::Object(Map::new());
+    for field in fields {
+        let path: Vec<&str> = field.split('.').collect();
+        if let Some(value) = path.iter().try_fold(record, |value, name| value.get(name)) {
+            insert_path(&mut projected, &path, value.clone());
+        }
+    }
+    projected
+}
+
+fn insert_path(target: &mut Value, path: &[&str], value: Value) {
+    let Some(object) = target.as_object_mut() else {
+        return;
+    };
+    match path {
+        [name] => {
+            object.insert(name.to_string(), value);
+        },
+        [name, rest @ ..] => {
+            let child = object.entry(name.to_string()).or_insert_with(|| Value::Object(Map::new()));
+            insert_path(child, rest, value);
+        },
+        [] => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn matched(group: &str) -> GroupMatch {
+        GroupMatch {
+            groups: vec![group.to_string()],
+            main: false,
+        }
+    }
+
+    #[test]
+    fn tells_account_records_of_a_transaction_apart() {
+        let mut grouped = GroupedRecords::default();
+        grouped.insert(RecordKind::Account, "first", 10, matched("first"));
+        grouped.insert(RecordKind::Account, "second", 10, matched("second"));
+
+        // Both records are keyed by the signature of their transaction
+        let record = json!({ "pubkey": "second", "slot": 10 });
+        assert_eq!(grouped.take(RecordKind::Account, "signature", &record).unwrap().groups, ["second"]);
+        let record = json!({ "pubkey": "first", "slot": 10 });
+        assert_eq!(grouped.take(RecordKind::Account, "signature", &record).unwrap().groups, ["first"]);
+        assert!(grouped.matches.is_empty());
+    }
+
+    #[test]
+    fn expires_records_never_published() {
+        let mut grouped = GroupedRecords::default();
+        grouped.insert(RecordKind::Transaction, "filtered", 10, matched("group"));
+        grouped.insert(RecordKind::Transaction, "recent", 100, matched("group"));
+        grouped.insert(RecordKind::Transaction, "latest", 10 + MAX_GROUPED_SLOTS + 1, matched("group"));
+
+        assert!(grouped.take(RecordKind::Transaction, "filtered", &json!({ "slot": 10 })).is_none());
+        assert!(grouped.take(RecordKind::Transaction, "recent", &json!({ "slot": 100 })).is_some());
+    }
+}
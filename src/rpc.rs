@@ -0,0 +1,177 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use reqwest::Client;
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize,
+};
+use serde_json::{json, Value};
+use solana_transaction_status::UiConfirmedBlock;
+
+/// JSON-RPC error codes returned by `getBlock` when there is no block for the slot
+const BLOCK_NOT_AVAILABLE_CODES: [i64; 3] = [
+    -32004, // Block not available for slot
+    -32007, // Slot was skipped, or missing due to ledger jump to recent snapshot
+    -32009, // Slot was skipped, or missing in long-term storage
+];
+
+/// Error object returned by the JSON-RPC server
+#[derive(Debug, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RPC error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+/// Result of the methods answering with the slot they were evaluated at
+#[derive(Debug, Deserialize)]
+struct RpcContextValue<T> {
+    value: T,
+}
+
+/// Vote account from `getVoteAccounts`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcVoteAccount {
+    pub vote_pubkey: String,
+    pub node_pubkey: String,
+    pub activated_stake: u64,
+    pub commission: u8,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcVoteAccounts {
+    pub current: Vec<RpcVoteAccount>,
+    pub delinquent: Vec<RpcVoteAccount>,
+}
+
+/// Minimal Solana JSON-RPC client
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    client: Client,
+    endpoint: String,
+}
+
+impl RpcClient {
+    pub fn new(endpoint: String) -> Self {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .expect("Failed to create HTTP client");
+
+        Self { client, endpoint }
+    }
+
+    /// Call a JSON-RPC method and deserialize its result
+    pub async fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> anyhow::Result<T> {
+        let payload = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+
+        let response: RpcResponse<T> = self.client
+            .post(&self.endpoint)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if let Some(error) = response.error {
+            return Err(error.into());
+        }
+        response.result.ok_or_else(|| anyhow::anyhow!("empty result for {}", method))
+    }
+
+    /// Fetch a full block with base64 encoded transactions, `None` if the slot has no block
+    pub async fn get_block(&self, slot: u64) -> anyhow::Result<Option<UiConfirmedBlock>> {
+        let params = json!([slot, {
+            "encoding": "base64",
+            "transactionDetails": "full",
+            "rewards": true,
+            "maxSupportedTransactionVersion": 0,
+            "commitment": "finalized",
+        }]);
+
+        match self.call("getBlock", params).await {
+            Ok(block) => Ok(Some(block)),
+            Err(e) => match e.downcast_ref::<RpcError>() {
+                Some(error) if BLOCK_NOT_AVAILABLE_CODES.contains(&error.code) => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Leader schedule of the epoch containing `slot`, identity to slot indexes within the epoch
+    pub async fn get_leader_schedule(&self, slot: u64) -> anyhow::Result<HashMap<String, Vec<u64>>> {
+        self.call("getLeaderSchedule", json!([slot])).await
+    }
+
+    /// Current and delinquent vote accounts
+    pub async fn get_vote_accounts(&self) -> anyhow::Result<RpcVoteAccounts> {
+        self.call("getVoteAccounts", json!([])).await
+    }
+
+    /// Number of accounts owned by `program` matching the `getProgramAccounts` `filters`, fetched
+    /// without their data
+    pub async fn count_program_accounts(&self, program: &str, filters: &[Value]) -> anyhow::Result<u64> {
+        let params = json!([program, {
+            "encoding": "base64",
+            "dataSlice": { "offset": 0, "length": 0 },
+            "filters": filters,
+        }]);
+        let accounts: Vec<IgnoredAny> = self.call("getProgramAccounts", params).await?;
+        Ok(accounts.len() as u64)
+    }
+
+    /// Whether each of `pubkeys` exists, at most 100 per call
+    pub async fn accounts_exist(&self, pubkeys: &[String]) -> anyhow::Result<Vec<bool>> {
+        let params = json!([pubkeys, {
+            "encoding": "base64",
+            "dataSlice": { "offset": 0, "length": 0 },
+        }]);
+        let accounts: RpcContextValue<Vec<Option<IgnoredAny>>> = self.call("getMultipleAccounts", params).await?;
+        Ok(accounts.value.iter().map(Option::is_some).collect())
+    }
+
+    /// Validator names published on-chain through `solana validator-info`, by identity
+    pub async fn get_validator_names(&self) -> anyhow::Result<HashMap<String, String>> {
+        let accounts: Vec<Value> = self
+            .call(
+                "getProgramAccounts",
+                json!(["Config1111111111111111111111111111111111111", { "encoding": "jsonParsed" }]),
+            )
+            .await?;
+
+        Ok(accounts
+            .iter()
+            .filter_map(|account| {
+                let parsed = &account["account"]["data"]["parsed"];
+                if parsed["type"] != "validatorInfo" {
+                    return None;
+                }
+                let info = &parsed["info"];
+                // The second key is the validator identity that signed the info
+                let identity = info["keys"].get(1)?["pubkey"].as_str()?;
+                let name = info["configData"]["name"].as_str()?;
+                Some((identity.to_owned(), name.to_owned()))
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use anyhow::Context;
+use log::info;
+use schemars::{gen::SchemaSettings, JsonSchema};
+use serde_json::{json, Value};
+use yellowstone_grpc_records::{
+    Account, AccountClosed, BlockMeta, Control, ProgramDeploy, Slot, SolTransfer, Stamps, StakeEvent, TokenCreation,
+    Transaction, TransactionStatus, TransactionSummary, Vote,
+};
+
+/// JSON Schema of `T`, nested types inlined so each file stands alone
+fn schema_for<T: JsonSchema>() -> Value {
+    let generator = SchemaSettings::draft2019_09().with(|settings| settings.inline_subschemas = true).into_generator();
+    serde_json::to_value(generator.into_root_schema_for::<T>()).expect("schemas serialize to JSON")
+}
+
+/// Schema of the record type `name`, derived from its typed record `T`, with the fields the
+/// streamer stamps on every record. Other fields, e.g. added by enrichment, are allowed.
+fn record<T: JsonSchema>(name: &str) -> Value {
+    let mut schema = schema_for::<T>();
+    let stamps = schema_for::<Stamps>();
+    if let (Some(properties), Some(stamps)) = (schema["properties"].as_object_mut(), stamps["properties"].as_object()) {
+        properties.extend(stamps.clone());
+    }
+    schema["$id"] = json!(format!("{}.schema.json", name));
+    schema["title"] = json!(name);
+    schema["additionalProperties"] = json!(true);
+    schema
+}
+
+/// JSON Schema of every record type, by name
+pub fn schemas() -> Vec<(&'static str, Value)> {
+    vec![
+        ("transaction", record::<Transaction>("transaction")),
+        ("transaction_summary", record::<TransactionSummary>("transaction_summary")),
+        ("transaction_status", record::<TransactionStatus>("transaction_status")),
+        ("account", record::<Account>("account")),
+        ("slot", record::<Slot>("slot")),
+        ("block_meta", record::<BlockMeta>("block_meta")),
+        ("vote", record::<Vote>("vote")),
+        ("stake_event", record::<StakeEvent>("stake_event")),
+        ("sol_transfer", record::<SolTransfer>("sol_transfer")),
+        ("token_creation", record::<TokenCreation>("token_creation")),
+        ("program_deploy", record::<ProgramDeploy>("program_deploy")),
+        ("account_closed", record::<AccountClosed>("account_closed")),
+        ("control", record::<Control>("control")),
+    ]
+}
+
+/// JSON Schema of the record type `name`
+pub fn schema(name: &str) -> Option<Value> {
+    schemas().into_iter().find(|(schema, _)| *schema == name).map(|(_, schema)| schema)
+}
+
+/// Write every schema to `dir` as `<name>.schema.json`
+pub fn export(dir: &str) -> anyhow::Result<()> {
+    let dir = Path::new(dir);
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {:?}", dir))?;
+    for (name, schema) in schemas() {
+        let path = dir.join(format!("{}.schema.json", name));
+        std::fs::write(&path, serde_json::to_string_pretty(&schema)?)
+            .with_context(|| format!("failed to write {:?}", path))?;
+    }
+    info!("Exported {} record schemas to {:?}", schemas().len(), dir);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn required(schema: &Value) -> Vec<&str> {
+        let mut required: Vec<&str> = schema["required"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+        required.sort_unstable();
+        required
+    }
+
+    #[test]
+    fn derives_the_schemas_from_the_typed_records() {
+        let vote = schema("vote").unwrap();
+        assert_eq!(vote["$id"], "vote.schema.json");
+        assert_eq!(vote["description"], "Vote of a validator, on the vote topic");
+        assert_eq!(required(&vote), ["kind", "signature", "slot", "slots", "voteAccount"]);
+        let kinds = json!(["vote", "updateVoteState", "compactUpdateVoteState", "towerSync"]);
+        assert_eq!(vote["properties"]["kind"]["enum"], kinds);
+        assert!(vote["properties"]["streamerVersion"].is_object());
+
+        let account = schema("account").unwrap();
+        assert_eq!(required(&account), ["lamports", "owner", "pubkey", "slot"]);
+        assert!(account["properties"]["writeVersion"].is_object());
+        assert!(schema("control").unwrap()["properties"]["type"].is_object());
+        assert_eq!(schemas().len(), 13);
+    }
+}
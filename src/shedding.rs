@@ -0,0 +1,81 @@
+use std::{collections::HashSet, sync::Arc};
+
+use log::{info, warn};
+
+use crate::{metrics::Metrics, ProcessingMessage};
+
+/// Latency budget settings
+#[derive(Debug, Clone)]
+pub struct LatencyBudget {
+    pub max_lag_slots: u64,
+    pub priority_programs: Vec<String>,
+}
+
+/// Drops low-priority updates while the stream lags behind the upstream watermark.
+///
+/// Shedding starts once the lag exceeds `max_lag_slots` and stops when it is back under half
+/// of it, so the pipeline does not flap around the threshold. Vote transactions and account
+/// updates not owned by a priority program are shed.
+pub struct LoadShedder {
+    max_lag_slots: u64,
+    priority_programs: HashSet<Vec<u8>>,
+    metrics: Option<Arc<Metrics>>,
+    shedding: bool,
+}
+
+impl LoadShedder {
+    pub fn new(budget: LatencyBudget, metrics: Option<Arc<Metrics>>) -> Self {
+        let priority_programs = budget
+            .priority_programs
+            .iter()
+            .filter_map(|program| bs58::decode(program).into_vec().ok())
+            .collect();
+
+        Self {
+            max_lag_slots: budget.max_lag_slots,
+            priority_programs,
+            metrics,
+            shedding: false,
+        }
+    }
+
+    /// Update the current lag in slots
+    pub fn observe_lag(&mut self, lag: u64) {
+        if !self.shedding && lag > self.max_lag_slots {
+            warn!("Stream is {} slots behind (budget {}), shedding low-priority updates", lag, self.max_lag_slots);
+            self.shedding = true;
+        } else if self.shedding && lag <= self.max_lag_slots / 2 {
+            info!("Stream caught up ({} slots behind), no longer shedding", lag);
+            self.shedding = false;
+        }
+    }
+
+    /// Whether `message` should be dropped, counting it in the shed metrics
+    pub fn shed(&self, message: &ProcessingMessage) -> bool {
+        if !self.shedding {
+            return false;
+        }
+
+        match message {
+            ProcessingMessage::Transaction(msg) if msg.transaction.as_ref().is_some_and(|info| info.is_vote) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.increment_shed_votes();
+                }
+                true
+            },
+            ProcessingMessage::Account(msg) => {
+                let priority = msg
+                    .account
+                    .as_ref()
+                    .is_some_and(|account| self.priority_programs.contains(account.owner.as_slice()));
+                if !priority {
+                    if let Some(metrics) = &self.metrics {
+                        metrics.increment_shed_accounts();
+                    }
+                }
+                !priority
+            },
+            _ => false,
+        }
+    }
+}
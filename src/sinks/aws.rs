@@ -0,0 +1,23 @@
+use aws_config::{BehaviorVersion, Region, SdkConfig};
+#[cfg(any(feature = "sqs", feature = "sns"))]
+use sha2::{Digest, Sha256};
+
+/// Shared AWS settings of the region, with credentials from the default provider chain
+/// (environment, profile, instance role), from the environment or profile when unset
+pub async fn sdk_config(region: Option<&str>) -> SdkConfig {
+    let mut loader = aws_config::defaults(BehaviorVersion::latest());
+    if let Some(region) = region {
+        loader = loader.region(Region::new(region.to_owned()));
+    }
+    loader.load().await
+}
+
+/// Deduplication id of a FIFO message, from the `idempotencyKey` of the record when it has one,
+/// otherwise its body: identical records sent again within the deduplication window (e.g. after
+/// a reconnect) are delivered once
+#[cfg(any(feature = "sqs", feature = "sns"))]
+pub fn deduplication_id(body: &str) -> String {
+    let record: Option<serde_json::Value> = serde_json::from_str(body).ok();
+    let idempotency_key = record.as_ref().and_then(|record| record["idempotencyKey"].as_str());
+    hex::encode(Sha256::digest(idempotency_key.unwrap_or(body).as_bytes()))
+}
@@ -0,0 +1,35 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use futures::future::BoxFuture;
+
+use super::Sink;
+
+/// Opens the sink of a topic on a backend added without a `SinkKind` of its own
+pub type SinkFactory = Arc<dyn Fn(&str) -> BoxFuture<'static, anyhow::Result<Box<dyn Sink>>> + Send + Sync>;
+
+fn factories() -> &'static Mutex<HashMap<String, SinkFactory>> {
+    static FACTORIES: OnceLock<Mutex<HashMap<String, SinkFactory>>> = OnceLock::new();
+    FACTORIES.get_or_init(Default::default)
+}
+
+/// Make the backend opened by `factory` available as `sink: custom` with `custom_sink: <name>`.
+/// Call it at the top of `main`, before the config is loaded, replacing any factory of `name`.
+#[allow(dead_code)]
+pub fn register_custom(name: &str, factory: SinkFactory) {
+    factories().lock().expect("sink factories lock").insert(name.to_owned(), factory);
+}
+
+/// Factory registered as `name`
+pub(super) fn factory(name: &str) -> Option<SinkFactory> {
+    factories().lock().expect("sink factories lock").get(name).cloned()
+}
+
+/// Names of the registered factories, for error messages
+pub(super) fn registered() -> Vec<String> {
+    let mut names: Vec<String> = factories().lock().expect("sink factories lock").keys().cloned().collect();
+    names.sort_unstable();
+    names
+}
@@ -0,0 +1,409 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::{
+    future::{join_all, BoxFuture},
+    stream::FuturesOrdered,
+    FutureExt, StreamExt,
+};
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
+
+use super::{Delivery, Sink, SinkKind};
+use crate::{metrics::Metrics, retention::RetentionCutoff};
+
+/// What a fan-out does with a record when the queue of one of its sinks is full
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Backpressure {
+    /// Wait for room, the pipeline slows down to the pace of the sink
+    #[default]
+    Block,
+    /// Drop the record for this sink only, the others still get it
+    Drop,
+}
+
+/// Queue and circuit breaker of one sink of a fan-out
+#[derive(Debug, Clone, Copy)]
+pub struct SinkQueueConfig {
+    /// Records waiting to be sent to the sink
+    pub queue_size: usize,
+    pub backpressure: Backpressure,
+    /// Consecutive failed records opening the circuit
+    pub breaker_failures: u32,
+    /// Time records skip an open circuit before the sink is tried again
+    pub breaker_cooldown: Duration,
+}
+
+/// Retries, queues and circuit breakers of the sinks of a fan-out
+#[derive(Debug, Clone)]
+pub struct FanOutConfig {
+    /// Resends of a record to a sink after its first attempt failed
+    pub max_retries: u32,
+    /// Delay before the first resend, doubled on each following one
+    pub retry_backoff: Duration,
+    /// Records of a sink awaiting their acknowledgement or being resent at the same time
+    pub in_flight: usize,
+    /// Queue of the sinks without their own settings
+    pub queue: SinkQueueConfig,
+    pub sink_queues: HashMap<SinkKind, SinkQueueConfig>,
+    /// Sinks a record must be delivered to, every sink that blocks on a full queue when `None`
+    pub required: Option<Vec<SinkKind>>,
+}
+
+impl FanOutConfig {
+    /// Queue and circuit settings of the sink `kind`
+    pub fn queue(&self, kind: SinkKind) -> SinkQueueConfig {
+        self.sink_queues.get(&kind).copied().unwrap_or(self.queue)
+    }
+
+    fn required(&self, kind: SinkKind) -> bool {
+        match &self.required {
+            Some(required) => required.contains(&kind),
+            None => self.queue(kind).backpressure == Backpressure::Block,
+        }
+    }
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Stops sending to a sink after `failures` records in a row failed, and tries it again after
+/// `cooldown`: the next failure reopens the circuit, a success closes it
+struct Breaker {
+    failures: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl Breaker {
+    fn allows(&self) -> bool {
+        let state = self.state.lock().expect("circuit breaker lock");
+        state.open_until.map_or(true, |until| Instant::now() >= until)
+    }
+
+    /// Count the outcome of a record, returning whether the circuit opened or closed
+    fn record(&self, delivered: bool) -> Option<bool> {
+        let mut state = self.state.lock().expect("circuit breaker lock");
+        let was_open = state.open_until.is_some();
+        if delivered {
+            *state = BreakerState::default();
+            return was_open.then_some(false);
+        }
+        state.consecutive_failures += 1;
+        if state.consecutive_failures < self.failures {
+            return None;
+        }
+        state.open_until = Some(Instant::now() + self.cooldown);
+        (!was_open).then_some(true)
+    }
+}
+
+/// One sink of a fan-out, shared by its queue task and the deliveries it resolves
+struct Lane {
+    kind: SinkKind,
+    topic: String,
+    sink: Arc<dyn Sink>,
+    breaker: Breaker,
+    max_retries: u32,
+    retry_backoff: Duration,
+    in_flight: usize,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl Lane {
+    /// Resolve the first attempt of a record, resending it until it is acknowledged, out of
+    /// retries or the circuit opens
+    async fn deliver(
+        &self,
+        key: String,
+        payload: Vec<u8>,
+        slot: Option<u64>,
+        first: anyhow::Result<Delivery>,
+    ) -> anyhow::Result<()> {
+        let mut error = match first {
+            Ok(delivery) => match delivery.await {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            },
+            Err(e) => e,
+        };
+        let mut backoff = self.retry_backoff;
+        for attempt in 1..=self.max_retries {
+            if !self.breaker.allows() {
+                break;
+            }
+            warn!(
+                "{:?} sink failed for a record of topic '{}', retry {}/{}: {:?}",
+                self.kind, self.topic, attempt, self.max_retries, error
+            );
+            if let Some(metrics) = &self.metrics {
+                metrics.increment_producer_retries();
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+
+            let result = match self.sink.send_at(key.clone(), payload.clone(), slot).await {
+                Ok(delivery) => delivery.await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) => error = e,
+            }
+        }
+        Err(error.context(format!("{:?} sink gave up on a record of topic '{}'", self.kind, self.topic)))
+    }
+
+    fn record(&self, delivered: bool) {
+        let Some(open) = self.breaker.record(delivered) else {
+            return;
+        };
+        if open {
+            warn!(
+                "Circuit of the {:?} sink of topic '{}' opened, skipping it for {:?}",
+                self.kind, self.topic, self.breaker.cooldown
+            );
+        } else {
+            info!("Circuit of the {:?} sink of topic '{}' closed", self.kind, self.topic);
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.set_sink_circuit(self.kind, open);
+        }
+    }
+
+    fn settled(&self) {
+        if let Some(metrics) = &self.metrics {
+            metrics.remove_sink_pending(self.kind);
+        }
+    }
+}
+
+enum Command {
+    Record {
+        key: String,
+        payload: Vec<u8>,
+        slot: Option<u64>,
+        ack: oneshot::Sender<anyhow::Result<()>>,
+    },
+    Flush(oneshot::Sender<anyhow::Result<()>>),
+}
+
+type Acked = (oneshot::Sender<anyhow::Result<()>>, anyhow::Result<()>);
+
+/// Sends the queued records of one sink in order, awaiting up to `in_flight` of them at a time.
+/// A failed record is resent by its own delivery while the following ones go on, and its
+/// acknowledgement is only resolved after the ones of the records before it.
+async fn run_lane(lane: Arc<Lane>, mut commands: mpsc::Receiver<Command>) {
+    let mut in_flight: FuturesOrdered<BoxFuture<'static, Acked>> = FuturesOrdered::new();
+    loop {
+        let command = tokio::select! {
+            Some(acked) = in_flight.next() => {
+                settle(&lane, acked);
+                continue;
+            },
+            command = commands.recv(), if in_flight.len() < lane.in_flight => command,
+        };
+        let Some(command) = command else {
+            break;
+        };
+        match command {
+            Command::Record { key, payload, slot, ack } => {
+                if !lane.breaker.allows() {
+                    lane.settled();
+                    if let Some(metrics) = &lane.metrics {
+                        metrics.increment_sink_dropped(lane.kind);
+                    }
+                    let _ = ack.send(Err(anyhow::anyhow!("circuit of the {:?} sink is open", lane.kind)));
+                    continue;
+                }
+                let first = lane.sink.send_at(key.clone(), payload.clone(), slot).await;
+                let lane = Arc::clone(&lane);
+                in_flight.push_back(async move { (ack, lane.deliver(key, payload, slot, first).await) }.boxed());
+            },
+            // Records sent before, resends included, are acknowledged before the sink flushes
+            Command::Flush(done) => {
+                while let Some(acked) = in_flight.next().await {
+                    settle(&lane, acked);
+                }
+                let _ = done.send(lane.sink.flush().await);
+            },
+        }
+    }
+    while let Some(acked) = in_flight.next().await {
+        settle(&lane, acked);
+    }
+}
+
+fn settle(lane: &Lane, (ack, result): Acked) {
+    lane.record(result.is_ok());
+    lane.settled();
+    let _ = ack.send(result);
+}
+
+struct Target {
+    lane: Arc<Lane>,
+    queue: mpsc::Sender<Command>,
+    backpressure: Backpressure,
+    required: bool,
+}
+
+/// Publishes each record to several sinks at once. Each sink has its own queue, retries and
+/// circuit breaker, so a slow or failing webhook delays neither the other sinks nor the
+/// acknowledgement of their records. The record fails when one of the required sinks did not
+/// take it, failures of the other sinks are only logged.
+pub struct FanOutSink {
+    targets: Vec<Target>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl FanOutSink {
+    pub fn new(
+        topic: &str,
+        sinks: Vec<(SinkKind, Box<dyn Sink>)>,
+        config: FanOutConfig,
+        metrics: Option<Arc<Metrics>>,
+    ) -> Self {
+        let targets = sinks
+            .into_iter()
+            .map(|(kind, sink)| {
+                let queue = config.queue(kind);
+                let lane = Arc::new(Lane {
+                    kind,
+                    topic: topic.to_owned(),
+                    sink: Arc::from(sink),
+                    breaker: Breaker {
+                        failures: queue.breaker_failures,
+                        cooldown: queue.breaker_cooldown,
+                        state: Mutex::new(BreakerState::default()),
+                    },
+                    max_retries: config.max_retries,
+                    retry_backoff: config.retry_backoff,
+                    in_flight: config.in_flight.max(1),
+                    metrics: metrics.clone(),
+                });
+                if let Some(metrics) = &metrics {
+                    metrics.register_sink(kind);
+                }
+                let (sender, receiver) = mpsc::channel(queue.queue_size);
+                tokio::spawn(run_lane(Arc::clone(&lane), receiver));
+                Target {
+                    lane,
+                    queue: sender,
+                    backpressure: queue.backpressure,
+                    required: config.required(kind),
+                }
+            })
+            .collect();
+        Self { targets, metrics }
+    }
+}
+
+#[async_trait]
+impl Sink for FanOutSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        self.send_at(key, payload, None).await
+    }
+
+    async fn send_at(&self, key: String, payload: Vec<u8>, slot: Option<u64>) -> anyhow::Result<Delivery> {
+        let mut acks = Vec::with_capacity(self.targets.len());
+        for target in &self.targets {
+            let (ack, acked) = oneshot::channel();
+            let command = Command::Record {
+                key: key.clone(),
+                payload: payload.clone(),
+                slot,
+                ack,
+            };
+            if let Some(metrics) = &self.metrics {
+                metrics.add_sink_pending(target.lane.kind);
+            }
+            let queued = match target.backpressure {
+                Backpressure::Block => target.queue.send(command).await.is_ok(),
+                Backpressure::Drop => match target.queue.try_send(command) {
+                    Ok(()) => true,
+                    Err(TrySendError::Full(_)) => {
+                        if let Some(metrics) = &self.metrics {
+                            metrics.increment_sink_dropped(target.lane.kind);
+                        }
+                        false
+                    },
+                    Err(TrySendError::Closed(_)) => false,
+                },
+            };
+            if !queued {
+                target.lane.settled();
+            }
+            acks.push((target.lane.kind, target.required, queued.then_some(acked)));
+        }
+
+        // Resolved in the background, the delivery metrics do not depend on the caller awaiting
+        // the acknowledgement
+        let sinks = acks.len();
+        let metrics = self.metrics.clone();
+        let resolved = tokio::spawn(async move {
+            let results = join_all(acks.into_iter().map(|(kind, required, acked)| async move {
+                let result = match acked {
+                    Some(acked) => acked.await.unwrap_or_else(|_| Err(anyhow::anyhow!("{:?} sink closed", kind))),
+                    None => Err(anyhow::anyhow!("queue of the {:?} sink is full", kind)),
+                };
+                (required, result)
+            }))
+            .await;
+            let delivered = results.iter().filter(|(_, result)| result.is_ok()).count();
+            if let Some(metrics) = &metrics {
+                metrics.record_fanout(delivered, sinks);
+            }
+            let mut required_error = None;
+            for (required, result) in results {
+                match result {
+                    Err(e) if required && required_error.is_none() => required_error = Some(e),
+                    Err(e) => warn!("Record delivered to {} of {} sinks: {:?}", delivered, sinks, e),
+                    Ok(()) => {},
+                }
+            }
+            match required_error {
+                Some(e) => Err(e.context(format!("record delivered to {} of {} sinks", delivered, sinks))),
+                None => Ok(()),
+            }
+        });
+        Ok(async move { resolved.await? }.boxed())
+    }
+
+    /// Flush each sink once the records queued before are sent
+    async fn flush(&self) -> anyhow::Result<()> {
+        let flushed = join_all(self.targets.iter().map(|target| async move {
+            let (done, flushed) = oneshot::channel();
+            let closed = || anyhow::anyhow!("{:?} sink closed", target.lane.kind);
+            target.queue.send(Command::Flush(done)).await.map_err(|_| closed())?;
+            flushed.await?
+        }))
+        .await;
+        for result in flushed {
+            result?;
+        }
+        Ok(())
+    }
+
+    async fn prune(&self, cutoff: &RetentionCutoff) -> anyhow::Result<Option<u64>> {
+        let mut pruned = None;
+        for target in &self.targets {
+            if let Some(count) = target.lane.sink.prune(cutoff).await? {
+                pruned = Some(pruned.unwrap_or(0) + count);
+            }
+        }
+        Ok(pruned)
+    }
+}
@@ -0,0 +1,112 @@
+use std::{collections::HashSet, sync::Arc, time::Instant};
+
+use async_trait::async_trait;
+use fluvio::{
+    metadata::topic::{CleanupPolicy, SegmentBasedPolicy, TopicSpec},
+    Fluvio, RecordKey, TopicProducerConfigBuilder, TopicProducerPool,
+};
+use futures::FutureExt;
+use log::info;
+
+use super::{Delivery, Sink};
+use crate::{
+    batching::BatchingConfig,
+    metrics::{Metrics, ProducerErrorKind},
+    provision::ProvisionConfig,
+};
+
+/// Producer of one Fluvio topic
+pub struct FluvioSink {
+    producer: TopicProducerPool,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl FluvioSink {
+    /// Create the producer of `topic`, provisioned at startup
+    pub async fn open(
+        fluvio: &Fluvio,
+        topic: &str,
+        batching: Option<&BatchingConfig>,
+        metrics: Option<Arc<Metrics>>,
+    ) -> anyhow::Result<Self> {
+        let producer = match batching {
+            // The producer's own linger is the upper bound, the processor flushes earlier under low load
+            Some(batching) => {
+                let producer_config = TopicProducerConfigBuilder::default()
+                    .batch_size(batching.batch_size)
+                    .linger(batching.max_linger)
+                    .build()?;
+                fluvio.topic_producer_with_config(topic, producer_config).await?
+            },
+            None => fluvio.topic_producer(topic).await?,
+        };
+        Ok(Self { producer, metrics })
+    }
+
+    fn record_error(&self, error: &anyhow::Error) {
+        if let Some(metrics) = &self.metrics {
+            metrics.increment_producer_errors(ProducerErrorKind::classify(error));
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for FluvioSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        let output = match self.producer.send(RecordKey::from(key), payload).await {
+            Ok(output) => output,
+            Err(e) => {
+                let error = anyhow::Error::from(e);
+                self.record_error(&error);
+                return Err(error);
+            },
+        };
+
+        let metrics = self.metrics.clone();
+        let sent = Instant::now();
+        Ok(async move {
+            let result = output.wait().await.map_err(anyhow::Error::from);
+            if let Some(metrics) = &metrics {
+                match &result {
+                    Ok(_) => metrics.record_producer_ack(sent.elapsed()),
+                    Err(e) => metrics.increment_producer_errors(ProducerErrorKind::classify(e)),
+                }
+            }
+            result?;
+            Ok(())
+        }
+        .boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.producer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Create the missing `topics` after listing the existing ones, returning the created topics
+pub(crate) async fn provision_topics(
+    fluvio: &Fluvio,
+    topics: &[String],
+    config: &ProvisionConfig,
+) -> anyhow::Result<Vec<String>> {
+    let admin = fluvio.admin().await;
+    let existing: HashSet<String> = admin.all::<TopicSpec>().await?.into_iter().map(|topic| topic.name).collect();
+    let missing: Vec<String> = topics.iter().filter(|topic| !existing.contains(*topic)).cloned().collect();
+
+    if !config.create && !missing.is_empty() {
+        anyhow::bail!("missing topics with `provision.create` disabled: {}", missing.join(", "));
+    }
+    for topic in &missing {
+        info!("Topic '{}' does not exist. Creating it now...", topic);
+        let mut topic_spec = TopicSpec::new_computed(config.partitions, config.replicas, None);
+        if let Some(retention) = config.retention {
+            topic_spec.set_cleanup_policy(CleanupPolicy::Segment(SegmentBasedPolicy {
+                time_in_seconds: retention.as_secs().try_into().unwrap_or(u32::MAX),
+            }));
+        }
+        admin.create(topic.clone(), false, topic_spec).await?;
+    }
+
+    Ok(missing)
+}
@@ -0,0 +1,148 @@
+use std::{
+    io,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::{future, FutureExt};
+use log::{info, warn};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    sync::Mutex,
+};
+
+use super::{Delivery, Sink};
+
+/// Delay between connection attempts while the consumer is away
+const RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+/// Local IPC sink settings
+#[derive(Debug, Clone)]
+pub struct IpcConfig {
+    /// Unix socket path, or named pipe (`\\.\pipe\<name>`) on Windows
+    pub path: String,
+    /// How long a record waits for the consumer to come back before it is dropped
+    pub reconnect_timeout: Duration,
+}
+
+type Connection = Pin<Box<dyn AsyncWrite + Send>>;
+
+async fn connect(path: &str) -> io::Result<Connection> {
+    #[cfg(unix)]
+    {
+        Ok(Box::pin(tokio::net::UnixStream::connect(path).await?))
+    }
+    #[cfg(windows)]
+    {
+        Ok(Box::pin(tokio::net::windows::named_pipe::ClientOptions::new().open(path)?))
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        Err(io::Error::new(io::ErrorKind::Unsupported, format!("no local IPC for `{}` on this platform", path)))
+    }
+}
+
+#[derive(Default)]
+struct ConnectionState {
+    connection: Option<Connection>,
+    // Set while the consumer is away, records then wait at most until the reconnect timeout
+    down_since: Option<Instant>,
+}
+
+/// Connection to the consumer shared by the sinks of every topic, re-established when the
+/// consumer restarts
+pub struct IpcWriter {
+    config: IpcConfig,
+    state: Mutex<ConnectionState>,
+}
+
+impl IpcWriter {
+    pub fn new(config: IpcConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            state: Mutex::new(ConnectionState::default()),
+        })
+    }
+
+    /// Write one frame, reconnecting until the reconnect timeout while the consumer is away
+    async fn write(&self, frame: &[u8]) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+        loop {
+            if state.connection.is_none() {
+                match connect(&self.config.path).await {
+                    Ok(connection) => {
+                        info!("Connected to the IPC consumer at {}", self.config.path);
+                        state.connection = Some(connection);
+                        state.down_since = None;
+                    },
+                    Err(e) => {
+                        let down_since = *state.down_since.get_or_insert_with(Instant::now);
+                        if down_since.elapsed() >= self.config.reconnect_timeout {
+                            return Err(e).with_context(|| format!("IPC consumer at {} is away", self.config.path));
+                        }
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                        continue;
+                    },
+                }
+            }
+
+            let connection = state.connection.as_mut().expect("connected above");
+            match connection.write_all(frame).await {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    // The frame is written again in full on the next connection
+                    warn!("IPC consumer at {} disconnected: {}", self.config.path, e);
+                    state.connection = None;
+                    state.down_since.get_or_insert_with(Instant::now);
+                },
+            }
+        }
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        if let Some(connection) = &mut self.state.lock().await.connection {
+            connection.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes records to a local consumer as frames of a 4-byte big-endian length followed by a
+/// JSON object with the topic, key and record, like the stdout sink lines
+pub struct IpcSink {
+    topic: String,
+    writer: Arc<IpcWriter>,
+}
+
+impl IpcSink {
+    pub fn new(topic: &str, writer: Arc<IpcWriter>) -> Self {
+        Self {
+            topic: topic.to_owned(),
+            writer,
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for IpcSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        let record: Value = serde_json::from_slice(&payload)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&payload).into_owned()));
+        let body = serde_json::to_vec(&json!({ "topic": self.topic, "key": key, "record": record }))?;
+        let length = u32::try_from(body.len()).context("record too large for an IPC frame")?;
+
+        let mut frame = Vec::with_capacity(4 + body.len());
+        frame.extend_from_slice(&length.to_be_bytes());
+        frame.extend_from_slice(&body);
+        self.writer.write(&frame).await?;
+        Ok(future::ready(Ok(())).boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.writer.flush().await
+    }
+}
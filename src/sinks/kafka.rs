@@ -0,0 +1,174 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::FutureExt;
+use log::info;
+use rdkafka::{
+    admin::{AdminClient, AdminOptions, NewTopic, TopicReplication},
+    client::DefaultClientContext,
+    error::{KafkaError, KafkaResult, RDKafkaErrorCode},
+    producer::{FutureProducer, FutureRecord, Producer},
+    util::Timeout,
+    ClientConfig,
+};
+
+use super::{Delivery, Sink};
+use crate::provision::ProvisionConfig;
+
+/// Wait before retrying a record when the producer queue is full
+const QUEUE_FULL_BACKOFF: Duration = Duration::from_millis(10);
+/// Longest wait for the records in flight on flush and for broker metadata
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Kafka sink settings
+#[derive(Debug, Clone)]
+pub struct KafkaConfig {
+    /// Bootstrap brokers, `host:port` separated by commas
+    pub brokers: String,
+    /// Kafka topic of the records of each topic, with a `{topic}` placeholder
+    pub topic_template: String,
+    /// `0`, `1` or `all`
+    pub acks: String,
+    /// `none`, `gzip`, `snappy`, `lz4` or `zstd`
+    pub compression: String,
+    pub linger: Duration,
+    /// librdkafka properties set after the ones above, e.g. TLS and SASL settings
+    pub properties: HashMap<String, String>,
+}
+
+impl KafkaConfig {
+    fn client_config(&self) -> ClientConfig {
+        let mut client = ClientConfig::new();
+        client
+            .set("bootstrap.servers", &self.brokers)
+            .set("client.id", "yellowstone-grpc-json")
+            .set("acks", &self.acks)
+            .set("compression.type", &self.compression)
+            .set("linger.ms", self.linger.as_millis().to_string());
+        for (name, value) in &self.properties {
+            client.set(name, value);
+        }
+        client
+    }
+
+    /// Kafka topic of the records of `topic`
+    fn topic(&self, topic: &str) -> String {
+        self.topic_template.replace("{topic}", topic)
+    }
+}
+
+/// Producer shared by the sinks of every topic
+pub fn connect(config: &KafkaConfig) -> anyhow::Result<FutureProducer> {
+    let producer: FutureProducer = config.client_config().create().context("invalid Kafka producer settings")?;
+    info!("Publishing to the Kafka brokers {}", config.brokers);
+    Ok(producer)
+}
+
+/// Publishes the records of a topic to its Kafka topic, with their key as message key so the
+/// updates of an account stay ordered within a partition
+pub struct KafkaSink {
+    producer: FutureProducer,
+    topic: String,
+}
+
+impl KafkaSink {
+    pub fn open(producer: &FutureProducer, topic: &str, config: &KafkaConfig) -> Self {
+        Self {
+            producer: producer.clone(),
+            topic: config.topic(topic),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for KafkaSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        let mut record = FutureRecord::to(&self.topic).key(key.as_str());
+        // Tombstones are messages without a value, not with an empty one
+        if !payload.is_empty() {
+            record = record.payload(&payload);
+        }
+        let delivered = loop {
+            match self.producer.send_result(record) {
+                Ok(delivered) => break delivered,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), returned)) => {
+                    record = returned;
+                    tokio::time::sleep(QUEUE_FULL_BACKOFF).await;
+                },
+                Err((e, _)) => {
+                    return Err(e).with_context(|| format!("failed to produce to Kafka topic '{}'", self.topic))
+                },
+            }
+        };
+
+        let topic = self.topic.clone();
+        Ok(async move {
+            match delivered.await {
+                Ok(Ok(_)) => Ok(()),
+                Ok(Err((e, _))) => Err(anyhow::anyhow!("delivery to Kafka topic '{}' failed: {}", topic, e)),
+                Err(_) => Err(anyhow::anyhow!("Kafka producer of topic '{}' stopped", topic)),
+            }
+        }
+        .boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        let producer = self.producer.clone();
+        tokio::task::spawn_blocking(move || producer.flush(Timeout::After(REQUEST_TIMEOUT))).await??;
+        Ok(())
+    }
+}
+
+/// Create the missing Kafka topics of `topics`, returning the created ones
+pub(crate) async fn provision_topics(
+    producer: &FutureProducer,
+    kafka: &KafkaConfig,
+    topics: &[String],
+    config: &ProvisionConfig,
+) -> anyhow::Result<Vec<String>> {
+    let client = producer.clone();
+    let existing: HashSet<String> = tokio::task::spawn_blocking(move || {
+        let metadata = client.client().fetch_metadata(None, Timeout::After(REQUEST_TIMEOUT))?;
+        KafkaResult::Ok(metadata.topics().iter().map(|topic| topic.name().to_owned()).collect())
+    })
+    .await??;
+    let mut missing: Vec<String> =
+        topics.iter().map(|topic| kafka.topic(topic)).filter(|topic| !existing.contains(topic)).collect();
+    missing.sort_unstable();
+    missing.dedup();
+    if missing.is_empty() {
+        return Ok(missing);
+    }
+    if !config.create {
+        anyhow::bail!("missing topics with `provision.create` disabled: {}", missing.join(", "));
+    }
+
+    let admin: AdminClient<DefaultClientContext> =
+        kafka.client_config().create().context("invalid Kafka admin settings")?;
+    let retention_ms = config.retention.map(|retention| retention.as_millis().to_string());
+    let (partitions, replicas) = (config.partitions as i32, config.replicas as i32);
+    let new_topics: Vec<NewTopic> = missing
+        .iter()
+        .map(|topic| {
+            info!("Topic '{}' does not exist. Creating it now...", topic);
+            let new_topic = NewTopic::new(topic, partitions, TopicReplication::Fixed(replicas));
+            match &retention_ms {
+                Some(retention_ms) => new_topic.set("retention.ms", retention_ms),
+                None => new_topic,
+            }
+        })
+        .collect();
+    let options = AdminOptions::new().operation_timeout(Some(Timeout::After(REQUEST_TIMEOUT)));
+    for result in admin.create_topics(&new_topics, &options).await? {
+        match result {
+            Ok(_) | Err((_, RDKafkaErrorCode::TopicAlreadyExists)) => {},
+            Err((topic, code)) => anyhow::bail!("failed to create Kafka topic '{}': {}", topic, code),
+        }
+    }
+
+    Ok(missing)
+}
@@ -0,0 +1,197 @@
+use std::{collections::HashSet, time::Duration};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use aws_sdk_kinesis::{error::DisplayErrorContext, primitives::Blob, types::PutRecordsRequestEntry, Client};
+use futures::FutureExt;
+use log::warn;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+
+use super::{aws, Delivery, Sink};
+
+/// PutRecords limits of one request
+const MAX_BATCH_RECORDS: usize = 500;
+const MAX_BATCH_BYTES: usize = 5 * 1024 * 1024;
+/// Retries of the records of a batch failing with a throttling or internal error
+const MAX_PUT_RETRIES: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Kinesis sink settings
+#[derive(Debug, Clone)]
+pub struct KinesisConfig {
+    /// AWS region, from the environment or profile when unset
+    pub region: Option<String>,
+    /// Longest time a record waits for its batch to fill
+    pub linger: Duration,
+}
+
+/// Client of the configured region
+pub async fn connect(config: &KinesisConfig) -> Client {
+    Client::new(&aws::sdk_config(config.region.as_deref()).await)
+}
+
+type Ack = oneshot::Sender<anyhow::Result<()>>;
+
+enum Command {
+    Record(PutRecordsRequestEntry, usize, Ack),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Publishes the records of a topic to the Kinesis stream of the same name, with their key as
+/// partition key so the updates of an account stay ordered within a shard
+pub struct KinesisSink {
+    stream: String,
+    commands: mpsc::Sender<Command>,
+}
+
+impl KinesisSink {
+    pub fn open(client: &Client, stream: &str, config: &KinesisConfig) -> Self {
+        let (commands, receiver) = mpsc::channel(MAX_BATCH_RECORDS * 4);
+        tokio::spawn(batch_records(client.clone(), stream.to_owned(), config.linger, receiver));
+        Self {
+            stream: stream.to_owned(),
+            commands,
+        }
+    }
+
+    async fn command(&self, command: Command) -> anyhow::Result<()> {
+        self.commands
+            .send(command)
+            .await
+            .map_err(|_| anyhow::anyhow!("Kinesis producer of stream '{}' stopped", self.stream))
+    }
+}
+
+#[async_trait]
+impl Sink for KinesisSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        let size = key.len() + payload.len();
+        let entry = PutRecordsRequestEntry::builder()
+            .partition_key(key)
+            .data(Blob::new(payload))
+            .build()
+            .context("invalid Kinesis record")?;
+        let (ack, acknowledged) = oneshot::channel();
+        self.command(Command::Record(entry, size, ack)).await?;
+
+        let stream = self.stream.clone();
+        Ok(async move {
+            acknowledged
+                .await
+                .map_err(|_| anyhow::anyhow!("Kinesis producer of stream '{}' stopped", stream))?
+        }
+        .boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.command(Command::Flush(done)).await?;
+        flushed.await?;
+        Ok(())
+    }
+}
+
+/// Batch the records of a stream until PutRecords limits are reached or the oldest record
+/// waited for `linger`
+async fn batch_records(client: Client, stream: String, linger: Duration, mut commands: mpsc::Receiver<Command>) {
+    let mut batch: Vec<(PutRecordsRequestEntry, Ack)> = Vec::new();
+    let mut batch_bytes = 0;
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let command = match deadline {
+            Some(at) => match tokio::time::timeout_at(at, commands.recv()).await {
+                Ok(command) => command,
+                Err(_) => {
+                    put_records(&client, &stream, std::mem::take(&mut batch)).await;
+                    (batch_bytes, deadline) = (0, None);
+                    continue;
+                },
+            },
+            None => commands.recv().await,
+        };
+
+        match command {
+            Some(Command::Record(entry, size, ack)) => {
+                if batch_bytes + size > MAX_BATCH_BYTES {
+                    put_records(&client, &stream, std::mem::take(&mut batch)).await;
+                    (batch_bytes, deadline) = (0, None);
+                }
+                batch.push((entry, ack));
+                batch_bytes += size;
+                deadline.get_or_insert_with(|| Instant::now() + linger);
+                if batch.len() >= MAX_BATCH_RECORDS {
+                    put_records(&client, &stream, std::mem::take(&mut batch)).await;
+                    (batch_bytes, deadline) = (0, None);
+                }
+            },
+            Some(Command::Flush(done)) => {
+                put_records(&client, &stream, std::mem::take(&mut batch)).await;
+                (batch_bytes, deadline) = (0, None);
+                let _ = done.send(());
+            },
+            None => {
+                put_records(&client, &stream, batch).await;
+                return;
+            },
+        }
+    }
+}
+
+/// Put a batch, retrying the failed records, and acknowledge each record with its outcome.
+/// Once a record of a partition key fails, the later records of that key are resent after it,
+/// even those that were stored, so the records of a key are never stored out of order.
+async fn put_records(client: &Client, stream: &str, mut pending: Vec<(PutRecordsRequestEntry, Ack)>) {
+    let mut attempt = 0;
+    let error = loop {
+        if pending.is_empty() {
+            return;
+        }
+        let entries = pending.iter().map(|(entry, _)| entry.clone()).collect();
+        let error = match client.put_records().stream_name(stream).set_records(Some(entries)).send().await {
+            Ok(output) => {
+                // Results are in request order, failed records carry an error code
+                let mut failed = Vec::new();
+                let mut failed_keys = HashSet::new();
+                let mut error = String::new();
+                for ((entry, ack), result) in pending.into_iter().zip(output.records()) {
+                    if let Some(code) = result.error_code() {
+                        error = format!("{}: {}", code, result.error_message().unwrap_or_default());
+                        failed_keys.insert(entry.partition_key().to_owned());
+                        failed.push((entry, ack));
+                    } else if failed_keys.contains(entry.partition_key()) {
+                        failed.push((entry, ack));
+                    } else {
+                        let _ = ack.send(Ok(()));
+                    }
+                }
+                pending = failed;
+                if pending.is_empty() {
+                    return;
+                }
+                error
+            },
+            Err(e) => DisplayErrorContext(&e).to_string(),
+        };
+        if attempt >= MAX_PUT_RETRIES {
+            break error;
+        }
+        attempt += 1;
+        warn!(
+            "Retrying {} records to Kinesis stream '{}' ({}/{}): {}",
+            pending.len(),
+            stream,
+            attempt,
+            MAX_PUT_RETRIES,
+            error
+        );
+        tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+    };
+
+    for (_, ack) in pending {
+        let _ = ack.send(Err(anyhow::anyhow!("PutRecords to stream '{}' failed: {}", stream, error)));
+    }
+}
@@ -0,0 +1,634 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::FutureExt;
+use log::{error, info};
+use serde_json::Value;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+
+use super::{Delivery, Sink};
+use crate::retention::RetentionCutoff;
+
+/// Tables created in every capture file, records of other kinds go to `records`
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT,
+    slot BIGINT,
+    block_time BIGINT,
+    success BOOLEAN,
+    fee BIGINT,
+    fee_payer TEXT,
+    record TEXT,
+    received_ms BIGINT
+);
+CREATE TABLE IF NOT EXISTS accounts (
+    pubkey TEXT,
+    slot BIGINT,
+    owner TEXT,
+    lamports BIGINT,
+    data TEXT,
+    txn_signature TEXT,
+    record TEXT,
+    received_ms BIGINT
+);
+CREATE TABLE IF NOT EXISTS records (
+    key TEXT,
+    slot BIGINT,
+    record TEXT,
+    received_ms BIGINT
+);
+CREATE INDEX IF NOT EXISTS transactions_slot ON transactions (slot);
+CREATE INDEX IF NOT EXISTS accounts_slot ON accounts (slot);
+CREATE INDEX IF NOT EXISTS accounts_pubkey ON accounts (pubkey);
+";
+
+const INSERT_TRANSACTION: &str = "INSERT INTO transactions VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
+const INSERT_ACCOUNT: &str = "INSERT INTO accounts VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
+const INSERT_RECORD: &str = "INSERT INTO records VALUES (?, ?, ?, ?)";
+/// Block time of the transactions written before the block metadata of their slot
+const UPDATE_BLOCK_TIME: &str = "UPDATE transactions SET block_time = ? WHERE slot = ? AND block_time IS NULL";
+/// Slots whose block time is kept for the transactions written after their block metadata
+const BLOCK_TIME_SLOTS: usize = 512;
+const TABLES: [&str; 3] = ["transactions", "accounts", "records"];
+/// Key, slot and record of the rows of a slot range, keyed like the published records
+const SCAN: &str = "
+SELECT key, slot, record FROM (
+    SELECT signature AS key, slot, record, received_ms FROM transactions
+    UNION ALL SELECT coalesce(txn_signature, pubkey), slot, record, received_ms FROM accounts
+    UNION ALL SELECT key, slot, record, received_ms FROM records
+) WHERE slot BETWEEN ? AND ? ORDER BY received_ms
+";
+
+/// Embedded database engine of the capture files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalDbEngine {
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    #[cfg(feature = "duckdb")]
+    DuckDb,
+}
+
+impl LocalDbEngine {
+    /// Engine of a capture file, from its extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            #[cfg(feature = "sqlite")]
+            "sqlite" | "db" => Some(LocalDbEngine::Sqlite),
+            #[cfg(feature = "duckdb")]
+            "duckdb" => Some(LocalDbEngine::DuckDb),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            #[cfg(feature = "sqlite")]
+            LocalDbEngine::Sqlite => "sqlite",
+            #[cfg(feature = "duckdb")]
+            LocalDbEngine::DuckDb => "duckdb",
+        }
+    }
+
+    fn open(self, path: &Path) -> anyhow::Result<Box<dyn Backend>> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            LocalDbEngine::Sqlite => {
+                let connection = rusqlite::Connection::open(path)?;
+                connection.execute_batch(SCHEMA)?;
+                Ok(Box::new(connection))
+            },
+            #[cfg(feature = "duckdb")]
+            LocalDbEngine::DuckDb => {
+                let connection = duckdb::Connection::open(path)?;
+                connection.execute_batch(SCHEMA)?;
+                Ok(Box::new(connection))
+            },
+        }
+    }
+}
+
+/// Local database sink settings
+#[derive(Debug, Clone)]
+pub struct LocalDbConfig {
+    pub engine: LocalDbEngine,
+    /// Directory of the capture files
+    pub dir: PathBuf,
+    /// Start a new file every `rotate_slots` slots, one file per topic when unset
+    pub rotate_slots: Option<u64>,
+    /// Rows written in one database transaction
+    pub batch_size: usize,
+    /// Longest time a row waits for its batch to fill
+    pub linger: Duration,
+}
+
+/// Row of one of the capture tables, from the published record
+enum Row {
+    Transaction {
+        signature: Option<String>,
+        slot: Option<i64>,
+        block_time: Option<i64>,
+        success: bool,
+        fee: Option<i64>,
+        fee_payer: Option<String>,
+        record: String,
+        received_ms: i64,
+    },
+    Account {
+        pubkey: Option<String>,
+        slot: Option<i64>,
+        owner: Option<String>,
+        lamports: Option<i64>,
+        data: Option<String>,
+        txn_signature: Option<String>,
+        record: String,
+        received_ms: i64,
+    },
+    Record {
+        key: String,
+        slot: Option<i64>,
+        /// Block time of a block metadata record
+        block_time: Option<i64>,
+        record: String,
+        received_ms: i64,
+    },
+}
+
+impl Row {
+    /// Row of a record, `slot` is read from the record when it is not given
+    fn new(key: String, payload: Vec<u8>, slot: Option<u64>) -> Self {
+        let received_ms = Utc::now().timestamp_millis();
+        let value: Value = serde_json::from_slice(&payload).unwrap_or_default();
+        let record = String::from_utf8(payload).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+        let slot = slot.or(value["slot"].as_u64()).map(|slot| slot as i64);
+        let string = |value: &Value| value.as_str().map(str::to_owned);
+
+        if value["transaction"]["signatures"].is_array() {
+            let message = &value["transaction"]["message"];
+            Row::Transaction {
+                signature: string(&value["transaction"]["signatures"][0]),
+                slot,
+                block_time: value["blockTime"].as_i64(),
+                success: value["meta"]["err"].is_null(),
+                fee: value["meta"]["fee"].as_i64(),
+                // JsonParsed account keys are objects, other encodings plain strings
+                fee_payer: string(message["accountKeys"][0].get("pubkey").unwrap_or(&message["accountKeys"][0])),
+                record,
+                received_ms,
+            }
+        } else if value["pubkey"].is_string() && value["owner"].is_string() {
+            Row::Account {
+                pubkey: string(&value["pubkey"]),
+                slot,
+                owner: string(&value["owner"]),
+                lamports: value["lamports"].as_i64(),
+                data: string(&value["data"]),
+                txn_signature: string(&value["txn_signature"]),
+                record,
+                received_ms,
+            }
+        } else {
+            Row::Record {
+                key,
+                slot,
+                block_time: value.get("parentSlot").and(value["blockTime"].as_i64()),
+                record,
+                received_ms,
+            }
+        }
+    }
+
+    fn slot(&self) -> Option<u64> {
+        match self {
+            Row::Transaction { slot, .. } | Row::Account { slot, .. } | Row::Record { slot, .. } => {
+                slot.map(|slot| slot as u64)
+            },
+        }
+    }
+}
+
+/// Operations on an open capture file
+trait Backend: Send {
+    /// Insert rows in one transaction
+    fn insert(&mut self, rows: &[Row]) -> anyhow::Result<()>;
+
+    /// Delete the rows of slots before `before_slot` or received before `before_ms`
+    fn delete(&self, before_slot: i64, before_ms: i64) -> anyhow::Result<u64>;
+
+    fn count(&self) -> anyhow::Result<u64>;
+
+    /// Call `each` with the key, slot and record of the rows of slots `from_slot..=to_slot`, in
+    /// the order they were received
+    fn scan(
+        &self,
+        from_slot: i64,
+        to_slot: i64,
+        each: &mut dyn FnMut(String, u64, String) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()>;
+}
+
+// rusqlite and duckdb share their API, down to the `params!` macro
+macro_rules! impl_backend {
+    ($connection:ty, $engine:ident) => {
+        impl Backend for $connection {
+            fn insert(&mut self, rows: &[Row]) -> anyhow::Result<()> {
+                let transaction = self.transaction()?;
+                for row in rows {
+                    match row {
+                        Row::Transaction {
+                            signature,
+                            slot,
+                            block_time,
+                            success,
+                            fee,
+                            fee_payer,
+                            record,
+                            received_ms,
+                        } => transaction.execute(
+                            INSERT_TRANSACTION,
+                            $engine::params![signature, slot, block_time, success, fee, fee_payer, record, received_ms],
+                        )?,
+                        Row::Account {
+                            pubkey,
+                            slot,
+                            owner,
+                            lamports,
+                            data,
+                            txn_signature,
+                            record,
+                            received_ms,
+                        } => transaction.execute(
+                            INSERT_ACCOUNT,
+                            $engine::params![pubkey, slot, owner, lamports, data, txn_signature, record, received_ms],
+                        )?,
+                        Row::Record {
+                            key,
+                            slot,
+                            block_time,
+                            record,
+                            received_ms,
+                        } => {
+                            if block_time.is_some() {
+                                transaction.execute(UPDATE_BLOCK_TIME, $engine::params![block_time, slot])?;
+                            }
+                            transaction.execute(INSERT_RECORD, $engine::params![key, slot, record, received_ms])?
+                        },
+                    };
+                }
+                transaction.commit()?;
+                Ok(())
+            }
+
+            fn delete(&self, before_slot: i64, before_ms: i64) -> anyhow::Result<u64> {
+                let mut deleted = 0;
+                for table in TABLES {
+                    let sql = format!("DELETE FROM {} WHERE slot < ? OR received_ms < ?", table);
+                    deleted += self.execute(&sql, $engine::params![before_slot, before_ms])? as u64;
+                }
+                Ok(deleted)
+            }
+
+            fn count(&self) -> anyhow::Result<u64> {
+                let mut count = 0;
+                for table in TABLES {
+                    let sql = format!("SELECT count(*) FROM {}", table);
+                    count += self.query_row(&sql, [], |row| row.get::<_, i64>(0))? as u64;
+                }
+                Ok(count)
+            }
+
+            fn scan(
+                &self,
+                from_slot: i64,
+                to_slot: i64,
+                each: &mut dyn FnMut(String, u64, String) -> anyhow::Result<()>,
+            ) -> anyhow::Result<()> {
+                let mut statement = self.prepare(SCAN)?;
+                let mut rows = statement.query($engine::params![from_slot, to_slot])?;
+                while let Some(row) = rows.next()? {
+                    let key = row.get::<_, Option<String>>(0)?.unwrap_or_default();
+                    each(key, row.get::<_, i64>(1)? as u64, row.get::<_, String>(2)?)?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+#[cfg(feature = "sqlite")]
+impl_backend!(rusqlite::Connection, rusqlite);
+#[cfg(feature = "duckdb")]
+impl_backend!(duckdb::Connection, duckdb);
+
+/// Read the records of slots `from_slot..=to_slot` from a capture file, in the order they were
+/// received, with the key they were published with and their slot
+pub fn scan_capture(
+    engine: LocalDbEngine,
+    path: &Path,
+    from_slot: u64,
+    to_slot: u64,
+    mut each: impl FnMut(String, u64, Vec<u8>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(path.exists(), "capture file {:?} does not exist", path);
+    let connection = engine.open(path).with_context(|| format!("failed to open {:?}", path))?;
+    connection.scan(from_slot as i64, to_slot as i64, &mut |key, slot, record| each(key, slot, record.into_bytes()))
+}
+
+/// Record the block times of the block metadata rows and set them on the transaction rows of
+/// their slots written later; transactions written before are updated with the block metadata
+fn fill_block_times(block_times: &mut BTreeMap<u64, i64>, rows: &mut [Row]) {
+    for row in rows {
+        match row {
+            Row::Record {
+                slot: Some(slot),
+                block_time: Some(block_time),
+                ..
+            } => {
+                block_times.insert(*slot as u64, *block_time);
+                if block_times.len() > BLOCK_TIME_SLOTS {
+                    block_times.pop_first();
+                }
+            },
+            Row::Transaction {
+                slot: Some(slot),
+                block_time: block_time @ None,
+                ..
+            } => *block_time = block_times.get(&(*slot as u64)).copied(),
+            _ => {},
+        }
+    }
+}
+
+/// Capture files of one topic, `<topic>.<ext>` or `<topic>-<first slot>.<ext>` with rotation
+struct Store {
+    config: LocalDbConfig,
+    topic: String,
+    // First slot of the open file and its connection
+    current: Option<(u64, Box<dyn Backend>)>,
+    // Block time of the latest slots with block metadata
+    block_times: BTreeMap<u64, i64>,
+}
+
+impl Store {
+    fn path(&self, first_slot: u64) -> PathBuf {
+        let name = match self.config.rotate_slots {
+            Some(_) => format!("{}-{}.{}", self.topic, first_slot, self.config.engine.extension()),
+            None => format!("{}.{}", self.topic, self.config.engine.extension()),
+        };
+        self.config.dir.join(name)
+    }
+
+    /// First slot of the file of `slot`, rows without a slot go to the open file
+    fn partition(&self, slot: Option<u64>) -> u64 {
+        match (self.config.rotate_slots, slot) {
+            (Some(rotate_slots), Some(slot)) => slot - slot % rotate_slots,
+            (Some(_), None) => self.current.as_ref().map_or(0, |(first_slot, _)| *first_slot),
+            (None, _) => 0,
+        }
+    }
+
+    fn connection(&mut self, first_slot: u64) -> anyhow::Result<&mut Box<dyn Backend>> {
+        if self.current.as_ref().map(|(current, _)| *current) != Some(first_slot) {
+            let path = self.path(first_slot);
+            let connection = self.config.engine.open(&path).with_context(|| format!("failed to open {:?}", path))?;
+            if self.config.rotate_slots.is_some() {
+                info!("Capturing topic '{}' to {:?}", self.topic, path);
+            }
+            self.current = Some((first_slot, connection));
+        }
+        Ok(&mut self.current.as_mut().expect("opened above").1)
+    }
+
+    /// Insert rows in runs of the same file
+    fn write(&mut self, rows: &mut [Row]) -> anyhow::Result<()> {
+        fill_block_times(&mut self.block_times, rows);
+        let mut start = 0;
+        while start < rows.len() {
+            let first_slot = self.partition(rows[start].slot());
+            let end = rows[start..]
+                .iter()
+                .position(|row| self.partition(row.slot()) != first_slot)
+                .map_or(rows.len(), |length| start + length);
+            self.connection(first_slot)?.insert(&rows[start..end])?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    /// Delete the rows older than `cutoff`, and the rotated files entirely before it
+    fn prune(&mut self, cutoff: &RetentionCutoff) -> anyhow::Result<u64> {
+        let before_slot = cutoff.before_slot.map_or(0, |slot| slot as i64);
+        let before_ms = cutoff.before_timestamp_ms.unwrap_or(i64::MIN);
+        let Some(rotate_slots) = self.config.rotate_slots else {
+            return self.connection(0)?.delete(before_slot, before_ms);
+        };
+
+        let prefix = format!("{}-", self.topic);
+        let extension = format!(".{}", self.config.engine.extension());
+        let mut pruned = 0;
+        for entry in std::fs::read_dir(&self.config.dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let Some(first_slot) = name
+                .strip_prefix(&prefix)
+                .and_then(|name| name.strip_suffix(&extension))
+                .and_then(|first_slot| first_slot.parse::<u64>().ok())
+            else {
+                continue;
+            };
+
+            let current = self.current.as_ref().is_some_and(|(current, _)| *current == first_slot);
+            if !current && first_slot + rotate_slots <= before_slot as u64 {
+                let path = self.path(first_slot);
+                pruned += self.config.engine.open(&path)?.count()?;
+                std::fs::remove_file(&path)?;
+                // DuckDB's write-ahead log, if any
+                let _ = std::fs::remove_file(path.with_extension(format!("{}.wal", self.config.engine.extension())));
+                info!("Removed capture file {}", name);
+            } else if current {
+                pruned += self.connection(first_slot)?.delete(before_slot, before_ms)?;
+            } else {
+                pruned += self.config.engine.open(&self.path(first_slot))?.delete(before_slot, before_ms)?;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+type Ack = oneshot::Sender<anyhow::Result<()>>;
+
+enum Command {
+    Row(Row, Ack),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes the records of a topic to SQLite or DuckDB capture files for offline analysis:
+/// transactions and account updates in their own tables with their main fields as columns,
+/// other records in `records`, each with the full record JSON
+pub struct LocalDbSink {
+    topic: String,
+    store: Arc<Mutex<Store>>,
+    commands: mpsc::Sender<Command>,
+}
+
+impl LocalDbSink {
+    pub fn open(topic: &str, config: &LocalDbConfig) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&config.dir).with_context(|| format!("failed to create {:?}", config.dir))?;
+        let store = Arc::new(Mutex::new(Store {
+            config: config.clone(),
+            topic: topic.to_owned(),
+            current: None,
+            block_times: BTreeMap::new(),
+        }));
+        let (commands, receiver) = mpsc::channel(config.batch_size * 4);
+        tokio::spawn(batch_rows(Arc::clone(&store), config.batch_size, config.linger, receiver));
+        Ok(Self {
+            topic: topic.to_owned(),
+            store,
+            commands,
+        })
+    }
+
+    async fn command(&self, command: Command) -> anyhow::Result<()> {
+        self.commands
+            .send(command)
+            .await
+            .map_err(|_| anyhow::anyhow!("capture writer of topic '{}' stopped", self.topic))
+    }
+}
+
+#[async_trait]
+impl Sink for LocalDbSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        self.send_at(key, payload, None).await
+    }
+
+    async fn send_at(&self, key: String, payload: Vec<u8>, slot: Option<u64>) -> anyhow::Result<Delivery> {
+        let (ack, acknowledged) = oneshot::channel();
+        self.command(Command::Row(Row::new(key, payload, slot), ack)).await?;
+
+        let topic = self.topic.clone();
+        Ok(async move {
+            acknowledged
+                .await
+                .map_err(|_| anyhow::anyhow!("capture writer of topic '{}' stopped", topic))?
+        }
+        .boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        let (done, flushed) = oneshot::channel();
+        self.command(Command::Flush(done)).await?;
+        flushed.await?;
+        Ok(())
+    }
+
+    async fn prune(&self, cutoff: &RetentionCutoff) -> anyhow::Result<Option<u64>> {
+        let store = Arc::clone(&self.store);
+        let cutoff = *cutoff;
+        let pruned = tokio::task::spawn_blocking(move || store.lock().expect("capture store lock").prune(&cutoff)).await??;
+        Ok(Some(pruned))
+    }
+}
+
+/// Batch rows until `batch_size` or until the oldest row waited for `linger`
+async fn batch_rows(store: Arc<Mutex<Store>>, batch_size: usize, linger: Duration, mut commands: mpsc::Receiver<Command>) {
+    let mut batch: Vec<(Row, Ack)> = Vec::new();
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let command = match deadline {
+            Some(at) => match tokio::time::timeout_at(at, commands.recv()).await {
+                Ok(command) => command,
+                Err(_) => {
+                    write_rows(&store, std::mem::take(&mut batch)).await;
+                    deadline = None;
+                    continue;
+                },
+            },
+            None => commands.recv().await,
+        };
+
+        match command {
+            Some(Command::Row(row, ack)) => {
+                batch.push((row, ack));
+                deadline.get_or_insert_with(|| Instant::now() + linger);
+                if batch.len() >= batch_size {
+                    write_rows(&store, std::mem::take(&mut batch)).await;
+                    deadline = None;
+                }
+            },
+            Some(Command::Flush(done)) => {
+                write_rows(&store, std::mem::take(&mut batch)).await;
+                deadline = None;
+                let _ = done.send(());
+            },
+            None => {
+                write_rows(&store, batch).await;
+                return;
+            },
+        }
+    }
+}
+
+/// Write a batch on a blocking thread and acknowledge its rows with the outcome
+async fn write_rows(store: &Arc<Mutex<Store>>, batch: Vec<(Row, Ack)>) {
+    if batch.is_empty() {
+        return;
+    }
+    let store = Arc::clone(store);
+    let (mut rows, acks): (Vec<Row>, Vec<Ack>) = batch.into_iter().unzip();
+    let result = tokio::task::spawn_blocking(move || store.lock().expect("capture store lock").write(&mut rows))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result);
+
+    if let Err(e) = &result {
+        error!("Error writing {} rows to the capture file: {:?}", acks.len(), e);
+    }
+    for ack in acks {
+        let _ = ack.send(result.as_ref().map(|_| ()).map_err(|e| anyhow::anyhow!("{:#}", e)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn row(record: Value) -> Row {
+        Row::new("key".to_string(), serde_json::to_vec(&record).unwrap(), None)
+    }
+
+    fn block_time(row: &Row) -> Option<i64> {
+        match row {
+            Row::Transaction { block_time, .. } | Row::Record { block_time, .. } => *block_time,
+            Row::Account { .. } => None,
+        }
+    }
+
+    #[test]
+    fn sets_the_block_time_of_transactions_from_block_metadata() {
+        let transaction = |slot: u64| row(json!({ "slot": slot, "transaction": { "signatures": ["signature"] } }));
+        let mut rows = vec![
+            row(json!({ "slot": 7, "blockhash": "hash", "parentSlot": 6, "blockTime": 1_700_000_000 })),
+            transaction(7),
+            transaction(8),
+            row(json!({ "slot": 7, "blockTime": 1 })),
+        ];
+        let mut block_times = BTreeMap::new();
+        fill_block_times(&mut block_times, &mut rows);
+        let filled: Vec<_> = rows.iter().map(block_time).collect();
+        assert_eq!(filled, [Some(1_700_000_000), Some(1_700_000_000), None, None]);
+        assert_eq!(block_times, BTreeMap::from([(7, 1_700_000_000)]));
+    }
+}
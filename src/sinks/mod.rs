@@ -0,0 +1,468 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+#[cfg(feature = "fluvio")]
+use crate::batching::BatchingConfig;
+use crate::{config::YellowstoneGrpcConfig, metrics::Metrics, provision::ProvisionConfig, retention::RetentionCutoff};
+
+#[cfg(any(feature = "kinesis", feature = "sqs", feature = "sns", feature = "s3"))]
+mod aws;
+mod custom;
+mod fanout;
+#[cfg(feature = "fluvio")]
+mod fluvio_sink;
+mod ipc;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "kinesis")]
+mod kinesis;
+#[cfg(any(feature = "sqlite", feature = "duckdb"))]
+mod local_db;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod notify;
+#[cfg(feature = "pubsub")]
+mod pubsub;
+#[cfg(feature = "sns")]
+mod sns;
+#[cfg(feature = "sqs")]
+mod sqs;
+mod stdout;
+
+#[cfg(feature = "s3")]
+pub use aws::sdk_config as aws_sdk_config;
+pub use custom::{register_custom, SinkFactory};
+pub use fanout::{Backpressure, FanOutConfig, SinkQueueConfig};
+use fanout::FanOutSink;
+#[cfg(feature = "fluvio")]
+use fluvio_sink::FluvioSink;
+pub use ipc::IpcConfig;
+use ipc::{IpcSink, IpcWriter};
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaConfig;
+#[cfg(feature = "kafka")]
+use kafka::KafkaSink;
+#[cfg(feature = "kinesis")]
+pub use kinesis::KinesisConfig;
+#[cfg(feature = "kinesis")]
+use kinesis::KinesisSink;
+#[cfg(any(feature = "sqlite", feature = "duckdb"))]
+pub use local_db::{scan_capture, LocalDbConfig, LocalDbEngine};
+#[cfg(any(feature = "sqlite", feature = "duckdb"))]
+use local_db::LocalDbSink;
+#[cfg(feature = "mqtt")]
+pub use mqtt::MqttConfig;
+#[cfg(feature = "mqtt")]
+use mqtt::{MqttConnection, MqttSink};
+pub use notify::{NotifyConfig, NotifyTarget};
+use notify::{NotifySink, Notifier};
+#[cfg(feature = "pubsub")]
+pub use pubsub::PubSubConfig;
+#[cfg(feature = "pubsub")]
+use pubsub::PubSubSink;
+#[cfg(feature = "sns")]
+pub use sns::SnsConfig;
+#[cfg(feature = "sns")]
+use sns::SnsSink;
+#[cfg(feature = "sqs")]
+pub use sqs::SqsConfig;
+#[cfg(feature = "sqs")]
+use sqs::SqsSink;
+pub use stdout::ArchivedLine;
+use stdout::StdoutSink;
+
+/// Acknowledgement of the records produced for one published record
+pub type Delivery = BoxFuture<'static, anyhow::Result<()>>;
+
+/// Destination of the records of one topic. The publisher, and through it the processors, only
+/// see `Box<dyn Sink>`: a new backend implements it in its own module, gets a `SinkKind` variant
+/// and is opened by `SinkConnector::open_backend`, without touching the processing loops. A
+/// backend kept out of the tree registers a `SinkFactory` with `register_custom` instead, and
+/// is selected with `sink: custom`.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Send a record without waiting for its acknowledgement
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery>;
+
+    /// Send a record of `slot`. Sinks archiving records (`stdout`, `sqlite`, `duckdb`) keep the
+    /// slot next to the payload, which can be compressed or encrypted; the others ignore it.
+    async fn send_at(&self, key: String, payload: Vec<u8>, _slot: Option<u64>) -> anyhow::Result<Delivery> {
+        self.send(key, payload).await
+    }
+
+    /// Flush records buffered by the sink
+    async fn flush(&self) -> anyhow::Result<()>;
+
+    /// Delete the records older than `cutoff`, for sinks storing records locally. Returns the
+    /// number of pruned records, `None` when the sink does not prune (e.g. broker-side retention)
+    async fn prune(&self, _cutoff: &RetentionCutoff) -> anyhow::Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// Where records are published
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SinkKind {
+    /// Fluvio topics, requires the `fluvio` feature
+    #[default]
+    Fluvio,
+    /// JSON lines on stdout with the topic, key and record of each record
+    Stdout,
+    /// Length-prefixed JSON frames on a Unix socket, or a named pipe on Windows, for a colocated
+    /// consumer, see `ipc`
+    Ipc,
+    /// MQTT broker, one message per record on a templated topic, requires the `mqtt` feature,
+    /// see `mqtt`
+    Mqtt,
+    /// Amazon Kinesis streams named after the topics, requires the `kinesis` feature
+    Kinesis,
+    /// Google Cloud Pub/Sub topics, requires the `pubsub` feature
+    #[serde(rename = "pubsub")]
+    PubSub,
+    /// Amazon SQS queues named after the topics, for low-volume filtered streams, requires the
+    /// `sqs` feature
+    Sqs,
+    /// Amazon SNS topics named after the topics, for low-volume filtered streams, requires the
+    /// `sns` feature
+    Sns,
+    /// SQLite capture files for research, see `local_db`, requires the `sqlite` feature
+    Sqlite,
+    /// DuckDB capture files for research, see `local_db`, requires the `duckdb` feature
+    #[serde(rename = "duckdb")]
+    DuckDb,
+    /// Messages rendered from the records posted to a Discord webhook, see `notify`
+    Discord,
+    /// Messages rendered from the records posted to a Telegram chat, see `notify`
+    Telegram,
+    /// Kafka topics named after the topics, see `kafka`, requires the `kafka` feature
+    Kafka,
+    /// Backend registered with `register_custom` under the name in `custom_sink`
+    Custom,
+}
+
+// Names are only used in reported metrics
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+impl SinkKind {
+    pub const ALL: [SinkKind; 14] = [
+        SinkKind::Fluvio,
+        SinkKind::Stdout,
+        SinkKind::Ipc,
+        SinkKind::Mqtt,
+        SinkKind::Kinesis,
+        SinkKind::PubSub,
+        SinkKind::Sqs,
+        SinkKind::Sns,
+        SinkKind::Sqlite,
+        SinkKind::DuckDb,
+        SinkKind::Discord,
+        SinkKind::Telegram,
+        SinkKind::Kafka,
+        SinkKind::Custom,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Whether the backend carries payloads byte-exact, text backends and the ones parsing the
+    /// records do not
+    pub fn binary(self) -> bool {
+        matches!(
+            self,
+            SinkKind::Fluvio | SinkKind::Mqtt | SinkKind::Kinesis | SinkKind::PubSub | SinkKind::Kafka | SinkKind::Custom
+        )
+    }
+
+    /// Name of the backend in the config
+    pub fn name(self) -> &'static str {
+        match self {
+            SinkKind::Fluvio => "fluvio",
+            SinkKind::Stdout => "stdout",
+            SinkKind::Ipc => "ipc",
+            SinkKind::Mqtt => "mqtt",
+            SinkKind::Kinesis => "kinesis",
+            SinkKind::PubSub => "pubsub",
+            SinkKind::Sqs => "sqs",
+            SinkKind::Sns => "sns",
+            SinkKind::Sqlite => "sqlite",
+            SinkKind::DuckDb => "duckdb",
+            SinkKind::Discord => "discord",
+            SinkKind::Telegram => "telegram",
+            SinkKind::Kafka => "kafka",
+            SinkKind::Custom => "custom",
+        }
+    }
+}
+
+/// Opens the sink of each topic on the configured backend
+pub enum SinkConnector {
+    #[cfg(feature = "fluvio")]
+    Fluvio {
+        fluvio: ::fluvio::Fluvio,
+        batching: Option<BatchingConfig>,
+        metrics: Option<Arc<Metrics>>,
+    },
+    Stdout,
+    Ipc(Arc<IpcWriter>),
+    #[cfg(feature = "mqtt")]
+    Mqtt(Arc<MqttConnection>),
+    #[cfg(feature = "kinesis")]
+    Kinesis {
+        client: aws_sdk_kinesis::Client,
+        config: KinesisConfig,
+    },
+    #[cfg(feature = "pubsub")]
+    PubSub {
+        client: google_cloud_pubsub::client::Client,
+        config: PubSubConfig,
+    },
+    #[cfg(feature = "sqs")]
+    Sqs {
+        client: aws_sdk_sqs::Client,
+        config: SqsConfig,
+    },
+    #[cfg(feature = "sns")]
+    Sns {
+        client: aws_sdk_sns::Client,
+        config: SnsConfig,
+    },
+    #[cfg(any(feature = "sqlite", feature = "duckdb"))]
+    LocalDb(LocalDbConfig),
+    Notify(Arc<Notifier>),
+    #[cfg(feature = "kafka")]
+    Kafka {
+        producer: rdkafka::producer::FutureProducer,
+        config: KafkaConfig,
+    },
+    Custom(SinkFactory),
+    /// Several backends receiving every record, see `fanout`
+    FanOut {
+        connectors: Vec<(SinkKind, SinkConnector)>,
+        config: FanOutConfig,
+        metrics: Option<Arc<Metrics>>,
+    },
+}
+
+impl SinkConnector {
+    /// Connect the configured backends, `metrics` tracks the health of their producers
+    pub async fn connect(config: &YellowstoneGrpcConfig, metrics: Option<Arc<Metrics>>) -> anyhow::Result<Self> {
+        let kinds = config.get_sink_kinds();
+        if let &[kind] = kinds.as_slice() {
+            return Self::connect_backend(config, kind, metrics).await;
+        }
+        let mut connectors = Vec::new();
+        for kind in kinds {
+            connectors.push((kind, Self::connect_backend(config, kind, metrics.clone()).await?));
+        }
+        Ok(Self::FanOut {
+            connectors,
+            config: config.get_fanout_config(),
+            metrics,
+        })
+    }
+
+    #[cfg_attr(not(feature = "fluvio"), allow(unused_variables))]
+    async fn connect_backend(
+        config: &YellowstoneGrpcConfig,
+        kind: SinkKind,
+        metrics: Option<Arc<Metrics>>,
+    ) -> anyhow::Result<Self> {
+        match kind {
+            #[cfg(feature = "fluvio")]
+            SinkKind::Fluvio => Ok(Self::Fluvio {
+                fluvio: ::fluvio::Fluvio::connect().await?,
+                batching: config.get_batching_config(),
+                metrics,
+            }),
+            #[cfg(not(feature = "fluvio"))]
+            SinkKind::Fluvio => anyhow::bail!("built without the `fluvio` feature, set `sink: stdout`"),
+            SinkKind::Stdout => Ok(Self::Stdout),
+            SinkKind::Ipc => {
+                let ipc = config.get_ipc_config().ok_or_else(|| anyhow::anyhow!("`sink: ipc` requires `ipc.path`"))?;
+                Ok(Self::Ipc(IpcWriter::new(ipc)))
+            },
+            #[cfg(feature = "mqtt")]
+            SinkKind::Mqtt => {
+                let mqtt = config.get_mqtt_config().ok_or_else(|| anyhow::anyhow!("`sink: mqtt` requires `mqtt.host`"))?;
+                Ok(Self::Mqtt(MqttConnection::connect(mqtt)))
+            },
+            #[cfg(not(feature = "mqtt"))]
+            SinkKind::Mqtt => anyhow::bail!("built without the `mqtt` feature"),
+            #[cfg(feature = "kinesis")]
+            SinkKind::Kinesis => {
+                let kinesis = config.get_kinesis_config();
+                Ok(Self::Kinesis {
+                    client: kinesis::connect(&kinesis).await,
+                    config: kinesis,
+                })
+            },
+            #[cfg(not(feature = "kinesis"))]
+            SinkKind::Kinesis => anyhow::bail!("built without the `kinesis` feature"),
+            #[cfg(feature = "pubsub")]
+            SinkKind::PubSub => {
+                let pubsub = config.get_pubsub_config();
+                Ok(Self::PubSub {
+                    client: pubsub::connect(&pubsub).await?,
+                    config: pubsub,
+                })
+            },
+            #[cfg(not(feature = "pubsub"))]
+            SinkKind::PubSub => anyhow::bail!("built without the `pubsub` feature"),
+            #[cfg(feature = "sqs")]
+            SinkKind::Sqs => {
+                let sqs = config.get_sqs_config();
+                Ok(Self::Sqs {
+                    client: sqs::connect(&sqs).await,
+                    config: sqs,
+                })
+            },
+            #[cfg(not(feature = "sqs"))]
+            SinkKind::Sqs => anyhow::bail!("built without the `sqs` feature"),
+            #[cfg(feature = "sns")]
+            SinkKind::Sns => {
+                let sns = config.get_sns_config();
+                Ok(Self::Sns {
+                    client: sns::connect(&sns).await,
+                    config: sns,
+                })
+            },
+            #[cfg(not(feature = "sns"))]
+            SinkKind::Sns => anyhow::bail!("built without the `sns` feature"),
+            #[cfg(feature = "sqlite")]
+            SinkKind::Sqlite => Ok(Self::LocalDb(local_db_config(config, LocalDbEngine::Sqlite)?)),
+            #[cfg(not(feature = "sqlite"))]
+            SinkKind::Sqlite => anyhow::bail!("built without the `sqlite` feature"),
+            #[cfg(feature = "duckdb")]
+            SinkKind::DuckDb => Ok(Self::LocalDb(local_db_config(config, LocalDbEngine::DuckDb)?)),
+            #[cfg(not(feature = "duckdb"))]
+            SinkKind::DuckDb => anyhow::bail!("built without the `duckdb` feature"),
+            kind @ (SinkKind::Discord | SinkKind::Telegram) => {
+                let notify = config.get_notify_config(kind).ok_or_else(|| {
+                    anyhow::anyhow!("`sink: discord` requires `notify.webhook_url`, `sink: telegram` `notify.bot_token` and `notify.chat_id`")
+                })?;
+                Ok(Self::Notify(Notifier::new(notify)))
+            },
+            #[cfg(feature = "kafka")]
+            SinkKind::Kafka => {
+                let kafka =
+                    config.get_kafka_config().ok_or_else(|| anyhow::anyhow!("`sink: kafka` requires `kafka.brokers`"))?;
+                Ok(Self::Kafka {
+                    producer: kafka::connect(&kafka)?,
+                    config: kafka,
+                })
+            },
+            #[cfg(not(feature = "kafka"))]
+            SinkKind::Kafka => anyhow::bail!("built without the `kafka` feature"),
+            SinkKind::Custom => {
+                let name = config
+                    .yellowstone_grpc
+                    .custom_sink
+                    .as_deref()
+                    .ok_or_else(|| anyhow::anyhow!("`sink: custom` requires `custom_sink`"))?;
+                let factory = custom::factory(name).ok_or_else(|| {
+                    anyhow::anyhow!("no custom sink '{}' is registered, registered: {:?}", name, custom::registered())
+                })?;
+                Ok(Self::Custom(factory))
+            },
+        }
+    }
+
+    /// Make sure `topics` exist on backends with topics, returning the created ones
+    #[cfg_attr(not(any(feature = "fluvio", feature = "kafka")), allow(unused_variables))]
+    pub async fn provision(&self, topics: &[String], config: &ProvisionConfig) -> anyhow::Result<Vec<String>> {
+        match self {
+            #[cfg(feature = "fluvio")]
+            Self::Fluvio { fluvio, .. } => fluvio_sink::provision_topics(fluvio, topics, config).await,
+            Self::Stdout | Self::Ipc(_) => Ok(Vec::new()),
+            #[cfg(feature = "mqtt")]
+            Self::Mqtt(_) => Ok(Vec::new()),
+            // Streams and topics are managed with the cloud provider's tooling
+            #[cfg(feature = "kinesis")]
+            Self::Kinesis { .. } => Ok(Vec::new()),
+            #[cfg(feature = "pubsub")]
+            Self::PubSub { .. } => Ok(Vec::new()),
+            #[cfg(feature = "sqs")]
+            Self::Sqs { .. } => Ok(Vec::new()),
+            #[cfg(feature = "sns")]
+            Self::Sns { .. } => Ok(Vec::new()),
+            #[cfg(any(feature = "sqlite", feature = "duckdb"))]
+            Self::LocalDb(_) => Ok(Vec::new()),
+            Self::Notify(_) | Self::Custom(_) => Ok(Vec::new()),
+            #[cfg(feature = "kafka")]
+            Self::Kafka { producer, config: kafka } => kafka::provision_topics(producer, kafka, topics, config).await,
+            Self::FanOut { connectors, .. } => {
+                let mut created = Vec::new();
+                for (_, connector) in connectors {
+                    created.extend(Box::pin(connector.provision(topics, config)).await?);
+                }
+                Ok(created)
+            },
+        }
+    }
+
+    /// Sink publishing to `topic`
+    pub async fn open(&self, topic: &str) -> anyhow::Result<Box<dyn Sink>> {
+        self.open_to(topic, None).await
+    }
+
+    /// Sink publishing to `topic` on the backends of `kinds`, every configured one when `None`
+    pub async fn open_to(&self, topic: &str, kinds: Option<&[SinkKind]>) -> anyhow::Result<Box<dyn Sink>> {
+        let Self::FanOut { connectors, config, metrics } = self else {
+            return self.open_backend(topic).await;
+        };
+        let mut sinks = Vec::new();
+        for (kind, connector) in connectors {
+            if kinds.map_or(true, |kinds| kinds.contains(kind)) {
+                sinks.push((*kind, connector.open_backend(topic).await?));
+            }
+        }
+        match sinks.len() {
+            0 => anyhow::bail!("none of the sinks {:?} of topic '{}' is configured", kinds, topic),
+            1 => Ok(sinks.remove(0).1),
+            _ => Ok(Box::new(FanOutSink::new(topic, sinks, config.clone(), metrics.clone()))),
+        }
+    }
+
+    async fn open_backend(&self, topic: &str) -> anyhow::Result<Box<dyn Sink>> {
+        let sink: Box<dyn Sink> = match self {
+            #[cfg(feature = "fluvio")]
+            Self::Fluvio { fluvio, batching, metrics } => Box::new(
+                FluvioSink::open(fluvio, topic, batching.as_ref(), metrics.clone()).await?,
+            ),
+            Self::Stdout => Box::new(StdoutSink::new(topic)),
+            Self::Ipc(writer) => Box::new(IpcSink::new(topic, Arc::clone(writer))),
+            #[cfg(feature = "mqtt")]
+            Self::Mqtt(connection) => Box::new(MqttSink::new(topic, Arc::clone(connection))),
+            #[cfg(feature = "kinesis")]
+            Self::Kinesis { client, config } => Box::new(KinesisSink::open(client, topic, config)),
+            #[cfg(feature = "pubsub")]
+            Self::PubSub { client, config } => Box::new(PubSubSink::open(client, topic, config).await?),
+            #[cfg(feature = "sqs")]
+            Self::Sqs { client, config } => Box::new(SqsSink::open(client, topic, config).await?),
+            #[cfg(feature = "sns")]
+            Self::Sns { client, config } => Box::new(SnsSink::open(client, topic, config).await?),
+            #[cfg(any(feature = "sqlite", feature = "duckdb"))]
+            Self::LocalDb(config) => Box::new(LocalDbSink::open(topic, config)?),
+            Self::Notify(notifier) => Box::new(NotifySink::new(topic, Arc::clone(notifier))),
+            #[cfg(feature = "kafka")]
+            Self::Kafka { producer, config } => Box::new(KafkaSink::open(producer, topic, config)),
+            Self::Custom(factory) => factory(topic).await?,
+            Self::FanOut { .. } => anyhow::bail!("fan-out sinks cannot be nested"),
+        };
+        #[cfg(feature = "chaos")]
+        let sink = crate::chaos::wrap_sink(sink);
+        Ok(sink)
+    }
+}
+
+#[cfg(any(feature = "sqlite", feature = "duckdb"))]
+fn local_db_config(config: &YellowstoneGrpcConfig, engine: LocalDbEngine) -> anyhow::Result<LocalDbConfig> {
+    config
+        .get_local_db_config(engine)
+        .ok_or_else(|| anyhow::anyhow!("`sink: sqlite` and `sink: duckdb` require `local_db.path`"))
+}
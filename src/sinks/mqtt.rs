@@ -0,0 +1,139 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::{future, FutureExt};
+use log::{info, warn};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use serde_json::Value;
+
+use super::{Delivery, Sink};
+
+/// Requests queued for the MQTT event loop before publishing waits
+const CHANNEL_CAPACITY: usize = 1024;
+/// Largest packet sent or received, well above account records with data
+const MAX_PACKET_SIZE: usize = 16 * 1024 * 1024;
+/// Delay before reconnecting to the broker after an error
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// MQTT sink settings
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub credentials: Option<(String, String)>,
+    /// MQTT topic of each record, with `{topic}`, `{key}`, `{pubkey}` and `{owner}` placeholders
+    pub topic_template: String,
+    pub qos: QoS,
+    /// Publish account records as retained messages, so new subscribers get the last value of
+    /// each account right away
+    pub retain_accounts: bool,
+    pub keep_alive: Duration,
+}
+
+/// Broker connection shared by the sinks of every topic. The event loop reconnects on its own
+/// and re-sends unacknowledged QoS 1 and 2 messages.
+pub struct MqttConnection {
+    client: AsyncClient,
+    config: MqttConfig,
+}
+
+impl MqttConnection {
+    pub fn connect(config: MqttConfig) -> Arc<Self> {
+        let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+        options.set_keep_alive(config.keep_alive);
+        options.set_max_packet_size(MAX_PACKET_SIZE, MAX_PACKET_SIZE);
+        if let Some((username, password)) = &config.credentials {
+            options.set_credentials(username, password);
+        }
+        let (client, mut event_loop) = AsyncClient::new(options, CHANNEL_CAPACITY);
+
+        let broker = format!("{}:{}", config.host, config.port);
+        tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => info!("Connected to the MQTT broker at {}", broker),
+                    Ok(_) => {},
+                    Err(e) => {
+                        warn!("MQTT connection to {} failed: {}", broker, e);
+                        tokio::time::sleep(RECONNECT_DELAY).await;
+                    },
+                }
+            }
+        });
+
+        Arc::new(Self { client, config })
+    }
+}
+
+/// Publishes records to an MQTT broker, one message per record with the record JSON as payload
+pub struct MqttSink {
+    topic: String,
+    connection: Arc<MqttConnection>,
+}
+
+impl MqttSink {
+    pub fn new(topic: &str, connection: Arc<MqttConnection>) -> Self {
+        Self {
+            topic: topic.to_owned(),
+            connection,
+        }
+    }
+
+    fn mqtt_topic(&self, key: &str, record: Option<&Value>) -> String {
+        render_topic(&self.connection.config.topic_template, &self.topic, key, record)
+    }
+}
+
+/// Render the topic template for a record, `{pubkey}` and `{owner}` are `none` for records
+/// without them
+fn render_topic(template: &str, topic: &str, key: &str, record: Option<&Value>) -> String {
+    let field = |name: &str| record.and_then(|record| record[name].as_str()).unwrap_or("none").to_owned();
+    template
+        .replace("{topic}", topic)
+        .replace("{key}", key)
+        .replace("{pubkey}", &field("pubkey"))
+        .replace("{owner}", &field("owner"))
+}
+
+/// Account records are the only ones with both a pubkey and an owner
+fn is_account(record: Option<&Value>) -> bool {
+    record.is_some_and(|record| record["pubkey"].is_string() && record["owner"].is_string())
+}
+
+#[async_trait]
+impl Sink for MqttSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        // Encrypted envelopes hide the record, they get no owner and are never retained
+        let record: Option<Value> = serde_json::from_slice(&payload).ok();
+        let topic = self.mqtt_topic(&key, record.as_ref());
+        let retain = self.connection.config.retain_accounts && is_account(record.as_ref());
+
+        // Queued for the event loop, which handles QoS acknowledgements and retries
+        self.connection
+            .client
+            .publish(topic, self.connection.config.qos, retain, payload)
+            .await?;
+        Ok(future::ready(Ok(())).boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn renders_account_topics_from_the_pubkey() {
+        // Account updates of a transaction are keyed by its signature
+        let record = json!({ "pubkey": "Acc1", "owner": "Prog1", "txnSignature": "Sig1" });
+        assert_eq!(render_topic("y/{owner}/{pubkey}", "accounts", "Sig1", Some(&record)), "y/Prog1/Acc1");
+        assert_eq!(render_topic("{topic}/{key}", "accounts", "Sig1", Some(&record)), "accounts/Sig1");
+        assert_eq!(render_topic("y/{owner}/{pubkey}", "accounts", "Sig1", None), "y/none/none");
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use log::warn;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+
+use super::{Delivery, Sink};
+
+/// Window of `max_per_minute`
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+/// Longest pause asked for by a service
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(3600);
+/// Longest message of each service, longer ones are truncated
+const DISCORD_MAX_LENGTH: usize = 2000;
+const TELEGRAM_MAX_LENGTH: usize = 4096;
+
+/// Where notifications are posted
+#[derive(Debug, Clone)]
+pub enum NotifyTarget {
+    Discord { webhook_url: String },
+    Telegram { bot_token: String, chat_id: String },
+}
+
+/// Notification sink settings
+#[derive(Debug, Clone)]
+pub struct NotifyConfig {
+    pub target: NotifyTarget,
+    /// Message of each record, see [`render`]
+    pub template: String,
+    /// Notifications posted per minute, the others are dropped and counted
+    pub max_per_minute: usize,
+}
+
+#[derive(Default)]
+struct RateState {
+    sent: VecDeque<Instant>,
+    // Set by a 429 response, nothing is posted before
+    blocked_until: Option<Instant>,
+    suppressed: u64,
+}
+
+/// Webhook client and rate limit shared by the sinks of every topic
+pub struct Notifier {
+    client: Client,
+    config: NotifyConfig,
+    rate: Mutex<RateState>,
+}
+
+impl Notifier {
+    pub fn new(config: NotifyConfig) -> Arc<Self> {
+        Arc::new(Self {
+            client: Client::new(),
+            config,
+            rate: Mutex::new(RateState::default()),
+        })
+    }
+
+    /// Take a slot in the rate limit, returning the count of notifications suppressed since the
+    /// last one posted, `None` when this one must be dropped as well
+    fn acquire(&self) -> Option<u64> {
+        let mut rate = self.rate.lock().expect("notification rate lock");
+        let now = Instant::now();
+        while rate.sent.front().is_some_and(|sent| now.duration_since(*sent) >= RATE_WINDOW) {
+            rate.sent.pop_front();
+        }
+        let blocked = rate.blocked_until.is_some_and(|until| now < until);
+        if blocked || rate.sent.len() >= self.config.max_per_minute {
+            rate.suppressed += 1;
+            return None;
+        }
+        rate.sent.push_back(now);
+        Some(std::mem::take(&mut rate.suppressed))
+    }
+
+    fn block(&self, retry_after: Duration) {
+        let mut rate = self.rate.lock().expect("notification rate lock");
+        rate.blocked_until = Some(Instant::now() + retry_after);
+        rate.suppressed += 1;
+    }
+
+    async fn post(&self, text: String) -> anyhow::Result<()> {
+        let request = match &self.config.target {
+            NotifyTarget::Discord { webhook_url } => {
+                self.client.post(webhook_url).json(&json!({ "content": truncate(text, DISCORD_MAX_LENGTH) }))
+            },
+            NotifyTarget::Telegram { bot_token, chat_id } => self
+                .client
+                .post(format!("https://api.telegram.org/bot{}/sendMessage", bot_token))
+                .json(&json!({ "chat_id": chat_id, "text": truncate(text, TELEGRAM_MAX_LENGTH) })),
+        };
+        let response = request.send().await?;
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = retry_after(response.headers().get("retry-after").and_then(|value| value.to_str().ok()));
+            warn!("Notification rate limited by the service, pausing for {:?}", retry_after);
+            self.block(retry_after);
+            return Ok(());
+        }
+        response.error_for_status()?;
+        Ok(())
+    }
+}
+
+fn truncate(mut text: String, max_length: usize) -> String {
+    if text.chars().count() > max_length {
+        text = text.chars().take(max_length - 1).collect();
+        text.push('…');
+    }
+    text
+}
+
+/// Render a message template. `{topic}` and `{key}` are the record's topic and key, other
+/// placeholders are dotted paths into the record, e.g. `{meta.fee}`, optionally followed by a
+/// format: `{lamports|sol}` in SOL, `{to|short}` for an abbreviated pubkey. Missing fields render
+/// as `?`.
+pub fn render(template: &str, topic: &str, key: &str, record: &Value) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(length) = rest[start..].find('}') else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let placeholder = &rest[start + 1..start + length];
+        let (path, format) = placeholder.split_once('|').unwrap_or((placeholder, ""));
+        let value = match path {
+            "topic" => Some(Value::from(topic)),
+            "key" => Some(Value::from(key)),
+            _ => path.split('.').try_fold(record, |value, name| value.get(name)).cloned(),
+        };
+        rendered.push_str(&format_value(value.as_ref(), format));
+        rest = &rest[start + length + 1..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn format_value(value: Option<&Value>, format: &str) -> String {
+    let Some(value) = value.filter(|value| !value.is_null()) else {
+        return "?".to_string();
+    };
+    match (format, value) {
+        ("sol", value) => match value.as_u64().or_else(|| value.as_str().and_then(|value| value.parse().ok())) {
+            Some(lamports) => {
+                let sol = format!("{}.{:09}", lamports / 1_000_000_000, lamports % 1_000_000_000);
+                sol.trim_end_matches('0').trim_end_matches('.').to_string()
+            },
+            None => value.to_string(),
+        },
+        ("short", Value::String(text)) if text.chars().count() > 8 => {
+            let chars: Vec<char> = text.chars().collect();
+            format!("{}…{}", chars[..4].iter().collect::<String>(), chars[chars.len() - 4..].iter().collect::<String>())
+        },
+        (_, Value::String(text)) => text.clone(),
+        (_, value) => value.to_string(),
+    }
+}
+
+/// Posts a message rendered from each record to a Discord webhook or Telegram chat, for alert
+/// bots on highly filtered streams
+pub struct NotifySink {
+    topic: String,
+    notifier: Arc<Notifier>,
+}
+
+impl NotifySink {
+    pub fn new(topic: &str, notifier: Arc<Notifier>) -> Self {
+        Self {
+            topic: topic.to_owned(),
+            notifier,
+        }
+    }
+}
+
+/// Pause asked for by a `Retry-After` header in seconds, `RATE_WINDOW` when missing or invalid
+fn retry_after(value: Option<&str>) -> Duration {
+    value
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .map_or(RATE_WINDOW, |retry_after| retry_after.min(MAX_RETRY_AFTER))
+}
+
+#[async_trait]
+impl Sink for NotifySink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        let Some(suppressed) = self.notifier.acquire() else {
+            return Ok(futures::future::ready(Ok(())).boxed());
+        };
+        let record: Value = serde_json::from_slice(&payload).unwrap_or_default();
+        let mut text = render(&self.notifier.config.template, &self.topic, &key, &record);
+        if suppressed > 0 {
+            text.push_str(&format!("\n({} more notifications suppressed by the rate limit)", suppressed));
+        }
+
+        // Posted in the background, the processor never waits for the service
+        let notifier = Arc::clone(&self.notifier);
+        let posted = tokio::spawn(async move { notifier.post(text).await });
+        Ok(async move { posted.await? }.boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_on_invalid_retry_after() {
+        assert_eq!(retry_after(Some("1.5")), Duration::from_millis(1500));
+        assert_eq!(retry_after(Some("1e9")), MAX_RETRY_AFTER);
+        for value in [None, Some("-1"), Some("NaN"), Some("inf"), Some("Wed, 21 Oct 2026 07:28:00 GMT")] {
+            assert_eq!(retry_after(value), RATE_WINDOW);
+        }
+    }
+}
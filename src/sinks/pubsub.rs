@@ -0,0 +1,85 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use google_cloud_googleapis::pubsub::v1::PubsubMessage;
+use google_cloud_pubsub::{
+    client::{Client, ClientConfig},
+    publisher::Publisher,
+};
+use tokio::sync::{oneshot, watch};
+
+use super::{Delivery, Sink};
+
+/// Google Cloud Pub/Sub sink settings
+#[derive(Debug, Clone)]
+pub struct PubSubConfig {
+    /// Project of the topics, from the credentials when unset
+    pub project_id: Option<String>,
+    /// Publish with the record key as ordering key
+    pub ordering_keys: bool,
+}
+
+/// Client authenticated with the application default credentials
+pub async fn connect(config: &PubSubConfig) -> anyhow::Result<Client> {
+    let mut client_config = ClientConfig::default().with_auth().await?;
+    if let Some(project_id) = &config.project_id {
+        client_config.project_id = Some(project_id.clone());
+    }
+    Ok(Client::new(client_config).await?)
+}
+
+/// Publishes the records of a topic to the Pub/Sub topic of the same name, with their key in
+/// the `key` attribute and, when enabled, as ordering key so subscriptions with message
+/// ordering get the updates of an account in order
+pub struct PubSubSink {
+    publisher: Publisher,
+    ordering_keys: bool,
+    /// Messages published and not stored yet, `flush` waits for none to be left
+    outstanding: Arc<watch::Sender<usize>>,
+}
+
+impl PubSubSink {
+    pub async fn open(client: &Client, topic: &str, config: &PubSubConfig) -> anyhow::Result<Self> {
+        let topic = client.topic(topic);
+        if !topic.exists(None).await? {
+            anyhow::bail!("Pub/Sub topic '{}' does not exist", topic.id());
+        }
+        Ok(Self {
+            publisher: topic.new_publisher(None),
+            ordering_keys: config.ordering_keys,
+            outstanding: Arc::new(watch::Sender::new(0)),
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for PubSubSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        let message = PubsubMessage {
+            data: payload,
+            ordering_key: if self.ordering_keys { key.clone() } else { String::new() },
+            attributes: HashMap::from([("key".to_string(), key)]),
+            ..Default::default()
+        };
+        // The publisher bundles messages and retries on its own, the awaiter resolves once
+        // the message is stored. It is awaited in its own task, so the message is counted as
+        // outstanding until then even when the delivery is dropped.
+        let awaiter = self.publisher.publish(message).await;
+        self.outstanding.send_modify(|count| *count += 1);
+        let outstanding = Arc::clone(&self.outstanding);
+        let (ack, acknowledged) = oneshot::channel();
+        tokio::spawn(async move {
+            let result = awaiter.get().await.map_err(|status| anyhow::anyhow!("Pub/Sub publish failed: {}", status));
+            outstanding.send_modify(|count| *count -= 1);
+            let _ = ack.send(result);
+        });
+        Ok(async move { acknowledged.await.map_err(|_| anyhow::anyhow!("Pub/Sub publisher stopped"))? }.boxed())
+    }
+
+    /// Wait until every message published so far is stored
+    async fn flush(&self) -> anyhow::Result<()> {
+        self.outstanding.subscribe().wait_for(|count| *count == 0).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use aws_sdk_sns::{error::DisplayErrorContext, types::MessageAttributeValue, Client};
+use futures::{future, FutureExt};
+
+use super::{aws, Delivery, Sink};
+
+/// SNS sink settings
+#[derive(Debug, Clone)]
+pub struct SnsConfig {
+    /// AWS region, from the environment or profile when unset
+    pub region: Option<String>,
+    /// Publish to FIFO topics, named after the topics with the `.fifo` suffix
+    pub fifo: bool,
+}
+
+/// Client of the configured region
+pub async fn connect(config: &SnsConfig) -> Client {
+    Client::new(&aws::sdk_config(config.region.as_deref()).await)
+}
+
+/// Publishes the records of a topic to the SNS topic of the same name, one notification per
+/// record with the record key in the `key` attribute for subscription filter policies. Like the
+/// SQS sink, each record is one `Publish` request, acknowledged before `send` returns.
+pub struct SnsSink {
+    client: Client,
+    topic_arn: String,
+    fifo: bool,
+}
+
+impl SnsSink {
+    pub async fn open(client: &Client, topic: &str, config: &SnsConfig) -> anyhow::Result<Self> {
+        let name = if config.fifo { format!("{}.fifo", topic) } else { topic.to_owned() };
+        Ok(Self {
+            client: client.clone(),
+            topic_arn: find_topic(client, &name).await?,
+            fifo: config.fifo,
+        })
+    }
+}
+
+/// ARN of the topic named `name`, topics are listed rather than created
+async fn find_topic(client: &Client, name: &str) -> anyhow::Result<String> {
+    let mut topics = client.list_topics().into_paginator().items().send();
+    while let Some(topic) = topics.next().await {
+        let topic = topic.map_err(|e| anyhow::anyhow!("error listing SNS topics: {}", DisplayErrorContext(&e)))?;
+        if let Some(arn) = topic.topic_arn().filter(|arn| arn.rsplit(':').next() == Some(name)) {
+            return Ok(arn.to_owned());
+        }
+    }
+    anyhow::bail!("SNS topic '{}' not found", name)
+}
+
+#[async_trait]
+impl Sink for SnsSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        let message = String::from_utf8(payload).context("SNS messages must be text")?;
+        let attribute = MessageAttributeValue::builder().data_type("String").string_value(&key).build()?;
+        let mut request = self.client.publish().topic_arn(&self.topic_arn);
+        if self.fifo {
+            request = request.message_group_id(&key).message_deduplication_id(aws::deduplication_id(&message));
+        }
+        request
+            .message_attributes("key", attribute)
+            .message(message)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("Publish to {} failed: {}", self.topic_arn, DisplayErrorContext(&e)))?;
+        Ok(future::ready(Ok(())).boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use aws_sdk_sqs::{error::DisplayErrorContext, types::MessageAttributeValue, Client};
+use futures::{future, FutureExt};
+
+use super::{aws, Delivery, Sink};
+
+/// SQS sink settings
+#[derive(Debug, Clone)]
+pub struct SqsConfig {
+    /// AWS region, from the environment or profile when unset
+    pub region: Option<String>,
+    /// Publish to FIFO queues, named after the topics with the `.fifo` suffix
+    pub fifo: bool,
+}
+
+/// Client of the configured region
+pub async fn connect(config: &SqsConfig) -> Client {
+    Client::new(&aws::sdk_config(config.region.as_deref()).await)
+}
+
+/// Sends the records of a topic to the SQS queue of the same name, one message per record with
+/// the record key in the `key` attribute. Meant for highly filtered streams: each record is one
+/// `SendMessage` request, acknowledged before `send` returns.
+pub struct SqsSink {
+    client: Client,
+    queue_url: String,
+    fifo: bool,
+}
+
+impl SqsSink {
+    pub async fn open(client: &Client, topic: &str, config: &SqsConfig) -> anyhow::Result<Self> {
+        let queue_name = if config.fifo { format!("{}.fifo", topic) } else { topic.to_owned() };
+        let queue = client
+            .get_queue_url()
+            .queue_name(&queue_name)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("SQS queue '{}' not found: {}", queue_name, DisplayErrorContext(&e)))?;
+        Ok(Self {
+            client: client.clone(),
+            queue_url: queue.queue_url().context("SQS queue without URL")?.to_owned(),
+            fifo: config.fifo,
+        })
+    }
+}
+
+#[async_trait]
+impl Sink for SqsSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        let body = String::from_utf8(payload).context("SQS message bodies must be text")?;
+        let attribute = MessageAttributeValue::builder().data_type("String").string_value(&key).build()?;
+        let mut request = self.client.send_message().queue_url(&self.queue_url);
+        if self.fifo {
+            // Updates of an account are delivered in order, records of other keys in parallel
+            request = request.message_group_id(&key).message_deduplication_id(aws::deduplication_id(&body));
+        }
+        request
+            .message_attributes("key", attribute)
+            .message_body(body)
+            .send()
+            .await
+            .map_err(|e| anyhow::anyhow!("SendMessage to {} failed: {}", self.queue_url, DisplayErrorContext(&e)))?;
+        Ok(future::ready(Ok(())).boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::Write;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures::{future, FutureExt};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+
+use super::{Delivery, Sink};
+
+/// Line written for each record, which `republish` reads back
+#[derive(Serialize, Deserialize)]
+pub struct ArchivedLine {
+    pub topic: String,
+    pub key: String,
+    /// Slot of the record, kept outside the payload, which can be compressed or encrypted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u64>,
+    /// The payload byte for byte when it is single-line JSON
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record: Option<Box<RawValue>>,
+    /// Any other payload, base64
+    #[serde(default, rename = "recordBase64", skip_serializing_if = "Option::is_none")]
+    pub record_base64: Option<String>,
+}
+
+impl ArchivedLine {
+    pub fn new(topic: &str, key: String, payload: &[u8], slot: Option<u64>) -> Self {
+        let record = std::str::from_utf8(payload)
+            .ok()
+            .filter(|text| !text.contains('\n'))
+            .and_then(|text| RawValue::from_string(text.to_owned()).ok())
+            // Whitespace around the value is trimmed, such payloads would not be kept as they are
+            .filter(|record| record.get().len() == payload.len());
+        Self {
+            topic: topic.to_owned(),
+            key,
+            slot,
+            record_base64: record.is_none().then(|| STANDARD.encode(payload)),
+            record,
+        }
+    }
+
+    /// The payload as it was published
+    pub fn payload(&self) -> anyhow::Result<Vec<u8>> {
+        match (&self.record, &self.record_base64) {
+            (Some(record), _) => Ok(record.get().as_bytes().to_vec()),
+            (None, Some(encoded)) => Ok(STANDARD.decode(encoded)?),
+            (None, None) => anyhow::bail!("line without a record"),
+        }
+    }
+}
+
+/// Writes records as JSON lines on stdout, for minimal deployments and local debugging
+pub struct StdoutSink {
+    topic: String,
+}
+
+impl StdoutSink {
+    pub fn new(topic: &str) -> Self {
+        Self { topic: topic.to_owned() }
+    }
+}
+
+#[async_trait]
+impl Sink for StdoutSink {
+    async fn send(&self, key: String, payload: Vec<u8>) -> anyhow::Result<Delivery> {
+        self.send_at(key, payload, None).await
+    }
+
+    async fn send_at(&self, key: String, payload: Vec<u8>, slot: Option<u64>) -> anyhow::Result<Delivery> {
+        let line = serde_json::to_string(&ArchivedLine::new(&self.topic, key, &payload, slot))?;
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{}", line)?;
+        Ok(future::ready(Ok(())).boxed())
+    }
+
+    async fn flush(&self) -> anyhow::Result<()> {
+        std::io::stdout().flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(payload: &[u8], slot: Option<u64>) -> ArchivedLine {
+        let line = serde_json::to_string(&ArchivedLine::new("solana", "key".to_string(), payload, slot)).unwrap();
+        assert!(!line.contains('\n'));
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn archives_payloads_byte_for_byte() {
+        // Key order and number formatting are kept, not normalized by a parse
+        let json = br#"{"z":1,"a":"9007199254740993","slot":7}"#;
+        let line = round_trip(json, Some(7));
+        assert_eq!(line.record.as_ref().map(|record| record.get()), Some(std::str::from_utf8(json).unwrap()));
+        assert_eq!(line.payload().unwrap(), json);
+        assert_eq!(line.slot, Some(7));
+
+        for payload in [&[0xff, 0x00, 0x9f][..], b"", b" {}", b"{\n}"] {
+            let line = round_trip(payload, None);
+            assert!(line.record.is_none());
+            assert_eq!(line.payload().unwrap(), payload);
+        }
+    }
+}
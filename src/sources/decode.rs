@@ -0,0 +1,219 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use yellowstone_grpc_proto::{
+    prelude::{subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdate},
+    prost::{bytes::Buf, Message},
+    tonic::{
+        codec::{BufferSettings, Codec, DecodeBuf, Decoder, EncodeBuf, Encoder},
+        Status,
+    },
+};
+
+/// Receive buffer of a stream without hints, tonic's default
+const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+/// Bytes decoded before the stream yields to the runtime, tonic's default
+const YIELD_THRESHOLD: usize = 32 * 1024;
+/// gRPC message header, a compression flag and the message length
+const HEADER_SIZE: usize = 5;
+
+/// Update kinds with their own capacity hint
+const KINDS: usize = 8;
+/// Capacity hint of each kind before any of its frames was received, in `kind_index` order
+const INITIAL_HINTS: [usize; KINDS] = [
+    16 * 1024,
+    1024,
+    16 * 1024,
+    1024,
+    4 * 1024 * 1024,
+    1024,
+    8 * 1024,
+    1024,
+];
+
+fn kind_index(update: Option<&UpdateOneof>) -> usize {
+    match update {
+        Some(UpdateOneof::Account(_)) => 0,
+        Some(UpdateOneof::Slot(_)) => 1,
+        Some(UpdateOneof::Transaction(_)) => 2,
+        Some(UpdateOneof::TransactionStatus(_)) => 3,
+        Some(UpdateOneof::Block(_)) => 4,
+        Some(UpdateOneof::BlockMeta(_)) => 5,
+        Some(UpdateOneof::Entry(_)) => 6,
+        Some(UpdateOneof::Ping(_)) | Some(UpdateOneof::Pong(_)) | None => 7,
+    }
+}
+
+/// Kinds of the updates `request` subscribes to
+fn subscribed_kinds(request: &SubscribeRequest) -> impl Iterator<Item = usize> {
+    [
+        !request.accounts.is_empty(),
+        !request.slots.is_empty(),
+        !request.transactions.is_empty(),
+        !request.transactions_status.is_empty(),
+        !request.blocks.is_empty(),
+        !request.blocks_meta.is_empty(),
+        !request.entry.is_empty(),
+    ]
+    .into_iter()
+    .enumerate()
+    .filter_map(|(kind, subscribed)| subscribed.then_some(kind))
+}
+
+/// Largest message received of each update kind.
+///
+/// tonic decodes every message of a stream out of one reused receive buffer, which grows by
+/// doubling whenever a message does not fit. Sizing it from these hints when the stream opens
+/// spares the reallocations and copies of that growth, e.g. on every reconnect of a block
+/// subscription.
+#[derive(Debug)]
+pub struct FrameHints {
+    largest: [AtomicUsize; KINDS],
+}
+
+impl Default for FrameHints {
+    fn default() -> Self {
+        Self {
+            largest: INITIAL_HINTS.map(AtomicUsize::new),
+        }
+    }
+}
+
+impl FrameHints {
+    fn observe(&self, update: &SubscribeUpdate, len: usize) {
+        self.largest[kind_index(update.update_oneof.as_ref())].fetch_max(len, Ordering::Relaxed);
+    }
+
+    /// Receive buffer for the update kinds of `request`, never above a frame of `max_message_size`
+    fn buffer_size(&self, request: &SubscribeRequest, max_message_size: usize) -> usize {
+        subscribed_kinds(request)
+            .map(|kind| self.largest[kind].load(Ordering::Relaxed))
+            .max()
+            .map_or(DEFAULT_BUFFER_SIZE, |largest| {
+                (largest.min(max_message_size) + HEADER_SIZE).max(DEFAULT_BUFFER_SIZE)
+            })
+    }
+}
+
+/// Protobuf codec of the `Subscribe` call, sizing the receive buffer with `FrameHints`
+pub struct SubscribeCodec {
+    hints: Arc<FrameHints>,
+    buffer_size: usize,
+}
+
+impl SubscribeCodec {
+    pub fn new(hints: Arc<FrameHints>, request: &SubscribeRequest, max_message_size: usize) -> Self {
+        let buffer_size = hints.buffer_size(request, max_message_size);
+        Self { hints, buffer_size }
+    }
+}
+
+impl Codec for SubscribeCodec {
+    type Encode = SubscribeRequest;
+    type Decode = SubscribeUpdate;
+    type Encoder = RequestEncoder;
+    type Decoder = UpdateDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        RequestEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        UpdateDecoder {
+            hints: Arc::clone(&self.hints),
+            buffer_size: self.buffer_size,
+        }
+    }
+}
+
+pub struct RequestEncoder;
+
+impl Encoder for RequestEncoder {
+    type Item = SubscribeRequest;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst).map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+pub struct UpdateDecoder {
+    hints: Arc<FrameHints>,
+    buffer_size: usize,
+}
+
+impl Decoder for UpdateDecoder {
+    type Item = SubscribeUpdate;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        let len = src.remaining();
+        let update = SubscribeUpdate::decode(src).map_err(|e| Status::internal(e.to_string()))?;
+        self.hints.observe(&update, len);
+        Ok(Some(update))
+    }
+
+    fn buffer_settings(&self) -> BufferSettings {
+        BufferSettings::new(self.buffer_size, YIELD_THRESHOLD)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use yellowstone_grpc_proto::{
+        prelude::{SubscribeRequestFilterBlocks, SubscribeUpdateBlock},
+        prost::bytes::{BufMut, BytesMut},
+    };
+
+    use super::*;
+
+    /// Allocations of a receive buffer of `buffer_size` bytes, fed messages of `lens` bytes in 16KB
+    /// HTTP/2 frames and consumed message by message like tonic's stream buffer
+    fn receive_allocations(buffer_size: usize, lens: &[usize]) -> usize {
+        let mut buf = BytesMut::with_capacity(buffer_size);
+        // Consumed space is reclaimed in place, the buffer only allocates to outgrow its largest capacity
+        let mut allocated = buf.capacity();
+        let mut allocations = 0;
+        for &len in lens {
+            for chunk in vec![0; len].chunks(16 * 1024) {
+                buf.put_slice(chunk);
+                if buf.capacity() > allocated {
+                    allocated = buf.capacity();
+                    allocations += 1;
+                }
+            }
+            buf.advance(len);
+        }
+        allocations
+    }
+
+    #[test]
+    fn sizes_the_receive_buffer_by_update_kind() {
+        let hints = FrameHints::default();
+        let max_message_size = 64 * 1024 * 1024;
+        let blocks = SubscribeRequest {
+            blocks: HashMap::from([("blocks".to_string(), SubscribeRequestFilterBlocks::default())]),
+            ..Default::default()
+        };
+        let buffer_size = hints.buffer_size(&blocks, max_message_size);
+        assert_eq!(hints.buffer_size(&SubscribeRequest::default(), max_message_size), DEFAULT_BUFFER_SIZE);
+
+        // 2MB blocks: the default buffer doubles its way up on the first one, the hinted one never grows
+        let lens = [2 * 1024 * 1024; 4];
+        assert_eq!(receive_allocations(DEFAULT_BUFFER_SIZE, &lens), 8);
+        assert_eq!(receive_allocations(buffer_size, &lens), 0);
+
+        // Larger blocks raise the hint of the next stream, up to the decoding limit
+        let block = SubscribeUpdate {
+            update_oneof: Some(UpdateOneof::Block(SubscribeUpdateBlock::default())),
+            ..Default::default()
+        };
+        hints.observe(&block, 6 * 1024 * 1024);
+        assert_eq!(hints.buffer_size(&blocks, max_message_size), 6 * 1024 * 1024 + HEADER_SIZE);
+        assert_eq!(hints.buffer_size(&blocks, 5 * 1024 * 1024), 5 * 1024 * 1024 + HEADER_SIZE);
+    }
+}
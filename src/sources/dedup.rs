@@ -0,0 +1,254 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, SubscribeRequest, SubscribeUpdate, SubscribeUpdateTransaction,
+};
+
+use super::{multiplex::SeenSignatures, update_slot, Source, SourceEvent, Watermarks};
+use crate::{health::StreamHealth, metrics::Metrics};
+
+/// Source whose copy of a transaction is published when both `transactions` and `blocks` with
+/// `include_transactions` deliver it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PrimarySource {
+    /// Transaction updates as they arrive, block transactions only fill in the ones they missed
+    #[default]
+    Transactions,
+    /// Block transactions, transaction updates wait for their block and are only published when
+    /// it did not carry them
+    Blocks,
+}
+
+/// Whether `request` subscribes to blocks with their transactions
+pub fn includes_block_transactions(request: &SubscribeRequest) -> bool {
+    request.blocks.values().any(|filter| filter.include_transactions == Some(true))
+}
+
+/// Fans the transactions of block updates in with the transaction updates, so a subscription to
+/// both `transactions` and `blocks` with `include_transactions` publishes each transaction once.
+///
+/// Block updates are replaced by one transaction update per transaction they carry, with the
+/// filters of the block. Duplicates are recognized by signature among the recent transactions.
+pub struct DedupSource {
+    inner: Box<dyn Source>,
+    primary: PrimarySource,
+    block_wait_slots: u64,
+    seen: SeenSignatures,
+    // Transaction updates waiting for the block of their slot, with `primary: blocks`
+    held: BTreeMap<u64, Vec<SubscribeUpdate>>,
+    ready: VecDeque<SubscribeUpdate>,
+    newest_slot: u64,
+}
+
+impl DedupSource {
+    pub fn new(inner: Box<dyn Source>, primary: PrimarySource, block_wait_slots: u64) -> Self {
+        Self {
+            inner,
+            primary,
+            block_wait_slots,
+            seen: SeenSignatures::new(),
+            held: BTreeMap::new(),
+            ready: VecDeque::new(),
+            newest_slot: 0,
+        }
+    }
+
+    /// Queue `update` if it is not a duplicate
+    fn emit(&mut self, update: SubscribeUpdate) {
+        if self.seen.first_seen(&update) {
+            self.ready.push_back(update);
+        }
+    }
+
+    fn accept(&mut self, mut update: SubscribeUpdate) {
+        if let Some(slot) = update.update_oneof.as_ref().and_then(update_slot) {
+            self.newest_slot = self.newest_slot.max(slot);
+        }
+
+        match update.update_oneof.take() {
+            Some(UpdateOneof::Block(block)) => {
+                for info in block.transactions {
+                    self.emit(SubscribeUpdate {
+                        filters: update.filters.clone(),
+                        created_at: update.created_at.clone(),
+                        update_oneof: Some(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+                            transaction: Some(info),
+                            slot: block.slot,
+                        })),
+                    });
+                }
+                for held in self.held.remove(&block.slot).unwrap_or_default() {
+                    self.emit(held);
+                }
+            },
+            Some(UpdateOneof::Transaction(transaction)) if self.primary == PrimarySource::Blocks => {
+                let slot = transaction.slot;
+                update.update_oneof = Some(UpdateOneof::Transaction(transaction));
+                self.held.entry(slot).or_default().push(update);
+            },
+            other => {
+                update.update_oneof = other;
+                self.emit(update);
+            },
+        }
+
+        // Blocks filtered by `account_include` may never carry some transactions
+        while let Some(entry) = self.held.first_entry() {
+            if *entry.key() + self.block_wait_slots >= self.newest_slot {
+                break;
+            }
+            for held in entry.remove() {
+                self.emit(held);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Source for DedupSource {
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+        loop {
+            if let Some(update) = self.ready.pop_front() {
+                return Some(Ok(update));
+            }
+            match self.inner.next().await {
+                Some(Ok(update)) => self.accept(update),
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    // Release the transactions still waiting for their block
+                    let held = std::mem::take(&mut self.held);
+                    for held in held.into_values().flatten() {
+                        self.emit(held);
+                    }
+                    return self.ready.pop_front().map(Ok);
+                },
+            }
+        }
+    }
+
+    async fn watermarks(&mut self) -> Option<Watermarks> {
+        self.inner.watermarks().await
+    }
+
+    fn historical(&self) -> bool {
+        self.inner.historical()
+    }
+
+    fn take_events(&mut self) -> Vec<SourceEvent> {
+        self.inner.take_events()
+    }
+
+    async fn update_request(&mut self, request: SubscribeRequest) -> anyhow::Result<()> {
+        self.inner.update_request(request).await
+    }
+
+    fn attach_health(&mut self, health: Arc<StreamHealth>) {
+        self.inner.attach_health(health);
+    }
+
+    fn attach_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.inner.attach_metrics(metrics);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yellowstone_grpc_proto::prelude::{SubscribeUpdateBlock, SubscribeUpdateSlot, SubscribeUpdateTransactionInfo};
+
+    use super::*;
+
+    struct Exhausted;
+
+    #[async_trait]
+    impl Source for Exhausted {
+        async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+            None
+        }
+    }
+
+    fn info(signature: &str) -> SubscribeUpdateTransactionInfo {
+        SubscribeUpdateTransactionInfo {
+            signature: signature.as_bytes().to_vec(),
+            ..Default::default()
+        }
+    }
+
+    fn update(update: UpdateOneof) -> SubscribeUpdate {
+        SubscribeUpdate {
+            filters: vec!["client".to_string()],
+            update_oneof: Some(update),
+            ..Default::default()
+        }
+    }
+
+    fn transaction(signature: &str, slot: u64) -> SubscribeUpdate {
+        update(UpdateOneof::Transaction(SubscribeUpdateTransaction {
+            transaction: Some(info(signature)),
+            slot,
+        }))
+    }
+
+    fn block(slot: u64, signatures: &[&str]) -> SubscribeUpdate {
+        update(UpdateOneof::Block(SubscribeUpdateBlock {
+            slot,
+            transactions: signatures.iter().map(|signature| info(signature)).collect(),
+            ..Default::default()
+        }))
+    }
+
+    /// Signatures of the transaction updates ready to be published, in order
+    fn drain(source: &mut DedupSource) -> Vec<String> {
+        source
+            .ready
+            .drain(..)
+            .filter_map(|update| match update.update_oneof {
+                Some(UpdateOneof::Transaction(transaction)) => transaction.transaction,
+                _ => None,
+            })
+            .map(|info| String::from_utf8(info.signature).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn fills_in_transactions_missed_by_transaction_updates() {
+        let mut source = DedupSource::new(Box::new(Exhausted), PrimarySource::Transactions, 10);
+        source.accept(transaction("a", 5));
+        assert_eq!(drain(&mut source), ["a"]);
+        source.accept(block(5, &["a", "b"]));
+        assert_eq!(drain(&mut source), ["b"]);
+        source.accept(transaction("b", 5));
+        assert!(drain(&mut source).is_empty());
+    }
+
+    #[test]
+    fn holds_transaction_updates_for_their_block() {
+        let mut source = DedupSource::new(Box::new(Exhausted), PrimarySource::Blocks, 10);
+        source.accept(transaction("a", 5));
+        source.accept(transaction("c", 5));
+        source.accept(transaction("d", 6));
+        assert!(drain(&mut source).is_empty());
+
+        // The block copies go first, updates it did not carry follow
+        source.accept(block(5, &["b", "a"]));
+        assert_eq!(drain(&mut source), ["b", "a", "c"]);
+
+        // Blocks that never carry an update release it after `block_wait_slots`
+        source.accept(update(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot: 16,
+            ..Default::default()
+        })));
+        assert!(drain(&mut source).is_empty());
+        source.accept(update(UpdateOneof::Slot(SubscribeUpdateSlot {
+            slot: 17,
+            ..Default::default()
+        })));
+        assert_eq!(drain(&mut source), ["d"]);
+    }
+}
@@ -0,0 +1,252 @@
+use std::{pin::Pin, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::{
+    channel::mpsc::{self, SendError},
+    stream::BoxStream,
+    Sink, SinkExt, StreamExt,
+};
+use yellowstone_grpc_client::GeyserGrpcClient;
+#[cfg(unix)]
+use yellowstone_grpc_proto::{prelude::geyser_client::GeyserClient, tonic::transport::Uri};
+use yellowstone_grpc_proto::{
+    prelude::{
+        CommitmentLevel, GetBlockHeightResponse, GetLatestBlockhashResponse, GetSlotResponse,
+        IsBlockhashValidResponse, SubscribeRequest, SubscribeUpdate,
+    },
+    tonic::{
+        client::Grpc,
+        codegen::{http::uri::PathAndQuery, InterceptedService},
+        metadata::AsciiMetadataValue,
+        service::Interceptor,
+        transport::{Channel, Endpoint},
+        Request, Status,
+    },
+};
+
+use super::{
+    decode::{FrameHints, SubscribeCodec},
+    Source, UnaryConfig, UnaryPool, Watermarks,
+};
+use crate::{config::YellowstoneGrpc, credentials};
+
+/// Method path of the `Subscribe` call
+const SUBSCRIBE_PATH: &str = "/geyser.Geyser/Subscribe";
+
+type SubscribeSink = Pin<Box<dyn Sink<SubscribeRequest, Error = SendError> + Send>>;
+
+/// Live Yellowstone gRPC subscription
+pub struct GrpcSource {
+    // Watermarks are fetched on the endpoint's unary pool, not on the subscription's connection
+    unary: Arc<UnaryPool>,
+    // Kept alive so the server does not see the request stream close, and used to update filters
+    subscribe_tx: SubscribeSink,
+    stream: BoxStream<'static, Result<SubscribeUpdate, Status>>,
+}
+
+/// Connection settings kept by sources that need to reconnect
+#[derive(Debug, Clone)]
+pub struct GrpcEndpoint {
+    pub endpoint: String,
+    pub x_token: Option<String>,
+    pub max_decoding_message_size: usize,
+    pub unary: UnaryConfig,
+}
+
+impl From<&YellowstoneGrpc> for GrpcEndpoint {
+    fn from(config: &YellowstoneGrpc) -> Self {
+        Self {
+            endpoint: config.endpoint.clone(),
+            x_token: config.x_token.clone(),
+            max_decoding_message_size: config.max_decoding_message_size as usize,
+            unary: config
+                .unary_calls
+                .as_ref()
+                .map(|unary| {
+                    let defaults = UnaryConfig::default();
+                    UnaryConfig {
+                        pool_size: unary.pool_size.unwrap_or(defaults.pool_size).max(1),
+                        timeout: unary.timeout_ms.map_or(defaults.timeout, Duration::from_millis),
+                        failure_threshold: unary.failure_threshold.unwrap_or(defaults.failure_threshold).max(1),
+                        cooldown: unary.cooldown_secs.map_or(defaults.cooldown, Duration::from_secs),
+                    }
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+async fn client(endpoint: &GrpcEndpoint) -> anyhow::Result<GeyserGrpcClient<impl Interceptor>> {
+    Ok(GeyserGrpcClient::build_from_shared(endpoint.endpoint.clone())?
+        .x_token(endpoint.x_token.clone())?
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(10))
+        .max_decoding_message_size(endpoint.max_decoding_message_size)
+        .connect()
+        .await?)
+}
+
+/// Socket path of a `uds://` (or `unix://`) endpoint, e.g. a Geyser plugin on the same host
+fn uds_path(endpoint: &str) -> Option<&str> {
+    endpoint.strip_prefix("uds://").or_else(|| endpoint.strip_prefix("unix://"))
+}
+
+/// Channel over a Unix domain socket, skipping the TCP stack for a colocated validator
+#[cfg(unix)]
+async fn uds_channel(path: &str) -> anyhow::Result<Channel> {
+    use hyper_util::rt::TokioIo;
+    use tokio::net::UnixStream;
+
+    let path = path.to_owned();
+    // tonic needs a URI, but every connection goes to the socket
+    Ok(Endpoint::from_static("http://localhost")
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(10))
+        .connect_with_connector(tower::service_fn(move |_: Uri| {
+            let path = path.clone();
+            async move { Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(path).await?)) }
+        }))
+        .await?)
+}
+
+/// Channel of the subscriptions, with the same settings as the client's
+async fn channel(endpoint: &GrpcEndpoint) -> anyhow::Result<Channel> {
+    if let Some(path) = uds_path(&endpoint.endpoint) {
+        #[cfg(unix)]
+        return uds_channel(path).await;
+        #[cfg(not(unix))]
+        anyhow::bail!("uds:// endpoint {} requires a Unix platform", path);
+    }
+    Ok(Endpoint::from_shared(endpoint.endpoint.clone())?
+        .connect_timeout(Duration::from_secs(10))
+        .timeout(Duration::from_secs(10))
+        .connect()
+        .await?)
+}
+
+/// Interceptor sending `x_token` with every call
+fn x_token_interceptor(endpoint: &GrpcEndpoint) -> anyhow::Result<impl Interceptor + Clone> {
+    let x_token: Option<AsciiMetadataValue> = endpoint.x_token.as_deref().map(str::parse).transpose()?;
+    Ok(move |mut request: Request<()>| {
+        if let Some(x_token) = x_token.clone() {
+            request.metadata_mut().insert("x-token", x_token);
+        }
+        Ok::<_, Status>(request)
+    })
+}
+
+/// Client over a Unix domain socket
+#[cfg(unix)]
+async fn uds_client(endpoint: &GrpcEndpoint, path: &str) -> anyhow::Result<GeyserGrpcClient<impl Interceptor + Clone>> {
+    let channel = uds_channel(path).await?;
+    let interceptor = x_token_interceptor(endpoint)?;
+
+    Ok(GeyserGrpcClient::new(
+        tonic_health::pb::health_client::HealthClient::with_interceptor(channel.clone(), interceptor.clone()),
+        GeyserClient::with_interceptor(channel, interceptor)
+            .max_decoding_message_size(endpoint.max_decoding_message_size),
+    ))
+}
+
+/// Unary Geyser methods, object safe so the client's interceptor type stays hidden
+#[async_trait]
+pub trait UnaryApi: Send {
+    async fn get_latest_blockhash(&mut self, commitment: Option<CommitmentLevel>) -> anyhow::Result<GetLatestBlockhashResponse>;
+    async fn get_block_height(&mut self, commitment: Option<CommitmentLevel>) -> anyhow::Result<GetBlockHeightResponse>;
+    async fn get_slot(&mut self, commitment: Option<CommitmentLevel>) -> anyhow::Result<GetSlotResponse>;
+    async fn is_blockhash_valid(
+        &mut self,
+        blockhash: String,
+        commitment: Option<CommitmentLevel>,
+    ) -> anyhow::Result<IsBlockhashValidResponse>;
+}
+
+#[async_trait]
+impl<F: Interceptor + Send + 'static> UnaryApi for GeyserGrpcClient<F> {
+    async fn get_latest_blockhash(&mut self, commitment: Option<CommitmentLevel>) -> anyhow::Result<GetLatestBlockhashResponse> {
+        Ok(GeyserGrpcClient::get_latest_blockhash(self, commitment).await?)
+    }
+
+    async fn get_block_height(&mut self, commitment: Option<CommitmentLevel>) -> anyhow::Result<GetBlockHeightResponse> {
+        Ok(GeyserGrpcClient::get_block_height(self, commitment).await?)
+    }
+
+    async fn get_slot(&mut self, commitment: Option<CommitmentLevel>) -> anyhow::Result<GetSlotResponse> {
+        Ok(GeyserGrpcClient::get_slot(self, commitment).await?)
+    }
+
+    async fn is_blockhash_valid(
+        &mut self,
+        blockhash: String,
+        commitment: Option<CommitmentLevel>,
+    ) -> anyhow::Result<IsBlockhashValidResponse> {
+        Ok(GeyserGrpcClient::is_blockhash_valid(self, blockhash, commitment).await?)
+    }
+}
+
+/// Connect a client for unary calls, with the active credential profile
+pub(super) async fn unary_client(endpoint: &GrpcEndpoint) -> anyhow::Result<Box<dyn UnaryApi>> {
+    let endpoint = &credentials::resolve(endpoint);
+    if let Some(path) = uds_path(&endpoint.endpoint) {
+        #[cfg(unix)]
+        return Ok(Box::new(uds_client(endpoint, path).await?));
+        #[cfg(not(unix))]
+        anyhow::bail!("uds:// endpoint {} requires a Unix platform", path);
+    }
+    Ok(Box::new(client(endpoint).await?))
+}
+
+/// Connect to the endpoint and subscribe with `request`, with the active credential profile.
+///
+/// The receive buffer of the stream is sized with `hints`, which learn from its updates.
+pub async fn connect(
+    endpoint: &GrpcEndpoint,
+    request: SubscribeRequest,
+    hints: Arc<FrameHints>,
+) -> anyhow::Result<Box<dyn Source>> {
+    let source = subscribe(&credentials::resolve(endpoint), request, hints).await?;
+    #[cfg(feature = "chaos")]
+    let source = crate::chaos::wrap_source(source);
+    Ok(source)
+}
+
+async fn subscribe(
+    endpoint: &GrpcEndpoint,
+    request: SubscribeRequest,
+    hints: Arc<FrameHints>,
+) -> anyhow::Result<Box<dyn Source>> {
+    // Called directly rather than through the generated client, to decode with our own codec
+    let service = InterceptedService::new(channel(endpoint).await?, x_token_interceptor(endpoint)?);
+    let mut grpc = Grpc::new(service).max_decoding_message_size(endpoint.max_decoding_message_size);
+    grpc.ready().await.map_err(|e| anyhow::anyhow!("gRPC channel not ready: {}", e))?;
+
+    let codec = SubscribeCodec::new(hints, &request, endpoint.max_decoding_message_size);
+    let (mut subscribe_tx, subscribe_rx) = mpsc::unbounded();
+    subscribe_tx.send(request).await?;
+    let stream = grpc
+        .streaming(Request::new(subscribe_rx), PathAndQuery::from_static(SUBSCRIBE_PATH), codec)
+        .await?
+        .into_inner();
+
+    Ok(Box::new(GrpcSource {
+        unary: UnaryPool::shared(endpoint),
+        subscribe_tx: Box::pin(subscribe_tx),
+        stream: stream.boxed(),
+    }))
+}
+
+#[async_trait]
+impl Source for GrpcSource {
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+        self.stream.next().await.map(|message| message.map_err(anyhow::Error::from))
+    }
+
+    async fn watermarks(&mut self) -> Option<Watermarks> {
+        self.unary.watermarks().await.ok()
+    }
+
+    async fn update_request(&mut self, request: SubscribeRequest) -> anyhow::Result<()> {
+        self.subscribe_tx.send(request).await?;
+        Ok(())
+    }
+}
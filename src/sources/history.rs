@@ -0,0 +1,73 @@
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use log::warn;
+use yellowstone_grpc_proto::prelude::{SubscribeRequest, SubscribeUpdate};
+
+use super::Source;
+use crate::{
+    config::YellowstoneGrpc,
+    history::{self, BlockProvider},
+};
+
+/// Replays a slot range from the configured history provider, keeping the updates matched by
+/// the filters of the subscribe request
+pub struct HistorySource {
+    provider: Box<dyn BlockProvider>,
+    request: SubscribeRequest,
+    next_slot: u64,
+    to_slot: u64,
+    pending: VecDeque<SubscribeUpdate>,
+}
+
+impl HistorySource {
+    pub async fn new(
+        config: &YellowstoneGrpc,
+        request: SubscribeRequest,
+        from_slot: u64,
+        to_slot: u64,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(from_slot <= to_slot, "source.from_slot must not be greater than source.to_slot");
+        let history = config.history.as_ref();
+        let endpoint = history
+            .and_then(|history| history.endpoint.clone())
+            .or_else(|| config.rpc_endpoint.clone());
+
+        Ok(Self {
+            provider: history::block_provider(history, endpoint).await?,
+            request,
+            next_slot: from_slot,
+            to_slot,
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+#[async_trait]
+impl Source for HistorySource {
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+        while self.pending.is_empty() {
+            if self.next_slot > self.to_slot {
+                return None;
+            }
+            let slot = self.next_slot;
+            self.next_slot += 1;
+
+            let block = match self.provider.get_block(slot).await {
+                Ok(Some(block)) => block,
+                Ok(None) => {
+                    warn!("No block for slot {}, skipping", slot);
+                    continue;
+                },
+                Err(e) => return Some(Err(e)),
+            };
+            self.pending.extend(history::filter_updates(&self.request, history::block_updates(slot, block)));
+        }
+
+        self.pending.pop_front().map(Ok)
+    }
+
+    fn historical(&self) -> bool {
+        true
+    }
+}
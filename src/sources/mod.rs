@@ -0,0 +1,186 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use futures::FutureExt;
+use log::info;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest, SubscribeUpdate,
+};
+
+use crate::{config::YellowstoneGrpc, health::StreamHealth, metrics::Metrics};
+
+mod decode;
+mod dedup;
+mod grpc;
+mod history;
+mod multiplex;
+mod reconnect;
+mod replay;
+mod sharded;
+mod unary;
+
+pub use dedup::{includes_block_transactions, DedupSource, PrimarySource};
+pub use grpc::{GrpcEndpoint, UnaryApi};
+pub use history::HistorySource;
+pub use multiplex::{split_subscribe_request, MultiplexSource};
+pub use reconnect::ReconnectingSource;
+pub use replay::ReplaySource;
+pub use sharded::ShardedSource;
+pub use unary::{UnaryConfig, UnaryPool};
+
+/// Upstream processed/confirmed/finalized slots
+#[derive(Debug, Clone, Copy)]
+pub struct Watermarks {
+    pub processed: u64,
+    pub confirmed: u64,
+    pub finalized: u64,
+}
+
+/// Connection events reported by sources, published as control records
+#[derive(Debug, Clone)]
+pub enum SourceEvent {
+    /// The subscription was re-established after failing or closing
+    Reconnect { attempt: u32, reason: String },
+    /// Slots that may have been missed while the subscription was down
+    Gap { from_slot: u64, to_slot: u64 },
+}
+
+/// Producer of `SubscribeUpdate`s feeding the processing pipeline
+#[async_trait]
+pub trait Source: Send {
+    /// Next update, `None` once the source is exhausted
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>>;
+
+    /// Current upstream watermarks, if the source can report them
+    async fn watermarks(&mut self) -> Option<Watermarks> {
+        None
+    }
+
+    /// Whether updates from this source should be flagged as `historical`
+    fn historical(&self) -> bool {
+        false
+    }
+
+    /// Events that happened since the last call
+    fn take_events(&mut self) -> Vec<SourceEvent> {
+        Vec::new()
+    }
+
+    /// Replace the filters of a live subscription without reconnecting
+    async fn update_request(&mut self, _request: SubscribeRequest) -> anyhow::Result<()> {
+        anyhow::bail!("source does not support subscription updates")
+    }
+
+    /// Report disconnects and reconnects to `health`, for sources that reconnect on their own
+    fn attach_health(&mut self, _health: Arc<StreamHealth>) {}
+
+    /// Report stream errors and reconnects to `metrics`, for sources that reconnect on their own
+    fn attach_metrics(&mut self, _metrics: Arc<Metrics>) {}
+}
+
+/// Slot an update belongs to, `None` for pings and pongs
+pub fn update_slot(update: &UpdateOneof) -> Option<u64> {
+    match update {
+        UpdateOneof::Account(msg) => Some(msg.slot),
+        UpdateOneof::Slot(msg) => Some(msg.slot),
+        UpdateOneof::Transaction(msg) => Some(msg.slot),
+        UpdateOneof::TransactionStatus(msg) => Some(msg.slot),
+        UpdateOneof::Block(msg) => Some(msg.slot),
+        UpdateOneof::BlockMeta(msg) => Some(msg.slot),
+        UpdateOneof::Entry(msg) => Some(msg.slot),
+        UpdateOneof::Ping(_) | UpdateOneof::Pong(_) => None,
+    }
+}
+
+/// Which `Source` implementation feeds the pipeline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    /// Live Yellowstone gRPC subscription (also used for mock servers)
+    #[default]
+    Grpc,
+    /// Length-delimited protobuf `SubscribeUpdate`s read from a file
+    Replay,
+    /// Blocks read from the `history` source for a slot range
+    History,
+}
+
+/// Whether the configured source is a live subscription, as opposed to a replay or backfill
+pub fn is_live(config: &YellowstoneGrpc) -> bool {
+    config.source.as_ref().and_then(|source| source.kind).unwrap_or_default() == SourceKind::Grpc
+}
+
+/// Current slot of the configured gRPC endpoint at `commitment`
+pub async fn tip_slot(config: &YellowstoneGrpc, commitment: Option<CommitmentLevel>) -> anyhow::Result<u64> {
+    let unary = UnaryPool::shared(&config.into());
+    Ok(unary.call(|client| client.get_slot(commitment)).await?.slot)
+}
+
+/// gRPC subscription for `request`, split across several when it exceeds `max_accounts`
+async fn grpc_source(
+    endpoint: GrpcEndpoint,
+    request: SubscribeRequest,
+    max_accounts: Option<usize>,
+) -> anyhow::Result<Box<dyn Source>> {
+    let Some(max_accounts) = max_accounts else {
+        return Ok(Box::new(ReconnectingSource::connect(endpoint, request).await?));
+    };
+
+    // Kept by the multiplexer to open subscriptions when a filter update adds chunks
+    let connect: multiplex::Connect = Box::new(move |request| {
+        let endpoint = endpoint.clone();
+        async move { Ok(Box::new(ReconnectingSource::connect(endpoint, request).await?) as Box<dyn Source>) }.boxed()
+    });
+    Ok(Box::new(MultiplexSource::connect(request, max_accounts, connect).await?))
+}
+
+/// Build the source selected by the `source` config
+pub async fn build(config: &YellowstoneGrpc, request: SubscribeRequest) -> anyhow::Result<Box<dyn Source>> {
+    let source_config = config.source.as_ref();
+    let kind = source_config.and_then(|source| source.kind).unwrap_or_default();
+    let block_transactions = includes_block_transactions(&request);
+
+    let source: Box<dyn Source> = match kind {
+        SourceKind::Grpc => match source_config.and_then(|source| source.shards).filter(|shards| *shards > 1) {
+            Some(shards) => {
+                let reorder_window = source_config.and_then(|source| source.reorder_window_ms).unwrap_or(200);
+                Box::new(
+                    ShardedSource::connect(
+                        config.into(),
+                        request,
+                        shards,
+                        config.max_accounts_per_subscription,
+                        Duration::from_millis(reorder_window),
+                    )
+                    .await?,
+                )
+            },
+            None => grpc_source(config.into(), request, config.max_accounts_per_subscription).await?,
+        },
+        SourceKind::Replay => {
+            let path = source_config
+                .and_then(|source| source.path.clone())
+                .ok_or_else(|| anyhow::anyhow!("replay source requires source.path"))?;
+            Box::new(ReplaySource::open(&path).await?)
+        },
+        SourceKind::History => {
+            let (Some(from_slot), Some(to_slot)) = (
+                source_config.and_then(|source| source.from_slot),
+                source_config.and_then(|source| source.to_slot),
+            ) else {
+                anyhow::bail!("history source requires source.from_slot and source.to_slot");
+            };
+            Box::new(HistorySource::new(config, request, from_slot, to_slot).await?)
+        },
+    };
+    if !block_transactions {
+        return Ok(source);
+    }
+
+    let primary = source_config.and_then(|source| source.primary_source).unwrap_or_default();
+    let block_wait_slots = source_config.and_then(|source| source.block_wait_slots).unwrap_or(32);
+    info!("Fanning in block transactions, {:?} are the primary source of duplicates", primary);
+    Ok(Box::new(DedupSource::new(source, primary, block_wait_slots)))
+}
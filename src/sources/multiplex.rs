@@ -0,0 +1,376 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use futures::future::{select_all, BoxFuture};
+use log::info;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, SubscribeRequest, SubscribeRequestFilterAccounts,
+    SubscribeRequestFilterTransactions, SubscribeUpdate,
+};
+
+use super::{Source, SourceEvent, Watermarks};
+use crate::{health::StreamHealth, metrics::Metrics};
+
+/// Number of recent transaction signatures remembered for de-duplication
+const SEEN_SIGNATURES_CAPACITY: usize = 100_000;
+
+/// Split `request` so that no subscription carries more than `max_accounts` pubkeys per filter.
+///
+/// Account, owner and `account_include` lists are chunked (values in a list are OR-ed, so every
+/// chunk is a valid subset, and a filter with both accounts and owners is split into every pair
+/// of an account chunk and an owner chunk). Everything else, including slots and block filters, stays on the
+/// first request only.
+pub fn split_subscribe_request(request: SubscribeRequest, max_accounts: usize) -> Vec<SubscribeRequest> {
+    let max_accounts = max_accounts.max(1);
+    let account_chunks: Vec<(String, Vec<SubscribeRequestFilterAccounts>)> = request
+        .accounts
+        .iter()
+        .map(|(name, filter)| (name.clone(), split_accounts_filter(filter, max_accounts)))
+        .collect();
+    let transaction_chunks: Vec<(String, Vec<SubscribeRequestFilterTransactions>)> = request
+        .transactions
+        .iter()
+        .map(|(name, filter)| (name.clone(), split_transactions_filter(filter, max_accounts)))
+        .collect();
+
+    let count = account_chunks
+        .iter()
+        .map(|(_, chunks)| chunks.len())
+        .chain(transaction_chunks.iter().map(|(_, chunks)| chunks.len()))
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    if count == 1 {
+        return vec![request];
+    }
+
+    (0..count)
+        .map(|index| {
+            let mut chunk = if index == 0 {
+                request.clone()
+            } else {
+                // Only the account-based filters are spread over the extra subscriptions
+                SubscribeRequest {
+                    commitment: request.commitment,
+                    accounts_data_slice: request.accounts_data_slice.clone(),
+                    ..Default::default()
+                }
+            };
+            // An empty filter map entry would match everything, so chunks without data are omitted
+            chunk.accounts = account_chunks
+                .iter()
+                .filter_map(|(name, chunks)| chunks.get(index).map(|filter| (name.clone(), filter.clone())))
+                .collect();
+            chunk.transactions = transaction_chunks
+                .iter()
+                .filter_map(|(name, chunks)| chunks.get(index).map(|filter| (name.clone(), filter.clone())))
+                .collect();
+            chunk
+        })
+        .collect()
+}
+
+fn split_accounts_filter(filter: &SubscribeRequestFilterAccounts, max_accounts: usize) -> Vec<SubscribeRequestFilterAccounts> {
+    // `account` and `owner` are AND-ed: each pair of an account chunk and an owner chunk is a
+    // subset of the filter, and together the pairs match everything the filter matches
+    let chunks = |list: &[String]| -> Vec<Vec<String>> {
+        if list.is_empty() {
+            vec![Vec::new()]
+        } else {
+            list.chunks(max_accounts).map(<[String]>::to_vec).collect()
+        }
+    };
+    let owners = chunks(&filter.owner);
+    chunks(&filter.account)
+        .into_iter()
+        .flat_map(|account| {
+            owners.iter().map(move |owner| SubscribeRequestFilterAccounts {
+                account: account.clone(),
+                owner: owner.clone(),
+                ..filter.clone()
+            })
+        })
+        .collect()
+}
+
+fn split_transactions_filter(
+    filter: &SubscribeRequestFilterTransactions,
+    max_accounts: usize,
+) -> Vec<SubscribeRequestFilterTransactions> {
+    // `account_exclude` and `account_required` must apply to every chunk unchanged
+    if filter.account_include.len() > max_accounts {
+        filter
+            .account_include
+            .chunks(max_accounts)
+            .map(|account_include| SubscribeRequestFilterTransactions {
+                account_include: account_include.to_vec(),
+                ..filter.clone()
+            })
+            .collect()
+    } else {
+        vec![filter.clone()]
+    }
+}
+
+/// Recently emitted transaction signatures, for subscriptions whose filters can overlap
+pub(super) struct SeenSignatures {
+    signatures: HashSet<Vec<u8>>,
+    order: VecDeque<Vec<u8>>,
+}
+
+impl SeenSignatures {
+    pub(super) fn new() -> Self {
+        Self {
+            signatures: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Record the signature of a transaction update, returns `false` if it was already emitted
+    pub(super) fn first_seen(&mut self, update: &SubscribeUpdate) -> bool {
+        let Some(UpdateOneof::Transaction(msg)) = &update.update_oneof else {
+            return true;
+        };
+        let Some(info) = &msg.transaction else {
+            return true;
+        };
+
+        if !self.signatures.insert(info.signature.clone()) {
+            return false;
+        }
+        self.order.push_back(info.signature.clone());
+        if self.order.len() > SEEN_SIGNATURES_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.signatures.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+/// Opens the subscription of one chunk of a `MultiplexSource`
+pub type Connect = Box<dyn Fn(SubscribeRequest) -> BoxFuture<'static, anyhow::Result<Box<dyn Source>>> + Send + Sync>;
+
+/// Merges several subscriptions into one logical stream.
+///
+/// `next` is raced across all inner sources, so they must be cancel-safe (gRPC streams are).
+/// Transactions matched by more than one chunk are only emitted once.
+pub struct MultiplexSource {
+    sources: Vec<Box<dyn Source>>,
+    seen: SeenSignatures,
+    max_accounts: usize,
+    connect: Connect,
+    health: Option<Arc<StreamHealth>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl MultiplexSource {
+    /// Split `request` with `split_subscribe_request` and open one subscription per chunk
+    pub async fn connect(request: SubscribeRequest, max_accounts: usize, connect: Connect) -> anyhow::Result<Self> {
+        let requests = split_subscribe_request(request, max_accounts);
+        if requests.len() > 1 {
+            info!("Splitting filters across {} subscriptions", requests.len());
+        }
+        let mut sources = Vec::with_capacity(requests.len());
+        for request in requests {
+            sources.push(connect(request).await?);
+        }
+        Ok(Self {
+            sources,
+            seen: SeenSignatures::new(),
+            max_accounts,
+            connect,
+            health: None,
+            metrics: None,
+        })
+    }
+}
+
+#[async_trait]
+impl Source for MultiplexSource {
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+        while !self.sources.is_empty() {
+            let (result, index, _) = select_all(self.sources.iter_mut().map(|source| source.next())).await;
+            match result {
+                Some(Ok(update)) => {
+                    if self.seen.first_seen(&update) {
+                        return Some(Ok(update));
+                    }
+                },
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    self.sources.remove(index);
+                },
+            }
+        }
+        None
+    }
+
+    async fn watermarks(&mut self) -> Option<Watermarks> {
+        self.sources.first_mut()?.watermarks().await
+    }
+
+    fn take_events(&mut self) -> Vec<SourceEvent> {
+        self.sources.iter_mut().flat_map(|source| source.take_events()).collect()
+    }
+
+    async fn update_request(&mut self, request: SubscribeRequest) -> anyhow::Result<()> {
+        let mut requests = split_subscribe_request(request, self.max_accounts);
+        // Subscriptions for extra chunks are opened first, so a failure leaves the current ones as they were
+        let mut added = Vec::new();
+        for request in requests.split_off(self.sources.len().min(requests.len())) {
+            let mut source = (self.connect)(request).await?;
+            if let Some(health) = &self.health {
+                source.attach_health(Arc::clone(health));
+            }
+            if let Some(metrics) = &self.metrics {
+                source.attach_metrics(Arc::clone(metrics));
+            }
+            added.push(source);
+        }
+        if requests.len() + added.len() != self.sources.len() {
+            info!("Splitting filters across {} subscriptions", requests.len() + added.len());
+        }
+
+        // Subscriptions of chunks that are gone are closed
+        self.sources.truncate(requests.len());
+        for (source, request) in self.sources.iter_mut().zip(requests) {
+            source.update_request(request).await?;
+        }
+        self.sources.extend(added);
+        Ok(())
+    }
+
+    fn attach_health(&mut self, health: Arc<StreamHealth>) {
+        for source in &mut self.sources {
+            source.attach_health(Arc::clone(&health));
+        }
+        self.health = Some(health);
+    }
+
+    fn attach_metrics(&mut self, metrics: Arc<Metrics>) {
+        for source in &mut self.sources {
+            source.attach_metrics(Arc::clone(&metrics));
+        }
+        self.metrics = Some(metrics);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use futures::{executor::block_on, FutureExt};
+
+    use super::*;
+
+    fn pubkeys(prefix: &str, count: usize) -> Vec<String> {
+        (0..count).map(|index| format!("{}{}", prefix, index)).collect()
+    }
+
+    /// Source recording the requests it was opened and updated with
+    struct Recorded(Arc<Mutex<Vec<SubscribeRequest>>>);
+
+    #[async_trait]
+    impl Source for Recorded {
+        async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+            None
+        }
+
+        async fn update_request(&mut self, request: SubscribeRequest) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(request);
+            Ok(())
+        }
+    }
+
+    fn accounts_request(count: usize) -> SubscribeRequest {
+        SubscribeRequest {
+            accounts: HashMap::from([("accounts".to_string(), SubscribeRequestFilterAccounts {
+                account: pubkeys("account", count),
+                ..Default::default()
+            })]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn chunks_both_accounts_and_owners() {
+        let filter = SubscribeRequestFilterAccounts {
+            account: pubkeys("account", 3),
+            owner: pubkeys("owner", 3),
+            ..Default::default()
+        };
+        let chunks = split_accounts_filter(&filter, 2);
+        assert_eq!(chunks.len(), 4);
+        assert!(chunks.iter().all(|chunk| chunk.account.len() <= 2 && chunk.owner.len() <= 2));
+        // Every (account, owner) pair the filter matches is matched by exactly one chunk
+        for account in &filter.account {
+            for owner in &filter.owner {
+                let matching = chunks
+                    .iter()
+                    .filter(|chunk| chunk.account.contains(account) && chunk.owner.contains(owner))
+                    .count();
+                assert_eq!(matching, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_filters_under_the_limit_and_spreads_extra_chunks() {
+        let small = SubscribeRequestFilterAccounts {
+            owner: pubkeys("owner", 2),
+            ..Default::default()
+        };
+        assert_eq!(split_accounts_filter(&small, 2), [small.clone()]);
+
+        let request = SubscribeRequest {
+            accounts: HashMap::from([
+                ("small".to_string(), small),
+                ("large".to_string(), SubscribeRequestFilterAccounts {
+                    account: pubkeys("account", 5),
+                    ..Default::default()
+                }),
+            ]),
+            ..Default::default()
+        };
+        let requests = split_subscribe_request(request, 2);
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].accounts.len(), 2);
+        assert!(requests[1..].iter().all(|request| request.accounts.keys().collect::<Vec<_>>() == ["large"]));
+    }
+
+    #[test]
+    fn updates_and_resizes_the_subscriptions() {
+        let opened: Arc<Mutex<Vec<Arc<Mutex<Vec<SubscribeRequest>>>>>> = Arc::default();
+        let connect: Connect = {
+            let opened = Arc::clone(&opened);
+            Box::new(move |request| {
+                let requests = Arc::new(Mutex::new(vec![request]));
+                opened.lock().unwrap().push(Arc::clone(&requests));
+                async move { Ok(Box::new(Recorded(requests)) as Box<dyn Source>) }.boxed()
+            })
+        };
+        let accounts = |requests: &Arc<Mutex<Vec<SubscribeRequest>>>| {
+            requests.lock().unwrap().last().unwrap().accounts["accounts"].account.clone()
+        };
+
+        let mut source = block_on(MultiplexSource::connect(accounts_request(4), 2, connect)).unwrap();
+        assert_eq!(source.sources.len(), 2);
+
+        // The open subscriptions are updated in place, the extra chunk gets its own
+        block_on(source.update_request(accounts_request(5))).unwrap();
+        assert_eq!(source.sources.len(), 3);
+        let subscriptions = opened.lock().unwrap().clone();
+        assert_eq!(subscriptions.len(), 3);
+        assert!(subscriptions[..2].iter().all(|requests| requests.lock().unwrap().len() == 2));
+        assert_eq!(subscriptions.iter().flat_map(accounts).collect::<Vec<_>>(), pubkeys("account", 5));
+
+        block_on(source.update_request(accounts_request(1))).unwrap();
+        assert_eq!(source.sources.len(), 1);
+        assert_eq!(opened.lock().unwrap().len(), 3);
+        assert_eq!(accounts(&subscriptions[0]), pubkeys("account", 1));
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use backoff::{backoff::Backoff, ExponentialBackoff};
+use log::{info, warn};
+use tokio::sync::watch;
+use yellowstone_grpc_proto::{
+    prelude::{SubscribeRequest, SubscribeUpdate},
+    prost::Message,
+    tonic::{Code, Status},
+};
+
+use super::{
+    decode::FrameHints,
+    grpc::{self, GrpcEndpoint},
+    update_slot, Source, SourceEvent, Watermarks,
+};
+use crate::{credentials, health::StreamHealth, metrics::Metrics, quota};
+
+/// Longest wait between two reconnect attempts
+const MAX_RECONNECT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// gRPC subscription that re-subscribes with backoff when the stream fails or closes.
+///
+/// Reconnects replay from the last slot seen, falling back to the tip when the endpoint cannot
+/// replay it. Reconnects and the slots missed while disconnected are reported as `SourceEvent`s.
+/// With credential profiles, it also re-subscribes whenever the active profile changes.
+/// State lives on the struct so a cancelled `next` (e.g. inside `MultiplexSource`) resumes the
+/// reconnect where it left off.
+pub struct ReconnectingSource {
+    endpoint: GrpcEndpoint,
+    request: SubscribeRequest,
+    inner: Option<Box<dyn Source>>,
+    // Kept across reconnects, so a new stream starts with a receive buffer fit for the updates
+    hints: Arc<FrameHints>,
+    backoff: ExponentialBackoff,
+    attempt: u32,
+    reason: String,
+    disconnected_at: Option<Instant>,
+    last_slot: Option<u64>,
+    // Last slot seen before the disconnect, until the first update after reconnecting
+    gap_from: Option<u64>,
+    events: Vec<SourceEvent>,
+    rotation: Option<watch::Receiver<usize>>,
+    health: Option<Arc<StreamHealth>>,
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl ReconnectingSource {
+    pub async fn connect(endpoint: GrpcEndpoint, mut request: SubscribeRequest) -> anyhow::Result<Self> {
+        let hints = Arc::new(FrameHints::default());
+        let inner = grpc::connect(&endpoint, request.clone(), Arc::clone(&hints)).await?;
+        // A checkpoint replay only applies to the first subscription, reconnects replay from the
+        // last slot seen
+        request.from_slot = None;
+        Ok(Self {
+            endpoint,
+            request,
+            inner: Some(inner),
+            hints,
+            backoff: new_backoff(),
+            attempt: 0,
+            reason: String::new(),
+            disconnected_at: None,
+            last_slot: None,
+            gap_from: None,
+            events: Vec::new(),
+            rotation: credentials::installed().map(|credentials| credentials.subscribe()),
+            health: None,
+            metrics: None,
+        })
+    }
+
+    /// Drop the failed subscription, `code` is the status it failed with, `None` when closed
+    fn disconnected(&mut self, reason: String, code: Option<Code>) {
+        warn!("gRPC subscription lost: {}", reason);
+        self.inner = None;
+        self.reason = reason;
+        self.disconnected_at.get_or_insert_with(Instant::now);
+        if let Some(health) = &self.health {
+            health.disconnected();
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.increment_stream_errors(code);
+        }
+        if self.gap_from.is_none() {
+            self.gap_from = self.last_slot;
+        }
+    }
+
+    /// Drop the subscription to re-subscribe with the credential profile that became active
+    fn rotated(&mut self) {
+        info!("Credential profile changed, re-subscribing");
+        self.inner = None;
+        self.reason = "credential rotation".to_string();
+        self.disconnected_at.get_or_insert_with(Instant::now);
+        self.backoff.reset();
+        if let Some(health) = &self.health {
+            health.disconnected();
+        }
+        if self.gap_from.is_none() {
+            self.gap_from = self.last_slot;
+        }
+    }
+
+    async fn reconnect(&mut self) -> Box<dyn Source> {
+        // The updates of the last slot seen may not all have been received
+        let mut from_slot = self.gap_from;
+        loop {
+            let delay = self.backoff.next_backoff().unwrap_or(MAX_RECONNECT_INTERVAL);
+            tokio::time::sleep(delay).await;
+            self.attempt += 1;
+            if let Some(metrics) = &self.metrics {
+                metrics.increment_reconnect_attempts();
+            }
+
+            let request = SubscribeRequest {
+                from_slot,
+                ..self.request.clone()
+            };
+            match grpc::connect(&self.endpoint, request, Arc::clone(&self.hints)).await {
+                Ok(inner) => {
+                    info!("gRPC subscription re-established after {} attempt(s)", self.attempt);
+                    self.events.push(SourceEvent::Reconnect {
+                        attempt: self.attempt,
+                        reason: std::mem::take(&mut self.reason),
+                    });
+                    self.attempt = 0;
+                    self.backoff.reset();
+                    if let Some(health) = &self.health {
+                        health.reconnected();
+                    }
+                    if let (Some(metrics), Some(disconnected_at)) = (&self.metrics, self.disconnected_at.take()) {
+                        metrics.record_reconnect(disconnected_at.elapsed());
+                    }
+                    return inner;
+                },
+                Err(e) => match from_slot.take() {
+                    Some(slot) => warn!(
+                        "Reconnect attempt {} replaying from slot {} failed, retrying from the tip: {:?}",
+                        self.attempt, slot, e
+                    ),
+                    None => warn!("Reconnect attempt {} failed: {:?}", self.attempt, e),
+                },
+            }
+        }
+    }
+
+    /// Account the update to the credential profile and the byte usage of the endpoint
+    fn consumed(&self, update: &SubscribeUpdate) {
+        let bytes = update.encoded_len() as u64;
+        if let Some(credentials) = credentials::installed() {
+            credentials.consumed(1);
+        }
+        quota::record(credentials::active_profile().unwrap_or(&self.endpoint.endpoint), bytes);
+        if let Some(metrics) = &self.metrics {
+            metrics.add_received_bytes(bytes);
+        }
+    }
+
+    fn observe(&mut self, update: &SubscribeUpdate) {
+        let Some(slot) = update.update_oneof.as_ref().and_then(update_slot) else {
+            return;
+        };
+        if let Some(last_slot) = self.gap_from.take() {
+            if slot > last_slot + 1 {
+                self.events.push(SourceEvent::Gap {
+                    from_slot: last_slot + 1,
+                    to_slot: slot - 1,
+                });
+            }
+        }
+        self.last_slot = Some(self.last_slot.map_or(slot, |last_slot| last_slot.max(slot)));
+    }
+}
+
+#[async_trait]
+impl Source for ReconnectingSource {
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+        loop {
+            if self.inner.is_none() {
+                let inner = self.reconnect().await;
+                self.inner = Some(inner);
+            }
+            let Some(inner) = self.inner.as_mut() else {
+                continue;
+            };
+
+            let next = match self.rotation.as_mut() {
+                Some(rotation) => tokio::select! {
+                    next = inner.next() => Some(next),
+                    Ok(()) = rotation.changed() => None,
+                },
+                None => Some(inner.next().await),
+            };
+            let Some(next) = next else {
+                self.rotated();
+                continue;
+            };
+
+            match next {
+                Some(Ok(update)) => {
+                    self.consumed(&update);
+                    self.observe(&update);
+                    return Some(Ok(update));
+                },
+                Some(Err(e)) => {
+                    // Transport failures reach the stream as statuses too
+                    let code = e.downcast_ref::<Status>().map_or(Code::Unknown, Status::code);
+                    self.disconnected(format!("{:?}", e), Some(code));
+                },
+                None => self.disconnected("stream closed".to_string(), None),
+            }
+        }
+    }
+
+    async fn watermarks(&mut self) -> Option<Watermarks> {
+        self.inner.as_mut()?.watermarks().await
+    }
+
+    fn take_events(&mut self) -> Vec<SourceEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    async fn update_request(&mut self, mut request: SubscribeRequest) -> anyhow::Result<()> {
+        request.from_slot = None;
+        // Kept for reconnects, a disconnected source picks it up on the next attempt
+        self.request = request.clone();
+        match self.inner.as_mut() {
+            Some(inner) => inner.update_request(request).await,
+            None => Ok(()),
+        }
+    }
+
+    fn attach_health(&mut self, health: Arc<StreamHealth>) {
+        self.health = Some(health);
+    }
+
+    fn attach_metrics(&mut self, metrics: Arc<Metrics>) {
+        self.metrics = Some(metrics);
+    }
+}
+
+fn new_backoff() -> ExponentialBackoff {
+    ExponentialBackoff {
+        max_interval: MAX_RECONNECT_INTERVAL,
+        max_elapsed_time: None,
+        ..Default::default()
+    }
+}
@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, BufReader},
+};
+use yellowstone_grpc_proto::{prelude::SubscribeUpdate, prost::Message};
+
+use super::Source;
+
+/// Largest frame accepted, anything bigger means the file is corrupt
+const MAX_FRAME_SIZE: u64 = 1024 * 1024 * 1024;
+/// Largest frame buffer kept between frames, so one large block does not pin its memory
+const MAX_RETAINED_FRAME: usize = 16 * 1024 * 1024;
+
+/// Replays length-delimited protobuf `SubscribeUpdate`s from a file, reading every frame into
+/// the same buffer
+pub struct ReplaySource {
+    reader: BufReader<File>,
+    frame: Vec<u8>,
+    done: bool,
+}
+
+impl ReplaySource {
+    pub async fn open(path: &str) -> anyhow::Result<Self> {
+        let file = File::open(path).await?;
+        Ok(Self {
+            reader: BufReader::new(file),
+            frame: Vec::new(),
+            done: false,
+        })
+    }
+
+    /// Length prefix of the next frame, `None` at the end of the file
+    async fn frame_len(&mut self) -> anyhow::Result<Option<u64>> {
+        let mut len = 0u64;
+        for index in 0..10 {
+            let byte = match self.reader.read_u8().await {
+                Ok(byte) => byte,
+                Err(e) if index == 0 && e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+            len |= u64::from(byte & 0x7f) << (7 * index);
+            if byte & 0x80 == 0 {
+                anyhow::ensure!(len <= MAX_FRAME_SIZE, "frame of {} bytes exceeds the maximum frame size", len);
+                return Ok(Some(len));
+            }
+        }
+        anyhow::bail!("invalid frame length prefix")
+    }
+
+    async fn read_update(&mut self) -> anyhow::Result<Option<SubscribeUpdate>> {
+        let Some(len) = self.frame_len().await? else {
+            return Ok(None);
+        };
+
+        // Decoding copies every field out of the frame, the buffer is reused right after
+        self.frame.clear();
+        self.frame.resize(len as usize, 0);
+        self.reader.read_exact(&mut self.frame).await?;
+        let update = SubscribeUpdate::decode(&self.frame[..]);
+        if self.frame.capacity() > MAX_RETAINED_FRAME {
+            self.frame = Vec::new();
+        }
+        Ok(Some(update?))
+    }
+}
+
+#[async_trait]
+impl Source for ReplaySource {
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+        if self.done {
+            return None;
+        }
+
+        match self.read_update().await {
+            Ok(Some(update)) => Some(Ok(update)),
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(e) => {
+                // A corrupt frame makes the rest of the file unreadable
+                self.done = true;
+                Some(Err(e))
+            },
+        }
+    }
+}
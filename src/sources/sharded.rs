@@ -0,0 +1,317 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use log::{error, info};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, SubscribeRequest, SubscribeRequestFilterSlots,
+    SubscribeRequestFilterTransactions, SubscribeUpdate,
+};
+
+use super::{grpc::GrpcEndpoint, grpc_source, multiplex::SeenSignatures, update_slot, Source, SourceEvent, Watermarks};
+use crate::{health::StreamHealth, metrics::Metrics};
+
+/// Slots filter added to every shard, its updates advance the shard frontier and are not emitted
+const FRONTIER_FILTER: &str = "shard_frontier";
+/// Updates buffered per shard before the reading task waits for the merge
+const SHARD_CHANNEL_SIZE: usize = 10_000;
+
+/// Shard of a base58 pubkey, from its first byte
+fn shard_of(pubkey: &str, shards: usize) -> usize {
+    let first_byte = bs58::decode(pubkey).into_vec().ok().and_then(|bytes| bytes.first().copied());
+    first_byte.map_or(0, |byte| byte as usize % shards)
+}
+
+fn partition(pubkeys: &[String], shards: usize) -> Vec<Vec<String>> {
+    let mut partitions = vec![Vec::new(); shards];
+    for pubkey in pubkeys {
+        partitions[shard_of(pubkey, shards)].push(pubkey.clone());
+    }
+    partitions
+}
+
+/// Split `request` into exactly `shards` requests by account prefix.
+///
+/// `account` (or, without accounts, `owner`) lists of account filters and `account_include`
+/// lists of transaction filters are partitioned by the first byte of each pubkey; their values
+/// are OR-ed, so every part is a valid subset. Filters without such a list, and every other
+/// filter kind, stay on the first shard. Every shard also gets a slots filter whose updates are
+/// the ordering hints of the merge.
+pub fn shard_subscribe_request(request: &SubscribeRequest, shards: usize) -> Vec<SubscribeRequest> {
+    let shards = shards.max(1);
+    let mut requests: Vec<SubscribeRequest> = (0..shards)
+        .map(|index| {
+            let mut shard = if index == 0 {
+                request.clone()
+            } else {
+                SubscribeRequest {
+                    commitment: request.commitment,
+                    accounts_data_slice: request.accounts_data_slice.clone(),
+                    ..Default::default()
+                }
+            };
+            shard.accounts = HashMap::new();
+            shard.transactions = HashMap::new();
+            shard.slots.insert(FRONTIER_FILTER.to_owned(), SubscribeRequestFilterSlots::default());
+            shard
+        })
+        .collect();
+
+    for (name, filter) in &request.accounts {
+        // `account` and `owner` are AND-ed, so only one of them can be partitioned
+        let by_account = !filter.account.is_empty();
+        let pubkeys = if by_account {
+            &filter.account
+        } else if !filter.owner.is_empty() {
+            &filter.owner
+        } else {
+            requests[0].accounts.insert(name.clone(), filter.clone());
+            continue;
+        };
+        for (index, part) in partition(pubkeys, shards).into_iter().enumerate() {
+            // An empty list would match every account, so shards without pubkeys get no filter
+            if part.is_empty() {
+                continue;
+            }
+            let mut sharded = filter.clone();
+            if by_account {
+                sharded.account = part;
+            } else {
+                sharded.owner = part;
+            }
+            requests[index].accounts.insert(name.clone(), sharded);
+        }
+    }
+
+    for (name, filter) in &request.transactions {
+        if filter.account_include.is_empty() {
+            requests[0].transactions.insert(name.clone(), filter.clone());
+            continue;
+        }
+        for (index, part) in partition(&filter.account_include, shards).into_iter().enumerate() {
+            if !part.is_empty() {
+                let sharded = SubscribeRequestFilterTransactions {
+                    account_include: part,
+                    ..filter.clone()
+                };
+                requests[index].transactions.insert(name.clone(), sharded);
+            }
+        }
+    }
+
+    requests
+}
+
+/// Whether an update only matched the frontier filter
+fn is_frontier(update: &SubscribeUpdate) -> bool {
+    matches!(update.update_oneof, Some(UpdateOneof::Slot(_)))
+        && update.filters.iter().all(|filter| filter == FRONTIER_FILTER)
+}
+
+enum ShardCommand {
+    UpdateRequest(SubscribeRequest, oneshot::Sender<anyhow::Result<()>>),
+    Watermarks(oneshot::Sender<Option<Watermarks>>),
+    AttachHealth(Arc<StreamHealth>),
+    AttachMetrics(Arc<Metrics>),
+}
+
+/// Filter set sharded across parallel subscriptions, each read and decoded by its own task.
+///
+/// Updates are merged in slot order: an update is emitted once every shard has moved past its
+/// slot, or after `reorder_window` at the latest so a quiet shard does not hold the others back.
+/// Transactions matched by more than one shard are only emitted once.
+pub struct ShardedSource {
+    shards: usize,
+    commands: Vec<mpsc::Sender<ShardCommand>>,
+    updates: mpsc::Receiver<(usize, anyhow::Result<SubscribeUpdate>)>,
+    events: Arc<Mutex<Vec<SourceEvent>>>,
+    // Highest slot seen by each shard
+    frontiers: Vec<u64>,
+    // Buffered updates by slot, with the arrival of the first one
+    buffered: BTreeMap<u64, (Instant, Vec<SubscribeUpdate>)>,
+    ready: VecDeque<SubscribeUpdate>,
+    reorder_window: Duration,
+    seen: SeenSignatures,
+    closed: bool,
+}
+
+impl ShardedSource {
+    pub async fn connect(
+        endpoint: GrpcEndpoint,
+        request: SubscribeRequest,
+        shards: usize,
+        max_accounts: Option<usize>,
+        reorder_window: Duration,
+    ) -> anyhow::Result<Self> {
+        let requests = shard_subscribe_request(&request, shards);
+        info!("Sharding filters across {} subscriptions", requests.len());
+
+        let (updates_tx, updates) = mpsc::channel(SHARD_CHANNEL_SIZE * requests.len());
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut commands = Vec::with_capacity(requests.len());
+        for (index, request) in requests.into_iter().enumerate() {
+            let source = grpc_source(endpoint.clone(), request, max_accounts).await?;
+            let (command_tx, command_rx) = mpsc::channel(4);
+            tokio::spawn(read_shard(index, source, command_rx, updates_tx.clone(), Arc::clone(&events)));
+            commands.push(command_tx);
+        }
+
+        Ok(Self {
+            shards: commands.len(),
+            frontiers: vec![0; commands.len()],
+            commands,
+            updates,
+            events,
+            buffered: BTreeMap::new(),
+            ready: VecDeque::new(),
+            reorder_window,
+            seen: SeenSignatures::new(),
+            closed: false,
+        })
+    }
+
+    fn push(&mut self, shard: usize, update: SubscribeUpdate) {
+        let Some(slot) = update.update_oneof.as_ref().and_then(update_slot) else {
+            // Pings and pongs carry no slot
+            self.ready.push_back(update);
+            return;
+        };
+        self.frontiers[shard] = self.frontiers[shard].max(slot);
+        if is_frontier(&update) || !self.seen.first_seen(&update) {
+            return;
+        }
+        self.buffered.entry(slot).or_insert_with(|| (Instant::now(), Vec::new())).1.push(update);
+    }
+
+    /// Move the buffered slots every shard moved past, or held for the reorder window, to `ready`
+    fn release(&mut self) {
+        let low_watermark = self.frontiers.iter().min().copied().unwrap_or(0);
+        let expired = self
+            .buffered
+            .iter()
+            .filter(|(_, (arrived, _))| arrived.elapsed() >= self.reorder_window)
+            .map(|(slot, _)| *slot)
+            .max();
+        let release_below = if self.closed {
+            u64::MAX
+        } else {
+            expired.map_or(low_watermark, |slot| low_watermark.max(slot.saturating_add(1)))
+        };
+
+        while let Some(entry) = self.buffered.first_entry() {
+            if *entry.key() >= release_below {
+                break;
+            }
+            self.ready.extend(entry.remove().1);
+        }
+    }
+
+    /// When the oldest buffered slot must be released
+    fn next_deadline(&self) -> Option<Instant> {
+        self.buffered.values().map(|(arrived, _)| *arrived + self.reorder_window).min()
+    }
+}
+
+#[async_trait]
+impl Source for ShardedSource {
+    async fn next(&mut self) -> Option<anyhow::Result<SubscribeUpdate>> {
+        loop {
+            self.release();
+            if let Some(update) = self.ready.pop_front() {
+                return Some(Ok(update));
+            }
+            if self.closed {
+                return None;
+            }
+
+            let deadline = self.next_deadline();
+            tokio::select! {
+                message = self.updates.recv() => match message {
+                    Some((shard, Ok(update))) => self.push(shard, update),
+                    Some((_, Err(e))) => return Some(Err(e)),
+                    None => self.closed = true,
+                },
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {},
+            }
+        }
+    }
+
+    async fn watermarks(&mut self) -> Option<Watermarks> {
+        let (tx, rx) = oneshot::channel();
+        self.commands.first()?.send(ShardCommand::Watermarks(tx)).await.ok()?;
+        rx.await.ok().flatten()
+    }
+
+    fn take_events(&mut self) -> Vec<SourceEvent> {
+        std::mem::take(&mut *self.events.lock().expect("shard events lock"))
+    }
+
+    async fn update_request(&mut self, request: SubscribeRequest) -> anyhow::Result<()> {
+        for (command, request) in self.commands.iter().zip(shard_subscribe_request(&request, self.shards)) {
+            let (tx, rx) = oneshot::channel();
+            command
+                .send(ShardCommand::UpdateRequest(request, tx))
+                .await
+                .map_err(|_| anyhow::anyhow!("shard stopped"))?;
+            rx.await.map_err(|_| anyhow::anyhow!("shard stopped"))??;
+        }
+        Ok(())
+    }
+
+    fn attach_health(&mut self, health: Arc<StreamHealth>) {
+        for command in &self.commands {
+            if command.try_send(ShardCommand::AttachHealth(Arc::clone(&health))).is_err() {
+                error!("Failed to attach health to a shard");
+            }
+        }
+    }
+
+    fn attach_metrics(&mut self, metrics: Arc<Metrics>) {
+        for command in &self.commands {
+            if command.try_send(ShardCommand::AttachMetrics(Arc::clone(&metrics))).is_err() {
+                error!("Failed to attach metrics to a shard");
+            }
+        }
+    }
+}
+
+/// Read one shard until the merge stops, serving its commands in between
+async fn read_shard(
+    index: usize,
+    mut source: Box<dyn Source>,
+    mut commands: mpsc::Receiver<ShardCommand>,
+    updates: mpsc::Sender<(usize, anyhow::Result<SubscribeUpdate>)>,
+    events: Arc<Mutex<Vec<SourceEvent>>>,
+) {
+    loop {
+        tokio::select! {
+            Some(command) = commands.recv() => match command {
+                ShardCommand::UpdateRequest(request, done) => {
+                    let _ = done.send(source.update_request(request).await);
+                },
+                ShardCommand::Watermarks(reply) => {
+                    let _ = reply.send(source.watermarks().await);
+                },
+                ShardCommand::AttachHealth(health) => source.attach_health(health),
+                ShardCommand::AttachMetrics(metrics) => source.attach_metrics(metrics),
+            },
+            update = source.next() => {
+                events.lock().expect("shard events lock").extend(source.take_events());
+                let Some(update) = update else {
+                    info!("Shard {} closed", index);
+                    return;
+                };
+                if updates.send((index, update)).await.is_err() {
+                    return;
+                }
+            },
+        }
+    }
+}
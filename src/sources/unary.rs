@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::future::BoxFuture;
+use log::warn;
+use tokio::sync::Mutex as AsyncMutex;
+use yellowstone_grpc_proto::prelude::CommitmentLevel;
+
+use super::{grpc::unary_client, GrpcEndpoint, UnaryApi, Watermarks};
+
+/// Unary call pool settings
+#[derive(Debug, Clone)]
+pub struct UnaryConfig {
+    pub pool_size: usize,
+    /// Deadline of a call, including connecting its client
+    pub timeout: Duration,
+    /// Consecutive failures opening the circuit
+    pub failure_threshold: u32,
+    /// How long calls fail fast once the circuit is open
+    pub cooldown: Duration,
+}
+
+impl Default for UnaryConfig {
+    fn default() -> Self {
+        Self {
+            pool_size: 3,
+            timeout: Duration::from_secs(5),
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Clients for unary calls such as `get_slot`, separate from the subscriptions so a slow or
+/// failing unary call never affects the stream, and the other way around.
+///
+/// Calls are spread round-robin over `pool_size` clients, connected on first use and dropped
+/// after an error. After `failure_threshold` consecutive failures the circuit opens and calls
+/// fail fast for `cooldown`, then the next call probes the endpoint again.
+pub struct UnaryPool {
+    endpoint: GrpcEndpoint,
+    config: UnaryConfig,
+    clients: Vec<AsyncMutex<Option<Box<dyn UnaryApi>>>>,
+    next: AtomicUsize,
+    breaker: Mutex<Breaker>,
+}
+
+impl UnaryPool {
+    fn new(endpoint: GrpcEndpoint) -> Self {
+        let config = endpoint.unary.clone();
+        Self {
+            clients: (0..config.pool_size.max(1)).map(|_| AsyncMutex::new(None)).collect(),
+            endpoint,
+            config,
+            next: AtomicUsize::new(0),
+            breaker: Mutex::new(Breaker::default()),
+        }
+    }
+
+    /// Pool of `endpoint`, shared by every source and handler calling it
+    pub fn shared(endpoint: &GrpcEndpoint) -> Arc<UnaryPool> {
+        static POOLS: OnceLock<Mutex<HashMap<String, Arc<UnaryPool>>>> = OnceLock::new();
+        let mut pools = POOLS.get_or_init(Default::default).lock().expect("unary pools lock");
+        Arc::clone(
+            pools
+                .entry(endpoint.endpoint.clone())
+                .or_insert_with(|| Arc::new(UnaryPool::new(endpoint.clone()))),
+        )
+    }
+
+    /// Run a unary call on the next client of the pool
+    pub async fn call<T>(
+        &self,
+        f: impl for<'a> FnOnce(&'a mut Box<dyn UnaryApi>) -> BoxFuture<'a, anyhow::Result<T>>,
+    ) -> anyhow::Result<T> {
+        if let Some(open_until) = self.breaker.lock().expect("unary breaker lock").open_until {
+            let remaining = open_until.saturating_duration_since(Instant::now());
+            anyhow::ensure!(
+                remaining.is_zero(),
+                "unary calls to {} failing, circuit open for another {:?}",
+                self.endpoint.endpoint,
+                remaining
+            );
+        }
+
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        let mut client = self.clients[index].lock().await;
+        let call = async {
+            if client.is_none() {
+                *client = Some(unary_client(&self.endpoint).await?);
+            }
+            f(client.as_mut().expect("unary client is connected")).await
+        };
+        let result = match tokio::time::timeout(self.config.timeout, call).await {
+            Ok(result) => result,
+            Err(_) => Err(anyhow::anyhow!("unary call timed out after {:?}", self.config.timeout)),
+        };
+
+        let mut breaker = self.breaker.lock().expect("unary breaker lock");
+        match &result {
+            Ok(_) => *breaker = Breaker::default(),
+            Err(e) => {
+                *client = None;
+                breaker.failures += 1;
+                if breaker.failures >= self.config.failure_threshold {
+                    warn!(
+                        "{} consecutive unary call failures on {}, pausing calls for {:?}: {:#}",
+                        breaker.failures, self.endpoint.endpoint, self.config.cooldown, e
+                    );
+                    breaker.open_until = Some(Instant::now() + self.config.cooldown);
+                }
+            },
+        }
+        result
+    }
+
+    /// Processed, confirmed and finalized slots, fetched concurrently
+    pub async fn watermarks(&self) -> anyhow::Result<Watermarks> {
+        let slot = |commitment| self.call(move |client| client.get_slot(Some(commitment)));
+        let (processed, confirmed, finalized) = tokio::try_join!(
+            slot(CommitmentLevel::Processed),
+            slot(CommitmentLevel::Confirmed),
+            slot(CommitmentLevel::Finalized),
+        )?;
+
+        Ok(Watermarks {
+            processed: processed.slot,
+            confirmed: confirmed.slot,
+            finalized: finalized.slot,
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use serde_json::{json, Value};
+use solana_sdk::stake::{self, instruction::StakeInstruction};
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+use crate::{
+    codec, config::YellowstoneGrpcConfig, derived::DerivedStream, instructions, outbox::Outbox, sinks::SinkConnector,
+};
+
+/// Normalized event of a stake instruction, from its data and account positions
+fn stake_event(instruction: StakeInstruction, account: impl Fn(usize) -> Option<String>) -> Option<Value> {
+    let event = match instruction {
+        StakeInstruction::DelegateStake => json!({
+            "event": "delegate",
+            "stakeAccount": account(0),
+            "voteAccount": account(1),
+            "authority": account(5),
+        }),
+        StakeInstruction::Deactivate => json!({
+            "event": "deactivate",
+            "stakeAccount": account(0),
+            "authority": account(2),
+        }),
+        StakeInstruction::Withdraw(lamports) => json!({
+            "event": "withdraw",
+            "stakeAccount": account(0),
+            "destination": account(1),
+            "authority": account(4),
+            "lamports": lamports,
+        }),
+        StakeInstruction::Split(lamports) => json!({
+            "event": "split",
+            "stakeAccount": account(0),
+            "destination": account(1),
+            "authority": account(2),
+            "lamports": lamports,
+        }),
+        StakeInstruction::Merge => json!({
+            "event": "merge",
+            // The source is merged into the destination and closed
+            "stakeAccount": account(1),
+            "destination": account(0),
+            "authority": account(4),
+        }),
+        _ => return None,
+    };
+    Some(event)
+}
+
+/// Stake account lifecycle events (delegate, deactivate, withdraw, split, merge) extracted from
+/// the top-level and inner stake instructions of successful transactions
+pub struct StakeEvents {
+    stream: DerivedStream,
+    stake_program: Vec<u8>,
+}
+
+impl StakeEvents {
+    /// Publish the stake events of a transaction, or hold them in `outbox` until its record is
+    /// acknowledged
+    pub async fn observe(&self, update: &SubscribeUpdateTransaction, outbox: Option<&Outbox>) {
+        let signature = update.transaction.as_ref().map(|info| codec::encode_bs58(&info.signature)).unwrap_or_default();
+        for instruction in instructions::program_instructions(update, &self.stake_program) {
+            let Ok(stake_instruction) = bincode::deserialize::<StakeInstruction>(instruction.data) else {
+                continue;
+            };
+            let Some(mut event) = stake_event(stake_instruction, |position| instruction.account(position)) else {
+                continue;
+            };
+
+            event["signature"] = json!(signature);
+            event["slot"] = json!(update.slot);
+            event["cpi"] = json!(instruction.cpi);
+            let key = event["stakeAccount"].as_str().unwrap_or(&signature).to_owned();
+            self.stream.emit(outbox, &signature, key, event).await;
+        }
+    }
+
+    /// Flush the stake events, at shutdown
+    pub async fn finish(&self) {
+        self.stream.flush().await;
+    }
+}
+
+/// Connect the stake event topic, `suffix` is appended to it in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<StakeEvents>> {
+    let Some(topic) = config.get_stake_events_topic() else {
+        return Ok(None);
+    };
+
+    Ok(Some(StakeEvents {
+        stream: DerivedStream::connect(sinks, config, "stake events", &topic, suffix).await?,
+        stake_program: stake::program::id().to_bytes().to_vec(),
+    }))
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::BTreeMap,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use yellowstone_grpc_proto::prost_types::Timestamp;
+
+use crate::{metrics::Metrics, ProcessingMessage};
+
+/// Block times kept to date the updates of recent slots
+const MAX_BLOCK_TIMES: usize = 512;
+/// Slot duration used to date slots whose block time is not known yet
+const SLOT_DURATION_MS: i64 = 400;
+
+/// Time the age of an update is measured from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StalenessBasis {
+    /// Block time of the update's slot, estimated from the latest block times until its block
+    /// metadata arrives
+    #[default]
+    BlockTime,
+    /// Time the upstream created the update
+    Received,
+}
+
+/// Staleness settings
+#[derive(Debug, Clone)]
+pub struct StalenessConfig {
+    pub max_age: Duration,
+    pub basis: StalenessBasis,
+}
+
+/// Drops updates older than `max_age` before they are published, e.g. while catching up from a
+/// checkpoint, for consumers that would rather skip a stale backlog than process it.
+pub struct StalenessGuard {
+    max_age_ms: i64,
+    basis: StalenessBasis,
+    // Block time in milliseconds by slot
+    block_times: BTreeMap<u64, i64>,
+    metrics: Option<Arc<Metrics>>,
+    dropping: bool,
+}
+
+impl StalenessGuard {
+    pub fn new(config: StalenessConfig, metrics: Option<Arc<Metrics>>) -> Self {
+        Self {
+            max_age_ms: config.max_age.as_millis() as i64,
+            basis: config.basis,
+            block_times: BTreeMap::new(),
+            metrics,
+            dropping: false,
+        }
+    }
+
+    /// Whether `message`, created upstream at `created_at`, is too old to be published
+    pub fn stale(&mut self, message: &ProcessingMessage, created_at: Option<&Timestamp>) -> bool {
+        if let ProcessingMessage::BlockMetadata(block_meta) = message {
+            if let Some(block_time) = &block_meta.block_time {
+                self.block_times.insert(block_meta.slot, block_time.timestamp * 1000);
+                while self.block_times.len() > MAX_BLOCK_TIMES {
+                    self.block_times.pop_first();
+                }
+            }
+        }
+
+        let time_ms = match self.basis {
+            StalenessBasis::BlockTime => message.slot().and_then(|slot| self.block_time(slot)),
+            StalenessBasis::Received => {
+                created_at.map(|created_at| created_at.seconds * 1000 + i64::from(created_at.nanos) / 1_000_000)
+            },
+        };
+        // Without a time the update cannot be dated, it is kept
+        let Some(time_ms) = time_ms else {
+            return false;
+        };
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+        let age_ms = now_ms - time_ms;
+
+        let stale = age_ms > self.max_age_ms;
+        if stale != self.dropping {
+            self.dropping = stale;
+            if stale {
+                warn!("Updates are {}s old (max {}s), dropping stale updates", age_ms / 1000, self.max_age_ms / 1000);
+            } else {
+                info!("Updates are fresh again, no longer dropping");
+            }
+        }
+        if stale {
+            if let Some(metrics) = &self.metrics {
+                metrics.increment_stale_dropped();
+            }
+        }
+        stale
+    }
+
+    /// Block time of a slot, estimated from the closest known block time
+    fn block_time(&self, slot: u64) -> Option<i64> {
+        if let Some(block_time) = self.block_times.get(&slot) {
+            return Some(*block_time);
+        }
+        let (known_slot, known_time) = self
+            .block_times
+            .range(..slot)
+            .next_back()
+            .or_else(|| self.block_times.range(slot..).next())?;
+        Some(known_time + (slot as i64 - *known_slot as i64) * SLOT_DURATION_MS)
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::ProcessingMessage;
+
+mod token_supply;
+
+use token_supply::TokenSupplyTracker;
+
+/// State machine over the stream, keeping one `State` per key (e.g. per mint or per market).
+///
+/// Implement it and add a variant to `StatefulProcessorKind` to run it in the processor: every
+/// message is offered to `keys`, then applied to the state of each returned key. The records
+/// returned by `apply` are published as `state` records, keyed by the state key.
+pub trait StatefulProcessor: Send {
+    type State: Default + Send;
+
+    /// Keys of the states `message` applies to, usually none or one
+    fn keys(&self, message: &ProcessingMessage) -> Vec<String>;
+
+    /// Apply `message` to the state of `key`, returning a record to publish when it changed
+    fn apply(&mut self, key: &str, state: &mut Self::State, message: &ProcessingMessage) -> Option<Value>;
+}
+
+/// Object safe runner of a `StatefulProcessor` holding its states
+pub trait StatefulStage: Send {
+    /// Records produced by `message`, with their keys
+    fn process(&mut self, message: &ProcessingMessage) -> Vec<(String, Value)>;
+}
+
+struct Stateful<P: StatefulProcessor> {
+    processor: P,
+    states: HashMap<String, P::State>,
+}
+
+impl<P: StatefulProcessor> StatefulStage for Stateful<P> {
+    fn process(&mut self, message: &ProcessingMessage) -> Vec<(String, Value)> {
+        let mut records = Vec::new();
+        for key in self.processor.keys(message) {
+            let state = self.states.entry(key.clone()).or_default();
+            if let Some(record) = self.processor.apply(&key, state, message) {
+                records.push((key, record));
+            }
+        }
+        records
+    }
+}
+
+fn stage<P: StatefulProcessor + 'static>(processor: P) -> Box<dyn StatefulStage> {
+    Box::new(Stateful {
+        processor,
+        states: HashMap::new(),
+    })
+}
+
+/// Built-in stateful processors
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum StatefulProcessorKind {
+    /// Supply of SPL Token and Token-2022 mints, from their account updates
+    TokenSupply,
+}
+
+/// Create the runners of `kinds`, each pipeline keeps its own states
+pub fn build(kinds: &[StatefulProcessorKind]) -> Vec<Box<dyn StatefulStage>> {
+    kinds
+        .iter()
+        .map(|kind| match kind {
+            StatefulProcessorKind::TokenSupply => stage(TokenSupplyTracker::default()),
+        })
+        .collect()
+}
@@ -0,0 +1,90 @@
+use serde_json::{json, Value};
+
+use super::StatefulProcessor;
+use crate::{
+    codec, control,
+    config::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID},
+    ProcessingMessage,
+};
+
+/// Size of the SPL Token mint layout, Token-2022 mints extend it
+const MINT_SIZE: usize = 82;
+/// Offset of the account type byte of Token-2022 accounts with extensions
+const ACCOUNT_TYPE_OFFSET: usize = 165;
+const ACCOUNT_TYPE_MINT: u8 = 1;
+
+/// Last known supply of a mint
+#[derive(Debug, Default)]
+pub struct MintSupply {
+    supply: Option<u64>,
+    slot: u64,
+}
+
+/// Tracks the supply of token mints from their account updates, publishing a `token_supply`
+/// record for each change. Needs an account subscription on the mints, e.g. `accounts_owner`
+/// set to the token programs with `accounts_datasize: 82`.
+pub struct TokenSupplyTracker {
+    token_programs: [Vec<u8>; 2],
+}
+
+impl Default for TokenSupplyTracker {
+    fn default() -> Self {
+        let decode = |program: &str| bs58::decode(program).into_vec().expect("valid program id");
+        Self {
+            token_programs: [decode(TOKEN_PROGRAM_ID), decode(TOKEN_2022_PROGRAM_ID)],
+        }
+    }
+}
+
+/// Supply and decimals of mint account data
+fn parse_mint(data: &[u8]) -> Option<(u64, u8)> {
+    let is_mint = data.len() == MINT_SIZE || data.get(ACCOUNT_TYPE_OFFSET) == Some(&ACCOUNT_TYPE_MINT);
+    if !is_mint {
+        return None;
+    }
+    let supply = u64::from_le_bytes(data.get(36..44)?.try_into().ok()?);
+    let decimals = *data.get(44)?;
+    Some((supply, decimals))
+}
+
+impl StatefulProcessor for TokenSupplyTracker {
+    type State = MintSupply;
+
+    fn keys(&self, message: &ProcessingMessage) -> Vec<String> {
+        let ProcessingMessage::Account(update) = message else {
+            return Vec::new();
+        };
+        let Some(account) = &update.account else {
+            return Vec::new();
+        };
+        if !self.token_programs.iter().any(|program| *program == account.owner) || parse_mint(&account.data).is_none() {
+            return Vec::new();
+        }
+        vec![codec::encode_bs58(&account.pubkey)]
+    }
+
+    fn apply(&mut self, key: &str, state: &mut MintSupply, message: &ProcessingMessage) -> Option<Value> {
+        let ProcessingMessage::Account(update) = message else {
+            return None;
+        };
+        let (supply, decimals) = parse_mint(&update.account.as_ref()?.data)?;
+        // Updates of an older slot than the last applied one are stale
+        if update.slot < state.slot || state.supply == Some(supply) {
+            return None;
+        }
+
+        let previous = state.supply.replace(supply);
+        state.slot = update.slot;
+        Some(control::control_record(
+            "token_supply",
+            json!({
+            "mint": key,
+            "supply": supply.to_string(),
+            "previousSupply": previous.map(|previous| previous.to_string()),
+            "change": previous.map(|previous| (supply as i128 - previous as i128).to_string()),
+            "decimals": decimals,
+            "slot": update.slot,
+            }),
+        ))
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::{error, info};
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+use crate::{
+    codec,
+    config::YellowstoneGrpcConfig,
+    priority_fee::ComputeBudget,
+    timeseries::{Point, TimeSeriesConfig, TimeSeriesWriter},
+};
+
+/// Derived stats settings
+#[derive(Debug, Clone)]
+pub struct StatsConfig {
+    /// Length of a stats window
+    pub interval: Duration,
+    /// Programs with the most transactions reported per window
+    pub top_programs: usize,
+    pub backend: TimeSeriesConfig,
+}
+
+/// Counters of the transactions observed during one window
+struct Window {
+    started: Instant,
+    transactions: u64,
+    failed: u64,
+    fees: u64,
+    priority_fees: u64,
+    compute_unit_prices: Vec<u64>,
+    // Transactions invoking each program from a top-level instruction
+    programs: HashMap<Vec<u8>, u64>,
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            transactions: 0,
+            failed: 0,
+            fees: 0,
+            priority_fees: 0,
+            compute_unit_prices: Vec::new(),
+            programs: HashMap::new(),
+        }
+    }
+
+    fn observe(&mut self, update: &SubscribeUpdateTransaction) {
+        let Some(info) = update.transaction.as_ref() else {
+            return;
+        };
+        self.transactions += 1;
+        if let Some(meta) = &info.meta {
+            self.fees += meta.fee;
+            if meta.err.is_some() {
+                self.failed += 1;
+            }
+        }
+
+        let Some(message) = info.transaction.as_ref().and_then(|transaction| transaction.message.as_ref()) else {
+            return;
+        };
+        let budget = ComputeBudget::from_message(message);
+        self.priority_fees += budget.priority_fee();
+        self.compute_unit_prices.push(budget.compute_unit_price);
+
+        // Program ids are always static keys, never loaded from lookup tables
+        let mut programs: Vec<&[u8]> = message
+            .instructions
+            .iter()
+            .filter_map(|instruction| message.account_keys.get(instruction.program_id_index as usize))
+            .map(Vec::as_slice)
+            .collect();
+        programs.sort_unstable();
+        programs.dedup();
+        for program in programs {
+            *self.programs.entry(program.to_vec()).or_default() += 1;
+        }
+    }
+
+    /// Points of the closed window, timestamped with its end
+    fn points(mut self, tags: &[(&'static str, String)], top_programs: usize) -> Vec<Point> {
+        let time = SystemTime::now();
+        let seconds = self.started.elapsed().as_secs_f64().max(f64::EPSILON);
+        let point = |measurement, fields| Point {
+            measurement,
+            tags: tags.to_vec(),
+            fields,
+            time,
+        };
+
+        let mut points = vec![point(
+            "yellowstone_transactions",
+            vec![
+                ("count", self.transactions as f64),
+                ("failed", self.failed as f64),
+                ("tps", self.transactions as f64 / seconds),
+            ],
+        )];
+        if self.transactions > 0 {
+            self.compute_unit_prices.sort_unstable();
+            points.push(point(
+                "yellowstone_fees",
+                vec![
+                    ("total_lamports", self.fees as f64),
+                    ("average_lamports", self.fees as f64 / self.transactions as f64),
+                    ("priority_lamports", self.priority_fees as f64),
+                    ("cu_price_p50", percentile(&self.compute_unit_prices, 50) as f64),
+                    ("cu_price_p90", percentile(&self.compute_unit_prices, 90) as f64),
+                ],
+            ));
+        }
+
+        let mut programs: Vec<(Vec<u8>, u64)> = self.programs.into_iter().collect();
+        programs.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+        for (program, transactions) in programs.into_iter().take(top_programs) {
+            let mut program_tags = tags.to_vec();
+            program_tags.push(("program", codec::encode_bs58(&program)));
+            points.push(Point {
+                measurement: "yellowstone_program_activity",
+                tags: program_tags,
+                fields: vec![("transactions", transactions as f64), ("tps", transactions as f64 / seconds)],
+                time,
+            });
+        }
+        points
+    }
+}
+
+/// Nearest-rank percentile of sorted values, 0 when empty
+fn percentile(sorted: &[u64], percent: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    sorted[(sorted.len() - 1) * percent / 100]
+}
+
+/// Transaction throughput, fees and program activity aggregated over fixed windows and written
+/// to a time-series database, tagged with the topic of the pipeline
+pub struct StatsStream {
+    window: Arc<Mutex<Window>>,
+    writer: Arc<TimeSeriesWriter>,
+    tags: Vec<(&'static str, String)>,
+    top_programs: usize,
+}
+
+impl StatsStream {
+    pub fn observe(&self, update: &SubscribeUpdateTransaction) {
+        self.window.lock().expect("stats window lock").observe(update);
+    }
+
+    /// Write the last, partial window, at shutdown
+    pub async fn finish(&self) {
+        let window = std::mem::replace(&mut *self.window.lock().expect("stats window lock"), Window::new());
+        if let Err(e) = self.writer.write(&window.points(&self.tags, self.top_programs)).await {
+            error!("Error writing stats: {:?}", e);
+        }
+    }
+}
+
+/// Connect the stats database, `suffix` is appended to the topic tag in `dual_commitment` mode
+pub async fn connect(config: &YellowstoneGrpcConfig, suffix: Option<&str>) -> anyhow::Result<Option<StatsStream>> {
+    let Some(stats) = config.get_stats_config() else {
+        return Ok(None);
+    };
+    let topic = match suffix {
+        Some(suffix) => format!("{}-{}", config.yellowstone_grpc.topic_name, suffix),
+        None => config.yellowstone_grpc.topic_name.clone(),
+    };
+    info!("Aggregating stats of topic '{}' every {:?}", topic, stats.interval);
+
+    let stream = StatsStream {
+        window: Arc::new(Mutex::new(Window::new())),
+        writer: Arc::new(TimeSeriesWriter::connect(stats.backend).await?),
+        tags: vec![("topic", topic)],
+        top_programs: stats.top_programs,
+    };
+
+    let window = Arc::clone(&stream.window);
+    let writer = Arc::clone(&stream.writer);
+    let tags = stream.tags.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(stats.interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let closed = std::mem::replace(&mut *window.lock().expect("stats window lock"), Window::new());
+            // A slow database delays the next window, never the processor
+            if let Err(e) = writer.write(&closed.points(&tags, stats.top_programs)).await {
+                error!("Error writing stats: {:?}", e);
+            }
+        }
+    });
+
+    Ok(Some(stream))
+}
@@ -0,0 +1,187 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use log::{error, info};
+use serde_json::Value;
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransactionStatus;
+
+use crate::{
+    codec, config::YellowstoneGrpcConfig, derived::DerivedStream, formatters, outbox::Outbox, sinks::SinkConnector,
+};
+
+/// Transaction status stream settings
+#[derive(Debug, Clone)]
+pub struct StatusStreamConfig {
+    pub topic: String,
+    /// Slots a status waits for its transaction record before being published alone
+    pub max_wait_slots: u64,
+}
+
+/// Records of `transactions_status` updates, on their own topic.
+///
+/// When transactions are subscribed too, the status of a signature is published after its
+/// transaction record, whichever update arrives first: statuses wait in a small buffer for
+/// their transaction, and behind its acknowledgement when the outbox is enabled. A status whose
+/// transaction is not subscribed, or lags by more than `max_wait_slots`, is published alone.
+pub struct StatusStream {
+    stream: DerivedStream,
+    config: StatusStreamConfig,
+    /// Hold statuses for their transaction record, only when transactions are subscribed
+    ordered: bool,
+    order: StatusOrder,
+}
+
+/// Statuses waiting for their transaction record and the transactions released before their
+/// status
+#[derive(Default)]
+struct StatusOrder {
+    // Statuses waiting for their transaction record, by signature
+    pending: HashMap<String, Value>,
+    // Signatures whose transaction record went out, their statuses are published right away
+    released: HashSet<String>,
+    // Signatures of the pending statuses and released transactions by slot, to expire them
+    slots: BTreeMap<u64, Vec<String>>,
+}
+
+impl StatusOrder {
+    /// Hold the status of `signature`, or return it when its transaction was already released
+    fn hold(&mut self, signature: String, slot: u64, record: Value) -> Option<(String, Value)> {
+        if self.released.remove(&signature) {
+            return Some((signature, record));
+        }
+        self.pending.insert(signature.clone(), record);
+        self.slots.entry(slot).or_default().push(signature);
+        None
+    }
+
+    /// Release the transaction of `signature`, returning its status if it is waiting
+    fn release(&mut self, signature: &str, slot: u64) -> Option<Value> {
+        let record = self.pending.remove(signature);
+        if record.is_none() {
+            self.released.insert(signature.to_owned());
+            self.slots.entry(slot).or_default().push(signature.to_owned());
+        }
+        record
+    }
+
+    /// Statuses held since before `cutoff`, forgetting the transactions released as long ago
+    fn expire(&mut self, cutoff: u64) -> Vec<(String, Value)> {
+        let retained = self.slots.split_off(&cutoff);
+        let expired = std::mem::replace(&mut self.slots, retained);
+        let mut statuses = Vec::new();
+        for signature in expired.into_values().flatten() {
+            self.released.remove(&signature);
+            if let Some(record) = self.pending.remove(&signature) {
+                statuses.push((signature, record));
+            }
+        }
+        statuses
+    }
+}
+
+impl StatusStream {
+    /// Publish the record of a status update, or hold it until its transaction record is published
+    pub async fn observe(&mut self, update: SubscribeUpdateTransactionStatus) {
+        let signature = codec::encode_bs58(&update.signature);
+        let slot = update.slot;
+        let record = match formatters::format_transaction_status(update) {
+            Ok(record) => record,
+            Err(e) => {
+                error!("Error formatting transaction status {}: {:?}", signature, e);
+                return;
+            },
+        };
+        let ready = if self.ordered { self.order.hold(signature, slot, record) } else { Some((signature, record)) };
+        if let Some((signature, record)) = ready {
+            self.stream.send(signature, &record).await;
+        }
+    }
+
+    /// The transaction record of `signature` was published or filtered out, release its status.
+    /// With `outbox` the status is held there until the record is acknowledged, call this before
+    /// releasing the record from the outbox.
+    pub async fn release(&mut self, signature: &str, slot: u64, outbox: Option<&Outbox>) {
+        if !self.ordered {
+            return;
+        }
+        if let Some(record) = self.order.release(signature, slot) {
+            self.stream.emit(outbox, signature, signature.to_owned(), record).await;
+        }
+    }
+
+    /// Publish the statuses that waited more than `max_wait_slots` for their transaction and
+    /// forget the transactions released as long ago
+    pub async fn expire(&mut self, slot: u64) {
+        let Some(cutoff) = slot.checked_sub(self.config.max_wait_slots) else {
+            return;
+        };
+        for (signature, record) in self.order.expire(cutoff) {
+            self.stream.send(signature, &record).await;
+        }
+    }
+
+    /// Publish the statuses still waiting for their transaction and flush, at shutdown
+    pub async fn finish(&mut self) {
+        for (signature, record) in std::mem::take(&mut self.order.pending) {
+            self.stream.send(signature, &record).await;
+        }
+        self.stream.flush().await;
+    }
+}
+
+/// Connect the transaction status topic, `suffix` is appended to it in `dual_commitment` mode.
+/// Statuses are ordered after their transaction when the pipeline subscribes to `transactions`.
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+    ordered: bool,
+) -> anyhow::Result<Option<StatusStream>> {
+    let Some(status_config) = config.get_status_stream_config() else {
+        return Ok(None);
+    };
+    let stream = DerivedStream::connect(sinks, config, "transaction statuses", &status_config.topic, suffix).await?;
+    if ordered {
+        info!("Transaction statuses are published after their transaction");
+    }
+
+    Ok(Some(StatusStream {
+        stream,
+        config: status_config,
+        ordered,
+        order: StatusOrder::default(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn publishes_statuses_after_their_transaction() {
+        let mut order = StatusOrder::default();
+
+        // Status first: held until its transaction is released
+        assert!(order.hold("a".to_string(), 5, json!({ "signature": "a" })).is_none());
+        assert_eq!(order.release("a", 5), Some(json!({ "signature": "a" })));
+
+        // Transaction first: its status goes out as soon as it arrives, once
+        assert!(order.release("b", 5).is_none());
+        assert!(order.hold("b".to_string(), 5, json!({ "signature": "b" })).is_some());
+        assert!(order.hold("b".to_string(), 5, json!({ "signature": "b" })).is_none());
+    }
+
+    #[test]
+    fn expires_statuses_and_released_transactions() {
+        let mut order = StatusOrder::default();
+        assert!(order.hold("late".to_string(), 5, json!({ "signature": "late" })).is_none());
+        assert!(order.release("unsubscribed", 6).is_none());
+        assert!(order.hold("recent".to_string(), 10, json!({ "signature": "recent" })).is_none());
+
+        let expired = order.expire(7);
+        assert_eq!(expired, [("late".to_string(), json!({ "signature": "late" }))]);
+        assert!(order.released.is_empty());
+        assert_eq!(order.pending.keys().collect::<Vec<_>>(), ["recent"]);
+    }
+}
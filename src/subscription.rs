@@ -0,0 +1,124 @@
+use std::{sync::Arc, time::Duration};
+
+use log::{error, info, warn};
+use tokio::{
+    sync::{mpsc, watch},
+    time::Interval,
+};
+use yellowstone_grpc_proto::prelude::{CommitmentLevel, SubscribeRequest};
+
+use crate::{
+    config::{self, Filters},
+    control,
+    dynamic::DynamicFilters,
+    publisher::Publisher,
+    volume::VolumeBudget,
+};
+
+/// Re-build the subscribe request every `interval`, e.g. to pick up mints added to
+/// `transactions_wallet_mints_path`, whenever `filters` are reloaded and whenever a `dynamic`
+/// filter is added or removed, sending it when it differs from the current one. Filters disabled
+/// by the `volume` budget are left out.
+///
+/// Every change is also published as a `subscription` control record.
+pub fn spawn_refresh(
+    mut filters: watch::Receiver<Filters>,
+    commitment: Option<CommitmentLevel>,
+    interval: Option<Duration>,
+    mut current: SubscribeRequest,
+    endpoint: String,
+    publisher: Arc<Publisher>,
+    dynamic: Option<Arc<DynamicFilters>>,
+    volume: Option<Arc<VolumeBudget>>,
+) -> mpsc::Receiver<SubscribeRequest> {
+    let (tx, rx) = mpsc::channel(1);
+    current.from_slot = None;
+
+    tokio::spawn(async move {
+        let mut ticker = interval.map(tokio::time::interval);
+        if let Some(ticker) = &mut ticker {
+            ticker.tick().await;
+        }
+        let mut versions = dynamic.as_ref().map(|dynamic| dynamic.subscribe());
+        let mut disabled = volume.as_ref().filter(|volume| volume.disables()).map(|volume| volume.subscribe());
+        // Without a reload watch the sender is dropped right away
+        let mut reloading = true;
+        loop {
+            tokio::select! {
+                _ = next_tick(&mut ticker) => {},
+                changed = filters.changed(), if reloading => {
+                    if changed.is_err() {
+                        reloading = false;
+                        if ticker.is_none() && versions.is_none() && disabled.is_none() {
+                            break;
+                        }
+                        continue;
+                    }
+                },
+                _ = next_version(&mut versions) => {},
+                _ = next_version(&mut disabled) => {},
+            }
+
+            let snapshot = filters.borrow_and_update().clone();
+            let loaded = match snapshot.load_files().await {
+                Ok(loaded) => loaded,
+                Err(e) => {
+                    error!("Error reading subscription filter files: {:?}", e);
+                    continue;
+                },
+            };
+            let (mut request, warnings) = match config::get_subscribe_request(&loaded, commitment) {
+                Ok(request) => request,
+                Err(e) => {
+                    error!("Error refreshing subscription filters: {:?}", e);
+                    continue;
+                },
+            };
+            if let Some(dynamic) = &dynamic {
+                dynamic.merge(&mut request);
+            }
+            if let Some(volume) = &volume {
+                volume.strip(&mut request);
+            }
+            if request == current {
+                continue;
+            }
+
+            info!("Subscription filters changed, updating the subscription");
+            for warning in warnings {
+                warn!("{}", warning);
+            }
+            let description = control::describe_subscription(&request, &endpoint);
+            if let Err(e) = publisher.send_control(&description).await {
+                error!("Error publishing subscription description: {:?}", e);
+            }
+            current = request.clone();
+            if tx.send(request).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    rx
+}
+
+async fn next_tick(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        },
+        None => std::future::pending().await,
+    }
+}
+
+async fn next_version(versions: &mut Option<watch::Receiver<u64>>) {
+    match versions {
+        Some(versions) => {
+            // The sender lives as long as the filters, stop waiting if it is dropped anyway
+            if versions.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        },
+        None => std::future::pending().await,
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::SystemTime;
+
+use log::info;
+#[cfg(feature = "timescale")]
+use log::error;
+use reqwest::Client;
+#[cfg(feature = "timescale")]
+use serde_json::{Map, Value};
+
+/// Time-series database the derived stats are written to
+#[derive(Debug, Clone)]
+pub enum TimeSeriesConfig {
+    /// InfluxDB 2.x write API, or the 1.8 compatibility endpoint
+    Influx {
+        url: String,
+        org: String,
+        bucket: String,
+        token: Option<String>,
+    },
+    /// TimescaleDB hypertable, created if missing
+    #[cfg(feature = "timescale")]
+    Timescale { url: String, table: String },
+}
+
+/// One measurement of a stats window
+#[derive(Debug, Clone)]
+pub struct Point {
+    pub measurement: &'static str,
+    pub tags: Vec<(&'static str, String)>,
+    pub fields: Vec<(&'static str, f64)>,
+    pub time: SystemTime,
+}
+
+/// Writes points to the configured database
+pub enum TimeSeriesWriter {
+    Influx {
+        client: Client,
+        url: String,
+        org: String,
+        bucket: String,
+        token: Option<String>,
+    },
+    #[cfg(feature = "timescale")]
+    Timescale {
+        url: String,
+        table: String,
+        // Re-established on the next write when the connection is lost
+        client: tokio::sync::Mutex<Option<tokio_postgres::Client>>,
+    },
+}
+
+impl TimeSeriesWriter {
+    pub async fn connect(config: TimeSeriesConfig) -> anyhow::Result<Self> {
+        match config {
+            TimeSeriesConfig::Influx { url, org, bucket, token } => {
+                info!("Writing stats to InfluxDB bucket '{}' at {}", bucket, url);
+                Ok(Self::Influx {
+                    client: Client::new(),
+                    url: format!("{}/api/v2/write", url.trim_end_matches('/')),
+                    org,
+                    bucket,
+                    token,
+                })
+            },
+            #[cfg(feature = "timescale")]
+            TimeSeriesConfig::Timescale { url, table } => {
+                let client = timescale_connect(&url, &table).await?;
+                info!("Writing stats to TimescaleDB table '{}'", table);
+                Ok(Self::Timescale {
+                    url,
+                    table,
+                    client: tokio::sync::Mutex::new(Some(client)),
+                })
+            },
+        }
+    }
+
+    pub async fn write(&self, points: &[Point]) -> anyhow::Result<()> {
+        if points.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Self::Influx { client, url, org, bucket, token } => {
+                let body: Vec<String> = points.iter().map(line_protocol).collect();
+                let mut request = client
+                    .post(url)
+                    .query(&[("org", org.as_str()), ("bucket", bucket.as_str()), ("precision", "ms")])
+                    .body(body.join("\n"));
+                if let Some(token) = token {
+                    request = request.header("Authorization", format!("Token {}", token));
+                }
+                request.send().await?.error_for_status()?;
+                Ok(())
+            },
+            #[cfg(feature = "timescale")]
+            Self::Timescale { url, table, client } => {
+                let mut client = client.lock().await;
+                if client.as_ref().map_or(true, tokio_postgres::Client::is_closed) {
+                    *client = Some(timescale_connect(url, table).await?);
+                }
+                let client = client.as_ref().expect("connected above");
+
+                // One row per field, inserted in a single statement
+                let (mut times, mut measurements, mut fields, mut values, mut tags) =
+                    (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+                for point in points {
+                    let point_tags: Map<String, Value> =
+                        point.tags.iter().map(|(name, value)| (name.to_string(), Value::from(value.as_str()))).collect();
+                    for (field, value) in &point.fields {
+                        times.push(point.time);
+                        measurements.push(point.measurement);
+                        fields.push(*field);
+                        values.push(*value);
+                        tags.push(Value::Object(point_tags.clone()).to_string());
+                    }
+                }
+                let insert = format!(
+                    "INSERT INTO {} (time, measurement, field, value, tags) \
+                     SELECT t, m, f, v, g::jsonb FROM UNNEST($1::timestamptz[], $2::text[], $3::text[], $4::float8[], $5::text[]) AS u(t, m, f, v, g)",
+                    table
+                );
+                client.execute(&insert, &[&times, &measurements, &fields, &values, &tags]).await?;
+                Ok(())
+            },
+        }
+    }
+}
+
+#[cfg(feature = "timescale")]
+async fn timescale_connect(url: &str, table: &str) -> anyhow::Result<tokio_postgres::Client> {
+    let (client, connection) = tokio_postgres::connect(url, tokio_postgres::NoTls).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            error!("TimescaleDB connection closed: {:?}", e);
+        }
+    });
+    client
+        .batch_execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                time TIMESTAMPTZ NOT NULL,
+                measurement TEXT NOT NULL,
+                field TEXT NOT NULL,
+                value DOUBLE PRECISION NOT NULL,
+                tags JSONB NOT NULL
+            );
+            SELECT create_hypertable('{table}', 'time', if_not_exists => TRUE);",
+            table = table
+        ))
+        .await?;
+    Ok(client)
+}
+
+/// `measurement,tag=value field=value timestamp`, in milliseconds
+fn line_protocol(point: &Point) -> String {
+    let mut line = escape(point.measurement, &[',', ' ']);
+    for (name, value) in &point.tags {
+        line.push_str(&format!(",{}={}", escape(name, &[',', '=', ' ']), escape(value, &[',', '=', ' '])));
+    }
+    let fields: Vec<String> = point
+        .fields
+        .iter()
+        .map(|(name, value)| format!("{}={}", escape(name, &[',', '=', ' ']), value))
+        .collect();
+    let timestamp = point.time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis();
+    line.push_str(&format!(" {} {}", fields.join(","), timestamp));
+    line
+}
+
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
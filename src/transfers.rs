@@ -0,0 +1,82 @@
+use serde_json::json;
+use solana_sdk::{system_instruction::SystemInstruction, system_program};
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+use crate::{
+    codec, config::YellowstoneGrpcConfig, derived::DerivedStream, instructions, outbox::Outbox, sinks::SinkConnector,
+};
+
+/// SOL transfer stream settings
+#[derive(Debug, Clone)]
+pub struct TransferStreamConfig {
+    pub topic: String,
+    /// Smaller transfers are not published
+    pub min_lamports: u64,
+}
+
+/// Native SOL transfers extracted from the top-level and inner System program instructions of
+/// successful transactions, in execution order: transfers, funded account creations and nonce
+/// withdrawals
+pub struct TransferStream {
+    stream: DerivedStream,
+    min_lamports: u64,
+    system_program: Vec<u8>,
+}
+
+impl TransferStream {
+    /// Publish the SOL transfers of a transaction, or hold them in `outbox` until its record is
+    /// acknowledged
+    pub async fn observe(&self, update: &SubscribeUpdateTransaction, outbox: Option<&Outbox>) {
+        let signature = update.transaction.as_ref().map(|info| codec::encode_bs58(&info.signature)).unwrap_or_default();
+        for instruction in instructions::program_instructions(update, &self.system_program) {
+            // Position of the recipient, the payer or nonce account is the first account
+            let (lamports, to) = match bincode::deserialize::<SystemInstruction>(instruction.data) {
+                Ok(SystemInstruction::Transfer { lamports }) => (lamports, 1),
+                Ok(SystemInstruction::TransferWithSeed { lamports, .. }) => (lamports, 2),
+                Ok(SystemInstruction::CreateAccount { lamports, .. }) => (lamports, 1),
+                Ok(SystemInstruction::CreateAccountWithSeed { lamports, .. }) => (lamports, 1),
+                Ok(SystemInstruction::WithdrawNonceAccount(lamports)) => (lamports, 1),
+                _ => continue,
+            };
+            if lamports < self.min_lamports {
+                continue;
+            }
+            let (Some(from), Some(to)) = (instruction.account(0), instruction.account(to)) else {
+                continue;
+            };
+
+            let record = json!({
+                "from": from,
+                "to": to,
+                "lamports": lamports,
+                "signature": signature,
+                "slot": update.slot,
+                "cpi": instruction.cpi,
+            });
+            // Keyed by recipient, so the payments to an address stay in order
+            self.stream.emit(outbox, &signature, to, record).await;
+        }
+    }
+
+    /// Flush the transfers, at shutdown
+    pub async fn finish(&self) {
+        self.stream.flush().await;
+    }
+}
+
+/// Connect the SOL transfer topic, `suffix` is appended to it in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<TransferStream>> {
+    let Some(transfers) = config.get_transfer_stream_config() else {
+        return Ok(None);
+    };
+
+    Ok(Some(TransferStream {
+        stream: DerivedStream::connect(sinks, config, "SOL transfers", &transfers.topic, suffix).await?,
+        min_lamports: transfers.min_lamports,
+        system_program: system_program::id().to_bytes().to_vec(),
+    }))
+}
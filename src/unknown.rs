@@ -0,0 +1,105 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{error, info, warn};
+use serde_json::json;
+use yellowstone_grpc_proto::{
+    prelude::{subscribe_update::UpdateOneof, SubscribeUpdate},
+    prost::Message,
+};
+
+use crate::{config::YellowstoneGrpcConfig, metrics::Metrics, publisher::Publisher, sinks::SinkConnector, sources};
+
+/// Name of an update type, `unknown` for a variant this build does not know, which the decoder
+/// leaves unset
+fn variant(update: Option<&UpdateOneof>) -> &'static str {
+    match update {
+        Some(UpdateOneof::Account(_)) => "account",
+        Some(UpdateOneof::Slot(_)) => "slot",
+        Some(UpdateOneof::Transaction(_)) => "transaction",
+        Some(UpdateOneof::TransactionStatus(_)) => "transaction_status",
+        Some(UpdateOneof::Block(_)) => "block",
+        Some(UpdateOneof::Ping(_)) => "ping",
+        Some(UpdateOneof::Pong(_)) => "pong",
+        Some(UpdateOneof::BlockMeta(_)) => "block_meta",
+        Some(UpdateOneof::Entry(_)) => "entry",
+        None => "unknown",
+    }
+}
+
+/// Updates of types the pipeline does not handle, such as variants added by a provider protocol
+/// upgrade, are counted, logged once per type and optionally published raw to their own topic,
+/// so they are noticed rather than silently discarded
+pub struct UnknownUpdates {
+    publisher: Option<Publisher>,
+    metrics: Option<Arc<Metrics>>,
+    seen: Mutex<HashSet<&'static str>>,
+}
+
+impl UnknownUpdates {
+    /// Account for an update the pipeline does not handle, pings and pongs are expected
+    pub async fn observe(&self, update: &SubscribeUpdate) {
+        let variant = variant(update.update_oneof.as_ref());
+        if matches!(variant, "ping" | "pong") {
+            return;
+        }
+
+        if let Some(metrics) = &self.metrics {
+            metrics.increment_unknown_updates();
+        }
+        if self.seen.lock().expect("unknown updates lock").insert(variant) {
+            warn!(
+                "Dropping `{}` updates (filters {:?}), the pipeline does not handle them{}",
+                variant,
+                update.filters,
+                if self.publisher.is_some() { ", publishing them raw" } else { "" }
+            );
+        }
+
+        let Some(publisher) = &self.publisher else {
+            return;
+        };
+        let record = json!({
+            "variant": variant,
+            "slot": update.update_oneof.as_ref().and_then(sources::update_slot),
+            "filters": update.filters,
+            // Fields of variants this build does not know are not kept by the decoder
+            "update": STANDARD.encode(update.encode_to_vec()),
+        });
+        if let Err(e) = publisher.send(variant.to_owned(), &record).await {
+            error!("Error publishing `{}` update: {:?}", variant, e);
+        }
+    }
+
+    /// Flush the raw updates, at shutdown
+    pub async fn finish(&self) {
+        if let Some(publisher) = &self.publisher {
+            if let Err(e) = publisher.flush().await {
+                error!("Error flushing unknown updates: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Connect the unknown update topic when publishing is enabled
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    metrics: Option<Arc<Metrics>>,
+) -> anyhow::Result<UnknownUpdates> {
+    let publisher = match config.get_unknown_updates_topic() {
+        Some(topic) => {
+            info!("Publishing updates of unhandled types to topic '{}'", topic);
+            Some(Publisher::connect(sinks, config, &topic).await?)
+        },
+        None => None,
+    };
+    Ok(UnknownUpdates {
+        publisher,
+        metrics,
+        seen: Mutex::new(HashSet::new()),
+    })
+}
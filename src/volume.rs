@@ -0,0 +1,184 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::watch;
+use yellowstone_grpc_proto::prelude::{subscribe_update::UpdateOneof, SubscribeRequest};
+
+use crate::control;
+
+/// What happens to a filter producing more updates than the budget
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeAction {
+    /// Log a warning and publish a `filter_over_budget` control record
+    #[default]
+    Warn,
+    /// Also remove the filter from the subscription until restart
+    Disable,
+}
+
+/// Volume budget settings
+#[derive(Debug, Clone)]
+pub struct VolumeBudgetConfig {
+    pub max_records_per_sec: u64,
+    /// Window the rate of each filter is measured over
+    pub window: Duration,
+    pub action: VolumeAction,
+}
+
+struct Window {
+    started: Instant,
+    // Updates by filter, `<kind>.<name>` as in `accounts.client`
+    counts: HashMap<String, u64>,
+    over: BTreeSet<String>,
+}
+
+/// Measures the updates matched by each subscription filter and flags the filters over the
+/// records/sec budget, so a broad owner filter cannot flood the pipeline unnoticed. With
+/// `action: disable` the subscription is re-sent without them.
+pub struct VolumeBudget {
+    config: VolumeBudgetConfig,
+    window: Mutex<Window>,
+    disabled: Mutex<BTreeSet<String>>,
+    // Bumped whenever a filter is disabled
+    version: watch::Sender<u64>,
+}
+
+impl VolumeBudget {
+    pub fn new(config: VolumeBudgetConfig) -> Self {
+        let (version, _) = watch::channel(0);
+        Self {
+            config,
+            window: Mutex::new(Window {
+                started: Instant::now(),
+                counts: HashMap::new(),
+                over: BTreeSet::new(),
+            }),
+            disabled: Mutex::new(BTreeSet::new()),
+            version,
+        }
+    }
+
+    /// Count an update for the filters it matched, returning the control records of the filters
+    /// that went over the budget when a window ends
+    pub fn observe(&self, update: Option<&UpdateOneof>, filters: &[String]) -> Vec<Value> {
+        let Some(kind) = update.and_then(filter_kind) else {
+            return Vec::new();
+        };
+        let mut window = self.window.lock().expect("volume budget lock");
+        for filter in filters {
+            *window.counts.entry(format!("{}.{}", kind, filter)).or_default() += 1;
+        }
+        let elapsed = window.started.elapsed();
+        if elapsed < self.config.window {
+            return Vec::new();
+        }
+
+        let counts = std::mem::take(&mut window.counts);
+        window.started = Instant::now();
+        let mut records = Vec::new();
+        let mut over = BTreeSet::new();
+        for (filter, count) in counts {
+            let rate = count as f64 / elapsed.as_secs_f64();
+            if rate <= self.config.max_records_per_sec as f64 {
+                continue;
+            }
+            if !window.over.contains(&filter) {
+                records.push(self.over_budget(&filter, rate));
+            }
+            over.insert(filter);
+        }
+        for filter in window.over.difference(&over) {
+            info!("Filter '{}' is back under its budget of {} records/sec", filter, self.config.max_records_per_sec);
+        }
+        window.over = over;
+        records
+    }
+
+    fn over_budget(&self, filter: &str, rate: f64) -> Value {
+        let disable = self.disables();
+        warn!(
+            "Filter '{}' produces {:.0} records/sec, over its budget of {}{}",
+            filter,
+            rate,
+            self.config.max_records_per_sec,
+            if disable { ", removing it from the subscription" } else { "" }
+        );
+        if disable && self.disabled.lock().expect("volume budget lock").insert(filter.to_owned()) {
+            self.version.send_modify(|version| *version += 1);
+        }
+        control::control_record(
+            "filter_over_budget",
+            json!({
+                "filter": filter,
+                "recordsPerSec": rate.round() as u64,
+                "maxRecordsPerSec": self.config.max_records_per_sec,
+                "disabled": disable,
+            }),
+        )
+    }
+
+    /// Whether filters over the budget are removed from the subscription
+    pub fn disables(&self) -> bool {
+        self.config.action == VolumeAction::Disable
+    }
+
+    /// Receiver notified whenever a filter is disabled
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+
+    /// Remove the disabled filters from a subscribe request
+    pub fn strip(&self, request: &mut SubscribeRequest) {
+        for filter in self.disabled.lock().expect("volume budget lock").iter() {
+            let Some((kind, name)) = filter.split_once('.') else {
+                continue;
+            };
+            match kind {
+                "accounts" => {
+                    request.accounts.remove(name);
+                },
+                "slots" => {
+                    request.slots.remove(name);
+                },
+                "transactions" => {
+                    request.transactions.remove(name);
+                },
+                "transactions_status" => {
+                    request.transactions_status.remove(name);
+                },
+                "entry" => {
+                    request.entry.remove(name);
+                },
+                "blocks" => {
+                    request.blocks.remove(name);
+                },
+                "blocks_meta" => {
+                    request.blocks_meta.remove(name);
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+/// Filter map of the subscribe request an update was matched by
+fn filter_kind(update: &UpdateOneof) -> Option<&'static str> {
+    match update {
+        UpdateOneof::Account(_) => Some("accounts"),
+        UpdateOneof::Slot(_) => Some("slots"),
+        UpdateOneof::Transaction(_) => Some("transactions"),
+        UpdateOneof::TransactionStatus(_) => Some("transactions_status"),
+        UpdateOneof::Entry(_) => Some("entry"),
+        UpdateOneof::Block(_) => Some("blocks"),
+        UpdateOneof::BlockMeta(_) => Some("blocks_meta"),
+        _ => None,
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde_json::json;
+use solana_sdk::vote::{instruction::VoteInstruction, state::Lockout};
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+use crate::{codec, config::YellowstoneGrpcConfig, derived::DerivedStream, sinks::SinkConnector};
+
+/// Vote stream settings
+#[derive(Debug, Clone)]
+pub struct VoteStreamConfig {
+    pub topic: String,
+    /// Publish vote transactions only as vote records, not to the main topic
+    pub exclude_transactions: bool,
+}
+
+/// Slots, root and timestamp carried by a vote instruction
+struct Tower {
+    kind: &'static str,
+    slots: Vec<u64>,
+    root: Option<u64>,
+    timestamp: Option<i64>,
+}
+
+fn tower(instruction: VoteInstruction) -> Option<Tower> {
+    let lockout_slots = |lockouts: &VecDeque<Lockout>| -> Vec<u64> { lockouts.iter().map(Lockout::slot).collect() };
+    let (kind, slots, root, timestamp) = match instruction {
+        VoteInstruction::Vote(vote) | VoteInstruction::VoteSwitch(vote, _) => ("vote", vote.slots, None, vote.timestamp),
+        VoteInstruction::UpdateVoteState(update) | VoteInstruction::UpdateVoteStateSwitch(update, _) => {
+            ("updateVoteState", lockout_slots(&update.lockouts), update.root, update.timestamp)
+        },
+        VoteInstruction::CompactUpdateVoteState(update) | VoteInstruction::CompactUpdateVoteStateSwitch(update, _) => {
+            ("compactUpdateVoteState", lockout_slots(&update.lockouts), update.root, update.timestamp)
+        },
+        VoteInstruction::TowerSync(sync) | VoteInstruction::TowerSyncSwitch(sync, _) => {
+            ("towerSync", lockout_slots(&sync.lockouts), sync.root, sync.timestamp)
+        },
+        _ => return None,
+    };
+    Some(Tower { kind, slots, root, timestamp })
+}
+
+/// Compact records of validator votes, extracted from vote transactions.
+///
+/// Each successful vote instruction is published keyed by its vote account with the slots it
+/// newly voted on, the last voted slot and the vote latency: slots between the last voted slot
+/// and the slot the vote landed in.
+pub struct VoteStream {
+    stream: DerivedStream,
+    config: VoteStreamConfig,
+    vote_program: Vec<u8>,
+    // Last voted slot of each vote account, to tell new slots from the rest of the tower
+    last_voted: HashMap<Vec<u8>, u64>,
+}
+
+impl VoteStream {
+    /// Whether vote transactions are left out of the main topic
+    pub fn excludes_transactions(&self) -> bool {
+        self.config.exclude_transactions
+    }
+
+    /// Publish the votes of a transaction, returning whether it is a vote transaction
+    pub async fn observe(&mut self, update: &SubscribeUpdateTransaction) -> bool {
+        let Some(info) = &update.transaction else {
+            return false;
+        };
+        if !info.is_vote {
+            return false;
+        }
+        // Failed votes did not change the tower
+        if info.meta.as_ref().is_some_and(|meta| meta.err.is_some()) {
+            return true;
+        }
+        let Some(message) = info.transaction.as_ref().and_then(|transaction| transaction.message.as_ref()) else {
+            return true;
+        };
+
+        let signature = codec::encode_bs58(&info.signature);
+        for instruction in &message.instructions {
+            let program = message.account_keys.get(instruction.program_id_index as usize);
+            if program != Some(&self.vote_program) {
+                continue;
+            }
+            let Some(tower) = bincode::deserialize(&instruction.data).ok().and_then(tower) else {
+                continue;
+            };
+            let account = |position: usize| {
+                instruction
+                    .accounts
+                    .get(position)
+                    .and_then(|index| message.account_keys.get(*index as usize))
+            };
+            let Some(vote_account) = account(0) else {
+                continue;
+            };
+            // The authority is the last account of every vote instruction
+            let authority = account(instruction.accounts.len().saturating_sub(1));
+
+            let last_voted_slot = tower.slots.last().copied();
+            let previous = last_voted_slot.and_then(|slot| self.last_voted.insert(vote_account.clone(), slot));
+            let new_slots: Vec<u64> = tower
+                .slots
+                .iter()
+                .copied()
+                .filter(|slot| previous.map_or(true, |previous| *slot > previous))
+                .collect();
+
+            let vote_account = codec::encode_bs58(vote_account);
+            let record = json!({
+                "voteAccount": vote_account,
+                "authority": authority.map(|authority| codec::encode_bs58(authority)),
+                "signature": signature,
+                "kind": tower.kind,
+                "slot": update.slot,
+                "lastVotedSlot": last_voted_slot,
+                "latency": last_voted_slot.map(|voted| update.slot.saturating_sub(voted)),
+                "slots": new_slots,
+                "root": tower.root,
+                "timestamp": tower.timestamp,
+            });
+            self.stream.send(vote_account, &record).await;
+        }
+        true
+    }
+
+    /// Flush the vote records, at shutdown
+    pub async fn finish(&self) {
+        self.stream.flush().await;
+    }
+}
+
+/// Connect the vote topic, `suffix` is appended to it in `dual_commitment` mode
+pub async fn connect(
+    sinks: &SinkConnector,
+    config: &YellowstoneGrpcConfig,
+    suffix: Option<&str>,
+) -> anyhow::Result<Option<VoteStream>> {
+    let Some(vote_config) = config.get_vote_stream_config() else {
+        return Ok(None);
+    };
+
+    Ok(Some(VoteStream {
+        stream: DerivedStream::connect(sinks, config, "validator votes", &vote_config.topic, suffix).await?,
+        config: vote_config,
+        vote_program: solana_sdk::vote::program::id().to_bytes().to_vec(),
+        last_voted: HashMap::new(),
+    }))
+}
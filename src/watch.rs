@@ -0,0 +1,274 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use log::{error, info};
+use serde_json::json;
+use tokio::sync::watch;
+use yellowstone_grpc_proto::prelude::{
+    subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest, SubscribeRequestFilterTransactions,
+    SubscribeUpdateTransactionStatus,
+};
+
+use crate::{
+    codec, control,
+    publisher::Publisher,
+    sources::{GrpcEndpoint, ReconnectingSource, Source},
+};
+
+/// How often expired signatures are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Signature watch settings
+#[derive(Debug, Clone)]
+pub struct SignatureWatchConfig {
+    pub topic: String,
+    pub signatures: Vec<String>,
+    /// Signatures not finalized within this time are dropped with a `signature_expired` record
+    pub ttl: Duration,
+    /// Signatures watched at the same time
+    pub max_signatures: usize,
+}
+
+/// Signatures watched at every commitment level until finalized or expired.
+///
+/// Each commitment level has its own subscription with one `transactions_status` filter per
+/// signature, updated in place when signatures are added or removed.
+pub struct SignatureWatch {
+    signatures: Mutex<HashMap<String, Instant>>,
+    ttl: Duration,
+    max_signatures: usize,
+    // Bumped on every change of the watch list
+    version: watch::Sender<u64>,
+}
+
+impl SignatureWatch {
+    /// Watch `signatures`, returning the signatures that were not watched yet. Nothing is added
+    /// when they would exceed `max_signatures`
+    pub fn add(&self, signatures: Vec<String>) -> anyhow::Result<Vec<String>> {
+        let mut watched = self.signatures.lock().expect("signature watch lock");
+        let new = signatures.iter().filter(|signature| !watched.contains_key(*signature)).count();
+        if watched.len() + new > self.max_signatures {
+            bail!("at most {} signatures are watched at the same time", self.max_signatures);
+        }
+        let added: Vec<String> = signatures
+            .into_iter()
+            .filter(|signature| watched.insert(signature.clone(), Instant::now()).is_none())
+            .collect();
+        drop(watched);
+
+        if !added.is_empty() {
+            self.version.send_modify(|version| *version += 1);
+        }
+        Ok(added)
+    }
+
+    /// Stop watching a signature, `false` if it was not watched
+    pub fn remove(&self, signature: &str) -> bool {
+        let removed = self.signatures.lock().expect("signature watch lock").remove(signature).is_some();
+        if removed {
+            self.version.send_modify(|version| *version += 1);
+        }
+        removed
+    }
+
+    /// Watched signatures
+    pub fn list(&self) -> Vec<String> {
+        let mut signatures: Vec<String> = self.signatures.lock().expect("signature watch lock").keys().cloned().collect();
+        signatures.sort();
+        signatures
+    }
+
+    fn prune(&self) -> Vec<String> {
+        let mut watched = self.signatures.lock().expect("signature watch lock");
+        let expired: Vec<String> = watched
+            .iter()
+            .filter(|(_, added)| added.elapsed() >= self.ttl)
+            .map(|(signature, _)| signature.clone())
+            .collect();
+        for signature in &expired {
+            watched.remove(signature);
+        }
+        drop(watched);
+
+        if !expired.is_empty() {
+            self.version.send_modify(|version| *version += 1);
+        }
+        expired
+    }
+
+    fn request(&self, commitment: CommitmentLevel) -> SubscribeRequest {
+        let transactions_status = self
+            .signatures
+            .lock()
+            .expect("signature watch lock")
+            .keys()
+            .map(|signature| {
+                let filter = SubscribeRequestFilterTransactions {
+                    signature: Some(signature.clone()),
+                    ..Default::default()
+                };
+                (signature.clone(), filter)
+            })
+            .collect();
+
+        SubscribeRequest {
+            transactions_status,
+            commitment: Some(commitment as i32),
+            ..Default::default()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.signatures.lock().expect("signature watch lock").is_empty()
+    }
+}
+
+/// Start watching the configured signatures, publishing a `signature_observed` record to the
+/// watch topic at each commitment level
+pub fn start(config: SignatureWatchConfig, endpoint: GrpcEndpoint, publisher: Publisher) -> Arc<SignatureWatch> {
+    let (version, _) = watch::channel(0);
+    let signature_watch = Arc::new(SignatureWatch {
+        signatures: Mutex::new(HashMap::new()),
+        ttl: config.ttl,
+        max_signatures: config.max_signatures,
+        version,
+    });
+    if let Err(e) = signature_watch.add(config.signatures) {
+        error!("Error watching the configured signatures: {}", e);
+    }
+    info!("Watching signatures, observations are published to topic '{}'", config.topic);
+
+    let publisher = Arc::new(publisher);
+    for commitment in [CommitmentLevel::Processed, CommitmentLevel::Confirmed, CommitmentLevel::Finalized] {
+        tokio::spawn(watch_commitment(
+            Arc::clone(&signature_watch),
+            commitment,
+            endpoint.clone(),
+            Arc::clone(&publisher),
+        ));
+    }
+
+    let pruned = Arc::clone(&signature_watch);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            for signature in pruned.prune() {
+                let record = control::control_record("signature_expired", json!({ "signature": signature }));
+                if let Err(e) = publisher.send(signature, &record).await {
+                    error!("Error publishing signature expiry: {:?}", e);
+                }
+            }
+        }
+    });
+
+    signature_watch
+}
+
+/// Subscription for one commitment level, connected while there are signatures to watch
+async fn watch_commitment(
+    signature_watch: Arc<SignatureWatch>,
+    commitment: CommitmentLevel,
+    endpoint: GrpcEndpoint,
+    publisher: Arc<Publisher>,
+) {
+    let mut version = signature_watch.version.subscribe();
+    let mut source: Option<ReconnectingSource> = None;
+
+    loop {
+        if signature_watch.is_empty() {
+            source = None;
+            if version.changed().await.is_err() {
+                return;
+            }
+            continue;
+        }
+
+        let Some(active) = source.as_mut() else {
+            version.borrow_and_update();
+            match ReconnectingSource::connect(endpoint.clone(), signature_watch.request(commitment)).await {
+                Ok(connected) => source = Some(connected),
+                Err(e) => {
+                    error!("Error subscribing to watched signatures at {:?}: {:?}", commitment, e);
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                },
+            }
+            continue;
+        };
+
+        tokio::select! {
+            changed = version.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                if let Err(e) = active.update_request(signature_watch.request(commitment)).await {
+                    error!("Error updating watched signatures at {:?}: {:?}", commitment, e);
+                    source = None;
+                }
+            },
+            update = active.next() => match update {
+                Some(Ok(update)) => {
+                    if let Some(UpdateOneof::TransactionStatus(status)) = update.update_oneof {
+                        observed(&signature_watch, commitment, status, &publisher).await;
+                    }
+                },
+                Some(Err(e)) => error!("Error watching signatures at {:?}: {:?}", commitment, e),
+                None => source = None,
+            },
+        }
+    }
+}
+
+async fn observed(
+    signature_watch: &SignatureWatch,
+    commitment: CommitmentLevel,
+    status: SubscribeUpdateTransactionStatus,
+    publisher: &Publisher,
+) {
+    let signature = codec::encode_bs58(&status.signature);
+    let record = control::control_record(
+        "signature_observed",
+        json!({
+            "signature": signature,
+            "commitment": commitment.as_str_name(),
+            "slot": status.slot,
+            "index": status.index,
+            "isVote": status.is_vote,
+            "failed": status.err.is_some(),
+        }),
+    );
+    if let Err(e) = publisher.send(signature.clone(), &record).await {
+        error!("Error publishing signature observation: {:?}", e);
+    }
+
+    if commitment == CommitmentLevel::Finalized {
+        signature_watch.remove(&signature);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_the_watched_signatures() {
+        let signature_watch = SignatureWatch {
+            signatures: Mutex::new(HashMap::new()),
+            ttl: Duration::from_secs(60),
+            max_signatures: 2,
+            version: watch::channel(0).0,
+        };
+        let signatures = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(signature_watch.add(signatures(&["a", "b"])).unwrap(), ["a", "b"]);
+        // Signatures already watched do not count
+        assert!(signature_watch.add(signatures(&["a"])).unwrap().is_empty());
+        assert!(signature_watch.add(signatures(&["c"])).is_err());
+        assert!(signature_watch.remove("a"));
+        assert_eq!(signature_watch.add(signatures(&["c"])).unwrap(), ["c"]);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+use crate::routing::RecordKind;
+
+#[cfg(feature = "web")]
+mod server;
+
+#[cfg(feature = "web")]
+pub use server::start;
+
+/// Web streaming endpoint settings
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "web"), allow(dead_code))]
+pub struct WebStreamConfig {
+    pub listen: SocketAddr,
+    /// Origins allowed to call the endpoint from a browser, `*` for any
+    pub allowed_origins: Vec<String>,
+    /// Bearer token expected in the `authorization` header, none required when unset
+    pub token: Option<String>,
+    /// Records a client can fall behind before it is disconnected
+    pub buffer: usize,
+}
+
+/// Formatted record as published to a main topic
+#[cfg_attr(not(feature = "web"), allow(dead_code))]
+pub struct WebRecord {
+    pub topic: Arc<str>,
+    pub key: String,
+    pub kind: RecordKind,
+    pub record: Value,
+}
+
+/// Formatted records of every pipeline, streamed to web clients
+#[cfg_attr(not(feature = "web"), allow(dead_code))]
+pub struct WebStream {
+    sender: broadcast::Sender<Arc<WebRecord>>,
+}
+
+#[cfg_attr(not(feature = "web"), allow(dead_code))]
+impl WebStream {
+    pub fn new(buffer: usize) -> Arc<Self> {
+        Arc::new(Self {
+            sender: broadcast::channel(buffer.max(1)).0,
+        })
+    }
+
+    /// Tap publishing the records of the pipeline publishing to `topic`
+    pub fn tap(self: &Arc<Self>, topic: &str) -> WebTap {
+        WebTap {
+            stream: Arc::clone(self),
+            topic: topic.into(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<WebRecord>> {
+        self.sender.subscribe()
+    }
+}
+
+/// Publishes the records of one pipeline to the web stream
+pub struct WebTap {
+    stream: Arc<WebStream>,
+    topic: Arc<str>,
+}
+
+impl WebTap {
+    /// Stream a published record, cloned only while clients are connected
+    pub fn publish(&self, key: &str, kind: RecordKind, record: &Value) {
+        if self.stream.sender.receiver_count() == 0 {
+            return;
+        }
+        // Fails only without clients
+        let _ = self.stream.sender.send(Arc::new(WebRecord {
+            topic: Arc::clone(&self.topic),
+            key: key.to_owned(),
+            kind,
+            record: record.clone(),
+        }));
+    }
+}
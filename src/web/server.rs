@@ -0,0 +1,259 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use futures::StreamExt;
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::{WebRecord, WebStream, WebStreamConfig};
+use crate::routing::RecordKind;
+
+/// Server-streaming method of the endpoint, for Connect and gRPC-web clients
+const SUBSCRIBE_PATH: &str = "/yellowstone.json.v1.RecordStream/Subscribe";
+
+/// Wire protocol of a request, from its content type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Connect,
+    GrpcWeb,
+}
+
+/// Error codes sent to clients
+#[derive(Debug, Clone, Copy)]
+enum Code {
+    InvalidArgument,
+    ResourceExhausted,
+    Unauthenticated,
+}
+
+impl Code {
+    fn connect_name(self) -> &'static str {
+        match self {
+            Code::InvalidArgument => "invalid_argument",
+            Code::ResourceExhausted => "resource_exhausted",
+            Code::Unauthenticated => "unauthenticated",
+        }
+    }
+
+    fn grpc_status(self) -> u8 {
+        match self {
+            Code::InvalidArgument => 3,
+            Code::ResourceExhausted => 8,
+            Code::Unauthenticated => 16,
+        }
+    }
+}
+
+impl Protocol {
+    fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+        match content_type.split(';').next()?.trim() {
+            "application/connect+json" => Some(Protocol::Connect),
+            "application/grpc-web+json" => Some(Protocol::GrpcWeb),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Protocol::Connect => "application/connect+json",
+            Protocol::GrpcWeb => "application/grpc-web+json",
+        }
+    }
+
+    /// Message envelope: flags, 4-byte big-endian length and JSON payload
+    fn message(self, payload: &[u8]) -> Bytes {
+        envelope(0x00, payload)
+    }
+
+    /// Last envelope of a response, with the error that ended it
+    fn end(self, error: Option<(Code, String)>) -> Bytes {
+        match self {
+            Protocol::Connect => {
+                let end = match error {
+                    Some((code, message)) => json!({ "error": { "code": code.connect_name(), "message": message } }),
+                    None => json!({}),
+                };
+                envelope(0x02, end.to_string().as_bytes())
+            },
+            Protocol::GrpcWeb => {
+                let trailers = match error {
+                    Some((code, message)) => format!("grpc-status:{}\r\ngrpc-message:{}\r\n", code.grpc_status(), message),
+                    None => "grpc-status:0\r\n".to_string(),
+                };
+                envelope(0x80, trailers.as_bytes())
+            },
+        }
+    }
+}
+
+fn envelope(flags: u8, payload: &[u8]) -> Bytes {
+    let mut frame = Vec::with_capacity(5 + payload.len());
+    frame.push(flags);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame.into()
+}
+
+/// Records a client asked for, every record when empty
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamRequest {
+    kinds: Option<Vec<RecordKind>>,
+    keys: Option<Vec<String>>,
+    topics: Option<Vec<String>>,
+}
+
+impl StreamRequest {
+    fn parse(body: &[u8]) -> Result<Self, (Code, String)> {
+        if body.is_empty() {
+            return Ok(Self::default());
+        }
+        let invalid = |message: &str| (Code::InvalidArgument, message.to_string());
+        let (flags, rest) = body.split_first().ok_or_else(|| invalid("empty envelope"))?;
+        if flags & 0x01 != 0 {
+            return Err(invalid("compressed messages are not supported"));
+        }
+        let length = rest
+            .get(..4)
+            .map(|length| u32::from_be_bytes(length.try_into().unwrap()) as usize)
+            .ok_or_else(|| invalid("truncated envelope"))?;
+        let payload = rest.get(4..4 + length).ok_or_else(|| invalid("truncated envelope"))?;
+        serde_json::from_slice(payload).map_err(|e| (Code::InvalidArgument, format!("invalid request: {}", e)))
+    }
+
+    fn matches(&self, record: &WebRecord) -> bool {
+        self.kinds.as_ref().map_or(true, |kinds| kinds.contains(&record.kind))
+            && self.keys.as_ref().map_or(true, |keys| keys.contains(&record.key))
+            && self.topics.as_ref().map_or(true, |topics| topics.iter().any(|topic| **topic == *record.topic))
+    }
+}
+
+struct WebState {
+    config: WebStreamConfig,
+    stream: Arc<WebStream>,
+}
+
+impl WebState {
+    /// CORS headers for the request's origin, none when it is not allowed
+    fn cors(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut cors = HeaderMap::new();
+        let Some(origin) = headers.get(header::ORIGIN) else {
+            return cors;
+        };
+        let allowed = if self.config.allowed_origins.iter().any(|allowed| allowed == "*") {
+            HeaderValue::from_static("*")
+        } else if self.config.allowed_origins.iter().any(|allowed| allowed.as_bytes() == origin.as_bytes()) {
+            cors.insert(header::VARY, HeaderValue::from_static("origin"));
+            origin.clone()
+        } else {
+            return cors;
+        };
+        cors.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed);
+        cors.insert(header::ACCESS_CONTROL_ALLOW_METHODS, HeaderValue::from_static("POST, OPTIONS"));
+        cors.insert(
+            header::ACCESS_CONTROL_ALLOW_HEADERS,
+            HeaderValue::from_static(
+                "authorization, content-type, connect-protocol-version, connect-timeout-ms, x-grpc-web, x-user-agent",
+            ),
+        );
+        cors.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, HeaderValue::from_static("grpc-status, grpc-message"));
+        cors
+    }
+
+    fn authorize(&self, headers: &HeaderMap) -> Result<(), (Code, String)> {
+        let Some(token) = &self.config.token else {
+            return Ok(());
+        };
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if bearer != Some(token.as_str()) {
+            return Err((Code::Unauthenticated, "invalid token".to_string()));
+        }
+        Ok(())
+    }
+}
+
+async fn preflight(State(state): State<Arc<WebState>>, headers: HeaderMap) -> Response {
+    (StatusCode::NO_CONTENT, state.cors(&headers)).into_response()
+}
+
+async fn subscribe(State(state): State<Arc<WebState>>, headers: HeaderMap, body: Bytes) -> Response {
+    let cors = state.cors(&headers);
+    let Some(protocol) = Protocol::from_headers(&headers) else {
+        return (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            cors,
+            "expected application/connect+json or application/grpc-web+json",
+        )
+            .into_response();
+    };
+
+    // Errors, even before the first record, are sent in the body so streaming clients see them
+    let body = match state.authorize(&headers).and_then(|_| StreamRequest::parse(&body)) {
+        Err(error) => Body::from(protocol.end(Some(error))),
+        Ok(request) => {
+            let request = Arc::new(request);
+            let records = futures::stream::unfold(Some(state.stream.subscribe()), move |receiver| {
+                let request = Arc::clone(&request);
+                async move {
+                    let mut receiver = receiver?;
+                    loop {
+                        match receiver.recv().await {
+                            Ok(record) if request.matches(&record) => {
+                                let message = json!({
+                                    "topic": &*record.topic,
+                                    "key": record.key,
+                                    "kind": record.kind,
+                                    "record": record.record,
+                                });
+                                return Some((protocol.message(message.to_string().as_bytes()), Some(receiver)));
+                            },
+                            Ok(_) => continue,
+                            Err(RecvError::Lagged(skipped)) => {
+                                let error = (Code::ResourceExhausted, format!("client fell {} records behind", skipped));
+                                return Some((protocol.end(Some(error)), None));
+                            },
+                            Err(RecvError::Closed) => return Some((protocol.end(None), None)),
+                        }
+                    }
+                }
+            });
+            Body::from_stream(records.map(Ok::<_, Infallible>))
+        },
+    };
+
+    let mut response = (cors, body).into_response();
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(protocol.content_type()));
+    response
+}
+
+/// Bind the web streaming endpoint and serve it in the background
+pub async fn start(config: WebStreamConfig, stream: Arc<WebStream>) -> anyhow::Result<()> {
+    let listen = config.listen;
+    let router = Router::new()
+        .route(SUBSCRIBE_PATH, post(subscribe).options(preflight))
+        .with_state(Arc::new(WebState { config, stream }));
+
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    info!("Web stream listening on {}{}", listen, SUBSCRIBE_PATH);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, router).await {
+            error!("Web stream server stopped: {:?}", e);
+        }
+    });
+    Ok(())
+}